@@ -1,7 +1,7 @@
 //! Benchmarks for the editor core
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use mini_word::{Editor, LayoutConstraints, Rect};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use mini_word::{Cursor, Editor, LayoutConstraints, LineBreakMode, NoteKind, Rect, SearchOptions, SearchQuery};
 
 fn default_constraints() -> LayoutConstraints {
     LayoutConstraints {
@@ -64,6 +64,43 @@ fn bench_layout_medium(c: &mut Criterion) {
     });
 }
 
+/// Typing into one paragraph, relaid out after every keystroke
+///
+/// Total-fit breaking should stay within a frame (16 ms) for typical
+/// paragraph lengths; greedy breaking is measured alongside for comparison.
+fn bench_typing_line_break_modes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("typing_paragraph");
+    for mode in [LineBreakMode::Greedy, LineBreakMode::Optimal] {
+        for words in [50, 200, 800] {
+            let id = BenchmarkId::new(format!("{mode:?}"), words);
+            group.bench_with_input(id, &words, |b, &words| {
+                let text: Vec<_> = (0..words).map(|i| ["paragraph", "of", "hyphenated", "text"][i % 4]).collect();
+                let text = text.join(" ");
+
+                // Each keystroke lands on a fresh paragraph of the same length,
+                // so neither the last word nor the undo stack grows
+                b.iter_batched(
+                    || {
+                        let mut editor = Editor::new(default_constraints());
+                        editor.layout.set_line_break_mode(mode);
+                        editor.layout.hyphenation_mut().settings.enabled = true;
+                        editor.insert_text(&text);
+                        editor.update_layout();
+                        editor
+                    },
+                    |mut editor| {
+                        editor.insert_text(black_box("x"));
+                        editor.update_layout();
+                        editor
+                    },
+                    BatchSize::LargeInput,
+                );
+            });
+        }
+    }
+    group.finish();
+}
+
 fn bench_build_display_list(c: &mut Criterion) {
     c.bench_function("build_display_list", |b| {
         let mut editor = Editor::new(default_constraints());
@@ -95,14 +132,78 @@ fn bench_undo_redo(c: &mut Criterion) {
     });
 }
 
+/// Searching a document of several megabytes
+///
+/// Find next and previous search a paragraph at a time from the cursor;
+/// highlights are searched once and then only in edited paragraphs, so
+/// building a display list does not search again.
+fn bench_search_large_document(c: &mut Criterion) {
+    let sentence = "Filler text of a long document that mentions a needle now and then. ";
+    let text: String = (0..8_000).map(|i| format!("{} needle {}\n", sentence.repeat(7), i % 97)).collect();
+    let mut editor = Editor::with_text(&text, default_constraints());
+    let query = SearchQuery::new("needle 42", SearchOptions { whole_word: true, ..Default::default() }).unwrap();
+    let middle = editor.document.offset_to_position(mini_word::editing::AbsoluteOffset(text.len() / 2));
+
+    let mut group = c.benchmark_group("search_large_document");
+    group.sample_size(20);
+    group.bench_function("find_all", |b| {
+        b.iter(|| black_box(editor.find(&query).len()));
+    });
+    for (name, backward) in [("find_next", false), ("find_previous", true)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                editor.cursor = Cursor::new(middle);
+                editor.selection = None;
+                black_box(editor.find_next(&query, backward))
+            });
+        });
+    }
+
+    editor.search = Some(query.clone());
+    editor.update_layout();
+    let viewport = Rect::new(0.0, 0.0, 612.0, 792.0);
+    group.bench_function("display_list_with_highlights", |b| {
+        b.iter(|| black_box(editor.build_display_list(viewport)));
+    });
+    group.finish();
+}
+
+/// Starting new paragraphs near the end of a long document with a
+/// footnote on its first page
+///
+/// Each new paragraph moves the lines after it, so pages are rebuilt; they
+/// are rebuilt from the page typed on, since no footnote before it changes
+/// or carries over, and note references are only collected again when a
+/// paragraph gains or loses one.
+fn bench_typing_with_footnote(c: &mut Criterion) {
+    let text: String = (0..2_000).map(|i| format!("Paragraph {i} of a long document with a footnote.\n")).collect();
+    let mut editor = Editor::with_text(&text, default_constraints());
+    editor.cursor.position = editor.document.offset_to_position(mini_word::editing::AbsoluteOffset(9));
+    editor.insert_note(NoteKind::Footnote);
+    editor.insert_text("A footnote.");
+    editor.exit_note();
+    editor.cursor.position = editor.document.offset_to_position(mini_word::editing::AbsoluteOffset(text.len() - 10));
+    editor.update_layout();
+
+    c.bench_function("typing_with_footnote", |b| {
+        b.iter(|| {
+            editor.insert_text(black_box("\n"));
+            editor.update_layout();
+        });
+    });
+}
+
 criterion_group!(
     benches,
     bench_insert_single_char,
     bench_insert_word,
     bench_layout_small,
     bench_layout_medium,
+    bench_typing_line_break_modes,
     bench_build_display_list,
     bench_undo_redo,
+    bench_search_large_document,
+    bench_typing_with_footnote,
 );
 
 criterion_main!(benches);
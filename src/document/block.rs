@@ -1,9 +1,20 @@
 //! Block-level element metadata
 
+use crate::document::{SectionId, TableId, TocId};
+
 /// Unique identifier for a list
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ListId(pub u64);
 
+/// A forced break placed before a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockBreak {
+    /// Start the block on a new page
+    Page,
+    /// Start a new section on a new page
+    Section(SectionId),
+}
+
 /// Type of list marker
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ListMarker {
@@ -34,6 +45,10 @@ pub enum BlockKind {
         indent_level: u8,
         marker: ListMarker,
     },
+    /// Table anchor (the paragraph itself holds no text)
+    Table { table_id: TableId },
+    /// Entry of a table of contents, listing a heading of `level`
+    TocEntry { toc_id: TocId, level: u8 },
 }
 
 impl Default for BlockKind {
@@ -54,6 +69,8 @@ impl BlockKind {
                 _ => 1.2,
             },
             BlockKind::ListItem { .. } => 1.0,
+            BlockKind::Table { .. } => 1.0,
+            BlockKind::TocEntry { .. } => 1.0,
         }
     }
 
@@ -63,6 +80,8 @@ impl BlockKind {
             BlockKind::Paragraph => 1.0,
             BlockKind::Heading { .. } => 0.5,
             BlockKind::ListItem { .. } => 0.25,
+            BlockKind::Table { .. } => 0.5,
+            BlockKind::TocEntry { .. } => 0.25,
         }
     }
 
@@ -75,6 +94,75 @@ impl BlockKind {
     pub fn is_list_item(&self) -> bool {
         matches!(self, BlockKind::ListItem { .. })
     }
+
+    /// Check if this is a table anchor
+    pub fn is_table(&self) -> bool {
+        matches!(self, BlockKind::Table { .. })
+    }
+}
+
+/// Base direction of a paragraph
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextDirection {
+    /// Taken from the first strong character (left-to-right if none)
+    #[default]
+    Auto,
+    Ltr,
+    Rtl,
+}
+
+/// How a paragraph is broken into lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineBreakMode {
+    /// Fill each line as far as it goes (first fit)
+    #[default]
+    Greedy,
+    /// Choose all breaks together to even out the lines (Knuth–Plass total fit)
+    Optimal,
+}
+
+/// How text after a tab lines up with its stop
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TabAlignment {
+    /// Text starts at the stop
+    #[default]
+    Left,
+    /// Text is centred on the stop
+    Center,
+    /// Text ends at the stop
+    Right,
+    /// The first decimal point sits at the stop
+    Decimal,
+}
+
+/// Characters drawn across the space a tab fills
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TabLeader {
+    #[default]
+    None,
+    Dots,
+    Dashes,
+    Underline,
+}
+
+/// A tab stop of a paragraph
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TabStop {
+    /// Distance from the start of the paragraph's lines
+    pub position: f32,
+    pub alignment: TabAlignment,
+    pub leader: TabLeader,
+}
+
+impl TabStop {
+    /// Create a left-aligned stop without a leader
+    pub fn new(position: f32) -> Self {
+        Self {
+            position,
+            alignment: TabAlignment::Left,
+            leader: TabLeader::None,
+        }
+    }
 }
 
 /// Style information for a span of text
@@ -88,6 +176,62 @@ pub struct StyleSpan {
     pub font_id: crate::layout::font::FontId,
 }
 
+/// Hyperlink target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub href: String,
+    /// Tooltip text
+    pub title: Option<String>,
+}
+
+impl Link {
+    /// Create a link without a title
+    pub fn new(href: impl Into<String>) -> Self {
+        Self {
+            href: href.into(),
+            title: None,
+        }
+    }
+
+    /// Whether the target is safe to follow: a web or mail address, or an
+    /// anchor within the document
+    ///
+    /// Other schemes (`javascript:`, `data:`, `file:` ...) can run code or
+    /// read local files when clicked, and links without a scheme resolve
+    /// against wherever the document is opened, so both are refused.
+    pub fn is_safe(&self) -> bool {
+        // Browsers skip whitespace and control characters inside a scheme
+        let href: String = self.href.chars().filter(|c| !c.is_whitespace() && !c.is_control()).collect();
+        if href.starts_with('#') {
+            return true;
+        }
+        href.split_once(':').is_some_and(|(scheme, _)| {
+            ["http", "https", "mailto"].iter().any(|safe| scheme.eq_ignore_ascii_case(safe))
+        })
+    }
+}
+
+/// A hyperlink over a span of text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkSpan {
+    /// Start offset relative to block start
+    pub start: usize,
+    /// End offset relative to block start
+    pub end: usize,
+    pub link: Link,
+}
+
+/// A language override over a span of text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LanguageSpan {
+    /// Start offset relative to block start
+    pub start: usize,
+    /// End offset relative to block start
+    pub end: usize,
+    /// BCP 47 language tag, e.g. "en-US"
+    pub language: String,
+}
+
 /// Metadata for a block-level element
 #[derive(Debug, Clone, PartialEq)]
 pub struct BlockMeta {
@@ -99,6 +243,23 @@ pub struct BlockMeta {
     pub byte_len: usize,
     /// Style spans for this block (sorted by start)
     pub styles: Vec<StyleSpan>,
+    /// Forced page or section break before this block
+    pub break_before: Option<BlockBreak>,
+    /// Hyperlinks in this block (sorted by start, non-overlapping)
+    pub links: Vec<LinkSpan>,
+    /// Base direction for bidi resolution
+    pub direction: TextDirection,
+    /// Paragraph language (BCP 47 tag) used for hyphenation
+    pub language: Option<String>,
+    /// Language overrides within the paragraph (sorted by start, non-overlapping)
+    pub language_spans: Vec<LanguageSpan>,
+    /// Line breaking for this paragraph (`None` follows the document)
+    pub line_break: Option<LineBreakMode>,
+    /// Tab stops (sorted by position)
+    pub tab_stops: Vec<TabStop>,
+    /// Distance between default tab stops past the last custom one (`None`
+    /// follows the document)
+    pub tab_interval: Option<f32>,
 }
 
 impl BlockMeta {
@@ -109,6 +270,14 @@ impl BlockMeta {
             start_offset,
             byte_len,
             styles: Vec::new(),
+            break_before: None,
+            links: Vec::new(),
+            direction: TextDirection::Auto,
+            language: None,
+            language_spans: Vec::new(),
+            line_break: None,
+            tab_stops: Vec::new(),
+            tab_interval: None,
         }
     }
 
@@ -119,6 +288,14 @@ impl BlockMeta {
             start_offset,
             byte_len,
             styles: Vec::new(),
+            break_before: None,
+            links: Vec::new(),
+            direction: TextDirection::Auto,
+            language: None,
+            language_spans: Vec::new(),
+            line_break: None,
+            tab_stops: Vec::new(),
+            tab_interval: None,
         }
     }
 
@@ -139,6 +316,32 @@ impl BlockMeta {
             start_offset,
             byte_len,
             styles: Vec::new(),
+            break_before: None,
+            links: Vec::new(),
+            direction: TextDirection::Auto,
+            language: None,
+            language_spans: Vec::new(),
+            line_break: None,
+            tab_stops: Vec::new(),
+            tab_interval: None,
+        }
+    }
+
+    /// Create a new table block
+    pub fn table(table_id: TableId, start_offset: usize) -> Self {
+        Self {
+            kind: BlockKind::Table { table_id },
+            start_offset,
+            byte_len: 0,
+            styles: Vec::new(),
+            break_before: None,
+            links: Vec::new(),
+            direction: TextDirection::Auto,
+            language: None,
+            language_spans: Vec::new(),
+            line_break: None,
+            tab_stops: Vec::new(),
+            tab_interval: None,
         }
     }
 
@@ -149,6 +352,26 @@ impl BlockMeta {
 
     /// Handle text insertion at the given relative offset
     pub fn on_insert(&mut self, offset: usize, len: usize) {
+        // Links only grow when typing inside them, never at their edges
+        for link in &mut self.links {
+            if offset > link.start && offset < link.end {
+                link.end += len;
+            } else if offset <= link.start {
+                link.start += len;
+                link.end += len;
+            }
+        }
+
+        // Language spans extend when typing at their end, like styles
+        for span in &mut self.language_spans {
+            if offset > span.start && offset <= span.end {
+                span.end += len;
+            } else if offset <= span.start {
+                span.start += len;
+                span.end += len;
+            }
+        }
+
         if self.styles.is_empty() {
              // If no styles, everything stays default. 
              // Ideally we should have a default style? 
@@ -176,11 +399,36 @@ impl BlockMeta {
 
     /// Handle text deletion
     pub fn on_delete(&mut self, start: usize, end: usize) {
+        let delete_len = end - start;
+        // Clamp both edges out of the deleted range
+        let shrink = |offset: usize| {
+            if offset <= start {
+                offset
+            } else {
+                offset.max(end) - delete_len
+            }
+        };
+        self.links = std::mem::take(&mut self.links)
+            .into_iter()
+            .filter_map(|mut link| {
+                link.start = shrink(link.start);
+                link.end = shrink(link.end);
+                (link.start < link.end).then_some(link)
+            })
+            .collect();
+        self.language_spans = std::mem::take(&mut self.language_spans)
+            .into_iter()
+            .filter_map(|mut span| {
+                span.start = shrink(span.start);
+                span.end = shrink(span.end);
+                (span.start < span.end).then_some(span)
+            })
+            .collect();
+
         if self.styles.is_empty() {
             return;
         }
         
-        let delete_len = end - start;
         // Filter map to remove/adjust spans
         self.styles = self.styles.iter().filter_map(|s| {
             let mut style = s.clone();
@@ -333,6 +581,147 @@ impl BlockMeta {
         self.styles = merged;
     }
 
+    /// Link a range, replacing any links it overlaps
+    pub fn set_link(&mut self, start: usize, end: usize, link: Link) {
+        if start >= end {
+            return;
+        }
+        self.remove_links(start, end);
+        let index = self.links.partition_point(|l| l.start < start);
+        self.links.insert(index, LinkSpan { start, end, link });
+    }
+
+    /// Remove links from a range, trimming links that extend past it
+    pub fn remove_links(&mut self, start: usize, end: usize) {
+        let mut links = Vec::with_capacity(self.links.len());
+        for span in std::mem::take(&mut self.links) {
+            if span.end <= start || span.start >= end {
+                links.push(span);
+                continue;
+            }
+            if span.start < start {
+                links.push(LinkSpan {
+                    end: start,
+                    ..span.clone()
+                });
+            }
+            if span.end > end {
+                links.push(LinkSpan { start: end, ..span });
+            }
+        }
+        self.links = links;
+    }
+
+    /// Get the link at a relative offset
+    ///
+    /// A link covers the character after the offset; failing that, a link
+    /// ending at the offset counts, so the caret just after a link is on it.
+    pub fn link_at(&self, offset: usize) -> Option<&LinkSpan> {
+        self.links
+            .iter()
+            .find(|l| l.start <= offset && offset < l.end)
+            .or_else(|| self.links.iter().find(|l| l.end == offset))
+    }
+
+    /// Split links at a relative offset, returning links for the new second block
+    pub fn split_links_at(&mut self, split_offset: usize) -> Vec<LinkSpan> {
+        let mut second = Vec::new();
+        self.links.retain_mut(|span| {
+            if span.end > split_offset {
+                second.push(LinkSpan {
+                    start: span.start.max(split_offset) - split_offset,
+                    end: span.end - split_offset,
+                    link: span.link.clone(),
+                });
+                span.end = split_offset;
+            }
+            span.start < span.end
+        });
+        second
+    }
+
+    /// Append links from another block (used when merging paragraphs)
+    pub fn append_links(&mut self, other_links: Vec<LinkSpan>, offset_shift: usize) {
+        self.links.extend(other_links.into_iter().map(|span| LinkSpan {
+            start: span.start + offset_shift,
+            end: span.end + offset_shift,
+            ..span
+        }));
+    }
+
+    /// Set the language of a range, replacing any overrides it overlaps;
+    /// `None` clears overrides so the range follows the paragraph language
+    pub fn set_language_range(&mut self, start: usize, end: usize, language: Option<String>) {
+        if start >= end {
+            return;
+        }
+        let mut spans = Vec::with_capacity(self.language_spans.len() + 1);
+        for span in std::mem::take(&mut self.language_spans) {
+            if span.end <= start || span.start >= end {
+                spans.push(span);
+                continue;
+            }
+            if span.start < start {
+                spans.push(LanguageSpan {
+                    end: start,
+                    ..span.clone()
+                });
+            }
+            if span.end > end {
+                spans.push(LanguageSpan { start: end, ..span });
+            }
+        }
+        if let Some(language) = language {
+            spans.push(LanguageSpan { start, end, language });
+        }
+        spans.sort_by_key(|span| span.start);
+
+        // Merge adjacent spans of the same language
+        let mut merged: Vec<LanguageSpan> = Vec::with_capacity(spans.len());
+        for span in spans {
+            match merged.last_mut() {
+                Some(last) if last.end == span.start && last.language == span.language => last.end = span.end,
+                _ => merged.push(span),
+            }
+        }
+        self.language_spans = merged;
+    }
+
+    /// Get the language of the character at a relative offset
+    pub fn language_at(&self, offset: usize) -> Option<&str> {
+        self.language_spans
+            .iter()
+            .find(|span| span.start <= offset && offset < span.end)
+            .map(|span| span.language.as_str())
+            .or(self.language.as_deref())
+    }
+
+    /// Split language spans at a relative offset, returning spans for the new second block
+    pub fn split_language_spans_at(&mut self, split_offset: usize) -> Vec<LanguageSpan> {
+        let mut second = Vec::new();
+        self.language_spans.retain_mut(|span| {
+            if span.end > split_offset {
+                second.push(LanguageSpan {
+                    start: span.start.max(split_offset) - split_offset,
+                    end: span.end - split_offset,
+                    language: span.language.clone(),
+                });
+                span.end = split_offset;
+            }
+            span.start < span.end
+        });
+        second
+    }
+
+    /// Append language spans from another block (used when merging paragraphs)
+    pub fn append_language_spans(&mut self, other_spans: Vec<LanguageSpan>, offset_shift: usize) {
+        self.language_spans.extend(other_spans.into_iter().map(|span| LanguageSpan {
+            start: span.start + offset_shift,
+            end: span.end + offset_shift,
+            ..span
+        }));
+    }
+
     /// Append styles from another block (used when merging paragraphs)
     pub fn append_styles(&mut self, mut other_styles: Vec<StyleSpan>, offset_shift: usize) {
         for style in &mut other_styles {
@@ -367,6 +756,80 @@ mod tests {
         assert!(list.is_list_item());
     }
 
+    #[test]
+    fn test_links_follow_edits() {
+        let mut meta = BlockMeta::paragraph(0, 20);
+        meta.set_link(5, 10, Link::new("https://example.com"));
+
+        // Typing at either edge stays outside the link
+        meta.on_insert(10, 2);
+        meta.on_insert(5, 1);
+        assert_eq!((meta.links[0].start, meta.links[0].end), (6, 11));
+
+        // Typing inside grows it
+        meta.on_insert(8, 3);
+        assert_eq!((meta.links[0].start, meta.links[0].end), (6, 14));
+
+        // Deleting across the start shrinks it; deleting all of it removes it
+        meta.on_delete(4, 8);
+        assert_eq!((meta.links[0].start, meta.links[0].end), (4, 10));
+        meta.on_delete(2, 12);
+        assert!(meta.links.is_empty());
+    }
+
+    #[test]
+    fn test_link_safety() {
+        assert!(Link::new("https://example.com").is_safe());
+        assert!(Link::new("HTTP://example.com").is_safe());
+        assert!(Link::new("mailto:me@example.com").is_safe());
+        assert!(Link::new("#heading-3").is_safe());
+        assert!(!Link::new("javascript:alert(1)").is_safe());
+        assert!(!Link::new(" java\tscript:alert(1)").is_safe());
+        assert!(!Link::new("\u{1}JavaScript:alert(1)").is_safe());
+        assert!(!Link::new("data:text/html,<script>").is_safe());
+        assert!(!Link::new("docs/page.html").is_safe());
+    }
+
+    #[test]
+    fn test_link_at_and_remove() {
+        let mut meta = BlockMeta::paragraph(0, 20);
+        meta.set_link(2, 8, Link::new("a"));
+        assert_eq!(meta.link_at(2).map(|l| l.link.href.as_str()), Some("a"));
+        assert!(meta.link_at(8).is_some());
+        assert!(meta.link_at(9).is_none());
+
+        meta.remove_links(4, 6);
+        let ranges: Vec<_> = meta.links.iter().map(|l| (l.start, l.end)).collect();
+        assert_eq!(ranges, vec![(2, 4), (6, 8)]);
+
+        let second = meta.split_links_at(7);
+        assert_eq!((second[0].start, second[0].end), (0, 1));
+        assert_eq!(meta.links.last().map(|l| l.end), Some(7));
+    }
+
+    #[test]
+    fn test_language_spans() {
+        let mut meta = BlockMeta::paragraph(0, 20);
+        meta.language = Some("en-US".to_string());
+        meta.set_language_range(5, 10, Some("de".to_string()));
+        assert_eq!(meta.language_at(4), Some("en-US"));
+        assert_eq!(meta.language_at(5), Some("de"));
+
+        // Typing at the end extends the override, unlike a link
+        meta.on_insert(10, 2);
+        assert_eq!((meta.language_spans[0].start, meta.language_spans[0].end), (5, 12));
+
+        // Clearing the middle falls back to the paragraph language
+        meta.set_language_range(7, 9, None);
+        assert_eq!(meta.language_at(8), Some("en-US"));
+        assert_eq!(meta.language_spans.len(), 2);
+
+        let second = meta.split_language_spans_at(10);
+        assert_eq!((second[0].start, second[0].end), (0, 2));
+        meta.on_delete(0, 20);
+        assert!(meta.language_spans.is_empty());
+    }
+
     #[test]
     fn test_list_marker_display() {
         assert_eq!(ListMarker::Bullet.display(), "•");
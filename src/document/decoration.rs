@@ -0,0 +1,156 @@
+//! Decorations: ranges painted over the text without being part of it
+//!
+//! Search hits, spelling errors, collaborators' selections and comments
+//! mark text without changing the document, so they are not edits and are
+//! never undone. Each story's document keeps its decorations as absolute
+//! ranges and shifts them as text is inserted and deleted, following the
+//! rules for style spans.
+
+use rustc_hash::FxHashMap;
+use std::ops::Range;
+
+/// Identifier of a decoration, unique within its document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DecorationId(pub u64);
+
+/// How a decoration is painted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecorationKind {
+    /// Background behind the text
+    Highlight,
+    /// Wavy line under the text
+    Squiggle,
+}
+
+/// A painted range of text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoration {
+    /// Start offset in the document
+    pub start: usize,
+    /// End offset in the document
+    pub end: usize,
+    pub kind: DecorationKind,
+    /// Color as 0xRRGGBBAA
+    pub color: u32,
+}
+
+/// Decorations of a document, kept in place through edits
+#[derive(Debug, Default)]
+pub struct DecorationSet {
+    decorations: FxHashMap<DecorationId, Decoration>,
+    next_id: u64,
+}
+
+impl DecorationSet {
+    /// Add a decoration over a range; returns None for an empty range
+    pub fn add(&mut self, range: Range<usize>, kind: DecorationKind, color: u32) -> Option<DecorationId> {
+        if range.is_empty() {
+            return None;
+        }
+        let id = DecorationId(self.next_id);
+        self.next_id += 1;
+        self.decorations.insert(id, Decoration {
+            start: range.start,
+            end: range.end,
+            kind,
+            color,
+        });
+        Some(id)
+    }
+
+    /// Remove a decoration; returns false if it was already gone
+    pub fn remove(&mut self, id: DecorationId) -> bool {
+        self.decorations.remove(&id).is_some()
+    }
+
+    /// Remove every decoration
+    pub fn clear(&mut self) {
+        self.decorations.clear();
+    }
+
+    /// A decoration, unless it was removed or its text deleted
+    pub fn get(&self, id: DecorationId) -> Option<&Decoration> {
+        self.decorations.get(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.decorations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decorations.is_empty()
+    }
+
+    /// Decorations overlapping a range, ordered by start (then ID)
+    pub fn overlapping(&self, range: Range<usize>) -> Vec<(DecorationId, &Decoration)> {
+        let mut found: Vec<_> = self
+            .decorations
+            .iter()
+            .filter(|(_, d)| d.start < range.end && d.end > range.start)
+            .map(|(&id, d)| (id, d))
+            .collect();
+        found.sort_by_key(|&(id, d)| (d.start, id));
+        found
+    }
+
+    /// Shift decorations for text inserted at an offset; one the text
+    /// lands inside or at the end of grows to cover it
+    pub fn on_insert(&mut self, offset: usize, len: usize) {
+        for decoration in self.decorations.values_mut() {
+            if offset > decoration.start && offset <= decoration.end {
+                decoration.end += len;
+            } else if offset <= decoration.start {
+                decoration.start += len;
+                decoration.end += len;
+            }
+        }
+    }
+
+    /// Shrink decorations for deleted text, dropping any left empty
+    pub fn on_delete(&mut self, start: usize, end: usize) {
+        let delete_len = end - start;
+        let shrink = |offset: usize| {
+            if offset <= start {
+                offset
+            } else {
+                offset.max(end) - delete_len
+            }
+        };
+        self.decorations.retain(|_, decoration| {
+            decoration.start = shrink(decoration.start);
+            decoration.end = shrink(decoration.end);
+            decoration.start < decoration.end
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decorations_through_edits() {
+        let mut set = DecorationSet::default();
+        let word = set.add(4..9, DecorationKind::Squiggle, 0xff0000ff).unwrap();
+        let later = set.add(12..15, DecorationKind::Highlight, 0xffff0080).unwrap();
+        assert!(set.add(3..3, DecorationKind::Highlight, 0).is_none());
+
+        // Typing at the end of a range extends it; before it, shifts it
+        set.on_insert(9, 2);
+        set.on_insert(0, 1);
+        assert_eq!((set.get(word).unwrap().start, set.get(word).unwrap().end), (5, 12));
+        assert_eq!((set.get(later).unwrap().start, set.get(later).unwrap().end), (15, 18));
+
+        // Deleting clips a range; deleting all of it removes it
+        set.on_delete(3, 7);
+        assert_eq!((set.get(word).unwrap().start, set.get(word).unwrap().end), (3, 8));
+        set.on_delete(10, 20);
+        assert!(set.get(later).is_none());
+        assert_eq!(set.overlapping(0..4).len(), 1);
+        assert!(set.overlapping(8..10).is_empty());
+
+        assert!(set.remove(word));
+        assert!(!set.remove(word));
+        assert!(set.is_empty());
+    }
+}
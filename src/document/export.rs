@@ -0,0 +1,386 @@
+//! HTML and Markdown export
+//!
+//! Both exporters walk the paragraphs in order. Inline text carries links,
+//! inline objects (as images) and note references; notes are numbered in
+//! order of reference and listed after the body.
+
+use crate::document::{heading_anchor, BlockKind, BlockMeta, Document, Link, ListMarker, NoteId, ObjectId};
+
+impl Document {
+    /// Export the document as an HTML fragment
+    pub fn to_html(&self) -> String {
+        let mut notes = Vec::new();
+        let mut out = self.html_blocks(&mut notes);
+
+        if !notes.is_empty() {
+            out.push_str("<ol class=\"notes\">\n");
+            for (index, note_id) in notes.iter().enumerate() {
+                let body = self
+                    .note(*note_id)
+                    .map(|note| note.body.html_blocks(&mut Vec::new()))
+                    .unwrap_or_default();
+                out.push_str(&format!("<li id=\"note-{}\">\n{}</li>\n", index + 1, body));
+            }
+            out.push_str("</ol>\n");
+        }
+        out
+    }
+
+    /// Export the document as Markdown
+    pub fn to_markdown(&self) -> String {
+        let mut notes = Vec::new();
+        let mut out = self.markdown_blocks(&mut notes);
+
+        for (index, note_id) in notes.iter().enumerate() {
+            let body = self
+                .note(*note_id)
+                .map(|note| note.body.markdown_blocks(&mut Vec::new()))
+                .unwrap_or_default();
+            out.push_str(&format!("\n[^{}]: {}\n", index + 1, body.trim().replace('\n', "\n    ")));
+        }
+        out
+    }
+
+    /// Render every paragraph as HTML blocks, collecting referenced notes
+    fn html_blocks(&self, notes: &mut Vec<NoteId>) -> String {
+        let mut out = String::new();
+        // Open list levels: each has an open <li>
+        let mut lists: Vec<&str> = Vec::new();
+        let numbers = self.heading_markers();
+
+        for para_id in self.paragraph_order() {
+            let Some(meta) = self.block_meta(para_id) else {
+                continue;
+            };
+            let text = self.paragraph_text(para_id);
+
+            if let BlockKind::ListItem { indent_level, marker, .. } = &meta.kind {
+                let level = *indent_level as usize + 1;
+                let tag = match marker {
+                    ListMarker::Bullet => "ul",
+                    ListMarker::Numbered { .. } => "ol",
+                };
+                while lists.len() > level {
+                    out.push_str(&format!("</li></{}>\n", lists.pop().unwrap_or(tag)));
+                }
+                if lists.len() == level {
+                    if lists.last() == Some(&tag) {
+                        out.push_str("</li>\n");
+                    } else {
+                        out.push_str(&format!("</li></{}>\n", lists.pop().unwrap_or(tag)));
+                    }
+                }
+                while lists.len() < level {
+                    out.push_str(&format!("<{}>", tag));
+                    lists.push(tag);
+                    if lists.len() < level {
+                        out.push_str("<li>");
+                    }
+                }
+                out.push_str(&format!("<li>{}", self.html_inline(&text, meta, notes)));
+                continue;
+            }
+
+            while let Some(tag) = lists.pop() {
+                out.push_str(&format!("</li></{}>\n", tag));
+            }
+
+            match &meta.kind {
+                BlockKind::Heading { level } => {
+                    let inline = self.html_inline(&text, meta, notes);
+                    let number = numbers.get(&para_id).map(|n| format!("{} ", escape_html(n))).unwrap_or_default();
+                    // Table of contents entries link to their headings
+                    let id = if self.tocs.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" id=\"{}\"", &heading_anchor(para_id)[1..])
+                    };
+                    out.push_str(&format!("<h{level}{id}>{number}{inline}</h{level}>\n"));
+                }
+                BlockKind::Table { table_id } => {
+                    let Some(table) = self.table(*table_id) else {
+                        continue;
+                    };
+                    out.push_str("<table>\n");
+                    for (row_idx, row) in table.rows.iter().enumerate() {
+                        let cell_tag = if row_idx < table.header_rows { "th" } else { "td" };
+                        out.push_str("<tr>");
+                        for cell in &row.cells {
+                            let body = cell.body.html_blocks(notes);
+                            out.push_str(&format!("<{cell_tag}>{}</{cell_tag}>", body.trim_end()));
+                        }
+                        out.push_str("</tr>\n");
+                    }
+                    out.push_str("</table>\n");
+                }
+                _ => {
+                    out.push_str(&format!("<p>{}</p>\n", self.html_inline(&text, meta, notes)));
+                }
+            }
+        }
+
+        while let Some(tag) = lists.pop() {
+            out.push_str(&format!("</li></{}>\n", tag));
+        }
+        out
+    }
+
+    /// Render every paragraph as Markdown blocks, collecting referenced notes
+    fn markdown_blocks(&self, notes: &mut Vec<NoteId>) -> String {
+        let mut out = String::new();
+        let mut in_list = false;
+        let numbers = self.heading_markers();
+
+        for para_id in self.paragraph_order() {
+            let Some(meta) = self.block_meta(para_id) else {
+                continue;
+            };
+            let text = self.paragraph_text(para_id);
+            let is_list = meta.kind.is_list_item();
+
+            // Blocks are separated by blank lines, list items by line breaks
+            let continues_list = is_list && in_list;
+            if !(out.is_empty() || continues_list) {
+                out.push('\n');
+            }
+            in_list = is_list;
+
+            match &meta.kind {
+                BlockKind::Heading { level } => {
+                    let hashes = "#".repeat(*level as usize);
+                    let number = numbers.get(&para_id).map(|n| format!("{n} ")).unwrap_or_default();
+                    out.push_str(&format!("{} {}{}\n", hashes, number, self.markdown_inline(&text, meta, notes)));
+                }
+                BlockKind::ListItem { indent_level, marker, .. } => {
+                    let bullet = match marker {
+                        ListMarker::Bullet => "-".to_string(),
+                        ListMarker::Numbered { ordinal } => format!("{}.", ordinal),
+                    };
+                    let indent = "  ".repeat(*indent_level as usize);
+                    let inline = self.markdown_inline(&text, meta, notes);
+                    out.push_str(&format!("{}{} {}\n", indent, bullet, inline));
+                }
+                BlockKind::Table { table_id } => {
+                    let Some(table) = self.table(*table_id) else {
+                        continue;
+                    };
+                    // Pipe tables always have a header row
+                    for (row_idx, row) in table.rows.iter().enumerate() {
+                        let cells: Vec<String> = row
+                            .cells
+                            .iter()
+                            .map(|cell| {
+                                let body = cell.body.markdown_blocks(notes);
+                                body.trim().replace("\n\n", "<br>").replace('|', "\\|")
+                            })
+                            .collect();
+                        out.push_str(&format!("| {} |\n", cells.join(" | ")));
+                        if row_idx == 0 {
+                            out.push_str(&format!("|{}\n", " --- |".repeat(cells.len())));
+                        }
+                    }
+                }
+                BlockKind::Paragraph | BlockKind::TocEntry { .. } => {
+                    out.push_str(&self.markdown_inline(&text, meta, notes));
+                    out.push('\n');
+                }
+            }
+        }
+        out
+    }
+
+    /// Render a paragraph's text as inline HTML
+    fn html_inline(&self, text: &str, meta: &BlockMeta, notes: &mut Vec<NoteId>) -> String {
+        let mut out = String::new();
+        for (segment, link) in link_segments(text, meta) {
+            if let Some(link) = link {
+                out.push_str(&format!("<a href=\"{}\"", escape_html(&link.href)));
+                if let Some(title) = &link.title {
+                    out.push_str(&format!(" title=\"{}\"", escape_html(title)));
+                }
+                out.push('>');
+            }
+
+            for c in segment.chars() {
+                if let Some(note_id) = NoteId::from_reference_char(c) {
+                    let number = note_number(notes, note_id);
+                    out.push_str(&format!("<sup><a href=\"#note-{number}\">{number}</a></sup>"));
+                } else if let Some(object) = ObjectId::from_replacement_char(c).and_then(|id| self.object(id)) {
+                    out.push_str(&format!(
+                        "<img src=\"{}\" width=\"{}\" height=\"{}\">",
+                        escape_html(&object.source.url()),
+                        object.width,
+                        object.height
+                    ));
+                } else {
+                    out.push_str(&escape_html(c.encode_utf8(&mut [0; 4])));
+                }
+            }
+
+            if link.is_some() {
+                out.push_str("</a>");
+            }
+        }
+        out
+    }
+
+    /// Render a paragraph's text as inline Markdown
+    fn markdown_inline(&self, text: &str, meta: &BlockMeta, notes: &mut Vec<NoteId>) -> String {
+        let mut out = String::new();
+        for (segment, link) in link_segments(text, meta) {
+            let mut inline = String::new();
+            for c in segment.chars() {
+                if let Some(note_id) = NoteId::from_reference_char(c) {
+                    inline.push_str(&format!("[^{}]", note_number(notes, note_id)));
+                } else if let Some(object) = ObjectId::from_replacement_char(c).and_then(|id| self.object(id)) {
+                    inline.push_str(&format!("![]({})", object.source.url()));
+                } else {
+                    if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']' | '<' | '>') {
+                        inline.push('\\');
+                    }
+                    inline.push(c);
+                }
+            }
+
+            match link {
+                Some(Link { href, title: Some(title) }) => {
+                    out.push_str(&format!("[{}]({} \"{}\")", inline, href, title.replace('"', "\\\"")));
+                }
+                Some(Link { href, title: None }) => out.push_str(&format!("[{}]({})", inline, href)),
+                None => out.push_str(&inline),
+            }
+        }
+        out
+    }
+}
+
+/// Split a paragraph's text at link boundaries; unsafe links (see
+/// `Link::is_safe`) are exported as plain text
+fn link_segments<'a>(text: &'a str, meta: &'a BlockMeta) -> Vec<(&'a str, Option<&'a Link>)> {
+    let mut segments = Vec::new();
+    let mut pos = 0;
+    for span in &meta.links {
+        let (Some(before), Some(linked)) = (text.get(pos..span.start), text.get(span.start..span.end)) else {
+            continue;
+        };
+        if !before.is_empty() {
+            segments.push((before, None));
+        }
+        segments.push((linked, Some(&span.link).filter(|link| link.is_safe())));
+        pos = span.end;
+    }
+    if let Some(rest) = text.get(pos..).filter(|rest| !rest.is_empty()) {
+        segments.push((rest, None));
+    }
+    segments
+}
+
+/// Get a note's number, numbering it on first reference
+fn note_number(notes: &mut Vec<NoteId>, note_id: NoteId) -> usize {
+    match notes.iter().position(|id| *id == note_id) {
+        Some(index) => index + 1,
+        None => {
+            notes.push(note_id);
+            notes.len()
+        }
+    }
+}
+
+/// Escape text for HTML content and attribute values
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{InlineObject, ObjectSource};
+    use crate::editing::EditOp;
+
+    fn linked_document() -> Document {
+        let mut doc = Document::from_text("Read the docs & more");
+        let mut meta = doc.block_meta(doc.first_paragraph()).unwrap().clone();
+        meta.set_link(9, 13, Link {
+            href: "https://example.com/docs".to_string(),
+            title: Some("Docs".to_string()),
+        });
+        doc.apply_edit(EditOp::set_block_meta(0, meta));
+        doc
+    }
+
+    #[test]
+    fn test_html_export_carries_links() {
+        let html = linked_document().to_html();
+        assert_eq!(
+            html,
+            "<p>Read the <a href=\"https://example.com/docs\" title=\"Docs\">docs</a> &amp; more</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_export_drops_unsafe_links() {
+        let mut doc = Document::from_text("Click \"me\" now");
+        let mut meta = doc.block_meta(doc.first_paragraph()).unwrap().clone();
+        meta.set_link(6, 10, Link::new("javascript:alert(\"x\")"));
+        meta.set_link(11, 14, Link::new("https://a.example/?q=\"><script>"));
+        doc.apply_edit(EditOp::set_block_meta(0, meta));
+
+        assert_eq!(
+            doc.to_html(),
+            "<p>Click &quot;me&quot; <a href=\"https://a.example/?q=&quot;&gt;&lt;script&gt;\">now</a></p>\n"
+        );
+        assert!(!doc.to_markdown().contains("javascript"));
+    }
+
+    #[test]
+    fn test_markdown_export_carries_links() {
+        let markdown = linked_document().to_markdown();
+        assert_eq!(markdown, "Read the [docs](https://example.com/docs \"Docs\") & more\n");
+    }
+
+    #[test]
+    fn test_export_lists_and_objects() {
+        let mut doc = Document::from_text("Title\none\ntwo\nimage ");
+        let offsets: Vec<_> = doc.paragraph_order().map(|id| doc.block_meta(id).unwrap().clone()).collect();
+        doc.apply_edit(EditOp::set_block_meta(offsets[0].start_offset, BlockMeta {
+            kind: BlockKind::Heading { level: 2 },
+            ..offsets[0].clone()
+        }));
+        for meta in &offsets[1..3] {
+            doc.apply_edit(EditOp::set_block_meta(meta.start_offset, BlockMeta {
+                kind: BlockKind::ListItem {
+                    list_id: crate::document::ListId(0),
+                    indent_level: 0,
+                    marker: ListMarker::Bullet,
+                },
+                ..meta.clone()
+            }));
+        }
+        let object_id = doc.add_object(InlineObject::new(ObjectSource::Url("a.png".to_string()), 10.0, 20.0)).unwrap();
+        doc.apply_edit(EditOp::Insert {
+            position: crate::editing::AbsoluteOffset(doc.len()),
+            text: object_id.replacement_char().to_string(),
+        });
+
+        assert_eq!(
+            doc.to_html(),
+            "<h2>Title</h2>\n<ul><li>one</li>\n<li>two</li></ul>\n<p>image <img src=\"a.png\" width=\"10\" height=\"20\"></p>\n"
+        );
+        assert_eq!(doc.to_markdown(), "## Title\n\n- one\n- two\n\nimage ![](a.png)\n");
+
+        // Numbered headings carry their number
+        doc.set_heading_numbering(Some(crate::document::ListDefinition::outline()));
+        assert!(doc.to_html().starts_with("<h2>1.1 Title</h2>\n"));
+        assert!(doc.to_markdown().starts_with("## 1.1 Title\n"));
+    }
+}
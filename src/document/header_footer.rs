@@ -0,0 +1,245 @@
+//! Page headers and footers with field codes
+
+/// A field resolved when the page is rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldCode {
+    /// 1-based page number
+    PageNumber,
+    /// Total number of pages
+    PageCount,
+    /// Current date
+    Date,
+    /// Document title
+    Title,
+}
+
+impl FieldCode {
+    /// Parse a field name as written in a template (`PAGE`, `NUMPAGES`, `DATE`, `TITLE`)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "PAGE" => Some(FieldCode::PageNumber),
+            "NUMPAGES" => Some(FieldCode::PageCount),
+            "DATE" => Some(FieldCode::Date),
+            "TITLE" => Some(FieldCode::Title),
+            _ => None,
+        }
+    }
+
+    /// Get the field name used in templates
+    pub fn name(&self) -> &'static str {
+        match self {
+            FieldCode::PageNumber => "PAGE",
+            FieldCode::PageCount => "NUMPAGES",
+            FieldCode::Date => "DATE",
+            FieldCode::Title => "TITLE",
+        }
+    }
+}
+
+/// Values available to fields at render time
+#[derive(Debug, Clone, Copy)]
+pub struct FieldContext<'a> {
+    pub page_number: usize,
+    pub page_count: usize,
+    pub date: &'a str,
+    pub title: &'a str,
+}
+
+/// A piece of header/footer content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderFooterRun {
+    Text(String),
+    Field(FieldCode),
+}
+
+/// Horizontal alignment of header/footer lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HeaderFooterAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// Content of a single header or footer
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderFooter {
+    pub runs: Vec<HeaderFooterRun>,
+    pub align: HeaderFooterAlign,
+}
+
+impl HeaderFooter {
+    /// Parse a template such as `"Page {PAGE} of {NUMPAGES}"`
+    ///
+    /// Unknown `{NAME}` sequences are kept as literal text.
+    pub fn parse(template: &str, align: HeaderFooterAlign) -> Self {
+        let mut runs = Vec::new();
+        let mut text = String::new();
+        let mut rest = template;
+
+        while let Some(open) = rest.find('{') {
+            let (before, after) = rest.split_at(open);
+            text.push_str(before);
+
+            let field = after[1..]
+                .find('}')
+                .and_then(|close| Some((FieldCode::from_name(&after[1..close + 1])?, close + 2)));
+            match field {
+                Some((code, consumed)) => {
+                    if !text.is_empty() {
+                        runs.push(HeaderFooterRun::Text(std::mem::take(&mut text)));
+                    }
+                    runs.push(HeaderFooterRun::Field(code));
+                    rest = &after[consumed..];
+                }
+                None => {
+                    text.push('{');
+                    rest = &after[1..];
+                }
+            }
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            runs.push(HeaderFooterRun::Text(text));
+        }
+
+        Self { runs, align }
+    }
+
+    /// Resolve fields into display text
+    pub fn resolve(&self, context: &FieldContext) -> String {
+        let mut out = String::new();
+        for run in &self.runs {
+            match run {
+                HeaderFooterRun::Text(text) => out.push_str(text),
+                HeaderFooterRun::Field(FieldCode::PageNumber) => {
+                    out.push_str(&context.page_number.to_string())
+                }
+                HeaderFooterRun::Field(FieldCode::PageCount) => {
+                    out.push_str(&context.page_count.to_string())
+                }
+                HeaderFooterRun::Field(FieldCode::Date) => out.push_str(context.date),
+                HeaderFooterRun::Field(FieldCode::Title) => out.push_str(context.title),
+            }
+        }
+        out
+    }
+}
+
+/// Which variant of a header or footer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderFooterKind {
+    Default,
+    First,
+    Even,
+}
+
+/// Header or footer variants for a section
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderFooterSet {
+    /// Used on every page without a more specific variant
+    pub default: Option<HeaderFooter>,
+    /// Used on the first page of the section when `different_first_page` is set
+    pub first: Option<HeaderFooter>,
+    /// Used on even pages when `different_odd_even` is set
+    pub even: Option<HeaderFooter>,
+    pub different_first_page: bool,
+    pub different_odd_even: bool,
+}
+
+impl HeaderFooterSet {
+    /// Get a variant slot
+    pub fn variant_mut(&mut self, kind: HeaderFooterKind) -> &mut Option<HeaderFooter> {
+        match kind {
+            HeaderFooterKind::Default => &mut self.default,
+            HeaderFooterKind::First => &mut self.first,
+            HeaderFooterKind::Even => &mut self.even,
+        }
+    }
+
+    /// Pick the variant for a page by its number within the section
+    /// (`None` means the region is blank)
+    pub fn for_page(&self, page_in_section: usize) -> Option<&HeaderFooter> {
+        if self.different_first_page && page_in_section == 1 {
+            self.first.as_ref()
+        } else if self.different_odd_even && page_in_section.is_multiple_of(2) {
+            self.even.as_ref()
+        } else {
+            self.default.as_ref()
+        }
+    }
+}
+
+/// Format a date as `YYYY-MM-DD` for the date field
+pub fn format_date(days_since_epoch: i64) -> String {
+    // Civil-from-days conversion (proleptic Gregorian calendar)
+    let z = days_since_epoch + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Today's date (UTC) for the date field
+pub fn today() -> String {
+    const MS_PER_DAY: f64 = 86_400_000.0;
+
+    #[cfg(target_arch = "wasm32")]
+    let millis = js_sys::Date::now();
+    #[cfg(not(target_arch = "wasm32"))]
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as f64)
+        .unwrap_or(0.0);
+
+    format_date((millis / MS_PER_DAY).floor() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> FieldContext<'static> {
+        FieldContext {
+            page_number: 3,
+            page_count: 10,
+            date: "2024-01-02",
+            title: "Report",
+        }
+    }
+
+    #[test]
+    fn test_parse_and_resolve() {
+        let footer = HeaderFooter::parse("Page {PAGE} of {NUMPAGES}", HeaderFooterAlign::Center);
+        assert_eq!(footer.runs.len(), 4);
+        assert_eq!(footer.resolve(&context()), "Page 3 of 10");
+
+        let header = HeaderFooter::parse("{TITLE} {UNKNOWN} {DATE}", HeaderFooterAlign::Left);
+        assert_eq!(header.resolve(&context()), "Report {UNKNOWN} 2024-01-02");
+    }
+
+    #[test]
+    fn test_variant_selection() {
+        let set = HeaderFooterSet {
+            default: Some(HeaderFooter::parse("odd", HeaderFooterAlign::Left)),
+            first: None,
+            even: Some(HeaderFooter::parse("even", HeaderFooterAlign::Left)),
+            different_first_page: true,
+            different_odd_even: true,
+        };
+        assert_eq!(set.for_page(1), None);
+        assert_eq!(set.for_page(2), set.even.as_ref());
+        assert_eq!(set.for_page(3), set.default.as_ref());
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(19_723), "2024-01-01");
+    }
+}
@@ -0,0 +1,335 @@
+//! List definitions: marker formats, numbering and indents per level
+
+use crate::document::ListId;
+use rustc_hash::FxHashMap;
+
+/// Number of levels a list definition describes
+pub const MAX_LIST_LEVELS: usize = 9;
+
+/// How a level's counter is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberFormat {
+    /// A fixed glyph instead of a number
+    Bullet(char),
+    Decimal,
+    LowerAlpha,
+    UpperAlpha,
+    LowerRoman,
+    UpperRoman,
+}
+
+impl NumberFormat {
+    /// Write a counter value in this format
+    ///
+    /// Letters run `a`…`z`, `aa`, `ab`…; values with no letter or roman
+    /// form (zero) are written in decimal.
+    pub fn format(&self, n: u32) -> String {
+        match self {
+            NumberFormat::Bullet(glyph) => glyph.to_string(),
+            NumberFormat::Decimal => n.to_string(),
+            NumberFormat::LowerAlpha | NumberFormat::UpperAlpha if n > 0 => {
+                let mut letters = Vec::new();
+                let mut rest = n;
+                while rest > 0 {
+                    rest -= 1;
+                    letters.push(b'a' + (rest % 26) as u8);
+                    rest /= 26;
+                }
+                let text: String = letters.iter().rev().map(|&b| b as char).collect();
+                match self {
+                    NumberFormat::UpperAlpha => text.to_ascii_uppercase(),
+                    _ => text,
+                }
+            }
+            NumberFormat::LowerRoman if n > 0 => to_roman(n),
+            NumberFormat::UpperRoman if n > 0 => to_roman(n).to_ascii_uppercase(),
+            _ => n.to_string(),
+        }
+    }
+
+    /// Check if this format writes a glyph rather than a number
+    pub fn is_bullet(&self) -> bool {
+        matches!(self, NumberFormat::Bullet(_))
+    }
+}
+
+/// Format a number as a lowercase roman numeral
+fn to_roman(mut n: u32) -> String {
+    const NUMERALS: [(u32, &str); 13] = [
+        (1000, "m"), (900, "cm"), (500, "d"), (400, "cd"), (100, "c"), (90, "xc"),
+        (50, "l"), (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i"),
+    ];
+    let mut out = String::new();
+    for &(value, numeral) in &NUMERALS {
+        while n >= value {
+            out.push_str(numeral);
+            n -= value;
+        }
+    }
+    out
+}
+
+/// How a marker lines up with its alignment point
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MarkerAlignment {
+    /// Marker starts at the point
+    #[default]
+    Left,
+    /// Marker is centered on the point
+    Center,
+    /// Marker ends at the point
+    Right,
+}
+
+/// Marker format and geometry of one list level
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListLevel {
+    pub format: NumberFormat,
+    /// Marker text for numbered levels; `%1` to `%9` are replaced by the
+    /// counters of levels 1 to 9, each written in its own level's format
+    pub pattern: String,
+    /// First value of the counter
+    pub start: u32,
+    /// Restart the counter after an item at this (zero-based) level or a
+    /// shallower one; `None` keeps counting through the whole list
+    pub restart_after: Option<u8>,
+    pub alignment: MarkerAlignment,
+    /// Distance of the text from the column edge
+    pub indent: f32,
+    /// How far the marker's alignment point hangs left of the text
+    pub hanging: f32,
+}
+
+impl ListLevel {
+    /// Level `level` (zero-based) of a bulleted list
+    pub fn bullet(level: u8, glyph: char) -> Self {
+        Self {
+            format: NumberFormat::Bullet(glyph),
+            pattern: glyph.to_string(),
+            ..Self::numbered(level, NumberFormat::Decimal, "")
+        }
+    }
+
+    /// Level `level` (zero-based) of a numbered list, restarting after
+    /// each item of the level above
+    pub fn numbered(level: u8, format: NumberFormat, pattern: &str) -> Self {
+        Self {
+            format,
+            pattern: pattern.to_string(),
+            start: 1,
+            restart_after: level.checked_sub(1),
+            alignment: MarkerAlignment::Left,
+            indent: 36.0 * (level as f32 + 1.0),
+            hanging: 18.0,
+        }
+    }
+}
+
+/// Levels of a list, shared by every item with its `ListId`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListDefinition {
+    pub levels: Vec<ListLevel>,
+}
+
+impl ListDefinition {
+    /// Bullets cycling through •, ◦ and ▪ by level
+    pub fn bulleted() -> Self {
+        const GLYPHS: [char; 3] = ['•', '◦', '▪'];
+        Self {
+            levels: (0..MAX_LIST_LEVELS as u8)
+                .map(|level| ListLevel::bullet(level, GLYPHS[level as usize % GLYPHS.len()]))
+                .collect(),
+        }
+    }
+
+    /// Numbers cycling through `1.`, `a.` and `i.` by level
+    pub fn numbered() -> Self {
+        const FORMATS: [NumberFormat; 3] = [NumberFormat::Decimal, NumberFormat::LowerAlpha, NumberFormat::LowerRoman];
+        Self {
+            levels: (0..MAX_LIST_LEVELS as u8)
+                .map(|level| {
+                    let pattern = format!("%{}.", level + 1);
+                    ListLevel::numbered(level, FORMATS[level as usize % FORMATS.len()], &pattern)
+                })
+                .collect(),
+        }
+    }
+
+    /// Decimal numbers joining every level above (`1.`, `1.1.`, `1.1.1.`)
+    pub fn multilevel() -> Self {
+        Self {
+            levels: (0..MAX_LIST_LEVELS as u8)
+                .map(|level| {
+                    let pattern: String = (1..=level + 1).map(|l| format!("%{l}.")).collect();
+                    ListLevel::numbered(level, NumberFormat::Decimal, &pattern)
+                })
+                .collect(),
+        }
+    }
+
+    /// Decimal numbers joined by dots (`1`, `1.1`, `1.1.1`), for numbering
+    /// headings by level with the text following the number
+    pub fn outline() -> Self {
+        Self {
+            levels: (0..MAX_LIST_LEVELS as u8)
+                .map(|level| {
+                    let pattern: Vec<String> = (1..=level + 1).map(|l| format!("%{l}")).collect();
+                    ListLevel {
+                        indent: 0.0,
+                        hanging: 0.0,
+                        ..ListLevel::numbered(level, NumberFormat::Decimal, &pattern.join("."))
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Get a level; items deeper than the definition use its last level
+    pub fn level(&self, level: u8) -> Option<&ListLevel> {
+        self.levels.get(level as usize).or(self.levels.last())
+    }
+}
+
+/// Counters of the lists in a document, advanced item by item in
+/// document order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ListCounters {
+    counters: FxHashMap<ListId, [Option<u32>; MAX_LIST_LEVELS]>,
+}
+
+impl ListCounters {
+    /// Count an item at `level` of a list and return its marker text
+    pub fn next(&mut self, list_id: ListId, definition: &ListDefinition, level: u8) -> String {
+        let level = (level as usize).min(MAX_LIST_LEVELS - 1);
+        let start = |l: usize| definition.level(l as u8).map_or(1, |d| d.start);
+        let counters = self.counters.entry(list_id).or_default();
+
+        // Deeper levels restart after this item when their rule says so
+        for (deeper, counter) in counters.iter_mut().enumerate().skip(level + 1) {
+            let restart = definition
+                .level(deeper as u8)
+                .and_then(|d| d.restart_after)
+                .is_some_and(|after| after as usize >= level);
+            if restart {
+                *counter = None;
+            }
+        }
+        counters[level] = Some(counters[level].map_or(start(level), |n| n + 1));
+
+        let Some(current) = definition.level(level as u8) else {
+            return String::new();
+        };
+        if current.format.is_bullet() {
+            return current.format.format(0);
+        }
+
+        let mut text = String::new();
+        let mut chars = current.pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            let placeholder = chars
+                .peek()
+                .and_then(|d| d.to_digit(10))
+                .filter(|d| c == '%' && (1..=MAX_LIST_LEVELS as u32).contains(d));
+            match placeholder {
+                Some(digit) => {
+                    chars.next();
+                    let l = digit as usize - 1;
+                    let format = definition.level(l as u8).map_or(NumberFormat::Decimal, |d| d.format);
+                    text.push_str(&format.format(counters[l].unwrap_or(start(l))));
+                }
+                None => text.push(c),
+            }
+        }
+        text
+    }
+
+    /// Count a heading at `level` of a heading numbering and return its
+    /// number
+    ///
+    /// Unlike list items, levels a heading skips count as started, so the
+    /// next heading at a skipped level comes second.
+    pub fn next_heading(&mut self, definition: &ListDefinition, level: u8) -> String {
+        let level = (level as usize).min(MAX_LIST_LEVELS - 1);
+        let counters = self.counters.entry(ListId(0)).or_default();
+        for (l, counter) in counters.iter_mut().enumerate().take(level) {
+            counter.get_or_insert(definition.level(l as u8).map_or(1, |d| d.start));
+        }
+        self.next(ListId(0), definition, level as u8)
+    }
+}
+
+/// List and heading counters carried through a document in order, so
+/// numbering can resume after any numbered paragraph
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarkerCounters {
+    pub lists: ListCounters,
+    pub headings: ListCounters,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_formats() {
+        assert_eq!(NumberFormat::Decimal.format(12), "12");
+        assert_eq!(NumberFormat::LowerAlpha.format(1), "a");
+        assert_eq!(NumberFormat::LowerAlpha.format(27), "aa");
+        assert_eq!(NumberFormat::UpperAlpha.format(28), "AB");
+        assert_eq!(NumberFormat::LowerRoman.format(14), "xiv");
+        assert_eq!(NumberFormat::UpperRoman.format(1999), "MCMXCIX");
+        assert_eq!(NumberFormat::UpperRoman.format(0), "0");
+        assert_eq!(NumberFormat::Bullet('◦').format(3), "◦");
+    }
+
+    #[test]
+    fn test_multilevel_numbering() {
+        let definition = ListDefinition::multilevel();
+        let mut counters = ListCounters::default();
+        let list = ListId(1);
+        let labels: Vec<String> = [0, 1, 1, 2, 0, 1]
+            .iter()
+            .map(|&level| counters.next(list, &definition, level))
+            .collect();
+        assert_eq!(labels, vec!["1.", "1.1.", "1.2.", "1.2.1.", "2.", "2.1."]);
+
+        // Lists count independently
+        assert_eq!(counters.next(ListId(2), &definition, 0), "1.");
+    }
+
+    #[test]
+    fn test_start_and_restart_rules() {
+        let mut definition = ListDefinition::numbered();
+        definition.levels[0].start = 5;
+        definition.levels[1].restart_after = None;
+        definition.levels[1].format = NumberFormat::UpperRoman;
+        definition.levels[1].pattern = "(%2)".to_string();
+
+        let mut counters = ListCounters::default();
+        let labels: Vec<String> = [0, 1, 0, 1]
+            .iter()
+            .map(|&level| counters.next(ListId(0), &definition, level))
+            .collect();
+        // The second level keeps counting across top-level items
+        assert_eq!(labels, vec!["5.", "(I)", "6.", "(II)"]);
+
+        // Headings skip levels without losing their place; list items
+        // start a skipped level afresh
+        let outline = ListDefinition::outline();
+        let mut headings = ListCounters::default();
+        let labels: Vec<String> = [0, 2, 1, 0]
+            .iter()
+            .map(|&level| headings.next_heading(&outline, level))
+            .collect();
+        assert_eq!(labels, vec!["1", "1.1.1", "1.2", "2"]);
+        let labels: Vec<String> = [0, 2, 1, 0]
+            .iter()
+            .map(|&level| counters.next(ListId(4), &outline, level))
+            .collect();
+        assert_eq!(labels, vec!["1", "1.1.1", "1.1", "2"]);
+
+        // Items deeper than the definition use its last level
+        let short = ListDefinition { levels: vec![ListLevel::bullet(0, '–')] };
+        assert_eq!(counters.next(ListId(3), &short, 4), "–");
+    }
+}
@@ -1,12 +1,38 @@
 //! Document model with rope-based storage
 
 mod block;
+mod decoration;
+mod export;
+mod header_footer;
+mod list;
+mod note;
+mod object;
+mod outline;
 mod paragraph;
 mod rope;
-
-pub use block::{BlockKind, BlockMeta, ListId, ListMarker};
+mod search;
+mod section;
+mod story;
+mod table;
+mod toc;
+
+pub use block::{BlockBreak, BlockKind, BlockMeta, LanguageSpan, LineBreakMode, Link, LinkSpan, ListId, ListMarker, StyleSpan, TabAlignment, TabLeader, TabStop, TextDirection};
+pub use decoration::{Decoration, DecorationId, DecorationKind, DecorationSet};
+pub use header_footer::{
+    format_date, today, FieldCode, FieldContext, HeaderFooter, HeaderFooterAlign,
+    HeaderFooterKind, HeaderFooterRun, HeaderFooterSet,
+};
+pub use list::{ListCounters, ListDefinition, ListLevel, MarkerAlignment, MarkerCounters, NumberFormat, MAX_LIST_LEVELS};
+pub use note::{Note, NoteId, NoteKind};
+pub use object::{InlineObject, ObjectId, ObjectSource, ObjectTable};
+pub use outline::{Outline, OutlineEntry};
 pub use paragraph::{ParagraphId, ParagraphIndex};
-pub use rope::Rope;
+pub use rope::{Chunks, Rope};
+pub use search::{Matches, SearchHighlights, SearchOptions, SearchQuery};
+pub use section::{Section, SectionColumns, SectionId};
+pub use story::StoryId;
+pub use table::{CellId, ColumnWidth, Table, TableCell, TableId, TableRow};
+pub use toc::{heading_anchor, TableOfContents, TocId};
 
 use crate::editing::{AbsoluteOffset, DocPosition, EditOp, EditResult};
 use rustc_hash::FxHashMap;
@@ -25,6 +51,36 @@ pub struct Document {
     version: u64,
     /// Next paragraph ID to assign
     next_para_id: u64,
+    /// Sections referenced by section breaks
+    sections: FxHashMap<SectionId, Section>,
+    /// Next section ID to assign
+    next_section_id: u64,
+    /// Document title (used by the title field)
+    title: String,
+    /// Footnotes and endnotes referenced from the text
+    notes: FxHashMap<NoteId, Note>,
+    /// Next note ID to assign
+    next_note_id: u32,
+    /// Tables anchored by table blocks
+    tables: FxHashMap<TableId, Table>,
+    /// Next table ID to assign
+    next_table_id: u32,
+    /// Inline objects placed by replacement characters
+    objects: ObjectTable,
+    /// Next object ID to assign
+    next_object_id: u32,
+    /// Marker formats of lists, by the ID their items share
+    lists: FxHashMap<ListId, ListDefinition>,
+    /// Next list ID to assign
+    next_list_id: u64,
+    /// Numbering of headings by level, if headings are numbered
+    heading_numbering: Option<ListDefinition>,
+    /// Tables of contents referenced by entry blocks
+    tocs: FxHashMap<TocId, TableOfContents>,
+    /// Next table of contents ID to assign
+    next_toc_id: u32,
+    /// Ranges painted over the text, moved along by edits
+    decorations: DecorationSet,
 }
 
 impl Default for Document {
@@ -38,15 +94,7 @@ impl Document {
     pub fn new() -> Self {
         let first_para = ParagraphId(0);
         let mut blocks = FxHashMap::default();
-        blocks.insert(
-            first_para,
-            BlockMeta {
-                kind: BlockKind::Paragraph,
-                start_offset: 0,
-                byte_len: 0,
-                styles: Vec::new(),
-            },
-        );
+        blocks.insert(first_para, BlockMeta::paragraph(0, 0));
 
         let mut paragraph_index = ParagraphIndex::new();
         paragraph_index.insert(first_para, 0, 0);
@@ -57,6 +105,21 @@ impl Document {
             paragraph_index,
             version: 0,
             next_para_id: 1,
+            sections: default_sections(),
+            next_section_id: 1,
+            title: String::new(),
+            notes: FxHashMap::default(),
+            next_note_id: 0,
+            tables: FxHashMap::default(),
+            next_table_id: 0,
+            objects: ObjectTable::default(),
+            next_object_id: 0,
+            lists: FxHashMap::default(),
+            next_list_id: 0,
+            heading_numbering: None,
+            tocs: FxHashMap::default(),
+            next_toc_id: 0,
+            decorations: DecorationSet::default(),
         }
     }
 
@@ -68,6 +131,21 @@ impl Document {
             paragraph_index: ParagraphIndex::new(),
             version: 0,
             next_para_id: 0,
+            sections: default_sections(),
+            next_section_id: 1,
+            title: String::new(),
+            notes: FxHashMap::default(),
+            next_note_id: 0,
+            tables: FxHashMap::default(),
+            next_table_id: 0,
+            objects: ObjectTable::default(),
+            next_object_id: 0,
+            lists: FxHashMap::default(),
+            next_list_id: 0,
+            heading_numbering: None,
+            tocs: FxHashMap::default(),
+            next_toc_id: 0,
+            decorations: DecorationSet::default(),
         };
 
         // Parse paragraphs (split by double newline or single newline for simplicity)
@@ -77,15 +155,7 @@ impl Document {
             doc.next_para_id += 1;
 
             let para_len = para_text.len();
-            doc.blocks.insert(
-                para_id,
-                BlockMeta {
-                    kind: BlockKind::Paragraph,
-                    start_offset: offset,
-                    byte_len: para_len,
-                    styles: Vec::new(),
-                },
-            );
+            doc.blocks.insert(para_id, BlockMeta::paragraph(offset, para_len));
             doc.paragraph_index.insert(para_id, offset, para_len);
             offset += para_len + 1; // +1 for the newline
         }
@@ -96,15 +166,7 @@ impl Document {
         if doc.blocks.is_empty() {
             let para_id = ParagraphId(doc.next_para_id);
             doc.next_para_id += 1;
-            doc.blocks.insert(
-                para_id,
-                BlockMeta {
-                    kind: BlockKind::Paragraph,
-                    start_offset: 0,
-                    byte_len: 0,
-                    styles: Vec::new(),
-                },
-            );
+            doc.blocks.insert(para_id, BlockMeta::paragraph(0, 0));
             doc.paragraph_index.insert(para_id, 0, 0);
         }
 
@@ -146,6 +208,283 @@ impl Document {
         self.blocks.get(&para_id)
     }
 
+    /// Register a new section and return its ID
+    pub fn add_section(&mut self, section: Section) -> SectionId {
+        let id = SectionId(self.next_section_id);
+        self.next_section_id += 1;
+        self.sections.insert(id, section);
+        id
+    }
+
+    /// Get a section by ID
+    pub fn section(&self, id: SectionId) -> Option<&Section> {
+        self.sections.get(&id)
+    }
+
+    /// Get a mutable section by ID
+    pub fn section_mut(&mut self, id: SectionId) -> Option<&mut Section> {
+        self.sections.get_mut(&id)
+    }
+
+    /// All sections with their IDs (in no particular order)
+    pub fn sections(&self) -> impl Iterator<Item = (SectionId, &Section)> {
+        self.sections.iter().map(|(&id, section)| (id, section))
+    }
+
+    /// Get the section a paragraph belongs to
+    pub fn section_of(&self, para_id: ParagraphId) -> SectionId {
+        let mut current = Some(para_id);
+        while let Some(id) = current {
+            if let Some(BlockBreak::Section(section_id)) =
+                self.blocks.get(&id).and_then(|meta| meta.break_before)
+            {
+                return section_id;
+            }
+            current = self.paragraph_index.prev(id);
+        }
+        SectionId::DEFAULT
+    }
+
+    /// Register a new note and return its ID; returns None once every
+    /// marker character is taken
+    ///
+    /// The note is not referenced until its marker character is inserted.
+    pub fn add_note(&mut self, note: Note) -> Option<NoteId> {
+        let id = NoteId::new(self.next_note_id)?;
+        self.next_note_id += 1;
+        self.notes.insert(id, note);
+        Some(id)
+    }
+
+    /// Get a note by ID
+    pub fn note(&self, id: NoteId) -> Option<&Note> {
+        self.notes.get(&id)
+    }
+
+    /// Get a mutable note by ID
+    pub fn note_mut(&mut self, id: NoteId) -> Option<&mut Note> {
+        self.notes.get_mut(&id)
+    }
+
+    /// Find the offset of a note's reference marker in the text
+    pub fn note_reference_offset(&self, id: NoteId) -> Option<AbsoluteOffset> {
+        self.find_char(id.reference_char()).map(AbsoluteOffset)
+    }
+
+    /// Find the first occurrence of a character, reading the rope's leaves
+    /// in place
+    fn find_char(&self, c: char) -> Option<usize> {
+        let mut offset = 0;
+        for chunk in self.content.chunks_at(0) {
+            if let Some(found) = chunk.find(c) {
+                return Some(offset + found);
+            }
+            offset += chunk.len();
+        }
+        None
+    }
+
+    /// Register a new inline object and return its ID; returns None once
+    /// every replacement character is taken
+    ///
+    /// The object is not shown until its replacement character is inserted.
+    pub fn add_object(&mut self, object: InlineObject) -> Option<ObjectId> {
+        let id = ObjectId::new(self.next_object_id)?;
+        self.next_object_id += 1;
+        self.objects.insert(id, object);
+        Some(id)
+    }
+
+    /// Get an inline object by ID
+    pub fn object(&self, id: ObjectId) -> Option<&InlineObject> {
+        self.objects.get(&id)
+    }
+
+    /// Get the object table
+    pub fn objects(&self) -> &ObjectTable {
+        &self.objects
+    }
+
+    /// Get the decorations painted over the text
+    pub fn decorations(&self) -> &DecorationSet {
+        &self.decorations
+    }
+
+    /// Get the decorations for adding and removing them
+    pub fn decorations_mut(&mut self) -> &mut DecorationSet {
+        &mut self.decorations
+    }
+
+    /// Find the offset of an object's replacement character in the text
+    pub fn object_offset(&self, id: ObjectId) -> Option<AbsoluteOffset> {
+        self.find_char(id.replacement_char()).map(AbsoluteOffset)
+    }
+
+    /// Get the link at a position
+    pub fn link_at(&self, pos: &DocPosition) -> Option<&LinkSpan> {
+        self.block_meta(pos.para_id)?.link_at(pos.offset)
+    }
+
+    /// Register a list definition and return the ID for its items
+    pub fn add_list(&mut self, definition: ListDefinition) -> ListId {
+        let id = ListId(self.next_list_id);
+        self.next_list_id += 1;
+        self.lists.insert(id, definition);
+        id
+    }
+
+    /// Get the definition of a list
+    pub fn list(&self, id: ListId) -> Option<&ListDefinition> {
+        self.lists.get(&id)
+    }
+
+    /// Define (or redefine) the list with an ID
+    pub fn set_list(&mut self, id: ListId, definition: ListDefinition) {
+        self.next_list_id = self.next_list_id.max(id.0 + 1);
+        self.lists.insert(id, definition);
+    }
+
+    /// Marker text of every list item, numbered in document order
+    ///
+    /// Items of lists without a definition keep their own marker.
+    pub fn list_markers(&self) -> FxHashMap<ParagraphId, String> {
+        let mut counters = MarkerCounters::default();
+        self.paragraph_order()
+            .filter_map(|para_id| Some((para_id, self.count_marker(para_id, &mut counters)?)))
+            .filter(|&(para_id, _)| matches!(self.block_meta(para_id).map(|m| &m.kind), Some(BlockKind::ListItem { .. })))
+            .collect()
+    }
+
+    /// Count a list item or numbered heading in with the markers before
+    /// it and return its marker text; `None` for other paragraphs
+    pub fn count_marker(&self, para_id: ParagraphId, counters: &mut MarkerCounters) -> Option<String> {
+        match &self.block_meta(para_id)?.kind {
+            BlockKind::ListItem { list_id, indent_level, marker } => Some(match self.lists.get(list_id) {
+                Some(definition) => counters.lists.next(*list_id, definition, *indent_level),
+                None => marker.display(),
+            }),
+            BlockKind::Heading { level } => {
+                let numbering = self.heading_numbering.as_ref()?;
+                Some(counters.headings.next_heading(numbering, level.saturating_sub(1)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the numbering of headings (level 1 headings use the
+    /// definition's first level)
+    pub fn heading_numbering(&self) -> Option<&ListDefinition> {
+        self.heading_numbering.as_ref()
+    }
+
+    /// Number headings by level, or stop numbering them with `None`
+    pub fn set_heading_numbering(&mut self, numbering: Option<ListDefinition>) {
+        self.heading_numbering = numbering;
+    }
+
+    /// Number of every heading, counted in document order (empty unless
+    /// headings are numbered)
+    pub fn heading_markers(&self) -> FxHashMap<ParagraphId, String> {
+        if self.heading_numbering.is_none() {
+            return FxHashMap::default();
+        }
+        let mut counters = MarkerCounters::default();
+        self.paragraph_order()
+            .filter(|&para_id| matches!(self.block_meta(para_id).map(|m| &m.kind), Some(BlockKind::Heading { .. })))
+            .filter_map(|para_id| Some((para_id, self.count_marker(para_id, &mut counters)?)))
+            .collect()
+    }
+
+    /// Register a new table and return its ID
+    ///
+    /// The table is not shown until a table block refers to it.
+    pub fn add_table(&mut self, table: Table) -> TableId {
+        let id = TableId(self.next_table_id);
+        self.next_table_id += 1;
+        self.tables.insert(id, table);
+        id
+    }
+
+    /// Register a new table of contents and return its ID
+    ///
+    /// The table of contents is not shown until entry blocks refer to it.
+    pub fn add_toc(&mut self, toc: TableOfContents) -> TocId {
+        let id = TocId(self.next_toc_id);
+        self.next_toc_id += 1;
+        self.tocs.insert(id, toc);
+        id
+    }
+
+    /// Get a table of contents by ID
+    pub fn toc(&self, id: TocId) -> Option<&TableOfContents> {
+        self.tocs.get(&id)
+    }
+
+    /// Get a table by ID
+    pub fn table(&self, id: TableId) -> Option<&Table> {
+        self.tables.get(&id)
+    }
+
+    /// Get a mutable table by ID
+    pub fn table_mut(&mut self, id: TableId) -> Option<&mut Table> {
+        self.tables.get_mut(&id)
+    }
+
+    /// Get a table cell
+    pub fn cell(&self, cell: CellId) -> Option<&TableCell> {
+        self.tables.get(&cell.table_id)?.cell(cell.row, cell.col)
+    }
+
+    /// Get the table anchored by a paragraph, if it is a table block
+    pub fn table_at(&self, para_id: ParagraphId) -> Option<TableId> {
+        match self.blocks.get(&para_id)?.kind {
+            BlockKind::Table { table_id } => Some(table_id),
+            _ => None,
+        }
+    }
+
+    /// Find the paragraph anchoring a table
+    pub fn table_paragraph(&self, id: TableId) -> Option<ParagraphId> {
+        self.paragraph_order().find(|&para_id| self.table_at(para_id) == Some(id))
+    }
+
+    /// Get the document holding a story (falls back to the body)
+    pub fn story(&self, story: StoryId) -> &Document {
+        match story {
+            StoryId::Body => self,
+            StoryId::Note(id) => self.notes.get(&id).map_or(self, |note| &note.body),
+            StoryId::Cell(cell) => self.cell(cell).map_or(self, |cell| &cell.body),
+        }
+    }
+
+    /// Get the mutable document holding a story (falls back to the body)
+    pub fn story_mut(&mut self, story: StoryId) -> &mut Document {
+        match story {
+            StoryId::Note(id) if self.notes.contains_key(&id) => {
+                &mut self.notes.get_mut(&id).unwrap().body
+            }
+            StoryId::Cell(cell) if self.cell(cell).is_some() => {
+                &mut self
+                    .tables
+                    .get_mut(&cell.table_id)
+                    .and_then(|table| table.cell_mut(cell.row, cell.col))
+                    .unwrap()
+                    .body
+            }
+            _ => self,
+        }
+    }
+
+    /// Get the document title
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Set the document title
+    pub fn set_title(&mut self, title: impl Into<String>) {
+        self.title = title.into();
+    }
+
     /// Get the first paragraph ID
     pub fn first_paragraph(&self) -> ParagraphId {
         self.paragraph_index.first()
@@ -194,6 +533,11 @@ impl Document {
         self.paragraph_index.para_at_offset(offset).0
     }
 
+    /// Get next paragraph
+    pub fn next_paragraph(&self, para_id: ParagraphId) -> Option<ParagraphId> {
+        self.paragraph_index.next(para_id)
+    }
+
     /// Get previous paragraph
     pub fn prev_paragraph(&self, para_id: ParagraphId) -> Option<ParagraphId> {
         self.paragraph_index.prev(para_id)
@@ -249,6 +593,18 @@ impl Document {
         match op {
             EditOp::Insert { position, text } => self.apply_insert(position, &text),
             EditOp::Delete { start, end } => self.apply_delete(start, end),
+            EditOp::SetBlockMeta { position, meta } => self.apply_set_block_meta(position, meta),
+            EditOp::ResizeObject {
+                position,
+                object_id,
+                width,
+                height,
+            } => self.apply_resize_object(position, object_id, width, height),
+            EditOp::SetSection {
+                position,
+                section_id,
+                section,
+            } => self.apply_set_section(position, section_id, section),
             EditOp::Transaction { ops } => {
                 let mut result = EditResult {
                     version: self.version,
@@ -282,6 +638,12 @@ impl Document {
 
         // Insert into rope
         self.content.insert(position.0, text);
+        self.decorations.on_insert(position.0, text.len());
+
+        // Shift offsets for paragraphs after insertion point (before any
+        // paragraphs are split off, so the new ones are not shifted twice)
+        self.shift_block_offsets_after(position.0, text.len() as isize);
+        self.paragraph_index.update_lengths_after(position.0, text.len() as isize);
 
         // Check for new paragraph boundaries
         let newline_positions: Vec<_> = text
@@ -297,7 +659,6 @@ impl Document {
                 meta.on_insert(offset_in_para, text.len());
                 meta.byte_len += text.len();
             }
-            self.paragraph_index.update_lengths_after(position.0, text.len() as isize);
         } else {
             // Split paragraph at newlines
             // First, insert text into original paragraph styles (logically)
@@ -318,6 +679,13 @@ impl Document {
                 // However, on_insert updated the styles.
                 
                 let mut current_start = meta.start_offset;
+                // New paragraphs keep the base direction, language, line
+                // breaking and tab stops of the one they split from
+                let direction = meta.direction;
+                let language = meta.language.clone();
+                let line_break = meta.line_break;
+                let tab_stops = meta.tab_stops.clone();
+                let tab_interval = meta.tab_interval;
                 // We need to carry over styles from previous split to next
                 let mut current_styles = meta.styles; // These are expanded styles
 
@@ -358,12 +726,24 @@ impl Document {
                 let (first_styles, remainder) = split_styles(current_styles, first_seg_len);
                 rest_styles = remainder;
 
+                // Links and language spans are split the same way, dropping each newline
+                let mut link_carrier = BlockMeta {
+                    links: meta.links,
+                    language_spans: meta.language_spans,
+                    ..BlockMeta::paragraph(0, 0)
+                };
+                let mut rest_links = link_carrier.split_links_at(first_seg_len);
+                let mut rest_languages = link_carrier.split_language_spans_at(first_seg_len);
+
                 if let Some(m) = self.blocks.get_mut(&para_id) {
                     m.byte_len = first_seg_len;
                     m.styles = first_styles;
+                    m.links = link_carrier.links;
+                    m.language_spans = link_carrier.language_spans;
                 }
                 self.paragraph_index.update_length(para_id, first_seg_len);
                 current_start += first_seg_len + 1; // +1 for newline
+                let mut prev_para = para_id;
 
                 // Create new paragraphs for each segment
                 for (i, &nl_pos) in newline_positions.iter().enumerate() {
@@ -383,29 +763,41 @@ impl Document {
                     let (seg_styles, remainder) = split_styles(rest_styles, segment_len);
                     rest_styles = remainder;
 
+                    let mut link_carrier = BlockMeta {
+                        links: rest_links,
+                        language_spans: rest_languages,
+                        ..BlockMeta::paragraph(0, 0)
+                    };
+                    link_carrier.on_delete(0, 1);
+                    rest_links = link_carrier.split_links_at(segment_len);
+                    rest_languages = link_carrier.split_language_spans_at(segment_len);
+
                     let new_para = ParagraphId(self.next_para_id);
                     self.next_para_id += 1;
 
                     self.blocks.insert(
                         new_para,
                         BlockMeta {
-                            kind: BlockKind::Paragraph,
-                            start_offset: current_start,
-                            byte_len: segment_len,
                             styles: seg_styles,
+                            links: link_carrier.links,
+                            language_spans: link_carrier.language_spans,
+                            direction,
+                            language: language.clone(),
+                            line_break,
+                            tab_stops: tab_stops.clone(),
+                            tab_interval,
+                            ..BlockMeta::paragraph(current_start, segment_len)
                         },
                     );
-                    self.paragraph_index.insert_after(para_id, new_para, current_start, segment_len);
+                    self.paragraph_index.insert_after(prev_para, new_para, current_start, segment_len);
                     created.push(new_para);
+                    prev_para = new_para;
 
                     current_start += segment_len + 1;
                 }
             }
         }
 
-        // Shift offsets for paragraphs after insertion point
-        self.shift_block_offsets_after(position.0, text.len() as isize);
-
         let new_offset = AbsoluteOffset(position.0 + text.len());
         let new_cursor = self.offset_to_position(new_offset);
 
@@ -435,12 +827,15 @@ impl Document {
 
         // Find affected paragraphs
         let (start_para, start_para_offset) = self.paragraph_index.para_at_offset(start.0);
-        let (end_para, _) = self.paragraph_index.para_at_offset(end.0.saturating_sub(1));
+        // A range ending at the start of a paragraph takes the newline
+        // before it, so that paragraph is merged into the first one
+        let (end_para, _) = self.paragraph_index.para_at_offset(end.0);
 
         affected.push(start_para);
 
         // Delete from rope
         self.content.delete(start.0, end.0);
+        self.decorations.on_delete(start.0, end.0);
 
         let delete_len = end.0 - start.0;
 
@@ -450,8 +845,8 @@ impl Document {
                 let offset_in_para = start.0 - meta.start_offset;
                 meta.on_delete(offset_in_para, offset_in_para + delete_len);
                 meta.byte_len = meta.byte_len.saturating_sub(delete_len);
+                self.paragraph_index.update_length(start_para, meta.byte_len);
             }
-            self.paragraph_index.update_lengths_after(start.0, -(delete_len as isize));
         } else {
             // Multiple paragraphs: merge first and last, delete middle ones
             let mut paras_to_check: Vec<_> = self.paragraph_index.iter().collect();
@@ -490,6 +885,9 @@ impl Document {
                                 start_meta.byte_len = new_start_len + remaining_in_end;
                                 
                                 start_meta.append_styles(end_meta_mod.styles, new_start_len);
+                                start_meta.append_links(end_meta_mod.links, new_start_len);
+                                start_meta.append_language_spans(end_meta_mod.language_spans, new_start_len);
+                                self.paragraph_index.update_length(start_para, start_meta.byte_len);
                             }
                         }
                         self.blocks.remove(&end_para);
@@ -508,6 +906,7 @@ impl Document {
 
         // Shift offsets for paragraphs after deletion
         self.shift_block_offsets_after(start.0, -(delete_len as isize));
+        self.paragraph_index.update_lengths_after(start.0, -(delete_len as isize));
 
         EditResult {
             version: self.version,
@@ -518,6 +917,102 @@ impl Document {
         }
     }
 
+    /// Apply a block metadata replacement
+    ///
+    /// Everything except the offsets is taken from `meta`; style, link and language
+    /// spans are clipped to the current length of the paragraph.
+    fn apply_set_block_meta(&mut self, position: AbsoluteOffset, meta: BlockMeta) -> EditResult {
+        let mut affected = SmallVec::new();
+        let para_id = self.paragraph_index.para_at_offset(position.0).0;
+
+        if let Some(block) = self.blocks.get_mut(&para_id) {
+            let byte_len = block.byte_len;
+            block.kind = meta.kind;
+            block.break_before = meta.break_before;
+            block.direction = meta.direction;
+            block.language = meta.language;
+            block.line_break = meta.line_break;
+            block.tab_stops = meta.tab_stops;
+            block.tab_interval = meta.tab_interval;
+            block.styles = meta
+                .styles
+                .into_iter()
+                .filter(|s| s.start < byte_len)
+                .map(|mut s| {
+                    s.end = s.end.min(byte_len);
+                    s
+                })
+                .collect();
+            block.links = meta
+                .links
+                .into_iter()
+                .filter(|l| l.start < byte_len)
+                .map(|mut l| {
+                    l.end = l.end.min(byte_len);
+                    l
+                })
+                .collect();
+            block.language_spans = meta
+                .language_spans
+                .into_iter()
+                .filter(|l| l.start < byte_len)
+                .map(|mut l| {
+                    l.end = l.end.min(byte_len);
+                    l
+                })
+                .collect();
+            affected.push(para_id);
+        }
+
+        EditResult {
+            version: self.version,
+            affected_paragraphs: affected,
+            created_paragraphs: SmallVec::new(),
+            deleted_paragraphs: SmallVec::new(),
+            new_cursor: self.offset_to_position(position),
+        }
+    }
+
+    /// Apply an object resize
+    fn apply_resize_object(
+        &mut self,
+        position: AbsoluteOffset,
+        object_id: ObjectId,
+        width: f32,
+        height: f32,
+    ) -> EditResult {
+        let mut affected = SmallVec::new();
+        if let Some(object) = self.objects.get_mut(&object_id) {
+            object.width = width;
+            object.height = height;
+            affected.push(self.paragraph_index.para_at_offset(position.0).0);
+        }
+
+        EditResult {
+            version: self.version,
+            affected_paragraphs: affected,
+            created_paragraphs: SmallVec::new(),
+            deleted_paragraphs: SmallVec::new(),
+            new_cursor: self.offset_to_position(position),
+        }
+    }
+
+    /// Apply a section replacement
+    ///
+    /// No paragraph is affected: the layout compares section widths itself,
+    /// and headers and footers are drawn from the section on every frame.
+    fn apply_set_section(&mut self, position: AbsoluteOffset, section_id: SectionId, section: Section) -> EditResult {
+        self.sections.insert(section_id, section);
+
+        EditResult {
+            version: self.version,
+            affected_paragraphs: SmallVec::new(),
+            created_paragraphs: SmallVec::new(),
+            deleted_paragraphs: SmallVec::new(),
+            new_cursor: self.offset_to_position(position),
+        }
+    }
+
     /// Shift block offsets after a position
     fn shift_block_offsets_after(&mut self, after_offset: usize, delta: isize) {
         for (_, meta) in self.blocks.iter_mut() {
@@ -586,6 +1081,29 @@ impl Document {
         }
     }
 
+    /// Clone the metadata of all paragraphs intersecting a byte range
+    fn block_metas_in_range(&self, start: usize, end: usize) -> Vec<BlockMeta> {
+        let (start_para, _) = self.paragraph_index.para_at_offset(start);
+        let (end_para, _) = self.paragraph_index.para_at_offset(end);
+
+        let mut metas = Vec::new();
+        let mut in_range = false;
+        for para_id in self.paragraph_index.iter() {
+            if para_id == start_para {
+                in_range = true;
+            }
+            if in_range {
+                if let Some(meta) = self.blocks.get(&para_id) {
+                    metas.push(meta.clone());
+                }
+                if para_id == end_para {
+                    break;
+                }
+            }
+        }
+        metas
+    }
+
     /// Compute the reverse operation for undo
     pub fn compute_reverse(&self, op: &EditOp) -> EditOp {
         match op {
@@ -595,11 +1113,61 @@ impl Document {
             },
             EditOp::Delete { start, end } => {
                 let deleted_text = self.text_range(start.0..end.0);
-                EditOp::Insert {
+                let insert = EditOp::Insert {
                     position: *start,
                     text: deleted_text,
+                };
+
+                // Re-inserting text only recreates plain paragraphs, so restore
+                // the metadata of every paragraph the deletion touches
+                let metas = self.block_metas_in_range(start.0, end.0);
+                if metas.len() == 1
+                    && metas[0].styles.is_empty()
+                    && metas[0].links.is_empty()
+                    && metas[0].language_spans.is_empty()
+                {
+                    insert
+                } else {
+                    let mut ops = vec![insert];
+                    ops.extend(metas.into_iter().map(|meta| EditOp::SetBlockMeta {
+                        position: AbsoluteOffset(meta.start_offset),
+                        meta,
+                    }));
+                    EditOp::Transaction { ops }
+                }
+            }
+            EditOp::SetBlockMeta { position, .. } => {
+                let para_id = self.paragraph_index.para_at_offset(position.0).0;
+                EditOp::SetBlockMeta {
+                    position: *position,
+                    meta: self
+                        .blocks
+                        .get(&para_id)
+                        .cloned()
+                        .unwrap_or_else(|| BlockMeta::paragraph(position.0, 0)),
+                }
+            }
+            EditOp::ResizeObject {
+                position,
+                object_id,
+                ..
+            } => {
+                let (width, height) = self
+                    .objects
+                    .get(object_id)
+                    .map_or((0.0, 0.0), |object| (object.width, object.height));
+                EditOp::ResizeObject {
+                    position: *position,
+                    object_id: *object_id,
+                    width,
+                    height,
                 }
             }
+            EditOp::SetSection { position, section_id, .. } => EditOp::SetSection {
+                position: *position,
+                section_id: *section_id,
+                section: self.sections.get(section_id).cloned().unwrap_or_default(),
+            },
             EditOp::Transaction { ops } => EditOp::Transaction {
                 ops: ops.iter().rev().map(|op| self.compute_reverse(op)).collect(),
             },
@@ -607,6 +1175,26 @@ impl Document {
     }
 }
 
+/// Remove the characters reserved for note references and inline objects
+/// from typed or pasted text, so that it cannot place notes or objects it
+/// did not create
+pub fn strip_markers(text: &str) -> std::borrow::Cow<'_, str> {
+    let is_marker =
+        |c: char| NoteId::from_reference_char(c).is_some() || ObjectId::from_replacement_char(c).is_some();
+    if text.contains(is_marker) {
+        text.chars().filter(|&c| !is_marker(c)).collect::<String>().into()
+    } else {
+        text.into()
+    }
+}
+
+/// Section table of a fresh document
+fn default_sections() -> FxHashMap<SectionId, Section> {
+    let mut sections = FxHashMap::default();
+    sections.insert(SectionId::DEFAULT, Section::default());
+    sections
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -636,6 +1224,53 @@ mod tests {
         assert_eq!(result.affected_paragraphs.len(), 1);
     }
 
+    #[test]
+    fn test_insert_several_paragraphs() {
+        let mut doc = Document::from_text("Hello\nWorld");
+        doc.apply_edit(EditOp::Insert {
+            position: AbsoluteOffset(5),
+            text: "\nA\nB".to_string(),
+        });
+        assert_eq!(doc.text(), "Hello\nA\nB\nWorld");
+
+        let paragraphs: Vec<_> = doc.paragraph_order().map(|id| doc.paragraph_text(id)).collect();
+        assert_eq!(paragraphs, vec!["Hello", "A", "B", "World"]);
+        let starts: Vec<_> = doc
+            .paragraph_order()
+            .map(|id| doc.block_meta(id).unwrap().start_offset)
+            .collect();
+        assert_eq!(starts, vec![0, 6, 8, 10]);
+    }
+
+    #[test]
+    fn test_links_split_and_merge_with_paragraphs() {
+        let mut doc = Document::from_text("Hello World");
+        let para = doc.first_paragraph();
+        let mut meta = doc.block_meta(para).unwrap().clone();
+        meta.set_link(2, 8, Link::new("https://example.com"));
+        doc.apply_edit(EditOp::set_block_meta(0, meta));
+
+        // Breaking the paragraph inside the link splits it
+        doc.apply_edit(EditOp::Insert {
+            position: AbsoluteOffset(4),
+            text: "\n".to_string(),
+        });
+        let second = doc.para_at_offset(5);
+        let ranges = |doc: &Document, id| {
+            doc.block_meta(id).unwrap().links.iter().map(|l| (l.start, l.end)).collect::<Vec<_>>()
+        };
+        assert_eq!(ranges(&doc, para), vec![(2, 4)]);
+        assert_eq!(ranges(&doc, second), vec![(0, 4)]);
+
+        // Joining them again appends the second half
+        doc.apply_edit(EditOp::delete(4, 5));
+        assert_eq!(ranges(&doc, para), vec![(2, 4), (4, 8)]);
+        assert_eq!(
+            doc.link_at(&DocPosition::new(para, 6)).map(|l| l.link.href.as_str()),
+            Some("https://example.com")
+        );
+    }
+
     #[test]
     fn test_delete() {
         let mut doc = Document::from_text("Hello World");
@@ -645,4 +1280,25 @@ mod tests {
         });
         assert_eq!(doc.text(), "Hello");
     }
+
+    #[test]
+    fn test_undo_delete_restores_block_meta() {
+        let mut doc = Document::from_text("Hello\nWorld");
+        let second = doc.para_at_offset(6);
+        let meta = BlockMeta {
+            break_before: Some(BlockBreak::Page),
+            ..doc.block_meta(second).unwrap().clone()
+        };
+        doc.apply_edit(EditOp::set_block_meta(6, meta));
+
+        let op = EditOp::delete(3, 8);
+        let reverse = doc.compute_reverse(&op);
+        doc.apply_edit(op);
+        assert_eq!(doc.text(), "Helrld");
+
+        doc.apply_edit(reverse);
+        assert_eq!(doc.text(), "Hello\nWorld");
+        let second = doc.para_at_offset(6);
+        assert_eq!(doc.block_meta(second).unwrap().break_before, Some(BlockBreak::Page));
+    }
 }
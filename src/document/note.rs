@@ -0,0 +1,81 @@
+//! Footnotes and endnotes
+//!
+//! A note is referenced from body text by a single marker character and
+//! keeps its content in its own `Document`. The marker encodes the note ID
+//! in the supplementary private use area, so references move, delete and
+//! come back with ordinary text edits and undo.
+
+use crate::document::Document;
+
+/// First code point used for note reference markers
+const NOTE_REF_BASE: u32 = 0xF0000;
+
+/// Number of note IDs that fit in the marker range
+const NOTE_REF_COUNT: u32 = 0xFFFE;
+
+/// Unique identifier for a note
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NoteId(pub u32);
+
+impl NoteId {
+    /// Create a note ID; returns None if it has no marker character
+    pub fn new(id: u32) -> Option<NoteId> {
+        (id < NOTE_REF_COUNT).then_some(NoteId(id))
+    }
+
+    /// Get the marker character that references this note in body text
+    pub fn reference_char(self) -> char {
+        char::from_u32(NOTE_REF_BASE + self.0).unwrap_or(char::REPLACEMENT_CHARACTER)
+    }
+
+    /// Decode a note reference marker
+    pub fn from_reference_char(c: char) -> Option<NoteId> {
+        let code = c as u32;
+        (NOTE_REF_BASE..NOTE_REF_BASE + NOTE_REF_COUNT)
+            .contains(&code)
+            .then(|| NoteId(code - NOTE_REF_BASE))
+    }
+}
+
+/// Where a note's content is placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteKind {
+    /// Bottom of the page holding the reference
+    Footnote,
+    /// Collected at the end of the document
+    Endnote,
+}
+
+/// A footnote or endnote
+#[derive(Debug)]
+pub struct Note {
+    pub kind: NoteKind,
+    /// Note content as its own block sequence
+    pub body: Document,
+}
+
+impl Note {
+    /// Create an empty note
+    pub fn new(kind: NoteKind) -> Self {
+        Self {
+            kind,
+            body: Document::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference_char_roundtrip() {
+        let id = NoteId(42);
+        assert_eq!(NoteId::from_reference_char(id.reference_char()), Some(id));
+        assert_eq!(NoteId::from_reference_char('a'), None);
+
+        // IDs past the marker range are refused rather than wrapped
+        assert!(NoteId::new(NOTE_REF_COUNT - 1).is_some());
+        assert!(NoteId::new(NOTE_REF_COUNT).is_none());
+    }
+}
@@ -0,0 +1,140 @@
+//! Inline objects (images and other embedded content)
+//!
+//! An object sits in the text as a single replacement character that
+//! encodes its ID, like note references, and keeps its source and size in
+//! the owning document's object table. Objects outlive their character so
+//! that undoing a deletion brings them back.
+
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+/// First code point used for object replacement characters
+const OBJECT_CHAR_BASE: u32 = 0x100000;
+
+/// Number of object IDs that fit in the replacement character range
+const OBJECT_CHAR_COUNT: u32 = 0xFFFE;
+
+/// Objects of a document by ID
+pub type ObjectTable = FxHashMap<ObjectId, InlineObject>;
+
+/// Unique identifier for an inline object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ObjectId(pub u32);
+
+impl ObjectId {
+    /// Create an object ID; returns None if it has no replacement character
+    pub fn new(id: u32) -> Option<ObjectId> {
+        (id < OBJECT_CHAR_COUNT).then_some(ObjectId(id))
+    }
+
+    /// Get the replacement character that places this object in text
+    pub fn replacement_char(self) -> char {
+        char::from_u32(OBJECT_CHAR_BASE + self.0).unwrap_or(char::REPLACEMENT_CHARACTER)
+    }
+
+    /// Decode an object replacement character
+    pub fn from_replacement_char(c: char) -> Option<ObjectId> {
+        let code = c as u32;
+        (OBJECT_CHAR_BASE..OBJECT_CHAR_BASE + OBJECT_CHAR_COUNT)
+            .contains(&code)
+            .then(|| ObjectId(code - OBJECT_CHAR_BASE))
+    }
+}
+
+/// Where an object's content comes from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ObjectSource {
+    /// Embedded bytes with their MIME type
+    Bytes { mime_type: String, data: Vec<u8> },
+    /// External resource
+    Url(String),
+}
+
+impl ObjectSource {
+    /// Get a URL for the content; embedded bytes become a `data:` URL
+    pub fn url(&self) -> String {
+        match self {
+            ObjectSource::Url(url) => url.clone(),
+            ObjectSource::Bytes { mime_type, data } => {
+                format!("data:{};base64,{}", mime_type, base64_encode(data))
+            }
+        }
+    }
+}
+
+/// An inline object placed in the text
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InlineObject {
+    pub source: ObjectSource,
+    /// Natural size of the content
+    pub intrinsic_width: f32,
+    pub intrinsic_height: f32,
+    /// Displayed size
+    pub width: f32,
+    pub height: f32,
+}
+
+impl InlineObject {
+    /// Create an object displayed at its intrinsic size
+    pub fn new(source: ObjectSource, width: f32, height: f32) -> Self {
+        Self {
+            source,
+            intrinsic_width: width,
+            intrinsic_height: height,
+            width,
+            height,
+        }
+    }
+}
+
+/// Encode bytes as standard base64
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replacement_char_roundtrip() {
+        let id = ObjectId(7);
+        assert_eq!(ObjectId::from_replacement_char(id.replacement_char()), Some(id));
+        assert_eq!(ObjectId::from_replacement_char('\u{F0001}'), None);
+
+        // IDs past the character range are refused rather than wrapped
+        assert!(ObjectId::new(OBJECT_CHAR_COUNT - 1).is_some());
+        assert!(ObjectId::new(OBJECT_CHAR_COUNT).is_none());
+    }
+
+    #[test]
+    fn test_data_url() {
+        let source = ObjectSource::Bytes {
+            mime_type: "image/png".to_string(),
+            data: b"hi!?".to_vec(),
+        };
+        assert_eq!(source.url(), "data:image/png;base64,aGkhPw==");
+    }
+
+    #[test]
+    fn test_serialize_object() {
+        let object = InlineObject::new(ObjectSource::Url("logo.png".to_string()), 64.0, 32.0);
+        let json = serde_json::to_string(&object).unwrap();
+        let back: InlineObject = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, object);
+    }
+}
@@ -0,0 +1,176 @@
+//! Document outline: the heading tree shown by navigation panes
+//!
+//! The editor keeps the headings of the body text in an `Outline`, updated
+//! from the paragraphs each `EditResult` touches rather than by scanning the
+//! document, so refreshing a navigator after an edit stays cheap. Page
+//! indices come from the layout when the tree is built.
+
+use crate::document::{BlockKind, Document, ParagraphId};
+use crate::editing::EditResult;
+use rustc_hash::FxHashMap;
+
+/// A heading in the outline, with the headings nested below it
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineEntry {
+    pub para_id: ParagraphId,
+    /// Heading level (1-6)
+    pub level: u8,
+    /// Plain text of the heading
+    pub text: String,
+    /// Page the heading starts on
+    pub page_index: usize,
+    /// Deeper headings up to the next heading of this level or above
+    pub children: Vec<OutlineEntry>,
+}
+
+/// Headings of a document, kept current from edit results
+#[derive(Debug, Default)]
+pub struct Outline {
+    /// Level and text of each heading
+    headings: FxHashMap<ParagraphId, (u8, String)>,
+    /// Whether `headings` matches the document; cleared to rebuild it
+    built: bool,
+}
+
+impl Outline {
+    /// Update the headings touched by an edit
+    pub fn update(&mut self, document: &Document, result: &EditResult) {
+        if !self.built {
+            return;
+        }
+        for para_id in &result.deleted_paragraphs {
+            self.headings.remove(para_id);
+        }
+        for &para_id in result.affected_paragraphs.iter().chain(&result.created_paragraphs) {
+            match Self::heading(document, para_id) {
+                Some(heading) => self.headings.insert(para_id, heading),
+                None => self.headings.remove(&para_id),
+            };
+        }
+    }
+
+    /// Rebuild from the whole document on next use, after changes that
+    /// report no edit result (undo, redo, replacing the document)
+    pub fn invalidate(&mut self) {
+        self.built = false;
+    }
+
+    /// Headings with their levels and text, in document order
+    pub fn headings(&mut self, document: &Document) -> Vec<(ParagraphId, u8, &str)> {
+        if !self.built {
+            self.headings = document
+                .paragraph_order()
+                .filter_map(|para_id| Some((para_id, Self::heading(document, para_id)?)))
+                .collect();
+            self.built = true;
+        }
+
+        // Paragraphs merged away without being reported leave stale entries
+        self.headings.retain(|&para_id, _| document.block_meta(para_id).is_some());
+        let mut headings: Vec<_> = self
+            .headings
+            .iter()
+            .map(|(&para_id, (level, text))| (para_id, *level, text.as_str()))
+            .collect();
+        headings.sort_by_key(|&(para_id, ..)| document.block_meta(para_id).map_or(0, |m| m.start_offset));
+        headings
+    }
+
+    /// Nest headings (in document order) under the nearest earlier
+    /// heading of a shallower level
+    pub fn tree(headings: impl IntoIterator<Item = OutlineEntry>) -> Vec<OutlineEntry> {
+        // Open headings, each still collecting children
+        let mut open: Vec<OutlineEntry> = Vec::new();
+        let mut roots = Vec::new();
+        let close = |open: &mut Vec<OutlineEntry>, roots: &mut Vec<OutlineEntry>| {
+            let entry = open.pop().expect("an open heading");
+            match open.last_mut() {
+                Some(parent) => parent.children.push(entry),
+                None => roots.push(entry),
+            }
+        };
+
+        for entry in headings {
+            while open.last().is_some_and(|last| last.level >= entry.level) {
+                close(&mut open, &mut roots);
+            }
+            open.push(entry);
+        }
+        while !open.is_empty() {
+            close(&mut open, &mut roots);
+        }
+        roots
+    }
+
+    /// Level and text of a paragraph, if it is a heading
+    fn heading(document: &Document, para_id: ParagraphId) -> Option<(u8, String)> {
+        match document.block_meta(para_id)?.kind {
+            BlockKind::Heading { level } => Some((level, document.toc_entry_text(para_id))),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::BlockMeta;
+    use crate::editing::{AbsoluteOffset, EditOp};
+
+    fn set_heading(doc: &mut Document, para_id: ParagraphId, level: u8) -> EditResult {
+        let meta = doc.block_meta(para_id).unwrap().clone();
+        doc.apply_edit(EditOp::set_block_meta(meta.start_offset, BlockMeta {
+            kind: BlockKind::Heading { level },
+            ..meta
+        }))
+    }
+
+    #[test]
+    fn test_incremental_headings() {
+        let mut doc = Document::from_text("Intro\nBody\nDetail");
+        let ids: Vec<ParagraphId> = doc.paragraph_order().collect();
+        set_heading(&mut doc, ids[2], 2);
+
+        let mut outline = Outline::default();
+        assert_eq!(outline.headings(&doc), vec![(ids[2], 2, "Detail")]);
+
+        // Headings made or edited after the first read are picked up
+        let result = set_heading(&mut doc, ids[0], 1);
+        outline.update(&doc, &result);
+        let result = doc.apply_edit(EditOp::Insert {
+            position: AbsoluteOffset(5),
+            text: "!".to_string(),
+        });
+        outline.update(&doc, &result);
+        assert_eq!(outline.headings(&doc), vec![(ids[0], 1, "Intro!"), (ids[2], 2, "Detail")]);
+
+        // Merging the heading into the paragraph above removes it
+        let start = doc.block_meta(ids[2]).unwrap().start_offset;
+        let result = doc.apply_edit(EditOp::Delete {
+            start: AbsoluteOffset(start - 1),
+            end: AbsoluteOffset(start),
+        });
+        outline.update(&doc, &result);
+        assert_eq!(outline.headings(&doc), vec![(ids[0], 1, "Intro!")]);
+    }
+
+    #[test]
+    fn test_tree() {
+        let entry = |id: u64, level: u8| OutlineEntry {
+            para_id: ParagraphId(id),
+            level,
+            text: String::new(),
+            page_index: 0,
+            children: Vec::new(),
+        };
+        let tree = Outline::tree([entry(0, 2), entry(1, 1), entry(2, 3), entry(3, 2), entry(4, 1)]);
+
+        let shape: Vec<(u64, Vec<u64>)> = tree
+            .iter()
+            .map(|e| (e.para_id.0, e.children.iter().map(|c| c.para_id.0).collect()))
+            .collect();
+        // A heading before any shallower one stays at the top
+        assert_eq!(shape, vec![(0, vec![]), (1, vec![2, 3]), (4, vec![])]);
+        assert_eq!(tree[1].children[0].level, 3);
+    }
+}
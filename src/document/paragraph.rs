@@ -94,22 +94,22 @@ impl ParagraphIndex {
             .map(|(&id, _)| id)
             .collect();
 
+        // Remove all old offset mappings first, so a shifted start never
+        // collides with one that has not been shifted yet
+        for para_id in &to_update {
+            if let Some((start, _)) = self.para_bounds.get(para_id) {
+                self.offset_to_para.remove(start);
+            }
+        }
+
         // Update offsets
         for para_id in to_update {
-            if let Some((start, _)) = self.para_bounds.get(&para_id).copied() {
-                // Remove old offset mapping
-                self.offset_to_para.remove(&start);
-                
+            if let Some((s, _)) = self.para_bounds.get_mut(&para_id) {
                 // Calculate new offset
-                let new_start = (start as isize + delta) as usize;
-                
-                // Update bounds
-                if let Some((s, _)) = self.para_bounds.get_mut(&para_id) {
-                    *s = new_start;
-                }
-                
+                *s = (*s as isize + delta) as usize;
+
                 // Add new offset mapping
-                self.offset_to_para.insert(new_start, para_id);
+                self.offset_to_para.insert(*s, para_id);
             }
         }
     }
@@ -151,6 +151,12 @@ impl ParagraphIndex {
         self.offset_to_para.range(start_offset..).map(|(_, v)| v).copied()
     }
 
+    /// Iterate back over the paragraphs starting at or before an offset,
+    /// from the one holding it to the first
+    pub fn iter_back_from(&self, offset: usize) -> impl Iterator<Item = ParagraphId> + '_ {
+        self.offset_to_para.range(..=offset).rev().map(|(_, v)| v).copied()
+    }
+
     /// Get paragraph count
     pub fn len(&self) -> usize {
         self.order.len()
@@ -311,6 +311,28 @@ impl Rope {
         result
     }
 
+    /// Iterate over the text from an offset as the leaves store it,
+    /// without copying
+    pub fn chunks_at(&self, offset: usize) -> Chunks<'_> {
+        let mut stack = Vec::new();
+        let mut node = &self.root;
+        let mut offset = offset;
+        while let RopeNode::Branch { left, right, left_weight, .. } = node {
+            if offset < *left_weight {
+                stack.push(&**right);
+                node = left;
+            } else {
+                offset -= left_weight;
+                node = right;
+            }
+        }
+        let first = match node {
+            RopeNode::Leaf { text, .. } => text.get(offset..),
+            _ => None,
+        };
+        Chunks { first, stack }
+    }
+
     /// Rebalance tree if needed
     fn rebalance_if_needed(&mut self) {
         let height = self.root.height();
@@ -330,6 +352,34 @@ impl Rope {
     }
 }
 
+/// Text of a rope in leaf-sized pieces (see `Rope::chunks_at`)
+pub struct Chunks<'a> {
+    /// Rest of the leaf the iteration started in
+    first: Option<&'a str>,
+    /// Subtrees still to visit, the next on top
+    stack: Vec<&'a RopeNode>,
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        if let Some(first) = self.first.take() {
+            return Some(first);
+        }
+        while let Some(mut node) = self.stack.pop() {
+            while let RopeNode::Branch { left, right, .. } = node {
+                self.stack.push(right);
+                node = left;
+            }
+            if let RopeNode::Leaf { text, .. } = node {
+                return Some(text);
+            }
+        }
+        None
+    }
+}
+
 impl Default for RopeNode {
     fn default() -> Self {
         RopeNode::Empty
@@ -474,6 +524,19 @@ mod tests {
         assert_eq!(rope.slice(7, 12), "World");
     }
 
+    #[test]
+    fn test_chunks_at() {
+        let text: String = (0..500).map(|i| format!("line {i}\n")).collect();
+        let mut rope = Rope::from_str(&text);
+        rope.insert(10, "inserted");
+        let text = rope.to_string();
+
+        assert_eq!(rope.chunks_at(0).collect::<String>(), text);
+        assert_eq!(rope.chunks_at(2500).collect::<String>(), text[2500..]);
+        assert!(rope.chunks_at(0).count() > 1);
+        assert_eq!(rope.chunks_at(text.len()).collect::<String>(), "");
+    }
+
     #[test]
     fn test_line_count() {
         let rope = Rope::from_str("Line 1\nLine 2\nLine 3");
@@ -0,0 +1,359 @@
+//! Find and replace: text search over the rope
+//!
+//! Searching walks the rope's leaves a paragraph at a time, so only the
+//! paragraph being matched is copied out rather than the whole text. Matches
+//! never cross a paragraph break. Every mode compiles to one regular
+//! expression: plain text is escaped, whole words are wrapped in word
+//! boundaries and case folding is a matcher option. Highlighted matches are
+//! kept in `SearchHighlights` and searched again only in edited paragraphs.
+
+use super::rope::Chunks;
+use super::{Document, ParagraphId};
+use crate::editing::EditResult;
+use regex::{Regex, RegexBuilder};
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::ops::Range;
+
+/// How a search pattern matches text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SearchOptions {
+    /// Match letter case exactly
+    pub case_sensitive: bool,
+    /// Only match whole words
+    pub whole_word: bool,
+    /// The pattern is a regular expression (replacements may use `$1`, `$name`)
+    pub regex: bool,
+}
+
+/// A compiled search pattern
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    regex: Regex,
+    options: SearchOptions,
+}
+
+impl SearchQuery {
+    /// Compile a pattern; returns None for an empty pattern or an invalid
+    /// regular expression
+    pub fn new(pattern: &str, options: SearchOptions) -> Option<Self> {
+        if pattern.is_empty() {
+            return None;
+        }
+        let mut source = if options.regex { pattern.to_string() } else { regex::escape(pattern) };
+        if options.whole_word {
+            source = format!(r"\b(?:{source})\b");
+        }
+        let regex = RegexBuilder::new(&source)
+            .case_insensitive(!options.case_sensitive)
+            .build()
+            .ok()?;
+        Some(Self { regex, options })
+    }
+
+    /// Options the query was compiled with
+    pub fn options(&self) -> SearchOptions {
+        self.options
+    }
+
+    /// Byte ranges of the (non-empty) matches in a paragraph's text
+    pub fn matches_in<'t>(&'t self, text: &'t str) -> impl Iterator<Item = Range<usize>> + 't {
+        self.regex
+            .find_iter(text)
+            .filter(|m| !m.is_empty())
+            .map(|m| m.range())
+    }
+
+    /// Text replacing the match at a range of a paragraph: regular
+    /// expressions expand group references, plain text is used as is
+    pub fn replacement(&self, text: &str, range: Range<usize>, replacement: &str) -> String {
+        if !self.options.regex {
+            return replacement.to_string();
+        }
+        let mut expanded = String::new();
+        if let Some(captures) = self.regex.captures_at(text, range.start) {
+            captures.expand(replacement, &mut expanded);
+        }
+        expanded
+    }
+}
+
+/// Matches of a query in document order, as absolute byte ranges
+pub struct Matches<'a> {
+    query: &'a SearchQuery,
+    chunks: Chunks<'a>,
+    /// Unread part of the current chunk
+    pending: &'a str,
+    /// Text of the paragraph last searched
+    paragraph: String,
+    /// Offset of the next paragraph to search
+    next_start: usize,
+    /// Matches of the paragraph last searched not yet returned
+    found: VecDeque<Range<usize>>,
+    done: bool,
+}
+
+impl Iterator for Matches<'_> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Range<usize>> {
+        loop {
+            if let Some(range) = self.found.pop_front() {
+                return Some(range);
+            }
+            if self.done {
+                return None;
+            }
+            self.search_paragraph();
+        }
+    }
+}
+
+impl Matches<'_> {
+    /// Gather the next paragraph from the chunks and match it
+    fn search_paragraph(&mut self) {
+        let start = self.next_start;
+        self.paragraph.clear();
+        loop {
+            if self.pending.is_empty() {
+                match self.chunks.next() {
+                    Some(chunk) => self.pending = chunk,
+                    None => {
+                        self.done = true;
+                        break;
+                    }
+                }
+            }
+            match self.pending.find('\n') {
+                Some(end) => {
+                    self.paragraph.push_str(&self.pending[..end]);
+                    self.pending = &self.pending[end + 1..];
+                    break;
+                }
+                None => {
+                    self.paragraph.push_str(self.pending);
+                    self.pending = "";
+                }
+            }
+        }
+        self.next_start = start + self.paragraph.len() + 1;
+        let found = self.query.matches_in(&self.paragraph).map(|range| start + range.start..start + range.end);
+        self.found.extend(found);
+    }
+}
+
+impl Document {
+    /// Matches of a query from the start of the paragraph holding an offset
+    /// to the end of the document
+    pub fn find_iter<'a>(&'a self, query: &'a SearchQuery, from: usize) -> Matches<'a> {
+        let (_, from) = self.paragraph_index.para_at_offset(from);
+        Matches {
+            query,
+            chunks: self.content.chunks_at(from),
+            pending: "",
+            paragraph: String::new(),
+            next_start: from,
+            found: VecDeque::new(),
+            done: false,
+        }
+    }
+}
+
+impl Document {
+    /// Last match of a query starting before an offset, searching back a
+    /// paragraph at a time from the one holding it
+    pub fn find_before(&self, query: &SearchQuery, offset: usize) -> Option<Range<usize>> {
+        self.paragraph_index.iter_back_from(offset).find_map(|para_id| {
+            let (start, _) = self.paragraph_index.bounds(para_id)?;
+            let text = self.paragraph_text(para_id);
+            query
+                .matches_in(&text)
+                .map(|range| start + range.start..start + range.end)
+                .take_while(|range| range.start < offset)
+                .last()
+        })
+    }
+}
+
+/// Matches of the highlighted search in each body paragraph
+///
+/// The matches are kept from one display list to the next, and only
+/// paragraphs an edit result touched are searched again, so highlighting
+/// does not repeat the search on every frame.
+#[derive(Debug, Default)]
+pub struct SearchHighlights {
+    /// Pattern and options the matches are for
+    query: Option<(String, SearchOptions)>,
+    /// Matches of each paragraph that has any, relative to its start
+    matches: FxHashMap<ParagraphId, Vec<Range<usize>>>,
+    /// Paragraphs edited since they were searched
+    stale: FxHashSet<ParagraphId>,
+}
+
+impl SearchHighlights {
+    /// Mark the paragraphs touched by an edit for searching again
+    pub fn update(&mut self, result: &EditResult) {
+        for para_id in &result.deleted_paragraphs {
+            self.matches.remove(para_id);
+        }
+        self.stale.extend(result.affected_paragraphs.iter().chain(&result.created_paragraphs));
+    }
+
+    /// Search the whole document again on next refresh, after changes that
+    /// report no edit result (undo, redo, replacing the document)
+    pub fn invalidate(&mut self) {
+        self.query = None;
+    }
+
+    /// Bring the matches up to date with the document and the query being
+    /// highlighted
+    pub fn refresh(&mut self, document: &Document, query: Option<&SearchQuery>) {
+        let key = query.map(|query| (query.regex.as_str().to_string(), query.options));
+        let Some(query) = query else {
+            self.query = None;
+            self.matches.clear();
+            self.stale.clear();
+            return;
+        };
+        if key != self.query {
+            self.query = key;
+            self.matches.clear();
+            self.stale.clear();
+            for para_id in document.paragraph_order() {
+                self.search(document, query, para_id);
+            }
+            return;
+        }
+        for para_id in std::mem::take(&mut self.stale) {
+            self.search(document, query, para_id);
+        }
+    }
+
+    /// Matches of a query in a paragraph, relative to its start; searched
+    /// afresh when the highlights are for another query or out of date
+    pub fn matches<'a>(&'a self, query: &SearchQuery, para_id: ParagraphId, text: &str) -> Cow<'a, [Range<usize>]> {
+        let current = self.query.as_ref().is_some_and(|(pattern, options)| {
+            *options == query.options && pattern == query.regex.as_str()
+        });
+        if !current || self.stale.contains(&para_id) {
+            return Cow::Owned(query.matches_in(text).collect());
+        }
+        Cow::Borrowed(self.matches.get(&para_id).map_or(&[], Vec::as_slice))
+    }
+
+    /// Search one paragraph
+    fn search(&mut self, document: &Document, query: &SearchQuery, para_id: ParagraphId) {
+        if document.block_meta(para_id).is_none() {
+            self.matches.remove(&para_id);
+            return;
+        }
+        let text = document.paragraph_text(para_id);
+        let found: Vec<Range<usize>> = query.matches_in(&text).collect();
+        if found.is_empty() {
+            self.matches.remove(&para_id);
+        } else {
+            self.matches.insert(para_id, found);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(text: &str, pattern: &str, options: SearchOptions) -> Vec<Range<usize>> {
+        let doc = Document::from_text(text);
+        let query = SearchQuery::new(pattern, options).unwrap();
+        doc.find_iter(&query, 0).collect()
+    }
+
+    #[test]
+    fn test_search_modes() {
+        let text = "Cat scatter\ncat CAT";
+        let plain = SearchOptions::default();
+        assert_eq!(find(text, "cat", plain), vec![0..3, 5..8, 12..15, 16..19]);
+
+        let exact = SearchOptions { case_sensitive: true, ..plain };
+        assert_eq!(find(text, "cat", exact), vec![5..8, 12..15]);
+
+        let words = SearchOptions { whole_word: true, ..plain };
+        assert_eq!(find(text, "cat", words), vec![0..3, 12..15, 16..19]);
+
+        // Matches stay within a paragraph
+        let regex = SearchOptions { regex: true, ..plain };
+        assert!(find(text, r"r\s+c", regex).is_empty());
+        assert_eq!(find(text, r"c\w+r", regex), vec![5..11]);
+        assert!(SearchQuery::new("(", regex).is_none());
+        assert!(SearchQuery::new("(", plain).is_some());
+    }
+
+    #[test]
+    fn test_search_across_leaves() {
+        // Text over many rope leaves, with paragraphs split between them
+        let text: String = (0..300).map(|i| format!("paragraph {i} needle {i}\n")).collect();
+        let query = SearchQuery::new("needle 299", SearchOptions::default()).unwrap();
+        let doc = Document::from_text(&text);
+        let start = text.find("needle 299").unwrap();
+        assert_eq!(doc.find_iter(&query, 0).collect::<Vec<_>>(), vec![start..start + 10]);
+
+        // Searching from an offset starts at its paragraph
+        let query = SearchQuery::new("needle", SearchOptions::default()).unwrap();
+        let from = text.find("paragraph 150").unwrap() + 3;
+        assert_eq!(doc.find_iter(&query, from).count(), 150);
+    }
+
+    #[test]
+    fn test_find_before() {
+        let doc = Document::from_text("cat one\ncat two cat\nnone");
+        let query = SearchQuery::new("cat", SearchOptions::default()).unwrap();
+        assert_eq!(doc.find_before(&query, 16), Some(8..11));
+        assert_eq!(doc.find_before(&query, 17), Some(16..19));
+        assert_eq!(doc.find_before(&query, 8), Some(0..3));
+        assert_eq!(doc.find_before(&query, 0), None);
+        // Past the end, the last match in the document
+        assert_eq!(doc.find_before(&query, usize::MAX), Some(16..19));
+    }
+
+    #[test]
+    fn test_highlights_follow_edits() {
+        use crate::editing::{AbsoluteOffset, EditOp};
+
+        let mut doc = Document::from_text("cat one\ndog\ncat cat");
+        let ids: Vec<ParagraphId> = doc.paragraph_order().collect();
+        let query = SearchQuery::new("cat", SearchOptions::default()).unwrap();
+        let mut highlights = SearchHighlights::default();
+        highlights.refresh(&doc, Some(&query));
+        let cached = |highlights: &SearchHighlights, doc: &Document, para_id| {
+            let matches = highlights.matches(&query, para_id, &doc.paragraph_text(para_id));
+            assert!(matches!(matches, Cow::Borrowed(_)), "searched {para_id:?} again");
+            matches.into_owned()
+        };
+        assert_eq!(cached(&highlights, &doc, ids[0]), vec![0..3]);
+        assert_eq!(cached(&highlights, &doc, ids[2]), vec![0..3, 4..7]);
+
+        // Only the edited paragraph is searched again
+        let result = doc.apply_edit(EditOp::Insert {
+            position: AbsoluteOffset(8),
+            text: "cat".to_string(),
+        });
+        highlights.update(&result);
+        assert!(matches!(highlights.matches(&query, ids[1], &doc.paragraph_text(ids[1])), Cow::Owned(_)));
+        highlights.refresh(&doc, Some(&query));
+        assert_eq!(cached(&highlights, &doc, ids[1]), vec![0..3]);
+
+        // Another query is searched afresh until the highlights catch up
+        let other = SearchQuery::new("dog", SearchOptions::default()).unwrap();
+        assert_eq!(highlights.matches(&other, ids[1], "catdog").into_owned(), vec![3..6]);
+        highlights.refresh(&doc, None);
+        assert!(matches!(highlights.matches(&query, ids[0], "cat"), Cow::Owned(_)));
+    }
+
+    #[test]
+    fn test_regex_replacement() {
+        let query = SearchQuery::new(r"(\w+)@(\w+)", SearchOptions { regex: true, ..Default::default() }).unwrap();
+        let text = "mail bob@example now";
+        let range = query.matches_in(text).next().unwrap();
+        assert_eq!(query.replacement(text, range, "$2 at $1"), "example at bob");
+    }
+}
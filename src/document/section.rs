@@ -0,0 +1,94 @@
+//! Document sections with their own page setup
+
+use crate::document::HeaderFooterSet;
+use crate::layout::LayoutConstraints;
+
+/// Unique identifier for a section
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SectionId(pub u64);
+
+impl SectionId {
+    /// The section that starts at the beginning of the document
+    pub const DEFAULT: SectionId = SectionId(0);
+}
+
+/// Columns the body text of a section's pages flows through
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SectionColumns {
+    /// Number of columns (at least one)
+    pub count: usize,
+    /// Space between neighbouring columns
+    pub gap: f32,
+    /// Draw a line down the middle of each gap
+    pub separator: bool,
+}
+
+impl Default for SectionColumns {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            gap: 36.0,
+            separator: false,
+        }
+    }
+}
+
+impl SectionColumns {
+    /// Create equal columns with a gap between them
+    pub fn new(count: usize, gap: f32) -> Self {
+        Self {
+            count: count.max(1),
+            gap,
+            separator: false,
+        }
+    }
+
+    /// Width of each column within a content area
+    pub fn width(&self, content_width: f32) -> f32 {
+        let count = self.count.max(1) as f32;
+        ((content_width - self.gap * (count - 1.0)) / count).max(0.0)
+    }
+
+    /// Offset of a column's left edge from the content area's
+    pub fn offset(&self, column: usize, content_width: f32) -> f32 {
+        column as f32 * (self.width(content_width) + self.gap)
+    }
+
+    /// Column under an offset from the content area's left edge (points in
+    /// a gap belong to the nearer column)
+    pub fn column_at(&self, x: f32, content_width: f32) -> usize {
+        let pitch = self.width(content_width) + self.gap;
+        if pitch <= 0.0 {
+            return 0;
+        }
+        let column = ((x + self.gap / 2.0) / pitch).floor().max(0.0) as usize;
+        column.min(self.count.max(1) - 1)
+    }
+}
+
+/// Page setup shared by a run of pages
+///
+/// A section starts at a block whose `break_before` is
+/// `BlockBreak::Section` and lasts until the next section break.
+/// Pages before the first section break belong to `SectionId::DEFAULT`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Section {
+    /// Page constraints for this section (`None` uses the layout defaults)
+    pub constraints: Option<LayoutConstraints>,
+    /// Headers drawn in the top margin
+    pub headers: HeaderFooterSet,
+    /// Footers drawn in the bottom margin
+    pub footers: HeaderFooterSet,
+    /// Columns of the body text
+    pub columns: SectionColumns,
+}
+
+impl Section {
+    /// Create a section with its own page constraints
+    pub fn with_constraints(constraints: LayoutConstraints) -> Self {
+        Self {
+            constraints: Some(constraints),
+            ..Self::default()
+        }
+    }
+}
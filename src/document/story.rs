@@ -0,0 +1,18 @@
+//! Text flows that can hold the cursor
+
+use crate::document::{CellId, NoteId};
+
+/// A text flow that can hold the cursor
+///
+/// Every story other than the body is a `Document` owned by the body
+/// document (note content, table cells).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum StoryId {
+    /// Main document text
+    #[default]
+    Body,
+    /// Content of a footnote or endnote
+    Note(NoteId),
+    /// Content of a table cell
+    Cell(CellId),
+}
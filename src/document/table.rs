@@ -0,0 +1,140 @@
+//! Tables
+//!
+//! A table is anchored in the body by an empty paragraph whose block kind
+//! is `BlockKind::Table`. Each cell keeps its paragraphs in its own
+//! `Document`, so cell text is edited like any other story.
+
+use crate::document::Document;
+
+/// Unique identifier for a table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TableId(pub u32);
+
+/// Address of a cell within a table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CellId {
+    pub table_id: TableId,
+    pub row: usize,
+    pub col: usize,
+}
+
+/// How a column's width is chosen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnWidth {
+    /// Share of the space left by fixed and percent columns, weighted by content
+    Auto,
+    /// Fixed width in points
+    Fixed(f32),
+    /// Percentage of the available width
+    Percent(f32),
+}
+
+/// A table cell
+#[derive(Debug, Default)]
+pub struct TableCell {
+    /// Cell content as its own block sequence
+    pub body: Document,
+}
+
+/// A table row
+#[derive(Debug, Default)]
+pub struct TableRow {
+    pub cells: Vec<TableCell>,
+}
+
+impl TableRow {
+    /// Create a row of empty cells
+    pub fn new(columns: usize) -> Self {
+        Self {
+            cells: (0..columns).map(|_| TableCell::default()).collect(),
+        }
+    }
+}
+
+/// A table block
+#[derive(Debug)]
+pub struct Table {
+    pub columns: Vec<ColumnWidth>,
+    pub rows: Vec<TableRow>,
+    /// Leading rows repeated at the top of each page the table continues on
+    pub header_rows: usize,
+    /// Space between a cell's border and its content
+    pub cell_padding: f32,
+    /// Border line width (0 draws no borders)
+    pub border_width: f32,
+}
+
+impl Table {
+    /// Create a table of empty cells with auto-width columns
+    pub fn new(rows: usize, columns: usize) -> Self {
+        Self {
+            columns: vec![ColumnWidth::Auto; columns.max(1)],
+            rows: (0..rows.max(1)).map(|_| TableRow::new(columns.max(1))).collect(),
+            header_rows: 0,
+            cell_padding: 4.0,
+            border_width: 1.0,
+        }
+    }
+
+    /// Get the number of rows
+    pub fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Get the number of columns
+    pub fn column_count(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Get a cell
+    pub fn cell(&self, row: usize, col: usize) -> Option<&TableCell> {
+        self.rows.get(row)?.cells.get(col)
+    }
+
+    /// Get a mutable cell
+    pub fn cell_mut(&mut self, row: usize, col: usize) -> Option<&mut TableCell> {
+        self.rows.get_mut(row)?.cells.get_mut(col)
+    }
+
+    /// Insert an empty row before `index` (clamped to the row count)
+    pub fn insert_row(&mut self, index: usize) {
+        let index = index.min(self.rows.len());
+        self.rows.insert(index, TableRow::new(self.columns.len()));
+    }
+
+    /// Get the cell after `(row, col)` in reading order
+    pub fn next_cell(&self, row: usize, col: usize) -> Option<(usize, usize)> {
+        if col + 1 < self.column_count() {
+            Some((row, col + 1))
+        } else {
+            (row + 1 < self.row_count()).then_some((row + 1, 0))
+        }
+    }
+
+    /// Get the cell before `(row, col)` in reading order
+    pub fn prev_cell(&self, row: usize, col: usize) -> Option<(usize, usize)> {
+        if col > 0 {
+            Some((row, col - 1))
+        } else {
+            (row > 0).then(|| (row - 1, self.column_count() - 1))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cell_traversal() {
+        let mut table = Table::new(2, 2);
+        assert_eq!(table.next_cell(0, 1), Some((1, 0)));
+        assert_eq!(table.next_cell(1, 1), None);
+        assert_eq!(table.prev_cell(1, 0), Some((0, 1)));
+        assert_eq!(table.prev_cell(0, 0), None);
+
+        table.insert_row(2);
+        assert_eq!(table.row_count(), 3);
+        assert_eq!(table.rows[2].cells.len(), 2);
+    }
+}
@@ -0,0 +1,124 @@
+//! Tables of contents
+//!
+//! A table of contents is a run of paragraphs whose block kind is
+//! `BlockKind::TocEntry`. Each entry is generated from a heading: its text,
+//! a tab with a dot leader to a right-aligned page number, and a link to the
+//! heading's anchor. The entries are regenerated by the editor's update
+//! operation, which needs the page layout.
+
+use crate::document::{BlockKind, Document, ParagraphId};
+
+/// Unique identifier for a table of contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TocId(pub u32);
+
+/// Settings of a table of contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableOfContents {
+    /// Deepest heading level listed (1-6)
+    pub max_level: u8,
+}
+
+impl TableOfContents {
+    /// List headings down to a level
+    pub fn new(max_level: u8) -> Self {
+        Self {
+            max_level: max_level.clamp(1, 6),
+        }
+    }
+}
+
+/// Prefix of the link target of a heading
+const ANCHOR_PREFIX: &str = "#heading-";
+
+/// Link target of a heading
+pub fn heading_anchor(para_id: ParagraphId) -> String {
+    format!("{ANCHOR_PREFIX}{}", para_id.0)
+}
+
+impl Document {
+    /// Paragraph a heading anchor (`heading_anchor`) points to, if it
+    /// still exists
+    pub fn anchor_target(&self, href: &str) -> Option<ParagraphId> {
+        let id = href.strip_prefix(ANCHOR_PREFIX)?.parse().ok()?;
+        let para_id = ParagraphId(id);
+        self.block_meta(para_id).is_some().then_some(para_id)
+    }
+
+    /// Entry paragraphs of a table of contents, in document order
+    pub fn toc_paragraphs(&self, toc_id: TocId) -> Vec<ParagraphId> {
+        self.paragraph_order()
+            .filter(|&para_id| {
+                self.block_meta(para_id)
+                    .is_some_and(|meta| matches!(meta.kind, BlockKind::TocEntry { toc_id: id, .. } if id == toc_id))
+            })
+            .collect()
+    }
+
+    /// Whether the entries of a table of contents follow each other with no
+    /// other paragraphs between them
+    pub fn toc_is_contiguous(&self, toc_id: TocId) -> bool {
+        let entries = self.toc_paragraphs(toc_id);
+        let Some(start) = entries.first().and_then(|&id| self.block_meta(id)) else {
+            return true;
+        };
+        self.paragraphs_from(start.start_offset)
+            .take(entries.len())
+            .eq(entries.iter().copied())
+    }
+
+    /// Headings a table of contents lists, with their levels, in document
+    /// order
+    pub fn toc_headings(&self, toc_id: TocId) -> Vec<(ParagraphId, u8)> {
+        let Some(toc) = self.toc(toc_id) else {
+            return Vec::new();
+        };
+        self.paragraph_order()
+            .filter_map(|para_id| match self.block_meta(para_id)?.kind {
+                BlockKind::Heading { level } if level <= toc.max_level => Some((para_id, level)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Plain text of a heading for its entry: note references and inline
+    /// objects are dropped and tabs become spaces
+    pub fn toc_entry_text(&self, para_id: ParagraphId) -> String {
+        self.paragraph_text(para_id)
+            .chars()
+            .filter(|&c| crate::document::NoteId::from_reference_char(c).is_none())
+            .filter(|&c| crate::document::ObjectId::from_replacement_char(c).is_none())
+            .map(|c| if c == '\t' { ' ' } else { c })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::BlockMeta;
+    use crate::editing::EditOp;
+
+    #[test]
+    fn test_anchors_and_headings() {
+        let mut doc = Document::from_text("Intro\nBody\nDetail");
+        let ids: Vec<ParagraphId> = doc.paragraph_order().collect();
+        for (id, level) in [(ids[0], 1), (ids[2], 3)] {
+            let meta = doc.block_meta(id).unwrap().clone();
+            doc.apply_edit(EditOp::set_block_meta(meta.start_offset, BlockMeta {
+                kind: BlockKind::Heading { level },
+                ..meta
+            }));
+        }
+
+        assert_eq!(doc.anchor_target(&heading_anchor(ids[2])), Some(ids[2]));
+        assert_eq!(doc.anchor_target("#heading-99"), None);
+        assert_eq!(doc.anchor_target("https://example.com"), None);
+
+        let shallow = doc.add_toc(TableOfContents::new(2));
+        let deep = doc.add_toc(TableOfContents::new(9));
+        assert_eq!(doc.toc_headings(shallow), vec![(ids[0], 1)]);
+        assert_eq!(doc.toc_headings(deep), vec![(ids[0], 1), (ids[2], 3)]);
+        assert!(doc.toc_paragraphs(deep).is_empty());
+    }
+}
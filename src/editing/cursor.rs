@@ -1,6 +1,8 @@
 //! Cursor and selection management
 
-use crate::document::ParagraphId;
+use crate::document::{ParagraphId, TableId};
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
 
 /// Position in document as (paragraph_id, offset_within_paragraph)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -81,27 +83,145 @@ impl Cursor {
     }
 }
 
+/// Cursor movements by more than a grapheme or a line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Movement {
+    /// Start of the previous word (Ctrl+Left)
+    WordLeft,
+    /// Start of the next word (Ctrl+Right)
+    WordRight,
+    /// Start of the visual line (Home)
+    LineStart,
+    /// End of the visual line (End)
+    LineEnd,
+    /// Start of the story (Ctrl+Home)
+    DocumentStart,
+    /// End of the story (Ctrl+End)
+    DocumentEnd,
+    /// Start of the paragraph, or of the previous one (Ctrl+Up)
+    ParagraphUp,
+    /// Start of the next paragraph (Ctrl+Down)
+    ParagraphDown,
+    /// Up by lines filling a viewport of this height (Page Up)
+    PageUp(f32),
+    /// Down by lines filling a viewport of this height (Page Down)
+    PageDown(f32),
+}
+
+/// How much text a delete command removes from the cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextUnit {
+    /// One grapheme cluster
+    Grapheme,
+    /// To the start of the word, or the start of the next one
+    Word,
+    /// To the start or end of the sentence
+    Sentence,
+    /// To the start or end of the visual line
+    Line,
+    /// To the start or end of the paragraph
+    Paragraph,
+}
+
+/// Byte offset of the first word starting after `offset`, or the end of
+/// the text
+///
+/// Words are the Unicode word segments holding a letter or digit, so
+/// spaces and punctuation are skipped over.
+pub fn next_word_start(text: &str, offset: usize) -> usize {
+    text.split_word_bound_indices()
+        .find(|&(start, word)| start > offset && is_word(word))
+        .map_or(text.len(), |(start, _)| start)
+}
+
+/// Byte offset of the start of the word holding `offset`, or of the word
+/// before it, or zero
+pub fn prev_word_start(text: &str, offset: usize) -> usize {
+    text.split_word_bound_indices()
+        .take_while(|&(start, _)| start < offset)
+        .filter(|&(_, word)| is_word(word))
+        .last()
+        .map_or(0, |(start, _)| start)
+}
+
+/// Byte offset of the first sentence starting after `offset`, or the end
+/// of the text
+///
+/// Sentences take the spaces following them, so this is also the end of
+/// the sentence holding `offset`.
+pub fn next_sentence_start(text: &str, offset: usize) -> usize {
+    text.split_sentence_bound_indices()
+        .find(|&(start, _)| start > offset)
+        .map_or(text.len(), |(start, _)| start)
+}
+
+/// Byte offset of the start of the sentence holding `offset`, or of the
+/// sentence before it when `offset` is a sentence start
+pub fn prev_sentence_start(text: &str, offset: usize) -> usize {
+    text.split_sentence_bound_indices()
+        .take_while(|&(start, _)| start < offset)
+        .last()
+        .map_or(0, |(start, _)| start)
+}
+
+/// Byte range of the word segment holding `offset` (a word, a run of
+/// spaces or a punctuation mark); at the end of the text, the last one
+pub fn word_at(text: &str, offset: usize) -> Range<usize> {
+    let mut last = 0..0;
+    for (start, segment) in text.split_word_bound_indices() {
+        last = start..start + segment.len();
+        if offset < last.end {
+            break;
+        }
+    }
+    last
+}
+
+/// Check whether a word segment is a word rather than spacing or punctuation
+fn is_word(segment: &str) -> bool {
+    segment.chars().any(char::is_alphanumeric)
+}
+
+/// Unit a selection grows by when it is extended by dragging
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Granularity {
+    /// Single characters (click and drag)
+    #[default]
+    Character,
+    /// Whole words (double-click and drag)
+    Word,
+    /// Whole paragraphs (triple-click and drag)
+    Paragraph,
+}
+
 /// Text selection (anchor + active point)
+///
+/// A selection made by word or paragraph keeps its first unit selected
+/// while it is extended: the anchor sits at the unit's start when the
+/// selection runs forward and at its end when it runs backward.
 #[derive(Debug, Clone, Default)]
 pub struct Selection {
     /// The anchor point (fixed during extension)
     pub anchor: DocPosition,
     /// The active point (moves during extension)
     pub active: DocPosition,
+    /// Unit the selection is extended by
+    pub granularity: Granularity,
 }
 
 impl Selection {
     /// Create a new selection
     pub fn new(anchor: DocPosition, active: DocPosition) -> Self {
-        Self { anchor, active }
+        Self {
+            anchor,
+            active,
+            granularity: Granularity::Character,
+        }
     }
 
     /// Create a collapsed selection (cursor)
     pub fn collapsed(position: DocPosition) -> Self {
-        Self {
-            anchor: position.clone(),
-            active: position,
-        }
+        Self::new(position, position)
     }
 
     /// Check if selection is collapsed (no text selected)
@@ -148,6 +268,26 @@ impl Selection {
     }
 }
 
+/// A rectangular block of table cells, selected by extending the
+/// selection past the edge of a cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellSelection {
+    pub table_id: TableId,
+    /// (row, column) where the selection started
+    pub anchor: (usize, usize),
+    /// (row, column) holding the cursor
+    pub active: (usize, usize),
+}
+
+impl CellSelection {
+    /// Check if a cell is inside the selected block
+    pub fn contains(&self, row: usize, col: usize) -> bool {
+        let rows = self.anchor.0.min(self.active.0)..=self.anchor.0.max(self.active.0);
+        let cols = self.anchor.1.min(self.active.1)..=self.anchor.1.max(self.active.1);
+        rows.contains(&row) && cols.contains(&col)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -181,4 +321,58 @@ mod tests {
         let sel = Selection::collapsed(pos);
         assert!(sel.is_collapsed());
     }
+
+    #[test]
+    fn test_cell_selection_contains() {
+        let sel = CellSelection {
+            table_id: TableId(0),
+            anchor: (2, 0),
+            active: (1, 1),
+        };
+        assert!(sel.contains(1, 0));
+        assert!(sel.contains(2, 1));
+        assert!(!sel.contains(0, 0));
+        assert!(!sel.contains(1, 2));
+    }
+
+    #[test]
+    fn test_word_boundaries() {
+        let text = "Hello, wide world's end";
+        assert_eq!(next_word_start(text, 0), 7);
+        assert_eq!(next_word_start(text, 3), 7);
+        // Apostrophes stay inside words
+        assert_eq!(next_word_start(text, 12), 20);
+        assert_eq!(next_word_start(text, 20), text.len());
+
+        assert_eq!(prev_word_start(text, text.len()), 20);
+        assert_eq!(prev_word_start(text, 9), 7);
+        assert_eq!(prev_word_start(text, 7), 0);
+        assert_eq!(prev_word_start(text, 0), 0);
+
+        // CJK ideographs are words of their own
+        assert_eq!(next_word_start("日本 語", 0), 3);
+    }
+
+    #[test]
+    fn test_word_at() {
+        let text = "Hi,  there";
+        assert_eq!(word_at(text, 0), 0..2);
+        assert_eq!(word_at(text, 1), 0..2);
+        assert_eq!(word_at(text, 2), 2..3);
+        assert_eq!(word_at(text, 4), 3..5);
+        assert_eq!(word_at(text, text.len()), 5..10);
+        assert_eq!(word_at("", 0), 0..0);
+    }
+
+    #[test]
+    fn test_sentence_boundaries() {
+        let text = "It rained. Was it cold? Yes.";
+        assert_eq!(next_sentence_start(text, 3), 11);
+        assert_eq!(next_sentence_start(text, 11), 24);
+        assert_eq!(next_sentence_start(text, 25), text.len());
+
+        assert_eq!(prev_sentence_start(text, 15), 11);
+        assert_eq!(prev_sentence_start(text, 11), 0);
+        assert_eq!(prev_sentence_start(text, 0), 0);
+    }
 }
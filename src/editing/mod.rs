@@ -3,5 +3,8 @@
 mod cursor;
 mod operation;
 
-pub use cursor::{Affinity, Cursor, DocPosition, Selection};
+pub use cursor::{
+    next_sentence_start, next_word_start, prev_sentence_start, prev_word_start, word_at, Affinity, CellSelection,
+    Cursor, DocPosition, Granularity, Movement, Selection, TextUnit,
+};
 pub use operation::{AbsoluteOffset, EditOp, EditResult};
@@ -1,6 +1,6 @@
 //! Edit operations and results
 
-use crate::document::ParagraphId;
+use crate::document::{BlockMeta, ObjectId, ParagraphId, Section, SectionId};
 use crate::editing::DocPosition;
 use smallvec::SmallVec;
 
@@ -16,7 +16,7 @@ impl AbsoluteOffset {
 }
 
 /// An atomic edit operation
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum EditOp {
     /// Insert text at a position
     Insert {
@@ -28,6 +28,25 @@ pub enum EditOp {
         start: AbsoluteOffset,
         end: AbsoluteOffset,
     },
+    /// Replace the metadata of the paragraph containing a position
+    SetBlockMeta {
+        position: AbsoluteOffset,
+        meta: BlockMeta,
+    },
+    /// Change the displayed size of the inline object at a position
+    ResizeObject {
+        position: AbsoluteOffset,
+        object_id: ObjectId,
+        width: f32,
+        height: f32,
+    },
+    /// Replace a section's page setup, headers and footers (the position
+    /// is where the cursor goes)
+    SetSection {
+        position: AbsoluteOffset,
+        section_id: SectionId,
+        section: Section,
+    },
     /// A composite transaction of multiple operations
     Transaction {
         ops: Vec<EditOp>,
@@ -51,6 +70,14 @@ impl EditOp {
         }
     }
 
+    /// Create a block metadata replacement
+    pub fn set_block_meta(position: usize, meta: BlockMeta) -> Self {
+        Self::SetBlockMeta {
+            position: AbsoluteOffset(position),
+            meta,
+        }
+    }
+
     /// Create a transaction
     pub fn transaction(ops: Vec<EditOp>) -> Self {
         Self::Transaction { ops }
@@ -61,6 +88,9 @@ impl EditOp {
         match self {
             EditOp::Insert { position, text } => (position.0, position.0 + text.len()),
             EditOp::Delete { start, end } => (start.0, end.0),
+            EditOp::SetBlockMeta { position, .. } => (position.0, position.0),
+            EditOp::ResizeObject { position, .. } => (position.0, position.0),
+            EditOp::SetSection { position, .. } => (position.0, position.0),
             EditOp::Transaction { ops } => {
                 let mut min_start = usize::MAX;
                 let mut max_end = 0;
@@ -1,9 +1,18 @@
 //! Core layout engine with incremental update support
 
-use crate::document::{BlockKind, BlockMeta, Document, ParagraphId};
-use crate::editing::{Cursor, DocPosition, EditResult, Selection};
+use crate::document::{
+    BlockBreak, BlockKind, BlockMeta, Document, LineBreakMode, ListMarker, MarkerAlignment, MarkerCounters, NoteId,
+    NoteKind, NumberFormat, ObjectTable, ParagraphId, SectionColumns, SectionId, StoryId, TabLeader, TableId,
+};
+use crate::editing::{Affinity, DocPosition, EditResult};
+use crate::layout::hyphenation::Hyphenation;
+use crate::layout::knuth_plass::OptimalFitParams;
 use crate::layout::line_break::LineBreaker;
-use crate::layout::pagination::PageLayout;
+use crate::layout::pagination::{NoteSlice, PageLayout};
+use crate::layout::font::FontId;
+use crate::layout::shaping::PositionedGlyph;
+use crate::layout::story::StoryLayout;
+use crate::layout::table::TableLayout;
 use crate::layout::FontMetrics;
 use crate::render::{RenderDiff, LayoutDiff};
 use crate::Rect;
@@ -18,8 +27,24 @@ pub const LINE_HEIGHT: f32 = 16.8;
 /// Indentation width per level
 pub const INDENT_WIDTH: f32 = 24.0;
 
+/// Space reserved above the footnote area for the separator
+pub const FOOTNOTE_SEPARATOR: f32 = 12.0;
+
+/// Space between the body text and the endnotes
+pub const ENDNOTE_GAP: f32 = 24.0;
+
+/// Least space between a heading's number and its text
+pub const HEADING_NUMBER_GAP: f32 = 8.0;
+
+/// Page orientation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Portrait,
+    Landscape,
+}
+
 /// Layout constraints for the document
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct LayoutConstraints {
     pub page_width: f32,
     pub page_height: f32,
@@ -52,9 +77,29 @@ impl LayoutConstraints {
     pub fn content_height(&self) -> f32 {
         self.page_height - self.margin_top - self.margin_bottom
     }
+
+    /// Get the page orientation
+    pub fn orientation(&self) -> Orientation {
+        if self.page_width > self.page_height {
+            Orientation::Landscape
+        } else {
+            Orientation::Portrait
+        }
+    }
+
+    /// Return these constraints rotated to the given orientation
+    pub fn with_orientation(mut self, orientation: Orientation) -> Self {
+        if self.orientation() != orientation {
+            std::mem::swap(&mut self.page_width, &mut self.page_height);
+        }
+        self
+    }
 }
 
 /// Information about a grapheme cluster for cursor positioning
+///
+/// Clusters are kept in logical order; `x` is their visual position after
+/// bidi reordering.
 #[derive(Debug, Clone)]
 pub struct ClusterInfo {
     /// Byte offset within paragraph
@@ -63,6 +108,33 @@ pub struct ClusterInfo {
     pub x: f32,
     /// Width of this cluster
     pub width: f32,
+    /// Resolved bidi embedding level (odd levels run right-to-left)
+    pub level: u8,
+}
+
+impl ClusterInfo {
+    /// Check if this cluster runs right-to-left
+    pub fn is_rtl(&self) -> bool {
+        self.level % 2 == 1
+    }
+
+    /// X of the edge a caret before this cluster sits at
+    fn leading_x(&self) -> f32 {
+        if self.is_rtl() {
+            self.x + self.width
+        } else {
+            self.x
+        }
+    }
+
+    /// X of the edge a caret after this cluster sits at
+    fn trailing_x(&self) -> f32 {
+        if self.is_rtl() {
+            self.x
+        } else {
+            self.x + self.width
+        }
+    }
 }
 
 /// Layout result for a single line
@@ -78,6 +150,17 @@ pub struct LineLayout {
     pub baseline: f32,
     /// Actual width of content
     pub width: f32,
+    /// Paragraph base direction is right-to-left
+    pub rtl: bool,
+    /// Shaped glyphs, left to right (only for fonts registered from font data)
+    pub glyphs: Vec<PositionedGlyph>,
+    /// Font each byte range of the line was measured with, after fallback
+    pub fonts: Vec<(Range<usize>, FontId)>,
+    /// Line ends at a hyphenation point; a hyphen (not in the text) is drawn
+    /// after it and counted in `width`
+    pub hyphen: bool,
+    /// Tabs on the line by byte offset, with the leader drawn across each
+    pub tabs: Vec<(usize, TabLeader)>,
 }
 
 impl LineLayout {
@@ -86,41 +169,111 @@ impl LineLayout {
         self.clusters.iter().find(|c| c.byte_offset == byte_offset)
     }
 
+    /// Find the cluster a caret at a byte offset attaches to
+    ///
+    /// `Upstream` attaches to the cluster before the offset, `Downstream` to
+    /// the one after it; at either end of the line the only neighbour wins.
+    /// Returns the cluster with the affinity actually used.
+    pub fn caret_cluster(&self, byte_offset: usize, affinity: Affinity) -> Option<(&ClusterInfo, Affinity)> {
+        let next = self.clusters.iter().position(|c| c.byte_offset >= byte_offset);
+        let prev = next.unwrap_or(self.clusters.len()).checked_sub(1);
+        match (affinity, prev, next) {
+            (Affinity::Upstream, Some(idx), _) | (_, Some(idx), None) => {
+                Some((&self.clusters[idx], Affinity::Upstream))
+            }
+            (_, _, Some(idx)) => Some((&self.clusters[idx], Affinity::Downstream)),
+            (_, None, None) => None,
+        }
+    }
+
     /// Find X position for byte offset
     pub fn x_for_offset(&self, byte_offset: usize) -> f32 {
-        for cluster in &self.clusters {
-            if cluster.byte_offset >= byte_offset {
-                return cluster.x;
-            }
+        self.x_for_position(byte_offset, Affinity::Downstream)
+    }
+
+    /// Find the caret X position for a byte offset
+    ///
+    /// At a direction boundary the clusters on either side of the offset are
+    /// not adjacent on screen, so the affinity picks which one the caret
+    /// is drawn against.
+    pub fn x_for_position(&self, byte_offset: usize, affinity: Affinity) -> f32 {
+        match self.caret_cluster(byte_offset, affinity) {
+            Some((cluster, Affinity::Upstream)) => cluster.trailing_x(),
+            Some((cluster, Affinity::Downstream)) => cluster.leading_x(),
+            None => self.width,
         }
-        self.width
     }
 
     /// Find byte offset for X position
     pub fn offset_for_x(&self, x: f32) -> usize {
-        let mut best_offset = self.byte_range.start;
-        let mut best_dist = f32::MAX;
+        self.position_for_x(x).0
+    }
 
-        for cluster in &self.clusters {
-            let dist = (cluster.x - x).abs();
-            if dist < best_dist {
-                best_dist = dist;
-                best_offset = cluster.byte_offset;
-            }
+    /// Find the caret position nearest an X position
+    ///
+    /// Returns the byte offset with the affinity that keeps the caret on the
+    /// edge of the cluster that was hit.
+    pub fn position_for_x(&self, x: f32) -> (usize, Affinity) {
+        let distance = |c: &ClusterInfo| (c.x - x).max(x - c.x - c.width).max(0.0);
+        let hit = self
+            .clusters
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)));
+        let Some((idx, cluster)) = hit else {
+            return (self.byte_range.start, Affinity::Downstream);
+        };
 
-            // Also check end of cluster
-            let end_x = cluster.x + cluster.width;
-            let end_dist = (end_x - x).abs();
-            if end_dist < best_dist {
-                best_dist = end_dist;
-                best_offset = cluster.byte_offset + 1; // Approximate
-            }
+        let left_half = x < cluster.x + cluster.width / 2.0;
+        if left_half != cluster.is_rtl() {
+            (cluster.byte_offset, Affinity::Downstream)
+        } else {
+            let end = self
+                .clusters
+                .get(idx + 1)
+                .map_or(self.byte_range.end, |c| c.byte_offset);
+            (end, Affinity::Upstream)
         }
+    }
 
-        best_offset
+    /// Visual extents (x, width) covering a byte range of the line
+    ///
+    /// A range crossing a direction boundary is not contiguous on screen and
+    /// can need several rectangles; they are returned left to right.
+    pub fn selection_rects(&self, range: Range<usize>) -> Vec<(f32, f32)> {
+        let mut spans: Vec<(f32, f32)> = self
+            .clusters
+            .iter()
+            .filter(|c| range.contains(&c.byte_offset))
+            .map(|c| (c.x, c.x + c.width))
+            .collect();
+        spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut rects: Vec<(f32, f32)> = Vec::new();
+        for (start, end) in spans {
+            match rects.last_mut() {
+                Some(last) if (last.0 + last.1 - start).abs() < 0.01 => last.1 = end - last.0,
+                _ => rects.push((start, end - start)),
+            }
+        }
+        rects
     }
 }
 
+/// Marker and indent of a list item, numbered heading or table of
+/// contents entry (whose marker is empty)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListItemLayout {
+    /// Marker text, numbered in document order
+    pub marker: String,
+    /// The marker is a glyph rather than a number
+    pub bullet: bool,
+    /// Distance of the text from the column edge
+    pub indent: f32,
+    /// Left edge of the marker from the column edge
+    pub marker_x: f32,
+}
+
 /// Layout result for a paragraph
 #[derive(Debug, Clone)]
 pub struct ParagraphLayout {
@@ -131,6 +284,12 @@ pub struct ParagraphLayout {
     pub total_height: f32,
     /// Hash of paragraph content for change detection
     pub content_hash: u64,
+    /// Width the paragraph was broken against
+    pub max_width: f32,
+    /// Forced break before the paragraph (drives pagination)
+    pub break_before: Option<BlockBreak>,
+    /// Note references as (line index, note)
+    pub note_refs: Vec<(usize, NoteId)>,
 }
 
 impl ParagraphLayout {
@@ -145,6 +304,21 @@ impl ParagraphLayout {
         self.lines.last().map(|l| (self.lines.len() - 1, l))
     }
 
+    /// Get the line a caret at a byte offset is on
+    ///
+    /// At a soft wrap the offset ends one line and starts the next;
+    /// `Upstream` keeps the caret at the end of the first.
+    pub fn caret_line(&self, byte_offset: usize, affinity: Affinity) -> Option<(usize, &LineLayout)> {
+        let wrapped = (affinity == Affinity::Upstream)
+            .then(|| {
+                self.lines.iter().enumerate().find(|(_, line)| {
+                    line.byte_range.end == byte_offset && line.byte_range.start < byte_offset
+                })
+            })
+            .flatten();
+        wrapped.or_else(|| self.line_at_offset(byte_offset))
+    }
+
     /// Get total line count
     pub fn line_count(&self) -> usize {
         self.lines.len()
@@ -169,6 +343,38 @@ pub struct LayoutState {
     line_breaker: LineBreaker,
     /// Y offset for each paragraph (cached)
     paragraph_y_offsets: FxHashMap<ParagraphId, f32>,
+    /// Layouts of referenced notes
+    note_layouts: FxHashMap<NoteId, StoryLayout>,
+    /// Referenced notes in document order
+    note_order: Vec<NoteId>,
+    /// Content width of the pages each referenced note is drawn on
+    note_widths: FxHashMap<NoteId, f32>,
+    /// Whether a paragraph with note references was deleted since the
+    /// references were collected
+    note_refs_moved: bool,
+    /// Notes whose line heights changed in the last relayout
+    changed_notes: FxHashSet<NoteId>,
+    /// Display label of each referenced note
+    note_labels: FxHashMap<NoteId, String>,
+    /// Layouts of tables anchored in the text
+    table_layouts: FxHashMap<TableId, TableLayout>,
+    /// Markers and indents of list items
+    list_items: FxHashMap<ParagraphId, ListItemLayout>,
+    /// Paragraphs with a marker in document order, with the counters after
+    /// each, so numbering resumes at the first one an edit touches
+    numbered: Vec<(ParagraphId, MarkerCounters)>,
+    /// Paragraphs edited since markers were last placed
+    markers_touched: FxHashSet<ParagraphId>,
+    /// Whether a paragraph with a marker was deleted since they were placed
+    marker_deleted: bool,
+    /// Whether markers must be placed from the start of the document
+    markers_stale: bool,
+    /// Column width of each section, as its paragraphs were broken
+    section_widths: FxHashMap<SectionId, f32>,
+    /// Whether section breaks moved, so paragraphs may be in other sections
+    sections_dirty: bool,
+    /// Paragraph anchoring each laid out table
+    table_anchors: FxHashMap<TableId, ParagraphId>,
 }
 
 impl LayoutState {
@@ -183,6 +389,21 @@ impl LayoutState {
             layout_version: 0,
             line_breaker: LineBreaker::new(),
             paragraph_y_offsets: FxHashMap::default(),
+            note_layouts: FxHashMap::default(),
+            note_order: Vec::new(),
+            note_widths: FxHashMap::default(),
+            note_refs_moved: true,
+            changed_notes: FxHashSet::default(),
+            note_labels: FxHashMap::default(),
+            table_layouts: FxHashMap::default(),
+            list_items: FxHashMap::default(),
+            numbered: Vec::new(),
+            markers_touched: FxHashSet::default(),
+            marker_deleted: false,
+            markers_stale: true,
+            section_widths: FxHashMap::default(),
+            sections_dirty: true,
+            table_anchors: FxHashMap::default(),
         }
     }
 
@@ -191,106 +412,240 @@ impl LayoutState {
         &self.constraints
     }
 
+    /// Hyphenation settings and dictionaries
+    pub fn hyphenation(&self) -> &Hyphenation {
+        &self.line_breaker.hyphenation
+    }
+
+    /// Change hyphenation; call `invalidate_all` afterwards to relayout
+    pub fn hyphenation_mut(&mut self) -> &mut Hyphenation {
+        &mut self.line_breaker.hyphenation
+    }
+
+    /// Line breaking for paragraphs that do not set their own
+    pub fn line_break_mode(&self) -> LineBreakMode {
+        self.line_breaker.mode
+    }
+
+    /// Set the document's line breaking; call `invalidate_all` afterwards
+    /// to relayout
+    pub fn set_line_break_mode(&mut self, mode: LineBreakMode) {
+        self.line_breaker.mode = mode;
+    }
+
+    /// Change the costs of total-fit line breaking; call `invalidate_all`
+    /// afterwards to relayout
+    pub fn optimal_fit_mut(&mut self) -> &mut OptimalFitParams {
+        &mut self.line_breaker.optimal
+    }
+
+    /// Distance between default tab stops for paragraphs that do not set
+    /// their own
+    pub fn tab_interval(&self) -> f32 {
+        self.line_breaker.tab_interval
+    }
+
+    /// Set the document's default tab interval; call `invalidate_all`
+    /// afterwards to relayout
+    pub fn set_tab_interval(&mut self, interval: f32) {
+        self.line_breaker.tab_interval = interval;
+    }
+
     /// Mark paragraphs as needing relayout based on edit result
     pub fn invalidate(&mut self, edit_result: &EditResult) {
         for para_id in &edit_result.affected_paragraphs {
             self.dirty_paragraphs.insert(*para_id);
+            self.markers_touched.insert(*para_id);
         }
 
         for para_id in &edit_result.created_paragraphs {
             self.dirty_paragraphs.insert(*para_id);
+            self.markers_touched.insert(*para_id);
         }
 
         for para_id in &edit_result.deleted_paragraphs {
-            self.paragraph_layouts.remove(para_id);
+            if self.list_items.remove(para_id).is_some() {
+                self.marker_deleted = true;
+            }
             self.paragraph_y_offsets.remove(para_id);
+            self.table_anchors.retain(|_, anchor| anchor != para_id);
+            let Some(layout) = self.paragraph_layouts.remove(para_id) else {
+                continue;
+            };
+            if matches!(layout.break_before, Some(BlockBreak::Section(_))) {
+                self.sections_dirty = true;
+            }
+            if !layout.note_refs.is_empty() {
+                self.note_refs_moved = true;
+            }
         }
     }
 
     /// Mark all paragraphs as dirty (for full relayout)
+    ///
+    /// Pages are dropped too, so pagination starts over with the current
+    /// section setup (columns) even if no paragraph changes height.
     pub fn invalidate_all(&mut self) {
         for para_id in self.paragraph_layouts.keys().copied().collect::<Vec<_>>() {
             self.dirty_paragraphs.insert(para_id);
         }
+        self.note_layouts.clear();
+        self.note_refs_moved = true;
+        self.table_layouts.clear();
+        self.table_anchors.clear();
+        self.pages.clear();
+        self.sections_dirty = true;
+        self.markers_stale = true;
+    }
+
+    /// Repaginate from the first page on next relayout, after a section's
+    /// page setup changes (page height, margins or columns)
+    ///
+    /// Paragraphs are only rebroken where their column width changed.
+    pub fn invalidate_pages(&mut self) {
+        self.pages.clear();
+        self.sections_dirty = true;
+    }
+
+    /// Renumber every list item and heading on next relayout, after
+    /// changes that report no edit result (heading numbering)
+    pub fn invalidate_markers(&mut self) {
+        self.markers_stale = true;
     }
 
     /// Perform incremental relayout
     pub fn relayout(&mut self, document: &Document) -> RenderDiff {
         let mut layout_diff = LayoutDiff::new();
 
-        // Phase 1: Relayout dirty paragraphs
-        let dirty: Vec<_> = self.dirty_paragraphs.drain().collect();
+        // Phase 1: Relayout dirty paragraphs. Lines are broken to the column
+        // width of their section, so every paragraph is checked only when a
+        // section's width changes or a section break moves
+        self.update_markers(document);
+        let dirty = std::mem::take(&mut self.dirty_paragraphs);
+        let section_widths: FxHashMap<SectionId, f32> = document
+            .sections()
+            .map(|(id, _)| (id, self.column_width(document, id)))
+            .collect();
+        let breaks_moved = dirty.iter().any(|para_id| {
+            let old = self.paragraph_layouts.get(para_id).and_then(|l| l.break_before);
+            let new = document.block_meta(*para_id).and_then(|m| m.break_before);
+            old != new && [old, new].iter().any(|b| matches!(b, Some(BlockBreak::Section(_))))
+        });
+        let full_pass = self.sections_dirty || breaks_moved || section_widths != self.section_widths;
+        self.section_widths = section_widths;
+        self.sections_dirty = false;
+
+        let mut removed_paragraphs = false;
+        // Note references are collected again only when they may have moved
+        let mut refs_moved = std::mem::take(&mut self.note_refs_moved) || full_pass;
+        let targets: Vec<(ParagraphId, f32)> = if full_pass {
+            // Paragraphs merged away by an edit (or its undo) leave their
+            // pages behind, so drop their layouts and repaginate from the start
+            let layout_count = self.paragraph_layouts.len();
+            self.paragraph_layouts.retain(|id, _| document.block_meta(*id).is_some());
+            self.table_anchors.retain(|_, anchor| document.block_meta(*anchor).is_some());
+            removed_paragraphs = self.paragraph_layouts.len() != layout_count;
+
+            let mut targets = Vec::new();
+            let mut max_width = self.section_width(SectionId::DEFAULT);
+            for para_id in document.paragraph_order() {
+                if let Some(BlockBreak::Section(section_id)) = document.block_meta(para_id).and_then(|m| m.break_before) {
+                    max_width = self.section_width(section_id);
+                }
+                if dirty.contains(&para_id)
+                    || self.table_stale(document, para_id, max_width)
+                    || self.paragraph_layouts.get(&para_id).is_none_or(|l| l.max_width != max_width)
+                {
+                    targets.push((para_id, max_width));
+                }
+            }
+            targets
+        } else {
+            let mut targets: Vec<(ParagraphId, f32)> = dirty
+                .iter()
+                .filter(|para_id| document.block_meta(**para_id).is_some())
+                .map(|&para_id| (para_id, self.paragraph_width(document, para_id)))
+                .collect();
+            // Table cells are edited as their own stories, so tables are
+            // checked for staleness on every pass
+            for &para_id in self.table_anchors.values() {
+                let max_width = self.paragraph_layouts.get(&para_id).map_or(0.0, |l| l.max_width);
+                if !dirty.contains(&para_id) && self.table_stale(document, para_id, max_width) {
+                    targets.push((para_id, max_width));
+                }
+            }
+            targets
+        };
+        if removed_paragraphs {
+            layout_diff.pagination_dirty = true;
+        }
 
-        for para_id in dirty {
-            let old_height = self.paragraph_layouts
-                .get(&para_id)
-                .map(|l| l.total_height);
+        for (para_id, max_width) in targets {
+            let table_id = document.table_at(para_id);
+            let old_layout = self.paragraph_layouts.get(&para_id);
+            let old_height = old_layout.map(|l| l.total_height);
+            let old_break = old_layout.and_then(|l| l.break_before);
+            let old_refs = old_layout.map(|l| l.note_refs.clone()).unwrap_or_default();
 
             // Get paragraph text and metadata
             let para_text = document.paragraph_text(para_id);
-            let block_meta = document.block_meta(para_id)
+            let block_meta = document
+                .block_meta(para_id)
                 .cloned()
-                .unwrap_or_else(|| BlockMeta {
-                    kind: BlockKind::Paragraph,
-                    start_offset: 0,
-                    byte_len: para_text.len(),
-                    styles: Vec::new(),
-                });
-
-            // Perform line breaking
-            let new_layout = self.line_breaker.layout_paragraph(
-                para_id,
-                &para_text,
-                &block_meta,
-                self.constraints.content_width(),
-                &self.font_library,
-            );
-
-            let new_height = new_layout.total_height;
-
-            // Record change
-            layout_diff.changed_paragraphs.insert(para_id);
-
-            // Store new layout
-            self.paragraph_layouts.insert(para_id, new_layout);
-
-            // Height change triggers repagination
-            if old_height != Some(new_height) {
-                layout_diff.pagination_dirty = true;
-            }
-        }
-
-        // Ensure all paragraphs have layouts
-        for para_id in document.paragraph_order() {
-            if !self.paragraph_layouts.contains_key(&para_id) {
-                let para_text = document.paragraph_text(para_id);
-                let block_meta = document.block_meta(para_id)
-                    .cloned()
-                    .unwrap_or_else(|| BlockMeta {
-                        kind: BlockKind::Paragraph,
-                        start_offset: 0,
-                        byte_len: para_text.len(),
-                        styles: Vec::new(),
-                    });
-
-                let layout = self.line_breaker.layout_paragraph(
+                .unwrap_or_else(|| BlockMeta::paragraph(0, para_text.len()));
+
+            // Perform line breaking (or table layout)
+            let new_layout = match table_id.and_then(|id| Some((id, document.table(id)?))) {
+                Some((table_id, table)) => {
+                    let previous = self.table_layouts.remove(&table_id);
+                    let table_layout = TableLayout::build(
+                        table,
+                        max_width,
+                        previous,
+                        &self.line_breaker,
+                        &self.font_library,
+                    );
+                    let layout = table_layout.paragraph_layout(para_id, &block_meta);
+                    self.table_layouts.insert(table_id, table_layout);
+                    self.table_anchors.insert(table_id, para_id);
+                    layout
+                }
+                None => self.line_breaker.layout_paragraph_indented(
                     para_id,
                     &para_text,
                     &block_meta,
-                    self.constraints.content_width(),
+                    max_width,
+                    self.indent_for(para_id),
                     &self.font_library,
-                );
+                    document.objects(),
+                ),
+            };
 
-                self.paragraph_layouts.insert(para_id, layout);
-                layout_diff.changed_paragraphs.insert(para_id);
+            // Height, break or note reference changes trigger repagination
+            if old_refs != new_layout.note_refs {
+                refs_moved = true;
+            }
+            if old_height != Some(new_layout.total_height)
+                || old_break != new_layout.break_before
+                || old_refs != new_layout.note_refs
+            {
                 layout_diff.pagination_dirty = true;
             }
+
+            // Record change and store new layout
+            layout_diff.changed_paragraphs.insert(para_id);
+            self.paragraph_layouts.insert(para_id, new_layout);
+        }
+
+        // Notes referenced from the text
+        if self.update_notes(document, refs_moved) {
+            layout_diff.pagination_dirty = true;
         }
 
         // Phase 2: Repaginate if needed
         let start_para_for_y_update = if layout_diff.pagination_dirty || self.pages.is_empty() {
             // Find the earliest paragraph that needs repagination
-            let start_para = if self.pages.is_empty() {
+            let start_para = if self.pages.is_empty() || removed_paragraphs {
                 None
             } else {
                 // Find minimum offset of changed paragraphs
@@ -298,7 +653,10 @@ impl LayoutState {
                     .filter_map(|id| document.block_meta(*id).map(|m| m.start_offset))
                     .min();
                 
-                if let Some(offset) = min_offset {
+                if min_offset.is_none() && !self.changed_notes.is_empty() {
+                    // Only note text changed: repaginate from its first page
+                    Some(self.pages.len().saturating_sub(1))
+                } else if let Some(offset) = min_offset {
                     let dirty_ids: Vec<_> = layout_diff.changed_paragraphs.iter().copied().collect();
                     let mut start_page_idx = 0;
                     let mut found = false;
@@ -316,7 +674,9 @@ impl LayoutState {
                     }
                     
                     if found {
-                        Some(start_page_idx)
+                        // Step back a page: a removed forced break can pull
+                        // the changed paragraph onto the previous page
+                        Some(start_page_idx.saturating_sub(1))
                     } else {
                         Some(self.pages.len().saturating_sub(1))
                     }
@@ -366,108 +726,625 @@ impl LayoutState {
 
     /// Recompute page breaks
     fn repaginate(&mut self, document: &Document, start_page_idx: Option<usize>) {
+        // Resume no later than the first page holding a changed note, after
+        // a page whose footnotes all end on it (none carry over)
+        let changed_notes = std::mem::take(&mut self.changed_notes);
+        let start_page_idx = start_page_idx.map(|idx| {
+            let mut idx = self
+                .pages
+                .iter()
+                .position(|page| page.footnotes.iter().chain(&page.endnotes).any(|s| changed_notes.contains(&s.note_id)))
+                .map_or(idx, |first| first.min(idx))
+                .min(self.pages.len());
+            while idx > 0 && !self.page_closes_notes(&self.pages[idx - 1]) {
+                idx -= 1;
+            }
+            idx
+        });
+
         // Prepare state for incremental update
-        let (mut current_page, mut y_on_page, start_iter_offset) = if let Some(idx) = start_page_idx {
-            if idx < self.pages.len() {
-                // Truncate valid pages
+        let resume = start_page_idx
+            .filter(|&idx| idx > 0 && idx < self.pages.len())
+            .and_then(|idx| {
+                // Truncate valid pages and continue after the last kept one
                 self.pages.truncate(idx);
-                
-                // Start fresh page from where the truncated pages left off
-                // We need the start paragraph for the new page.
-                // It should follow the last paragraph of the previous page.
-                if let Some(last_page) = self.pages.last() {
-                    let last_para = last_page.end_para;
-                    let last_line = last_page.end_line;
-                    
-                    // Logic to find next para/line
-                    if let Some(layout) = self.paragraph_layouts.get(&last_para) {
-                        if last_line + 1 < layout.lines.len() {
-                            // Continue same paragraph
-                            (
-                                PageLayout {
-                                    page_index: idx,
-                                    start_para: last_para,
-                                    start_line: last_line + 1,
-                                    end_para: last_para,
-                                    end_line: last_line + 1,
-                                },
-                                0.0,
-                                // We need offset to resume iteration.
-                                // We are inside `last_para`. So we just use `last_para`'s offset.
-                                document.block_meta(last_para).map(|m| m.start_offset).unwrap_or(0)
-                            )
-                        } else {
-                            // Next paragraph
-                            let next_offset = document.block_meta(last_para)
-                                .map(|m| m.start_offset + m.byte_len + 1) // +1 for newline
-                                .unwrap_or(0);
-                                
-                            (
-                                PageLayout::new(idx),
-                                0.0,
-                                next_offset
-                            )
-                        }
-                    } else {
-                         // Fallback full
-                         self.pages.clear();
-                         (PageLayout::new(0), 0.0, 0)
-                    }
+                let last_page = self.pages.last()?;
+                let last_para = last_page.end_para;
+                let last_line = last_page.end_line;
+                let layout = self.paragraph_layouts.get(&last_para)?;
+                let mut page = last_page.next_page(last_page.constraints);
+
+                if last_line + 1 < layout.lines.len() {
+                    // Continue same paragraph
+                    page.start_para = last_para;
+                    page.start_line = last_line + 1;
+                    let offset = document.block_meta(last_para)?.start_offset;
+                    Some((page, offset))
                 } else {
-                    // Page 0
-                    self.pages.clear();
-                    (PageLayout::new(0), 0.0, 0)
+                    // Next paragraph
+                    let next_para = document.next_paragraph(last_para)?;
+                    page.start_para = next_para;
+                    let offset = document.block_meta(next_para)?.start_offset;
+                    Some((page, offset))
                 }
-            } else {
+            });
+
+        let (mut current_page, start_iter_offset) = match resume {
+            Some(resume) => resume,
+            None => {
+                // Full repagination
                 self.pages.clear();
-                (PageLayout::new(0), 0.0, 0)
+                let mut page = PageLayout::new(0, self.section_constraints(document, SectionId::DEFAULT), 0.0);
+                page.columns = self.section_columns(document, SectionId::DEFAULT);
+                page.start_para = document.first_paragraph();
+                page.end_para = page.start_para;
+                (page, 0)
             }
-        } else {
-            self.pages.clear();
-            (PageLayout::new(0), 0.0, 0)
         };
-
-        let content_height = self.constraints.content_height();
+        let mut y_on_page =
+            self.repeated_header_height(document, current_page.start_para, current_page.start_line);
+        let mut page_has_content = false;
+        // Height of the tallest column filled before the current one
+        let mut columns_height: f32 = 0.0;
+        // Height of the footnote area on the current page
+        let mut notes_height: f32 = 0.0;
+        // Footnote remainders waiting for the next page
+        let mut carry: Vec<NoteSlice> = Vec::new();
 
         // Use efficient seeking iterator
         for para_id in document.paragraphs_from(start_iter_offset) {
             if let Some(para_layout) = self.paragraph_layouts.get(&para_id) {
-                // If we are continuing a paragraph (start_iter_offset points to it),
-                // check if we need to skip lines.
-                // `current_page.start_line` handles the start line index if `start_para` matches.
-                // But `paragraphs_from` gives us the WHOLE paragraph.
-                // So we need to handle the loop correctly.
-                
+                // When continuing a paragraph, skip the lines already placed
                 let start_line_idx = if para_id == current_page.start_para {
                     current_page.start_line
                 } else {
                     0
                 };
 
+                // Forced breaks apply before the first line of the paragraph
+                if start_line_idx == 0 {
+                    if let Some(block_break) = para_layout.break_before {
+                        let constraints = match block_break {
+                            BlockBreak::Page => current_page.constraints,
+                            BlockBreak::Section(id) => self.section_constraints(document, id),
+                        };
+
+                        if page_has_content {
+                            let next_page = current_page.next_page(constraints);
+                            self.pages.push(std::mem::replace(&mut current_page, next_page));
+                            current_page.start_para = para_id;
+                            current_page.start_line = 0;
+                            y_on_page = 0.0;
+                            columns_height = 0.0;
+                            notes_height = self.place_carried_notes(&mut current_page, &mut carry);
+                        } else {
+                            // Nothing placed yet: the page itself takes the new setup
+                            current_page.constraints = constraints;
+                        }
+
+                        if let BlockBreak::Section(id) = block_break {
+                            current_page.columns = self.section_columns(document, id);
+                            current_page.section_id = id;
+                            current_page.page_in_section = 1;
+                        }
+                    }
+                }
+
+                let content_height = current_page.constraints.content_height();
+
                 for (line_idx, line) in para_layout.lines.iter().enumerate().skip(start_line_idx) {
-                    // Check if line fits on current page
-                    if y_on_page + line.height > content_height && y_on_page > 0.0 {
-                        // Finalize current page
-                        self.pages.push(current_page);
-
-                        // Start new page
-                        current_page = PageLayout::new(self.pages.len());
-                        current_page.start_para = para_id;
-                        current_page.start_line = line_idx;
-                        y_on_page = 0.0;
+                    let footnotes: Vec<NoteId> = para_layout
+                        .note_refs
+                        .iter()
+                        .filter(|(ref_line, id)| *ref_line == line_idx && self.is_footnote(document, *id))
+                        .map(|(_, id)| *id)
+                        .collect();
+
+                    // The line needs room for itself and the first line of each of its footnotes
+                    let reserve = self.footnote_reserve(&footnotes, &current_page);
+
+                    // Check if line fits in the current column
+                    if y_on_page + line.height + notes_height + reserve > content_height && y_on_page > 0.0 {
+                        if current_page.column_breaks.len() + 1 < current_page.columns.count {
+                            // Continue at the top of the next column
+                            current_page.column_breaks.push((para_id, line_idx));
+                            columns_height = columns_height.max(y_on_page);
+                        } else {
+                            // Finalize current page and start a new one
+                            let next_page = current_page.next_page(current_page.constraints);
+                            self.pages.push(std::mem::replace(&mut current_page, next_page));
+                            current_page.start_para = para_id;
+                            current_page.start_line = line_idx;
+                            columns_height = 0.0;
+                            notes_height = self.place_carried_notes(&mut current_page, &mut carry);
+                        }
+                        y_on_page = self.repeated_header_height(document, para_id, line_idx);
                     }
 
                     current_page.end_para = para_id;
                     current_page.end_line = line_idx;
                     y_on_page += line.height;
+                    page_has_content = true;
+
+                    for note_id in footnotes {
+                        let room = content_height - y_on_page;
+                        if let Some(rest) = self.place_note(&mut current_page, &mut notes_height, room, note_id, 0) {
+                            carry.push(rest);
+                        }
+                    }
                 }
             }
         }
 
+        // Finish footnotes that ran past the end of the body
+        while !carry.is_empty() {
+            let next_page = current_page.continuation_page();
+            self.pages.push(std::mem::replace(&mut current_page, next_page));
+            y_on_page = 0.0;
+            columns_height = 0.0;
+            notes_height = self.place_carried_notes(&mut current_page, &mut carry);
+        }
+
+        // Endnotes follow the body text, below its tallest column
+        y_on_page = y_on_page.max(columns_height);
+        let endnotes: Vec<NoteId> = self
+            .note_order
+            .iter()
+            .copied()
+            .filter(|id| !self.is_footnote(document, *id))
+            .collect();
+        if !endnotes.is_empty() && current_page.has_body_lines() {
+            y_on_page += ENDNOTE_GAP;
+        }
+        for note_id in endnotes {
+            let heights = self.note_layouts.get(&note_id).map(|l| l.line_heights()).unwrap_or_default();
+            let mut line = 0;
+            while line < heights.len() {
+                let room = current_page.constraints.content_height() - notes_height - y_on_page;
+                let mut end = line;
+                let mut used = 0.0;
+                while end < heights.len() && used + heights[end] <= room {
+                    used += heights[end];
+                    end += 1;
+                }
+                if end == line && current_page.endnotes.is_empty() && y_on_page == 0.0 {
+                    // Taller than a whole page: place it anyway
+                    used = heights[end];
+                    end += 1;
+                }
+
+                if end == line {
+                    let next_page = current_page.continuation_page();
+                    self.pages.push(std::mem::replace(&mut current_page, next_page));
+                    y_on_page = 0.0;
+                    notes_height = 0.0;
+                    continue;
+                }
+
+                current_page.endnotes.push(NoteSlice {
+                    note_id,
+                    start_line: line,
+                    end_line: end,
+                });
+                y_on_page += used;
+                line = end;
+            }
+        }
+
         // Finalize last page
         self.pages.push(current_page);
     }
 
+    /// Number list items and headings and place their markers
+    ///
+    /// Numbering resumes at the first paragraph with a marker that an edit
+    /// touched (typed in, created, relevelled or made a plain paragraph),
+    /// from the counters after the marker before it, and stops once a
+    /// paragraph past every touched one counts the same as before. Undo,
+    /// redo and definition changes renumber the whole document. Paragraphs
+    /// whose indent changes (a heading renumbered from 9 to 10) are marked
+    /// for relayout.
+    fn update_markers(&mut self, document: &Document) {
+        let touched = std::mem::take(&mut self.markers_touched);
+        let deleted = std::mem::take(&mut self.marker_deleted);
+        let offset = |para_id: ParagraphId| document.block_meta(para_id).map(|meta| meta.start_offset);
+        // Touched paragraphs that have or had a marker; any touched one
+        // when a paragraph with a marker was merged away
+        let mut pending: FxHashSet<ParagraphId> = touched
+            .iter()
+            .copied()
+            .filter(|&para_id| offset(para_id).is_some())
+            .filter(|&para_id| deleted || self.has_marker(document, para_id) || self.list_items.contains_key(&para_id))
+            .collect();
+
+        let full = std::mem::take(&mut self.markers_stale) || (deleted && pending.is_empty());
+        let (resume, from) = if full {
+            (0, document.first_paragraph())
+        } else {
+            let Some((start, from)) = pending.iter().filter_map(|&para_id| Some((offset(para_id)?, para_id))).min()
+            else {
+                return;
+            };
+            let resume = self.numbered.partition_point(|&(para_id, _)| offset(para_id).is_some_and(|o| o < start));
+            (resume, from)
+        };
+        if full {
+            pending.clear();
+        }
+
+        let mut old = self.numbered.split_off(resume).into_iter().peekable();
+        let mut counters = self.numbered.last().map(|(_, counters)| counters.clone()).unwrap_or_default();
+        let mut placed = Vec::new();
+        let mut unnumbered = Vec::new();
+        for id in document.paragraphs_from(offset(from).unwrap_or(0)) {
+            let was_touched = pending.remove(&id) || touched.contains(&id);
+            if !self.has_marker(document, id) {
+                continue;
+            }
+            let marker = document.count_marker(id, &mut counters);
+
+            // Earlier entries no longer come before this one (or no longer
+            // have a marker)
+            if self.list_items.contains_key(&id) {
+                while let Some((old_id, _)) = old.next_if(|(old_id, _)| *old_id != id) {
+                    unnumbered.push(old_id);
+                }
+            }
+            let before = old.next_if(|(old_id, _)| *old_id == id);
+            let unchanged = before.is_some_and(|(_, before)| before == counters);
+            self.numbered.push((id, counters.clone()));
+            if unchanged && !full && !was_touched && pending.is_empty() {
+                // Everything after counts as it did
+                self.numbered.extend(old.by_ref());
+                break;
+            }
+            if !unchanged || full || was_touched {
+                placed.extend(self.marker_layout(document, id, marker).map(|item| (id, item)));
+            }
+        }
+        unnumbered.extend(old.map(|(old_id, _)| old_id));
+
+        for para_id in unnumbered {
+            if !self.has_marker(document, para_id) && self.list_items.remove(&para_id).is_some() {
+                self.dirty_paragraphs.insert(para_id);
+            }
+        }
+        for (para_id, item) in placed {
+            if self.list_items.get(&para_id).is_none_or(|old| old.indent != item.indent) {
+                self.dirty_paragraphs.insert(para_id);
+            }
+            self.list_items.insert(para_id, item);
+        }
+        // Touched paragraphs that lost their marker
+        for para_id in touched {
+            if !self.has_marker(document, para_id) && self.list_items.remove(&para_id).is_some() {
+                self.dirty_paragraphs.insert(para_id);
+            }
+        }
+        if full {
+            self.list_items.retain(|para_id, _| document.block_meta(*para_id).is_some());
+        }
+    }
+
+    /// Whether a paragraph is a list item, numbered heading or indented
+    /// table of contents entry
+    fn has_marker(&self, document: &Document, para_id: ParagraphId) -> bool {
+        match document.block_meta(para_id).map(|meta| &meta.kind) {
+            Some(BlockKind::ListItem { .. }) => true,
+            Some(BlockKind::Heading { .. }) => document.heading_numbering().is_some(),
+            Some(BlockKind::TocEntry { level, .. }) => *level > 1,
+            _ => false,
+        }
+    }
+
+    /// Place the marker of a paragraph and indent its text
+    ///
+    /// Lists without a definition indent by `INDENT_WIDTH` per level with
+    /// the marker hanging 16 points left of the text. Heading text starts
+    /// at the level's indent or past its number, whichever is further.
+    /// Table of contents entries indent by `INDENT_WIDTH` per level.
+    fn marker_layout(&self, document: &Document, para_id: ParagraphId, marker: Option<String>) -> Option<ListItemLayout> {
+        let metrics = self.font_library.get(FontId(0));
+        let width = |marker: &str| -> f32 { metrics.map_or(0.0, |m| marker.chars().map(|c| m.width(c)).sum()) };
+        let place = |indent: f32, hanging: f32, alignment: MarkerAlignment, width: f32| match alignment {
+            MarkerAlignment::Left => indent - hanging,
+            MarkerAlignment::Center => indent - hanging - width / 2.0,
+            MarkerAlignment::Right => indent - hanging - width,
+        };
+
+        match &document.block_meta(para_id)?.kind {
+            BlockKind::ListItem { list_id, indent_level, marker: own_marker } => {
+                let marker = marker?;
+                let (indent, hanging, alignment, bullet) =
+                    match document.list(*list_id).and_then(|list| list.level(*indent_level)) {
+                        Some(level) => (level.indent, level.hanging, level.alignment, level.format.is_bullet()),
+                        None => (
+                            *indent_level as f32 * INDENT_WIDTH,
+                            16.0,
+                            MarkerAlignment::Left,
+                            *own_marker == ListMarker::Bullet,
+                        ),
+                    };
+                let marker_x = place(indent, hanging, alignment, width(&marker));
+                Some(ListItemLayout { marker, bullet, indent, marker_x })
+            }
+            BlockKind::Heading { level } => {
+                let marker = marker?;
+                let level = document.heading_numbering()?.level(level.saturating_sub(1))?;
+                let marker_width = width(&marker);
+                let marker_x = place(level.indent, level.hanging, level.alignment, marker_width);
+                let indent = level.indent.max(marker_x + marker_width + HEADING_NUMBER_GAP);
+                Some(ListItemLayout { marker, bullet: false, indent, marker_x })
+            }
+            // Table of contents entries have no marker, only an indent by level
+            BlockKind::TocEntry { level, .. } if *level > 1 => {
+                let indent = (level - 1) as f32 * INDENT_WIDTH;
+                Some(ListItemLayout { marker: String::new(), bullet: false, indent, marker_x: indent })
+            }
+            _ => None,
+        }
+    }
+
+    /// Re-number referenced notes and refresh their layouts
+    ///
+    /// The references are collected again, walking the whole document, only
+    /// when `refs_moved` says one may have been added, removed or moved;
+    /// otherwise only the notes' own text is checked. Each note is laid out
+    /// at the content width of the pages it is drawn on: a footnote at that
+    /// of its reference's section, an endnote at that of the last section.
+    ///
+    /// Returns true if pagination is affected.
+    fn update_notes(&mut self, document: &Document, refs_moved: bool) -> bool {
+        let mut changed = false;
+        if refs_moved {
+            let mut section_id = SectionId::DEFAULT;
+            let mut refs: Vec<(NoteId, SectionId)> = Vec::new();
+            for para_id in document.paragraph_order() {
+                let Some(layout) = self.paragraph_layouts.get(&para_id) else {
+                    continue;
+                };
+                if let Some(BlockBreak::Section(id)) = layout.break_before {
+                    section_id = id;
+                }
+                refs.extend(
+                    layout
+                        .note_refs
+                        .iter()
+                        .filter(|(_, id)| document.note(*id).is_some())
+                        .map(|&(_, id)| (id, section_id)),
+                );
+            }
+
+            let end_width = self.section_constraints(document, section_id).content_width();
+            self.note_widths = refs
+                .iter()
+                .map(|&(note_id, section_id)| {
+                    let width = if self.is_footnote(document, note_id) {
+                        self.section_constraints(document, section_id).content_width()
+                    } else {
+                        end_width
+                    };
+                    (note_id, width)
+                })
+                .collect();
+            let order: Vec<NoteId> = refs.into_iter().map(|(note_id, _)| note_id).collect();
+            changed = order != self.note_order;
+            self.note_layouts.retain(|id, _| order.contains(id));
+            self.note_order = order;
+        }
+
+        self.note_labels.clear();
+        self.changed_notes.clear();
+        let (mut footnotes, mut endnotes) = (0, 0);
+        for &note_id in &self.note_order {
+            let Some(note) = document.note(note_id) else {
+                continue;
+            };
+
+            let label = match note.kind {
+                NoteKind::Footnote => {
+                    footnotes += 1;
+                    footnotes.to_string()
+                }
+                NoteKind::Endnote => {
+                    endnotes += 1;
+                    NumberFormat::LowerRoman.format(endnotes)
+                }
+            };
+            self.note_labels.insert(note_id, label);
+
+            let max_width = self
+                .note_widths
+                .get(&note_id)
+                .copied()
+                .unwrap_or_else(|| self.constraints.content_width());
+            let stale = self
+                .note_layouts
+                .get(&note_id)
+                .is_none_or(|layout| layout.is_stale(&note.body, max_width));
+            if stale {
+                let layout = StoryLayout::build(&note.body, max_width, &self.line_breaker, &self.font_library);
+                let old_heights = self.note_layouts.get(&note_id).map(|l| l.line_heights());
+                if old_heights.as_ref() != Some(&layout.line_heights()) {
+                    changed = true;
+                    self.changed_notes.insert(note_id);
+                }
+                self.note_layouts.insert(note_id, layout);
+            }
+        }
+        changed
+    }
+
+    /// Whether every footnote placed on a page ends there and no endnotes
+    /// follow, so pagination can resume on the next page
+    fn page_closes_notes(&self, page: &PageLayout) -> bool {
+        page.endnotes.is_empty()
+            && page.footnotes.iter().all(|slice| {
+                self.note_layouts
+                    .get(&slice.note_id)
+                    .is_some_and(|layout| slice.end_line >= layout.lines().count())
+            })
+    }
+
+    /// Check whether a note is a footnote
+    fn is_footnote(&self, document: &Document, note_id: NoteId) -> bool {
+        document
+            .note(note_id)
+            .is_some_and(|note| note.kind == NoteKind::Footnote)
+    }
+
+    /// Space needed to start the given footnotes on a page
+    fn footnote_reserve(&self, footnotes: &[NoteId], page: &PageLayout) -> f32 {
+        let first_lines: f32 = footnotes
+            .iter()
+            .filter_map(|id| self.note_layouts.get(id)?.paragraphs.first()?.lines.first())
+            .map(|line| line.height)
+            .sum();
+        if first_lines > 0.0 && page.footnotes.is_empty() {
+            first_lines + FOOTNOTE_SEPARATOR
+        } else {
+            first_lines
+        }
+    }
+
+    /// Place footnote lines at the bottom of a page
+    ///
+    /// At least one line is always placed. Returns the remainder that
+    /// must continue on the next page, if any.
+    fn place_note(
+        &self,
+        page: &mut PageLayout,
+        notes_height: &mut f32,
+        room: f32,
+        note_id: NoteId,
+        start_line: usize,
+    ) -> Option<NoteSlice> {
+        let heights = self.note_layouts.get(&note_id)?.line_heights();
+        if start_line >= heights.len() {
+            return None;
+        }
+        if page.footnotes.is_empty() {
+            *notes_height += FOOTNOTE_SEPARATOR;
+        }
+
+        let mut end = start_line;
+        while end < heights.len() && (end == start_line || *notes_height + heights[end] <= room) {
+            *notes_height += heights[end];
+            end += 1;
+        }
+
+        page.footnotes.push(NoteSlice {
+            note_id,
+            start_line,
+            end_line: end,
+        });
+
+        (end < heights.len()).then_some(NoteSlice {
+            note_id,
+            start_line: end,
+            end_line: heights.len(),
+        })
+    }
+
+    /// Place footnote remainders at the top of a new page's footnote area
+    fn place_carried_notes(&self, page: &mut PageLayout, carry: &mut Vec<NoteSlice>) -> f32 {
+        let mut notes_height = 0.0;
+        let room = page.constraints.content_height();
+        for slice in std::mem::take(carry) {
+            if let Some(rest) = self.place_note(page, &mut notes_height, room, slice.note_id, slice.start_line) {
+                carry.push(rest);
+            }
+        }
+        notes_height
+    }
+
+    /// Height of the table header rows repeated above a page that starts
+    /// at the given line (row) of a paragraph
+    pub fn repeated_header_height(&self, document: &Document, para_id: ParagraphId, line_idx: usize) -> f32 {
+        document
+            .table_at(para_id)
+            .and_then(|id| self.table_layouts.get(&id))
+            .filter(|table| table.header_rows > 0 && line_idx >= table.header_rows)
+            .map_or(0.0, |table| table.header_height())
+    }
+
+    /// Get the page constraints of a section
+    fn section_constraints(&self, document: &Document, section_id: SectionId) -> LayoutConstraints {
+        document
+            .section(section_id)
+            .and_then(|section| section.constraints)
+            .unwrap_or(self.constraints)
+    }
+
+    /// Width of a column of a section's pages
+    fn column_width(&self, document: &Document, section_id: SectionId) -> f32 {
+        self.section_columns(document, section_id)
+            .width(self.section_constraints(document, section_id).content_width())
+    }
+
+    /// Column width a section's paragraphs are broken to
+    fn section_width(&self, section_id: SectionId) -> f32 {
+        self.section_widths
+            .get(&section_id)
+            .or_else(|| self.section_widths.get(&SectionId::DEFAULT))
+            .copied()
+            .unwrap_or_else(|| self.constraints.content_width())
+    }
+
+    /// Column width of a paragraph's section, taken from the nearest
+    /// paragraph at or before it that starts a section or is laid out
+    fn paragraph_width(&self, document: &Document, para_id: ParagraphId) -> f32 {
+        let mut current = Some(para_id);
+        while let Some(id) = current {
+            if let Some(BlockBreak::Section(section_id)) = document.block_meta(id).and_then(|m| m.break_before) {
+                return self.section_width(section_id);
+            }
+            if let Some(layout) = self.paragraph_layouts.get(&id) {
+                return layout.max_width;
+            }
+            current = document.prev_paragraph(id);
+        }
+        self.section_width(SectionId::DEFAULT)
+    }
+
+    /// Check whether the table anchored by a paragraph needs laying out again
+    fn table_stale(&self, document: &Document, para_id: ParagraphId, max_width: f32) -> bool {
+        document.table_at(para_id).is_some_and(|id| {
+            document.table(id).is_some_and(|table| {
+                self.table_layouts
+                    .get(&id)
+                    .is_none_or(|layout| layout.is_stale(table, max_width))
+            })
+        })
+    }
+
+    /// Get the columns of a section
+    fn section_columns(&self, document: &Document, section_id: SectionId) -> SectionColumns {
+        document
+            .section(section_id)
+            .map(|section| section.columns)
+            .unwrap_or_default()
+    }
+
+    /// Page holding a line of the body text, and the column it is in
+    pub fn line_column(&self, document: &Document, para_id: ParagraphId, line_idx: usize) -> Option<(&PageLayout, usize)> {
+        // Lines in document order: paragraph start, then line index
+        let key = |para_id: ParagraphId, line_idx: usize| {
+            (document.block_meta(para_id).map_or(0, |m| m.start_offset), line_idx)
+        };
+        let target = key(para_id, line_idx);
+        let page = self
+            .pages
+            .iter()
+            .find(|page| key(page.start_para, page.start_line) <= target && target <= key(page.end_para, page.end_line))?;
+        let column = page
+            .column_breaks
+            .iter()
+            .take_while(|&&(para_id, line_idx)| key(para_id, line_idx) <= target)
+            .count();
+        Some((page, column))
+    }
+
     /// Update Y offsets for each paragraph
     fn update_y_offsets(&mut self, document: &Document, start_from: Option<ParagraphId>) {
         if start_from.is_none() {
@@ -522,36 +1399,58 @@ impl LayoutState {
         self.paragraph_y_offsets.get(&para_id).copied().unwrap_or(0.0)
     }
 
-    /// Convert position to X coordinate
-    pub fn position_to_x(&self, _document: &Document, pos: &DocPosition) -> Option<f32> {
+    /// Convert position to X coordinate on its page
+    pub fn position_to_x(&self, document: &Document, pos: &DocPosition) -> Option<f32> {
+        let layout = self.paragraph_layouts.get(&pos.para_id)?;
+        let (line_idx, _) = layout.line_at_offset(pos.offset)?;
+        let column_x = self
+            .line_column(document, pos.para_id, line_idx)
+            .map_or(self.constraints.margin_left, |(page, column)| page.column_x(column));
+        Some(column_x + self.x_in_column(pos, Affinity::Downstream)?)
+    }
+
+    /// X coordinate of a position from the left edge of its column
+    ///
+    /// Vertical movement keeps this distance, so the caret stays in place
+    /// when it moves on to the next or previous column.
+    pub fn x_in_column(&self, pos: &DocPosition, affinity: Affinity) -> Option<f32> {
         let layout = self.paragraph_layouts.get(&pos.para_id)?;
-        let (_, line) = layout.line_at_offset(pos.offset)?;
-        Some(line.x_for_offset(pos.offset) + self.constraints.margin_left)
+        let (_, line) = layout.caret_line(pos.offset, affinity)?;
+        let indent = self.indent_for(pos.para_id);
+        Some(indent + line.x_for_position(pos.offset, affinity))
     }
 
     /// Move cursor vertically
+    ///
+    /// `preferred_x` is measured from the left edge of the column (see
+    /// `x_in_column`); lines follow in document order, so moving past the
+    /// end of a column continues at the top of the next. The new position
+    /// comes with the affinity keeping it on the target line.
     pub fn move_cursor_vertical(
         &self,
         document: &Document,
         current_pos: &DocPosition,
+        affinity: Affinity,
         delta_lines: i32,
         preferred_x: Option<f32>,
-    ) -> Option<DocPosition> {
+    ) -> Option<(DocPosition, Affinity)> {
         let layout = self.paragraph_layouts.get(&current_pos.para_id)?;
-        let (current_line_idx, current_line) = layout.line_at_offset(current_pos.offset)?;
+        let (current_line_idx, _) = layout.caret_line(current_pos.offset, affinity)?;
 
-        // Get X position to maintain
-        let target_x = preferred_x.unwrap_or_else(|| {
-            current_line.x_for_offset(current_pos.offset)
-        });
+        // Get X position to maintain, and find it on a line past the indent
+        let target_x = preferred_x.or_else(|| self.x_in_column(current_pos, affinity))?;
+        let position_for_x = |para_id: ParagraphId, line: &LineLayout| {
+            let indent = self.indent_for(para_id);
+            line.position_for_x(target_x - indent)
+        };
 
         let target_line_idx = current_line_idx as i32 + delta_lines;
 
         if target_line_idx >= 0 && (target_line_idx as usize) < layout.lines.len() {
             // Same paragraph
             let target_line = &layout.lines[target_line_idx as usize];
-            let new_offset = target_line.offset_for_x(target_x);
-            Some(DocPosition::new(current_pos.para_id, new_offset))
+            let (new_offset, affinity) = position_for_x(current_pos.para_id, target_line);
+            Some((DocPosition::new(current_pos.para_id, new_offset), affinity))
         } else if delta_lines < 0 {
             // Move to previous paragraph
             let prev_para = document.paragraph_order()
@@ -560,8 +1459,8 @@ impl LayoutState {
             
             let prev_layout = self.paragraph_layouts.get(&prev_para)?;
             let target_line = prev_layout.lines.last()?;
-            let new_offset = target_line.offset_for_x(target_x);
-            Some(DocPosition::new(prev_para, new_offset))
+            let (new_offset, affinity) = position_for_x(prev_para, target_line);
+            Some((DocPosition::new(prev_para, new_offset), affinity))
         } else {
             // Move to next paragraph
             let mut found_current = false;
@@ -577,8 +1476,8 @@ impl LayoutState {
             
             let next_layout = self.paragraph_layouts.get(&next_para)?;
             let target_line = next_layout.lines.first()?;
-            let new_offset = target_line.offset_for_x(target_x);
-            Some(DocPosition::new(next_para, new_offset))
+            let (new_offset, affinity) = position_for_x(next_para, target_line);
+            Some((DocPosition::new(next_para, new_offset), affinity))
         }
     }
 
@@ -587,27 +1486,57 @@ impl LayoutState {
         &self,
         document: &Document,
         viewport: Rect,
-        cursor: &Cursor,
-        selection: Option<&Selection>,
+        focus: crate::render::Focus,
+        date: &str,
     ) -> crate::render::DisplayList {
-        crate::render::DisplayList::build(
-            document,
-            self,
-            viewport,
-            cursor,
-            selection,
+        crate::render::DisplayList::build(document, self, viewport, focus, date)
+    }
+
+    /// Break free-standing text (headers, footers) into lines
+    pub fn layout_text(&self, text: &str, max_width: f32) -> ParagraphLayout {
+        self.line_breaker.layout_paragraph(
+            ParagraphId(0),
+            text,
+            &BlockMeta::paragraph(0, text.len()),
+            max_width,
+            &self.font_library,
+            &ObjectTable::default(),
         )
     }
 
-    /// Get indent for block type
-    pub fn indent_for(&self, block_meta: &BlockMeta) -> f32 {
-        match &block_meta.kind {
-            BlockKind::ListItem { indent_level, .. } => {
-                *indent_level as f32 * INDENT_WIDTH
-            }
-            _ => 0.0,
+    /// Get the layout of a referenced note
+    pub fn note_layout(&self, note_id: NoteId) -> Option<&StoryLayout> {
+        self.note_layouts.get(&note_id)
+    }
+
+    /// Get the display label (number) of a referenced note
+    pub fn note_label(&self, note_id: NoteId) -> Option<&str> {
+        self.note_labels.get(&note_id).map(String::as_str)
+    }
+
+    /// Get the layout of a table
+    pub fn table_layout(&self, table_id: TableId) -> Option<&TableLayout> {
+        self.table_layouts.get(&table_id)
+    }
+
+    /// Get the layout of a story other than the body
+    pub fn story_layout(&self, story: StoryId) -> Option<&StoryLayout> {
+        match story {
+            StoryId::Body => None,
+            StoryId::Note(note_id) => self.note_layouts.get(&note_id),
+            StoryId::Cell(cell) => self.table_layouts.get(&cell.table_id)?.cell_layout(cell.row, cell.col),
         }
     }
+
+    /// Get the indent of a paragraph's text (list items)
+    pub fn indent_for(&self, para_id: ParagraphId) -> f32 {
+        self.list_items.get(&para_id).map_or(0.0, |item| item.indent)
+    }
+
+    /// Get the marker and indent of a list item
+    pub fn list_item(&self, para_id: ParagraphId) -> Option<&ListItemLayout> {
+        self.list_items.get(&para_id)
+    }
 }
 
 #[cfg(test)]
@@ -626,17 +1555,59 @@ mod tests {
         let line = LineLayout {
             byte_range: 0..5,
             clusters: vec![
-                ClusterInfo { byte_offset: 0, x: 0.0, width: 8.0 },
-                ClusterInfo { byte_offset: 1, x: 8.0, width: 8.0 },
-                ClusterInfo { byte_offset: 2, x: 16.0, width: 8.0 },
+                ClusterInfo { byte_offset: 0, x: 0.0, width: 8.0, level: 0 },
+                ClusterInfo { byte_offset: 1, x: 8.0, width: 8.0, level: 0 },
+                ClusterInfo { byte_offset: 2, x: 16.0, width: 8.0, level: 0 },
             ],
             height: LINE_HEIGHT,
             baseline: BASELINE,
             width: 24.0,
+            rtl: false,
+            glyphs: Vec::new(),
+            fonts: Vec::new(),
+            hyphen: false,
+            tabs: Vec::new(),
         };
 
         assert_eq!(line.x_for_offset(0), 0.0);
         assert_eq!(line.x_for_offset(1), 8.0);
         assert_eq!(line.x_for_offset(2), 16.0);
     }
+
+    #[test]
+    fn test_caret_at_direction_boundary() {
+        // "ab" then two right-to-left clusters, shown reversed after it
+        let line = LineLayout {
+            byte_range: 0..6,
+            clusters: vec![
+                ClusterInfo { byte_offset: 0, x: 0.0, width: 8.0, level: 0 },
+                ClusterInfo { byte_offset: 1, x: 8.0, width: 8.0, level: 0 },
+                ClusterInfo { byte_offset: 2, x: 24.0, width: 8.0, level: 1 },
+                ClusterInfo { byte_offset: 4, x: 16.0, width: 8.0, level: 1 },
+            ],
+            height: LINE_HEIGHT,
+            baseline: BASELINE,
+            width: 32.0,
+            rtl: false,
+            glyphs: Vec::new(),
+            fonts: Vec::new(),
+            hyphen: false,
+            tabs: Vec::new(),
+        };
+
+        // Between "b" and the first RTL cluster the two edges differ
+        assert_eq!(line.x_for_position(2, Affinity::Upstream), 16.0);
+        assert_eq!(line.x_for_position(2, Affinity::Downstream), 32.0);
+        // The end of the line sits at the left edge of the last RTL cluster
+        assert_eq!(line.x_for_position(6, Affinity::Downstream), 16.0);
+
+        assert_eq!(line.position_for_x(30.0), (2, Affinity::Downstream));
+        assert_eq!(line.position_for_x(26.0), (4, Affinity::Upstream));
+        assert_eq!(line.position_for_x(17.0), (6, Affinity::Upstream));
+        assert_eq!(line.position_for_x(-5.0), (0, Affinity::Downstream));
+
+        // "b" plus the first RTL cluster are not adjacent on screen
+        assert_eq!(line.selection_rects(1..4), vec![(8.0, 8.0), (24.0, 8.0)]);
+        assert_eq!(line.selection_rects(1..6), vec![(8.0, 24.0)]);
+    }
 }
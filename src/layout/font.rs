@@ -1,5 +1,125 @@
 //! Font metrics for layout
 
+use std::collections::HashMap;
+use std::sync::Arc;
+
+type ShapingFaceRef<'a> = rustybuzz::Face<'a>;
+
+self_cell::self_cell!(
+    /// Raw font data with the shaping face parsed from it
+    struct ShapingFace {
+        owner: Vec<u8>,
+        #[covariant]
+        dependent: ShapingFaceRef,
+    }
+);
+
+/// Per-glyph metrics parsed from TrueType/OpenType data, in font units
+pub struct FontFace {
+    /// Raw font data, parsed once for shaping
+    shaping: ShapingFace,
+    index: u32,
+    units_per_em: f32,
+    ascender: f32,
+    /// Negative below the baseline
+    descender: f32,
+    line_gap: f32,
+    /// Glyph for each character in the Unicode cmap subtables
+    glyphs: HashMap<char, u16>,
+    /// Horizontal advance of each glyph (hmtx)
+    advances: Vec<u16>,
+    /// Pair adjustments from the `kern` table
+    kerning: HashMap<(u16, u16), i16>,
+}
+
+impl FontFace {
+    /// Parse a font from raw TTF/OTF bytes (`index` selects a face in a
+    /// collection); returns None if the data is not a usable font
+    pub fn parse(data: &[u8], index: u32) -> Option<Self> {
+        let face = ttf_parser::Face::parse(data, index).ok()?;
+        let tables = face.tables();
+
+        let mut glyphs = HashMap::new();
+        for subtable in tables.cmap?.subtables.into_iter().filter(|s| s.is_unicode()) {
+            subtable.codepoints(|codepoint| {
+                let glyph = subtable.glyph_index(codepoint);
+                if let (Some(c), Some(glyph)) = (char::from_u32(codepoint), glyph) {
+                    glyphs.entry(c).or_insert(glyph.0);
+                }
+            });
+        }
+
+        let advances = (0..face.number_of_glyphs())
+            .map(|glyph| face.glyph_hor_advance(ttf_parser::GlyphId(glyph)).unwrap_or(0))
+            .collect();
+
+        let mut kerning = HashMap::new();
+        let kern_subtables = tables.kern.into_iter().flat_map(|kern| kern.subtables);
+        for subtable in kern_subtables.filter(|s| s.horizontal && !s.variable && !s.has_cross_stream) {
+            if let ttf_parser::kern::Format::Format0(pairs) = subtable.format {
+                for pair in pairs.pairs {
+                    kerning.entry((pair.left().0, pair.right().0)).or_insert(pair.value);
+                }
+            }
+        }
+
+        let shaping = ShapingFace::try_new(data.to_vec(), |data| rustybuzz::Face::from_slice(data, index).ok_or(())).ok()?;
+
+        Some(Self {
+            shaping,
+            index,
+            units_per_em: f32::from(face.units_per_em()),
+            ascender: f32::from(face.ascender()),
+            descender: f32::from(face.descender()),
+            line_gap: f32::from(face.line_gap()),
+            glyphs,
+            advances,
+            kerning,
+        })
+    }
+
+    /// Raw font data and the face index within it
+    pub fn data(&self) -> (&[u8], u32) {
+        (self.shaping.borrow_owner(), self.index)
+    }
+
+    /// Face with the OpenType layout tables, for shaping
+    pub fn shaping_face(&self) -> &rustybuzz::Face<'_> {
+        self.shaping.borrow_dependent()
+    }
+
+    /// Get the size of the em square in font units
+    pub fn units_per_em(&self) -> f32 {
+        self.units_per_em
+    }
+
+    /// Get the glyph for a character
+    pub fn glyph(&self, c: char) -> Option<u16> {
+        self.glyphs.get(&c).copied()
+    }
+
+    /// Get a glyph's advance in font units
+    pub fn advance(&self, glyph: u16) -> f32 {
+        self.advances.get(glyph as usize).copied().map_or(0.0, f32::from)
+    }
+
+    /// Get the kerning between two glyphs in font units
+    pub fn kerning(&self, left: u16, right: u16) -> f32 {
+        self.kerning.get(&(left, right)).copied().map_or(0.0, f32::from)
+    }
+}
+
+impl std::fmt::Debug for FontFace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FontFace")
+            .field("data_len", &self.shaping.borrow_owner().len())
+            .field("index", &self.index)
+            .field("units_per_em", &self.units_per_em)
+            .field("glyph_count", &self.advances.len())
+            .finish()
+    }
+}
+
 /// Metrics needed for text layout
 #[derive(Debug, Clone)]
 pub struct FontMetrics {
@@ -9,6 +129,12 @@ pub struct FontMetrics {
     pub char_widths: Vec<f32>,
     /// Default width for non-ASCII characters
     pub default_width: f32,
+    /// Distance from the top of the line box to the baseline
+    pub ascent: f32,
+    /// Parsed font data; when present, widths come from its glyphs
+    pub face: Option<Arc<FontFace>>,
+    /// Font size in logical pixels (scales `face`)
+    pub size: f32,
 }
 
 impl Default for FontMetrics {
@@ -26,6 +152,9 @@ impl Default for FontMetrics {
             line_height: 16.8,
             char_widths,
             default_width,
+            ascent: crate::layout::engine::BASELINE,
+            face: None,
+            size: 14.0,
         }
     }
 }
@@ -36,11 +165,53 @@ impl FontMetrics {
             line_height,
             char_widths,
             default_width,
+            // Same proportion as the default 11px baseline in a 16.8px line
+            ascent: line_height * crate::layout::engine::BASELINE / 16.8,
+            face: None,
+            size: line_height / 1.2,
         }
     }
 
+    /// Metrics for a parsed font at a size
+    ///
+    /// The line gap is split above and below the text, as browsers do.
+    pub fn from_face(face: Arc<FontFace>, size: f32) -> Self {
+        let scale = size / face.units_per_em;
+        let line_height = (face.ascender - face.descender + face.line_gap) * scale;
+        let ascent = (face.ascender + face.line_gap / 2.0) * scale;
+        let char_widths = (0..128u8)
+            .map(|c| face.glyph(c as char).map_or(0.0, |g| face.advance(g) * scale))
+            .collect();
+        // Missing glyphs draw as .notdef
+        let default_width = face.advance(0) * scale;
+
+        Self {
+            line_height,
+            char_widths,
+            default_width,
+            ascent,
+            face: Some(face),
+            size,
+        }
+    }
+
+    /// Parse TTF/OTF bytes into metrics at a size
+    pub fn from_font_data(data: &[u8], size: f32) -> Option<Self> {
+        FontFace::parse(data, 0).map(|face| Self::from_face(Arc::new(face), size))
+    }
+
+    /// Check if the font has a glyph for a character (fonts without data
+    /// are measured by the browser, which does its own fallback)
+    pub fn covers(&self, c: char) -> bool {
+        self.face.as_ref().is_none_or(|face| face.glyph(c).is_some())
+    }
+
     /// Get width of a character
     pub fn width(&self, c: char) -> f32 {
+        if let Some(face) = &self.face {
+            let glyph = face.glyph(c).unwrap_or(0);
+            return face.advance(glyph) * self.size / face.units_per_em;
+        }
         if c.is_ascii() {
             if let Some(w) = self.char_widths.get(c as usize) {
                 return *w;
@@ -48,6 +219,22 @@ impl FontMetrics {
         }
         self.default_width
     }
+
+    /// Get the kerning adjustment between two adjacent characters
+    pub fn kerning(&self, left: char, right: char) -> f32 {
+        let Some(face) = &self.face else {
+            return 0.0;
+        };
+        match (face.glyph(left), face.glyph(right)) {
+            (Some(l), Some(r)) => face.kerning(l, r) * self.size / face.units_per_em,
+            _ => 0.0,
+        }
+    }
+
+    /// Distance from the baseline to the bottom of the line box
+    pub fn descent(&self) -> f32 {
+        self.line_height - self.ascent
+    }
 }
 
 /// Unique identifier for a loaded font
@@ -59,6 +246,10 @@ pub struct FontId(pub u32);
 pub struct FontLibrary {
     fonts: std::collections::HashMap<FontId, FontMetrics>,
     next_id: u32,
+    /// Fonts tried, in order, for characters a font lacks
+    fallbacks: HashMap<FontId, Vec<FontId>>,
+    /// Document-wide fallbacks, tried after a font's own
+    default_fallbacks: Vec<FontId>,
 }
 
 impl Default for FontLibrary {
@@ -70,6 +261,8 @@ impl Default for FontLibrary {
         Self {
             fonts,
             next_id: 1,
+            fallbacks: HashMap::new(),
+            default_fallbacks: Vec::new(),
         }
     }
 }
@@ -92,6 +285,18 @@ impl FontLibrary {
         self.fonts.insert(id, metrics);
     }
 
+    /// Register TTF/OTF bytes at a size under a specific ID; returns false
+    /// (leaving the library unchanged) if the data is not a usable font
+    pub fn set_font_data(&mut self, id: FontId, data: &[u8], size: f32) -> bool {
+        match FontMetrics::from_font_data(data, size) {
+            Some(metrics) => {
+                self.set(id, metrics);
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Get font metrics by ID
     pub fn get(&self, id: FontId) -> Option<&FontMetrics> {
         self.fonts.get(&id)
@@ -101,4 +306,218 @@ impl FontLibrary {
     pub fn get_mut(&mut self, id: FontId) -> Option<&mut FontMetrics> {
         self.fonts.get_mut(&id)
     }
+
+    /// Set the fallback chain of a font
+    pub fn set_fallbacks(&mut self, id: FontId, chain: Vec<FontId>) {
+        if chain.is_empty() {
+            self.fallbacks.remove(&id);
+        } else {
+            self.fallbacks.insert(id, chain);
+        }
+    }
+
+    /// Set the document-wide fallback chain
+    pub fn set_default_fallbacks(&mut self, chain: Vec<FontId>) {
+        self.default_fallbacks = chain;
+    }
+
+    /// Fonts tried for text styled with a font: the font itself, its own
+    /// fallbacks, the document fallbacks, then the default font
+    pub fn fallback_chain(&self, id: FontId) -> impl Iterator<Item = FontId> + '_ {
+        std::iter::once(id)
+            .chain(self.fallbacks.get(&id).into_iter().flatten().copied())
+            .chain(self.default_fallbacks.iter().copied())
+            .chain(std::iter::once(FontId(0)))
+            .filter(|id| self.fonts.contains_key(id))
+    }
+
+    /// Resolve the font a grapheme styled with `id` is measured and drawn
+    /// with: the first in its chain with glyphs for all of it, or the first
+    /// available font (drawing .notdef) when none has
+    pub fn resolve(&self, id: FontId, grapheme: &str) -> FontId {
+        let mut chain = self.fallback_chain(id);
+        let Some(first) = chain.next() else {
+            return FontId(0);
+        };
+        let covered = |id: &FontId| {
+            let metrics = &self.fonts[id];
+            grapheme.chars().filter(|c| needs_glyph(*c)).all(|c| metrics.covers(c))
+        };
+        if covered(&first) {
+            return first;
+        }
+        chain.find(covered).unwrap_or(first)
+    }
+}
+
+/// Check if a character is drawn with a glyph of its own (controls, joiners,
+/// variation selectors and inline objects are not)
+fn needs_glyph(c: char) -> bool {
+    !c.is_control()
+        && !matches!(c, '\u{200C}' | '\u{200D}' | '\u{FE00}'..='\u{FE0F}' | '\u{E0100}'..='\u{E01EF}')
+        && crate::document::ObjectId::from_replacement_char(c).is_none()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A minimal TrueType font: 1000 units per em, ascender 800, descender
+    /// -200, line gap 100; glyphs .notdef (500), 'A' (600), 'V' (600) and
+    /// Hebrew alef (700), with "AV" kerned by -80
+    pub(crate) fn test_font_data() -> Vec<u8> {
+        fn be16(out: &mut Vec<u8>, values: &[u16]) {
+            values.iter().for_each(|v| out.extend_from_slice(&v.to_be_bytes()));
+        }
+        fn be32(out: &mut Vec<u8>, values: &[u32]) {
+            values.iter().for_each(|v| out.extend_from_slice(&v.to_be_bytes()));
+        }
+
+        let mut cmap = Vec::new();
+        be16(&mut cmap, &[0, 1, 3, 10]);
+        be32(&mut cmap, &[12]);
+        let groups: [(u32, u32); 3] = [('A' as u32, 1), ('V' as u32, 2), (0x05D0, 3)];
+        be16(&mut cmap, &[12, 0]);
+        be32(&mut cmap, &[16 + 12 * groups.len() as u32, 0, groups.len() as u32]);
+        for (c, glyph) in groups {
+            be32(&mut cmap, &[c, c, glyph]);
+        }
+
+        let mut head = Vec::new();
+        be32(&mut head, &[0x0001_0000, 0, 0, 0x5F0F_3CF5]);
+        be16(&mut head, &[0, 1000]);
+        head.extend_from_slice(&[0; 16]);
+        be16(&mut head, &[0, (-200i16) as u16, 700, 800, 0, 8, 2, 0, 0]);
+
+        let mut hhea = Vec::new();
+        be32(&mut hhea, &[0x0001_0000]);
+        be16(&mut hhea, &[800, (-200i16) as u16, 100, 700, 0, 0, 700, 1, 0, 0, 0, 0, 0, 0, 0, 4]);
+
+        let mut hmtx = Vec::new();
+        be16(&mut hmtx, &[500, 0, 600, 0, 600, 0, 700, 0]);
+
+        let mut kern = Vec::new();
+        be16(&mut kern, &[0, 1, 0, 6 + 8 + 6, 0x0001, 1, 6, 0, 0, 1, 2, (-80i16) as u16]);
+
+        let mut maxp = Vec::new();
+        be32(&mut maxp, &[0x0000_5000]);
+        be16(&mut maxp, &[4]);
+
+        let tables: [(&[u8; 4], Vec<u8>); 6] = [
+            (b"cmap", cmap),
+            (b"head", head),
+            (b"hhea", hhea),
+            (b"hmtx", hmtx),
+            (b"kern", kern),
+            (b"maxp", maxp),
+        ];
+        let mut font = Vec::new();
+        be32(&mut font, &[0x0001_0000]);
+        be16(&mut font, &[tables.len() as u16, 64, 2, 32]);
+        let mut offset = 12 + 16 * tables.len();
+        for (tag, data) in &tables {
+            font.extend_from_slice(*tag);
+            be32(&mut font, &[0, offset as u32, data.len() as u32]);
+            offset += data.len().next_multiple_of(4);
+        }
+        for (_, data) in &tables {
+            font.extend_from_slice(data);
+            font.resize(font.len().next_multiple_of(4), 0);
+        }
+        font
+    }
+
+    #[test]
+    fn test_metrics_from_font_data() {
+        let metrics = FontMetrics::from_font_data(&test_font_data(), 20.0).unwrap();
+
+        assert!((metrics.line_height - 22.0).abs() < 1e-4);
+        assert!((metrics.ascent - 17.0).abs() < 1e-4);
+        assert!((metrics.width('A') - 12.0).abs() < 1e-4);
+        assert!((metrics.width('\u{05D0}') - 14.0).abs() < 1e-4);
+        // Characters the font lacks are measured as .notdef
+        assert!((metrics.width('z') - 10.0).abs() < 1e-4);
+        assert!((metrics.kerning('A', 'V') + 1.6).abs() < 1e-4);
+        assert_eq!(metrics.kerning('V', 'A'), 0.0);
+    }
+
+    #[test]
+    fn test_fallback_resolution() {
+        let mut library = FontLibrary::new();
+        let latin = library.add(FontMetrics::from_font_data(&test_font_data(), 20.0).unwrap());
+        let measured = library.add(FontMetrics::new(10.0, vec![8.0; 128], 8.0));
+
+        // Covered text stays in its font; the rest walks the chain, ending
+        // at the default font
+        assert_eq!(library.resolve(latin, "A"), latin);
+        assert_eq!(library.resolve(latin, "\u{4E2D}"), FontId(0));
+        library.set_fallbacks(latin, vec![measured]);
+        assert_eq!(library.fallback_chain(latin).collect::<Vec<_>>(), vec![latin, measured, FontId(0)]);
+        assert_eq!(library.resolve(latin, "\u{4E2D}"), measured);
+        // Joiners need no glyph of their own
+        assert_eq!(library.resolve(latin, "\u{05D0}\u{200D}"), latin);
+
+        // Nothing in the chain has the glyph: keep the styled font
+        library.set_fallbacks(latin, Vec::new());
+        library.set(FontId(0), FontMetrics::from_font_data(&test_font_data(), 14.0).unwrap());
+        assert_eq!(library.resolve(latin, "\u{4E2D}"), latin);
+
+        // Unknown fonts in a chain are skipped
+        library.set_default_fallbacks(vec![FontId(7)]);
+        assert_eq!(library.fallback_chain(latin).collect::<Vec<_>>(), vec![latin, FontId(0)]);
+    }
+
+    #[test]
+    fn test_invalid_font_data() {
+        let mut library = FontLibrary::new();
+        assert!(!library.set_font_data(FontId(0), b"not a font", 14.0));
+        assert!(library.get(FontId(0)).unwrap().face.is_none());
+    }
+
+    #[test]
+    fn test_shaping_face_parsed_once() {
+        use crate::document::{BlockMeta, ObjectTable};
+
+        let mut library = FontLibrary::new();
+        assert!(library.set_font_data(FontId(0), &test_font_data(), 20.0));
+        let metrics = library.get(FontId(0)).unwrap().clone();
+        let face = metrics.face.as_ref().unwrap();
+        assert_eq!(face.shaping_face().units_per_em(), 1000);
+
+        // Clones of the metrics share the face and its parsed shaping face
+        let stored = library.get(FontId(0)).unwrap().face.as_ref().unwrap();
+        assert!(Arc::ptr_eq(face, stored));
+        assert!(std::ptr::eq(face.shaping_face(), stored.shaping_face()));
+
+        // Shaping text neither parses nor keeps another copy
+        let count = Arc::strong_count(face);
+        let meta = BlockMeta::paragraph(0, 5);
+        let bidi = unicode_bidi::ParagraphBidiInfo::new("Hello", None);
+        let runs = crate::layout::shaping::shape_paragraph("Hello", &meta, &bidi.levels, &library, &ObjectTable::default());
+        assert!(!runs.is_empty());
+        assert_eq!(Arc::strong_count(face), count);
+    }
+
+    #[test]
+    fn test_missing_default_font_uses_fallback_metrics() {
+        use crate::document::{BlockMeta, ObjectTable, ParagraphId};
+        let library = FontLibrary { fonts: std::collections::HashMap::new(), ..FontLibrary::new() };
+        let meta = BlockMeta::paragraph(0, 5);
+
+        let bidi = unicode_bidi::ParagraphBidiInfo::new("Hello", None);
+        let runs = crate::layout::shaping::shape_paragraph("Hello", &meta, &bidi.levels, &library, &ObjectTable::default());
+        let advance: f32 = runs.iter().flat_map(|r| &r.glyphs).map(|g| g.x_advance).sum();
+        assert_eq!(advance, 5.0 * FontMetrics::default().default_width);
+
+        let layout = crate::layout::line_break::LineBreaker::new().layout_paragraph(
+            ParagraphId(0),
+            "Hello",
+            &meta,
+            100.0,
+            &library,
+            &ObjectTable::default(),
+        );
+        assert_eq!(layout.lines.len(), 1);
+        assert_eq!(layout.lines[0].clusters.len(), 5);
+    }
 }
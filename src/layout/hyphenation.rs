@@ -0,0 +1,274 @@
+//! Hyphenation
+//!
+//! Words are hyphenated with Liang's algorithm from TeX pattern data. US
+//! English patterns are bundled; patterns for other languages are supplied at
+//! runtime and selected by the language of the text being broken.
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+/// Bundled US English patterns
+const EN_US_PATTERNS: &str = include_str!("patterns/hyph-en-us.tex");
+
+/// Entry of a map keyed by lowercase language tag for a language tag
+///
+/// Falls back from a regional tag to its language (`en-GB` to `en`) and
+/// then to any entry for that language (`en` to `en-US`); text with no
+/// language is US English.
+pub(crate) fn by_language<'a, T>(entries: &'a HashMap<String, T>, language: Option<&str>) -> Option<&'a T> {
+    let tag = language.unwrap_or("en-US").to_ascii_lowercase();
+    let primary = tag.split(['-', '_']).next().unwrap_or("");
+    entries
+        .get(&tag)
+        .or_else(|| entries.get(primary))
+        .or_else(|| {
+            let prefix = format!("{primary}-");
+            let mut matches: Vec<_> = entries.iter().filter(|(tag, _)| tag.starts_with(&prefix)).collect();
+            matches.sort_by(|a, b| a.0.cmp(b.0));
+            matches.first().map(|(_, entry)| *entry)
+        })
+}
+
+/// Hyphenation points for one language, from Liang patterns
+#[derive(Debug, Default)]
+pub struct Hyphenator {
+    /// Letters of each pattern, mapped to the priority between each pair
+    patterns: HashMap<String, Vec<u8>>,
+    /// Longest pattern in characters
+    max_len: usize,
+    /// Words hyphenated explicitly, mapped to their break points (in chars)
+    exceptions: HashMap<String, Vec<usize>>,
+}
+
+impl Hyphenator {
+    /// Parse TeX pattern data
+    ///
+    /// Tokens are whitespace separated and `%` starts a comment. A pattern
+    /// interleaves letters with priorities (`hy3ph`, `.ach4`, with `.` at a
+    /// word edge); a token containing `-` is an exception word (`ta-ble`).
+    /// `\patterns{…}` and `\hyphenation{…}` wrappers are skipped. Returns
+    /// `None` if the data contains no patterns or exceptions.
+    pub fn parse(data: &str) -> Option<Self> {
+        let mut hyphenator = Self::default();
+
+        let tokens = data
+            .lines()
+            .map(|line| line.split('%').next().unwrap_or(""))
+            .flat_map(str::split_whitespace)
+            .map(|token| token.trim_start_matches("\\patterns{").trim_start_matches("\\hyphenation{"))
+            .map(|token| token.trim_end_matches('}'))
+            .filter(|token| !token.is_empty() && !token.starts_with('\\'));
+
+        for token in tokens {
+            if token.contains('-') {
+                let mut word = String::new();
+                let mut points = Vec::new();
+                for c in token.chars() {
+                    if c == '-' {
+                        points.push(word.chars().count());
+                    } else {
+                        word.push(lowercase(c));
+                    }
+                }
+                hyphenator.exceptions.insert(word, points);
+                continue;
+            }
+
+            let mut letters = String::new();
+            let mut priorities = vec![0u8];
+            for c in token.chars() {
+                match c.to_digit(10) {
+                    Some(digit) => *priorities.last_mut().unwrap() = digit as u8,
+                    None => {
+                        letters.push(lowercase(c));
+                        priorities.push(0);
+                    }
+                }
+            }
+            hyphenator.max_len = hyphenator.max_len.max(priorities.len() - 1);
+            hyphenator.patterns.insert(letters, priorities);
+        }
+
+        (!hyphenator.patterns.is_empty() || !hyphenator.exceptions.is_empty()).then_some(hyphenator)
+    }
+
+    /// Byte offsets within `word` where it may be hyphenated
+    pub fn hyphenate(&self, word: &str) -> Vec<usize> {
+        let chars: Vec<char> = word.chars().map(lowercase).collect();
+        let lowered: String = chars.iter().collect();
+
+        let points: Vec<usize> = match self.exceptions.get(&lowered) {
+            Some(points) => points.clone(),
+            None => {
+                // Priorities between letters of ".word.", the odd ones are breaks
+                let dotted: Vec<char> = std::iter::once('.').chain(chars.iter().copied()).chain(Some('.')).collect();
+                let mut priorities = vec![0u8; dotted.len() + 1];
+                for start in 0..dotted.len() {
+                    for end in start + 1..=(start + self.max_len).min(dotted.len()) {
+                        let key: String = dotted[start..end].iter().collect();
+                        if let Some(pattern) = self.patterns.get(&key) {
+                            for (i, &priority) in pattern.iter().enumerate() {
+                                priorities[start + i] = priorities[start + i].max(priority);
+                            }
+                        }
+                    }
+                }
+                (1..chars.len()).filter(|&i| priorities[i + 1] % 2 == 1).collect()
+            }
+        };
+
+        let offsets: Vec<usize> = word.char_indices().map(|(offset, _)| offset).collect();
+        points.into_iter().filter_map(|i| offsets.get(i).copied()).filter(|&o| o > 0).collect()
+    }
+}
+
+/// Lowercase a letter without changing the number of characters
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// When and how words are hyphenated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HyphenationSettings {
+    /// Hyphenate words that overflow a line
+    pub enabled: bool,
+    /// Shortest word, in characters, that is hyphenated
+    pub min_word: usize,
+    /// Fewest characters left before a hyphen
+    pub min_prefix: usize,
+    /// Fewest characters carried to the next line
+    pub min_suffix: usize,
+    /// Most lines in a row that may end with a hyphen (0 for no limit)
+    pub max_consecutive: usize,
+}
+
+impl Default for HyphenationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_word: 5,
+            min_prefix: 2,
+            min_suffix: 3,
+            max_consecutive: 2,
+        }
+    }
+}
+
+/// Hyphenation settings and the pattern dictionaries by language
+#[derive(Debug, Clone)]
+pub struct Hyphenation {
+    pub settings: HyphenationSettings,
+    /// Dictionaries by lowercase language tag
+    dictionaries: HashMap<String, Arc<Hyphenator>>,
+}
+
+impl Default for Hyphenation {
+    fn default() -> Self {
+        static EN_US: OnceLock<Arc<Hyphenator>> = OnceLock::new();
+        let en_us = EN_US.get_or_init(|| Arc::new(Hyphenator::parse(EN_US_PATTERNS).unwrap_or_default()));
+
+        let mut dictionaries = HashMap::new();
+        dictionaries.insert("en-us".to_string(), en_us.clone());
+        Self {
+            settings: HyphenationSettings::default(),
+            dictionaries,
+        }
+    }
+}
+
+impl Hyphenation {
+    /// Add or replace the patterns for a language; returns false if the
+    /// data contains no patterns
+    pub fn add_patterns(&mut self, language: &str, data: &str) -> bool {
+        match Hyphenator::parse(data) {
+            Some(hyphenator) => {
+                self.dictionaries.insert(language.to_ascii_lowercase(), Arc::new(hyphenator));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Dictionary for a language tag (see `by_language`)
+    pub fn dictionary(&self, language: Option<&str>) -> Option<&Hyphenator> {
+        by_language(&self.dictionaries, language).map(|dictionary| dictionary.as_ref())
+    }
+
+    /// Byte offsets within `word` where it may be hyphenated under the
+    /// current settings
+    pub fn break_points(&self, word: &str, language: Option<&str>) -> Vec<usize> {
+        let settings = &self.settings;
+        let len = word.chars().count();
+        if !settings.enabled || len < settings.min_word.max(2) {
+            return Vec::new();
+        }
+        let Some(dictionary) = self.dictionary(language) else {
+            return Vec::new();
+        };
+
+        dictionary
+            .hyphenate(word)
+            .into_iter()
+            .filter(|&offset| {
+                let before = word[..offset].chars().count();
+                before >= settings.min_prefix.max(1) && len - before >= settings.min_suffix.max(1)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split<'a>(word: &'a str, points: &[usize]) -> Vec<&'a str> {
+        let mut parts = Vec::new();
+        let mut start = 0;
+        for &point in points.iter().chain(Some(&word.len())) {
+            parts.push(&word[start..point]);
+            start = point;
+        }
+        parts
+    }
+
+    #[test]
+    fn test_bundled_patterns() {
+        let hyphenator = Hyphenation::default();
+        let dictionary = hyphenator.dictionary(None).unwrap();
+        assert_eq!(split("hyphenation", &dictionary.hyphenate("hyphenation")), vec!["hy", "phen", "ation"]);
+        // Raw points ignore the minimum prefix and suffix
+        assert_eq!(split("Algorithm", &dictionary.hyphenate("Algorithm")), vec!["Al", "go", "rith", "m"]);
+        // Regional and bare tags find the bundled dictionary
+        assert!(hyphenator.dictionary(Some("en-GB")).is_some());
+        assert!(hyphenator.dictionary(Some("de")).is_none());
+    }
+
+    #[test]
+    fn test_parse_and_exceptions() {
+        let data = "% comment\n\\patterns{ 1ba a1n }\n\\hyphenation{ ta-ble }";
+        let hyphenator = Hyphenator::parse(data).unwrap();
+        assert_eq!(hyphenator.hyphenate("banana"), vec![2, 4]);
+        assert_eq!(hyphenator.hyphenate("Table"), vec![2]);
+        assert!(Hyphenator::parse("% nothing here").is_none());
+    }
+
+    #[test]
+    fn test_settings_limits() {
+        let mut hyphenation = Hyphenation::default();
+        assert!(hyphenation.break_points("hyphenation", None).is_empty());
+
+        hyphenation.settings.enabled = true;
+        assert_eq!(hyphenation.break_points("hyphenation", None), vec![2, 6]);
+        hyphenation.settings.min_prefix = 3;
+        hyphenation.settings.min_suffix = 6;
+        assert!(hyphenation.break_points("hyphenation", None).is_empty());
+        hyphenation.settings.min_word = 20;
+        hyphenation.settings.min_suffix = 3;
+        assert!(hyphenation.break_points("hyphenation", None).is_empty());
+
+        assert!(hyphenation.add_patterns("xx", "1ba"));
+        hyphenation.settings.min_word = 5;
+        hyphenation.settings.min_prefix = 2;
+        assert_eq!(hyphenation.break_points("ababab", Some("XX")), vec![3]);
+    }
+}
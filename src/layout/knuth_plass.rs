@@ -0,0 +1,364 @@
+//! Total-fit line breaking (Knuth–Plass)
+//!
+//! A paragraph is modelled as boxes (unbreakable text), glue (spaces, which
+//! may stretch) and penalties (hyphenation points and forced breaks). Every
+//! feasible sequence of breaks is scored by its demerits and the cheapest one
+//! wins, so a short line early on can buy evenly filled lines later. Lines are
+//! set ragged, so glue never shrinks: a line wider than the measure is
+//! infeasible.
+
+use std::collections::HashMap;
+
+/// Penalty of a forced break
+const FORCED: f64 = f64::NEG_INFINITY;
+/// Badness of a line that cannot stretch to the measure
+const INF_BAD: f64 = 10_000.0;
+/// Share of a space's width it may stretch by
+const SPACE_STRETCH: f32 = 0.5;
+
+/// Costs used to choose between sets of breaks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OptimalFitParams {
+    /// Added to the badness of every line, favouring fewer lines
+    pub line_penalty: f64,
+    /// Penalty for breaking at a hyphenation point
+    pub hyphen_penalty: f64,
+    /// Extra demerits for two hyphenated lines in a row
+    pub consecutive_hyphen_demerits: f64,
+    /// Extra demerits when neighbouring lines differ by more than one
+    /// fitness class (tight, normal, loose, very loose)
+    pub fitness_demerits: f64,
+}
+
+impl Default for OptimalFitParams {
+    fn default() -> Self {
+        // Plain TeX's \linepenalty, \hyphenpenalty, \doublehyphendemerits
+        // and \adjdemerits
+        Self {
+            line_penalty: 10.0,
+            hyphen_penalty: 50.0,
+            consecutive_hyphen_demerits: 10_000.0,
+            fitness_demerits: 10_000.0,
+        }
+    }
+}
+
+/// An element of the paragraph model
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Item {
+    /// Text that cannot be broken
+    Box { width: f32 },
+    /// Space between words; a line may end here if a box precedes it
+    Glue { width: f32, stretch: f32 },
+    /// A possible break costing `penalty`, adding `width` to a line ending
+    /// here; `flagged` marks hyphens
+    Penalty { width: f32, penalty: f64, flagged: bool },
+}
+
+/// An item and the byte range of paragraph text it stands for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Element {
+    pub item: Item,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Build the paragraph model from its graphemes
+///
+/// `widths` holds each grapheme's advance and `hyphens` the offsets where a
+/// word may be hyphenated with the width of the hyphen there. A `"\n"`
+/// grapheme is a forced break, runs of whitespace are glue and everything
+/// else is boxed. Lines ending at a forced break, like the last line, are
+/// filled with infinitely stretchable glue.
+pub(crate) fn paragraph_items(
+    graphemes: &[(usize, &str)],
+    widths: &[f32],
+    hyphens: &[(usize, f32)],
+    hyphen_penalty: f64,
+    text_len: usize,
+) -> Vec<Element> {
+    let mut elements: Vec<Element> = Vec::new();
+    let mut hyphens = hyphens.iter().peekable();
+
+    for (&(offset, grapheme), &width) in graphemes.iter().zip(widths) {
+        let end = offset + grapheme.len();
+        // Points inside a grapheme are skipped
+        while hyphens.next_if(|(point, _)| *point < offset).is_some() {}
+        if let Some(&(_, hyphen_width)) = hyphens.next_if(|(point, _)| *point == offset) {
+            elements.push(Element {
+                item: Item::Penalty { width: hyphen_width, penalty: hyphen_penalty, flagged: true },
+                start: offset,
+                end: offset,
+            });
+        }
+
+        if grapheme == "\n" {
+            elements.extend(forced_break(offset, end));
+            continue;
+        }
+        let item = if grapheme.chars().all(char::is_whitespace) {
+            Item::Glue { width, stretch: width * SPACE_STRETCH }
+        } else {
+            Item::Box { width }
+        };
+
+        // Merge neighbouring boxes, and neighbouring glue
+        match (elements.last_mut(), item) {
+            (Some(Element { item: Item::Box { width: last }, end: last_end, .. }), Item::Box { width }) => {
+                *last += width;
+                *last_end = end;
+            }
+            (
+                Some(Element { item: Item::Glue { width: last, stretch: last_stretch }, end: last_end, .. }),
+                Item::Glue { width, stretch },
+            ) => {
+                *last += width;
+                *last_stretch += stretch;
+                *last_end = end;
+            }
+            _ => elements.push(Element { item, start: offset, end }),
+        }
+    }
+
+    elements.extend(forced_break(text_len, text_len));
+    elements
+}
+
+/// Fill glue and a forced break
+fn forced_break(start: usize, end: usize) -> [Element; 2] {
+    [
+        Element {
+            item: Item::Glue { width: 0.0, stretch: f32::INFINITY },
+            start,
+            end: start,
+        },
+        Element {
+            item: Item::Penalty { width: 0.0, penalty: FORCED, flagged: false },
+            start,
+            end,
+        },
+    ]
+}
+
+/// A feasible break and the cheapest way found to reach it
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    /// Index of the element broken at
+    position: usize,
+    fitness: usize,
+    /// Hyphenated lines in a row ending here
+    hyphens: usize,
+    demerits: f64,
+    previous: Option<usize>,
+}
+
+/// Choose the breaks of a paragraph that minimize total demerits
+///
+/// Returns where each line after the first starts and whether the line
+/// before it ends with a hyphen; forced breaks are not listed. Returns `None`
+/// if there is no way to break the paragraph without an overfull line (a box
+/// wider than the measure), or when `max_consecutive` (if not 0) cannot be
+/// kept to.
+pub(crate) fn optimal_breaks(
+    elements: &[Element],
+    max_width: f32,
+    params: &OptimalFitParams,
+    max_consecutive: usize,
+) -> Option<Vec<(usize, bool)>> {
+    // Width, finite stretch and fill glue of the elements before each index
+    let mut widths = vec![0.0f64; elements.len() + 1];
+    let mut stretches = vec![0.0f64; elements.len() + 1];
+    let mut fills = vec![0usize; elements.len() + 1];
+    for (i, element) in elements.iter().enumerate() {
+        let (width, stretch) = match element.item {
+            Item::Box { width } => (width, 0.0),
+            Item::Glue { width, stretch } => (width, stretch),
+            Item::Penalty { .. } => (0.0, 0.0),
+        };
+        widths[i + 1] = widths[i] + width as f64;
+        stretches[i + 1] = stretches[i] + if stretch.is_finite() { stretch as f64 } else { 0.0 };
+        fills[i + 1] = fills[i] + usize::from(stretch.is_infinite());
+    }
+
+    // A line after a break starts at the next box or forced break; glue
+    // and penalties after a break are discarded
+    let line_start = |position: usize| {
+        elements[position + 1..]
+            .iter()
+            .position(|e| matches!(e.item, Item::Box { .. } | Item::Penalty { penalty: FORCED, .. }))
+            .map_or(elements.len(), |i| position + 1 + i)
+    };
+
+    let mut nodes = vec![Node {
+        position: 0,
+        fitness: 1,
+        hyphens: 0,
+        demerits: 0.0,
+        previous: None,
+    }];
+    // Active nodes with the index their lines start at
+    let mut active: Vec<(usize, usize)> = vec![(0, 0)];
+    let max_width = max_width as f64;
+
+    for (position, element) in elements.iter().enumerate() {
+        let (penalty, penalty_width, flagged) = match element.item {
+            Item::Penalty { width, penalty, flagged } if penalty < f64::INFINITY => (penalty, width as f64, flagged),
+            Item::Glue { stretch, .. }
+                if stretch.is_finite() && position > 0 && matches!(elements[position - 1].item, Item::Box { .. }) =>
+            {
+                (0.0, 0.0, false)
+            }
+            _ => continue,
+        };
+
+        // Cheapest new node for each fitness class and hyphen run
+        let mut best: HashMap<(usize, usize), (f64, usize)> = HashMap::new();
+        active.retain(|&(node_index, start)| {
+            let node = nodes[node_index];
+            let width = widths[position] - widths[start] + penalty_width;
+            let stretch = if fills[position] > fills[start] {
+                f64::INFINITY
+            } else {
+                stretches[position] - stretches[start]
+            };
+            if width > max_width + 1e-3 {
+                // Overfull, and only gets wider further on
+                return false;
+            }
+
+            let ratio = if stretch.is_infinite() {
+                0.0
+            } else if stretch > 0.0 {
+                (max_width - width) / stretch
+            } else if max_width - width < 1e-3 {
+                0.0
+            } else {
+                f64::INFINITY
+            };
+            let badness = (100.0 * ratio.powi(3)).min(INF_BAD);
+            let fitness: usize = match ratio {
+                r if r < 0.5 => 1,
+                r if r <= 1.0 => 2,
+                _ => 3,
+            };
+
+            let hyphens = if flagged { node.hyphens + 1 } else { 0 };
+            if max_consecutive == 0 || hyphens <= max_consecutive {
+                let mut demerits = (params.line_penalty + badness).powi(2);
+                if penalty >= 0.0 {
+                    demerits += penalty.powi(2);
+                } else if penalty > FORCED {
+                    demerits -= penalty.powi(2);
+                }
+                if flagged && node.hyphens > 0 {
+                    demerits += params.consecutive_hyphen_demerits;
+                }
+                if fitness.abs_diff(node.fitness) > 1 {
+                    demerits += params.fitness_demerits;
+                }
+
+                let total = node.demerits + demerits;
+                let entry = best.entry((fitness, hyphens)).or_insert((f64::INFINITY, node_index));
+                if total < entry.0 {
+                    *entry = (total, node_index);
+                }
+            }
+
+            // A forced break ends every line reaching it
+            penalty > FORCED
+        });
+
+        let start = line_start(position);
+        let mut created: Vec<_> = best.into_iter().collect();
+        created.sort_by_key(|a| a.0);
+        for ((fitness, hyphens), (demerits, previous)) in created {
+            nodes.push(Node {
+                position,
+                fitness,
+                hyphens,
+                demerits,
+                previous: Some(previous),
+            });
+            active.push((nodes.len() - 1, start));
+        }
+
+        if active.is_empty() {
+            return None;
+        }
+    }
+
+    // Every active node now ends the paragraph; follow the cheapest back
+    let mut node_index = active
+        .iter()
+        .map(|&(index, _)| index)
+        .min_by(|&a, &b| nodes[a].demerits.total_cmp(&nodes[b].demerits))?;
+    let mut breaks = Vec::new();
+    while let Some(previous) = nodes[node_index].previous {
+        let element = &elements[nodes[node_index].position];
+        match element.item {
+            Item::Glue { .. } => breaks.push((element.end, false)),
+            Item::Penalty { flagged: true, .. } => breaks.push((element.start, true)),
+            Item::Penalty { .. } | Item::Box { .. } => {}
+        }
+        node_index = previous;
+    }
+    breaks.reverse();
+    Some(breaks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unicode_segmentation::UnicodeSegmentation;
+
+    /// Lines of `text` broken at `max_width`, every grapheme 1 wide
+    fn break_lines(text: &str, max_width: f32, hyphens: &[(usize, f32)]) -> Option<Vec<String>> {
+        let graphemes: Vec<(usize, &str)> = text.grapheme_indices(true).collect();
+        let widths = vec![1.0; graphemes.len()];
+        let params = OptimalFitParams::default();
+        let elements = paragraph_items(&graphemes, &widths, hyphens, params.hyphen_penalty, text.len());
+        let breaks = optimal_breaks(&elements, max_width, &params, 0)?;
+
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for (offset, hyphen) in breaks {
+            lines.push(format!("{}{}", &text[start..offset], if hyphen { "-" } else { "" }));
+            start = offset;
+        }
+        lines.push(text[start..].to_string());
+        Some(lines)
+    }
+
+    #[test]
+    fn test_model() {
+        let text = "ab  cd\nef";
+        let graphemes: Vec<(usize, &str)> = text.grapheme_indices(true).collect();
+        let elements = paragraph_items(&graphemes, &[1.0; 9], &[(5, 0.5)], 50.0, text.len());
+        let items: Vec<_> = elements.iter().map(|e| (e.item, e.start..e.end)).collect();
+        assert_eq!(items[0], (Item::Box { width: 2.0 }, 0..2));
+        assert_eq!(items[1], (Item::Glue { width: 2.0, stretch: 1.0 }, 2..4));
+        assert_eq!(items[3], (Item::Penalty { width: 0.5, penalty: 50.0, flagged: true }, 5..5));
+        // The newline ends its line with fill glue, as does the paragraph
+        assert!(matches!(items[5].0, Item::Glue { stretch, .. } if stretch.is_infinite()));
+        assert_eq!(items[6], (Item::Penalty { width: 0.0, penalty: FORCED, flagged: false }, 6..7));
+        assert_eq!(items.len(), 10);
+    }
+
+    #[test]
+    fn test_total_fit_evens_lines() {
+        // First fit leaves "ddddd" alone on a line it cannot fill; the total
+        // fit moves "c" down to share it
+        let lines = break_lines("aa bb c ddddd eee", 7.0, &[]).unwrap();
+        assert_eq!(lines, vec!["aa bb ", "c ddddd ", "eee"]);
+    }
+
+    #[test]
+    fn test_hyphens_and_overfull() {
+        // "dddd-dd" only fits hyphenated
+        let lines = break_lines("aaa dddddd", 5.0, &[(8, 1.0)]).unwrap();
+        assert_eq!(lines, vec!["aaa ", "dddd-", "dd"]);
+
+        // A word wider than the line cannot be set
+        assert!(break_lines("aaa dddddd", 4.0, &[(8, 1.0)]).is_none());
+    }
+}
@@ -1,21 +1,54 @@
 //! Line breaking algorithm
 
-use crate::document::{BlockKind, BlockMeta, ParagraphId};
+use crate::document::{
+    BlockKind, BlockMeta, LineBreakMode, NoteId, ObjectId, ObjectTable, ParagraphId, TabAlignment, TabLeader, TextDirection,
+};
 use crate::layout::engine::{ClusterInfo, LineLayout, ParagraphLayout, BASELINE, INDENT_WIDTH};
 use crate::layout::font::FontMetrics;
+use crate::layout::hyphenation::Hyphenation;
+use crate::layout::knuth_plass::{optimal_breaks, paragraph_items, OptimalFitParams};
+use crate::layout::shaping::{font_at, font_spans, grapheme_widths, place_glyphs, shape_paragraph, GlyphRun, PositionedGlyph};
 use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use unicode_bidi::{Level, ParagraphBidiInfo};
 use unicode_segmentation::UnicodeSegmentation;
 
+/// Default distance between tab stops (half an inch)
+pub const DEFAULT_TAB_INTERVAL: f32 = 36.0;
+
 /// Line breaker
-#[derive(Default)]
-pub struct LineBreaker;
+pub struct LineBreaker {
+    /// Hyphenation of words that overflow a line
+    pub hyphenation: Hyphenation,
+    /// Line breaking for paragraphs that do not set their own
+    pub mode: LineBreakMode,
+    /// Costs for `LineBreakMode::Optimal`
+    pub optimal: OptimalFitParams,
+    /// Distance between default tab stops for paragraphs that do not set
+    /// their own
+    pub tab_interval: f32,
+}
+
+impl Default for LineBreaker {
+    fn default() -> Self {
+        Self {
+            hyphenation: Hyphenation::default(),
+            mode: LineBreakMode::default(),
+            optimal: OptimalFitParams::default(),
+            tab_interval: DEFAULT_TAB_INTERVAL,
+        }
+    }
+}
 
 impl LineBreaker {
     pub fn new() -> Self {
-        Self
+        Self::default()
     }
 
     /// Layout a paragraph into lines
+    ///
+    /// Inline objects are sized from `objects`, the table of the document
+    /// the paragraph belongs to.
     pub fn layout_paragraph(
         &self,
         para_id: ParagraphId,
@@ -23,19 +56,51 @@ impl LineBreaker {
         block_meta: &BlockMeta,
         max_width: f32,
         font_library: &crate::layout::font::FontLibrary,
+        objects: &ObjectTable,
     ) -> ParagraphLayout {
-        // Adjust width for list indentation
-        let effective_width = match &block_meta.kind {
-            BlockKind::ListItem { indent_level, .. } => {
-                max_width - (*indent_level as f32 * INDENT_WIDTH)
-            }
-            _ => max_width,
+        let indent = match &block_meta.kind {
+            BlockKind::ListItem { indent_level, .. } => *indent_level as f32 * INDENT_WIDTH,
+            _ => 0.0,
         };
+        self.layout_paragraph_indented(para_id, text, block_meta, max_width, indent, font_library, objects)
+    }
+
+    /// Layout a paragraph into lines narrowed by an indent (list items
+    /// whose list definition sets their indent)
+    #[allow(clippy::too_many_arguments)]
+    pub fn layout_paragraph_indented(
+        &self,
+        para_id: ParagraphId,
+        text: &str,
+        block_meta: &BlockMeta,
+        max_width: f32,
+        indent: f32,
+        font_library: &crate::layout::font::FontLibrary,
+        objects: &ObjectTable,
+    ) -> ParagraphLayout {
+        let effective_width = max_width - indent;
 
         // Determine default font (ID 0 usually)
         let default_font_id = crate::layout::font::FontId(0);
+        // Stand-in metrics if even the default font is missing
+        let fallback_metrics = FontMetrics::default();
+        let default_ascent = font_library.get(default_font_id).map_or(BASELINE, |m| m.ascent);
+        // Baseline sits above the deepest descent on the line
+        let baseline = |height: f32, descent: f32| {
+            if descent > 0.0 {
+                height - descent
+            } else {
+                default_ascent
+            }
+        };
         
+        // Resolve bidi levels (UAX #9), then shape each run of the paragraph
+        let bidi = ParagraphBidiInfo::new(text, base_level(block_meta.direction));
+        let runs = shape_paragraph(text, block_meta, &bidi.levels, font_library, objects);
+
         let mut lines = Vec::new();
+        // Tabs with the leader of the stop each one reaches
+        let mut tabs = Vec::new();
 
         if text.is_empty() {
              // Empty paragraph height depends on default font?
@@ -46,8 +111,13 @@ impl LineBreaker {
                 byte_range: 0..0,
                 clusters: Vec::new(),
                 height,
-                baseline: BASELINE,
+                baseline: default_ascent,
                 width: 0.0,
+                rtl: false,
+                glyphs: Vec::new(),
+                fonts: Vec::new(),
+                hyphen: false,
+                tabs: Vec::new(),
             });
         } else {
             let mut line_start: usize = 0;
@@ -55,22 +125,50 @@ impl LineBreaker {
             let mut clusters = Vec::new();
             let mut last_break_point: Option<usize> = None;
             let mut last_break_x: f32 = 0.0;
+            // Lines in a row ending with a hyphen
+            let mut hyphenated_lines = 0;
             
-            // Track line height (max of current line)
+            // Track line height and descent (max of current line)
             let mut current_line_height: f32 = 0.0;
-
-            for (byte_idx, grapheme) in text.grapheme_indices(true) {
-                // Determine font for this grapheme
-                let font_id = block_meta.styles.iter()
-                    .find(|s| byte_idx >= s.start && byte_idx < s.end)
-                    .map(|s| s.font_id)
-                    .unwrap_or(default_font_id);
-                    
-                let metrics = font_library.get(font_id)
+            let mut current_line_descent: f32 = 0.0;
+
+            // Cluster widths come from the shaped glyphs
+            let graphemes: Vec<(usize, &str)> = text.grapheme_indices(true).collect();
+            let starts: Vec<usize> = graphemes.iter().map(|(idx, _)| *idx).collect();
+            let widths = grapheme_widths(&runs, &starts);
+
+            // Total-fit breaks, when chosen and possible; otherwise lines
+            // are filled greedily. Tab widths depend on where the line
+            // starts, so paragraphs with tabs are always filled greedily.
+            let optimal = block_meta.line_break.unwrap_or(self.mode) == LineBreakMode::Optimal && !text.contains('\t');
+            let planned = optimal
+                .then(|| {
+                    let ltr = |offset: usize| bidi.levels[offset] == Level::ltr();
+                    let hyphens = self.hyphen_points(text, block_meta, &ltr, &runs, font_library);
+                    let elements = paragraph_items(&graphemes, &widths, &hyphens, self.optimal.hyphen_penalty, text.len());
+                    optimal_breaks(&elements, effective_width, &self.optimal, self.hyphenation.settings.max_consecutive)
+                })
+                .flatten();
+            let mut planned = planned.as_deref().map(|breaks| breaks.iter().copied().peekable());
+
+            for (idx, &(byte_idx, grapheme)) in graphemes.iter().enumerate() {
+                // Metrics of the font shaping resolved for this grapheme
+                let metrics = font_library.get(font_at(&runs, byte_idx))
                     .or_else(|| font_library.get(default_font_id))
-                    .expect("Default font missing");
+                    .unwrap_or(&fallback_metrics);
+
+                // An inline object is a single unbreakable cluster with its own size
+                let object = grapheme
+                    .chars()
+                    .next()
+                    .and_then(ObjectId::from_replacement_char)
+                    .and_then(|id| objects.get(&id));
+                let cluster_height = object.map_or(metrics.line_height, |o| o.height.max(metrics.line_height));
 
-                current_line_height = current_line_height.max(metrics.line_height);
+                // A tab reaches the next stop from where it starts
+                let tab_at = |x: f32| self.tab_advance(block_meta, x, &graphemes[idx + 1..], &widths[idx + 1..]);
+                let mut tab = (grapheme == "\t").then(|| tab_at(x));
+                let mut cluster_width = tab.map_or(widths[idx], |(width, _)| width);
 
                 // Check for explicit line break
                 if grapheme == "\n" {
@@ -78,42 +176,67 @@ impl LineBreaker {
                         byte_range: line_start..byte_idx,
                         clusters: std::mem::take(&mut clusters),
                         height: if current_line_height == 0.0 { metrics.line_height } else { current_line_height },
-                        baseline: BASELINE,
+                        baseline: if current_line_height == 0.0 {
+                            metrics.ascent
+                        } else {
+                            baseline(current_line_height, current_line_descent)
+                        },
                         width: x,
+                        rtl: false,
+                        glyphs: Vec::new(),
+                        fonts: Vec::new(),
+                        hyphen: false,
+                        tabs: Vec::new(),
                     });
                     line_start = byte_idx + grapheme.len();
                     x = 0.0;
                     last_break_point = None;
+                    hyphenated_lines = 0;
                     current_line_height = 0.0;
+                    current_line_descent = 0.0;
                     continue;
                 }
 
-                // Calculate width using provided metrics
-                let cluster_width = if grapheme == "\t" {
-                    metrics.default_width * 4.0
-                } else if grapheme.chars().all(|c| c.is_control()) {
-                    0.0
-                } else if grapheme.len() == 1 {
-                     metrics.width(grapheme.chars().next().unwrap())
-                } else {
-                     grapheme.chars().map(|c| metrics.width(c)).sum()
-                };
-
                 // Track potential break points (after whitespace)
                 if grapheme.chars().all(|c| c.is_whitespace()) {
                     last_break_point = Some(byte_idx + grapheme.len());
                     last_break_x = x + cluster_width;
                 }
 
+                // Planned break before this grapheme (skipping any that fell
+                // on an explicit break)
+                let planned_break = planned.as_mut().and_then(|breaks| {
+                    while breaks.next_if(|(offset, _)| *offset < byte_idx).is_some() {}
+                    breaks.next_if(|(offset, _)| *offset == byte_idx)
+                });
+
                 // Check for soft wrap
-                if x + cluster_width > effective_width && !clusters.is_empty() {
-                    // Break at last break point if available
-                    let (break_offset, break_x) = if let Some(bp) = last_break_point {
+                let overflow = planned.is_none() && x + cluster_width > effective_width && !clusters.is_empty();
+                if overflow || planned_break.is_some() {
+                    // Hyphenate the overflowing word if a hyphen still fits,
+                    // else break at last break point if available
+                    let max_hyphenated = self.hyphenation.settings.max_consecutive;
+                    let hyphen = match planned_break {
+                        Some((_, hyphen)) => hyphen.then(|| (byte_idx, x, hyphen_width(text, byte_idx, &runs, font_library))),
+                        None => (max_hyphenated == 0 || hyphenated_lines < max_hyphenated)
+                            .then(|| {
+                                let ltr = |offset: usize| bidi.levels[offset] == Level::ltr();
+                                let word = WordContext { text, block_meta, line_start, byte_idx, x, ltr: &ltr };
+                                self.hyphen_point(word, &clusters, effective_width, &runs, font_library)
+                            })
+                            .flatten(),
+                    };
+                    let (break_offset, break_x) = if let Some((offset, hyphen_x, _)) = hyphen {
+                        (offset, hyphen_x)
+                    } else if planned_break.is_some() {
+                        (byte_idx, x)
+                    } else if let Some(bp) = last_break_point {
                         (bp, last_break_x)
                     } else {
                         // Emergency break at current position
                         (byte_idx, x)
                     };
+                    hyphenated_lines = if hyphen.is_some() { hyphenated_lines + 1 } else { 0 };
 
                     // Split clusters at break point
                     let break_idx = clusters
@@ -124,7 +247,8 @@ impl LineBreaker {
                     let line_clusters: Vec<_> = clusters.drain(..break_idx).collect();
                     let line_width = line_clusters.last()
                         .map(|c| c.x + c.width)
-                        .unwrap_or(0.0);
+                        .unwrap_or(0.0)
+                        + hyphen.map_or(0.0, |(_, _, width)| width);
                     
                     // Note: height should be calculated from the clusters in the line properly if we wrapped.
                     // But we used accumulating max height. 
@@ -135,8 +259,13 @@ impl LineBreaker {
                         byte_range: line_start..break_offset,
                         clusters: line_clusters,
                         height: current_line_height,
-                        baseline: BASELINE,
+                        baseline: baseline(current_line_height, current_line_descent),
                         width: line_width,
+                        rtl: false,
+                        glyphs: Vec::new(),
+                        fonts: Vec::new(),
+                        hyphen: hyphen.is_some(),
+                        tabs: Vec::new(),
                     });
 
                     // Adjust remaining clusters
@@ -147,13 +276,28 @@ impl LineBreaker {
                     line_start = break_offset;
                     x -= break_x;
                     last_break_point = None;
-                    current_line_height = metrics.line_height; // Start next line with current char's height
+                    current_line_height = cluster_height; // Start next line with current char's height
+                    current_line_descent = metrics.descent();
+
+                    // A tab starting the new line is measured again from there
+                    if tab.is_some() && break_offset <= byte_idx {
+                        tab = Some(tab_at(x));
+                        cluster_width = tab.map_or(cluster_width, |(width, _)| width);
+                    }
                 }
 
+                // Counted after any wrap, so a tall cluster only grows its own line
+                current_line_height = current_line_height.max(cluster_height);
+                current_line_descent = current_line_descent.max(metrics.descent());
+
+                if let Some((_, leader)) = tab {
+                    tabs.push((byte_idx, leader));
+                }
                 clusters.push(ClusterInfo {
                     byte_offset: byte_idx,
                     x,
                     width: cluster_width,
+                    level: 0,
                 });
                 x += cluster_width;
             }
@@ -171,21 +315,297 @@ impl LineBreaker {
                     byte_range: line_start..text.len(),
                     clusters,
                     height: final_height,
-                    baseline: BASELINE,
+                    baseline: baseline(final_height, current_line_descent),
                     width: x,
+                    rtl: false,
+                    glyphs: Vec::new(),
+                    fonts: Vec::new(),
+                    hyphen: false,
+                    tabs: Vec::new(),
                 });
             }
         }
 
+        // Place each line's clusters in visual order, then its glyphs
+        let rtl = bidi.paragraph_level.is_rtl();
+        for line in &mut lines {
+            line.rtl = rtl;
+            if !bidi.is_pure_ltr {
+                reorder_line(&bidi, line);
+            }
+            line.glyphs = place_glyphs(line, &runs, text);
+            line.fonts = font_spans(&runs, line.byte_range.clone());
+            line.tabs = tabs.iter().filter(|(offset, _)| line.byte_range.contains(offset)).copied().collect();
+            if line.hyphen {
+                line.glyphs.extend(hyphen_glyph(line, &runs, font_library));
+            }
+        }
+
         let total_height = lines.iter().map(|l| l.height).sum::<f32>()
             + (block_meta.kind.spacing_after() * 16.0); // Spacing after uses default/fixed unit? 
             // Or should correspond to last line height?
-            
+
+        // Note references, by the line they fall on
+        let note_refs = text
+            .char_indices()
+            .filter_map(|(offset, c)| {
+                let note_id = NoteId::from_reference_char(c)?;
+                let line_idx = lines
+                    .iter()
+                    .position(|l| l.byte_range.contains(&offset))
+                    .unwrap_or(lines.len() - 1);
+                Some((line_idx, note_id))
+            })
+            .collect();
+
         ParagraphLayout {
             para_id,
             lines,
             total_height,
             content_hash: hash_text(text),
+            max_width,
+            break_before: block_meta.break_before,
+            note_refs,
+        }
+    }
+}
+
+impl LineBreaker {
+    /// Width of a tab starting at `x`, and the leader of the stop it
+    /// reaches
+    ///
+    /// `following` (with its `widths`) is the text after the tab; the part
+    /// up to the next tab or line break is aligned to the stop. A stop the
+    /// aligned text would have to start before `x` to meet is skipped for the
+    /// next one; past the last custom stop, tabs reach the next multiple of
+    /// the tab interval.
+    fn tab_advance(&self, block_meta: &BlockMeta, x: f32, following: &[(usize, &str)], widths: &[f32]) -> (f32, TabLeader) {
+        let segment = || {
+            following
+                .iter()
+                .zip(widths)
+                .take_while(|((_, grapheme), _)| *grapheme != "\t" && *grapheme != "\n")
+        };
+        let separator = following
+            .first()
+            .map_or(block_meta.language.as_deref(), |&(offset, _)| block_meta.language_at(offset));
+        let separator = decimal_separator(separator);
+
+        for stop in block_meta.tab_stops.iter().filter(|stop| stop.position > x) {
+            let aligned = match stop.alignment {
+                TabAlignment::Left => 0.0,
+                TabAlignment::Center => segment().map(|(_, width)| width).sum::<f32>() / 2.0,
+                TabAlignment::Right => segment().map(|(_, width)| width).sum(),
+                TabAlignment::Decimal => segment()
+                    .take_while(|((_, grapheme), _)| !grapheme.starts_with(separator))
+                    .map(|(_, width)| width)
+                    .sum(),
+            };
+            if stop.position - aligned >= x {
+                return (stop.position - x - aligned, stop.leader);
+            }
+        }
+
+        let interval = block_meta.tab_interval.unwrap_or(self.tab_interval);
+        if interval <= 0.0 {
+            return (0.0, TabLeader::None);
+        }
+        (((x / interval).floor() + 1.0) * interval - x, TabLeader::None)
+    }
+}
+
+/// Decimal separator of a language (BCP 47 tag), for decimal tab stops;
+/// text with no language uses a point
+fn decimal_separator(language: Option<&str>) -> char {
+    let tag = language.unwrap_or("en-US").to_ascii_lowercase();
+    let primary = tag.split(['-', '_']).next().unwrap_or("");
+    // Swiss German keeps the point its neighbours replace with a comma
+    if tag == "de-ch" {
+        return '.';
+    }
+    match primary {
+        "bg" | "ca" | "cs" | "da" | "de" | "el" | "es" | "et" | "eu" | "fi" | "fr" | "gl" | "hr" | "hu" | "id" | "is"
+        | "it" | "lt" | "lv" | "nb" | "nl" | "nn" | "no" | "pl" | "pt" | "ro" | "ru" | "sk" | "sl" | "sr" | "sv"
+        | "tr" | "uk" | "vi" => ',',
+        _ => '.',
+    }
+}
+
+/// The word being broken when a line overflows
+struct WordContext<'a> {
+    /// Paragraph text
+    text: &'a str,
+    block_meta: &'a BlockMeta,
+    /// Start of the line being filled
+    line_start: usize,
+    /// Offset of the grapheme that overflows the line
+    byte_idx: usize,
+    /// Pen position of that grapheme
+    x: f32,
+    /// Whether the text at an offset is left-to-right at the paragraph level
+    ltr: &'a dyn Fn(usize) -> bool,
+}
+
+impl LineBreaker {
+    /// Latest hyphenation point in the overflowing word at which the line,
+    /// with a hyphen appended, still fits
+    ///
+    /// Returns the offset to break at, the pen position there and the width
+    /// of the hyphen. Only words laid out left to right in a left-to-right
+    /// paragraph are hyphenated, so the hyphen always ends the line.
+    fn hyphen_point(
+        &self,
+        word: WordContext,
+        clusters: &[ClusterInfo],
+        max_width: f32,
+        runs: &[GlyphRun],
+        font_library: &crate::layout::font::FontLibrary,
+    ) -> Option<(usize, f32, f32)> {
+        if !self.hyphenation.settings.enabled {
+            return None;
+        }
+
+        // The run of letters around the overflowing grapheme
+        let text = word.text;
+        let Range { start, end } = word_around(text, word.byte_idx);
+        if start >= end || !(word.ltr)(start) {
+            return None;
+        }
+
+        let language = word.block_meta.language_at(start);
+        self.hyphenation
+            .break_points(&text[start..end], language)
+            .into_iter()
+            .rev()
+            .map(|point| start + point)
+            .filter(|&offset| offset > word.line_start && offset <= word.byte_idx)
+            .find_map(|offset| {
+                let x = clusters
+                    .iter()
+                    .find(|c| c.byte_offset == offset)
+                    .map_or(word.x, |c| c.x);
+                let hyphen_width = hyphen_width(text, offset, runs, font_library);
+                (x + hyphen_width <= max_width).then_some((offset, x, hyphen_width))
+            })
+    }
+
+    /// Every hyphenation point of the paragraph, with the width of the
+    /// hyphen there, for total-fit breaking
+    fn hyphen_points(
+        &self,
+        text: &str,
+        block_meta: &BlockMeta,
+        ltr: &dyn Fn(usize) -> bool,
+        runs: &[GlyphRun],
+        font_library: &crate::layout::font::FontLibrary,
+    ) -> Vec<(usize, f32)> {
+        if !self.hyphenation.settings.enabled {
+            return Vec::new();
+        }
+
+        let mut points = Vec::new();
+        let mut chars = text.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            if !c.is_alphabetic() {
+                continue;
+            }
+            let mut end = start + c.len_utf8();
+            while let Some((offset, c)) = chars.next_if(|(_, c)| c.is_alphabetic()) {
+                end = offset + c.len_utf8();
+            }
+            if ltr(start) {
+                let language = block_meta.language_at(start);
+                for point in self.hyphenation.break_points(&text[start..end], language) {
+                    let offset = start + point;
+                    points.push((offset, hyphen_width(text, offset, runs, font_library)));
+                }
+            }
+        }
+        points
+    }
+}
+
+/// The run of letters around an offset
+fn word_around(text: &str, offset: usize) -> Range<usize> {
+    let start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphabetic())
+        .last()
+        .map_or(offset, |(start, _)| start);
+    let end = text[offset..]
+        .char_indices()
+        .find(|(_, c)| !c.is_alphabetic())
+        .map_or(text.len(), |(end, _)| offset + end);
+    start..end
+}
+
+/// Width of a hyphen ending a line at an offset, in the font of the
+/// character before it
+fn hyphen_width(
+    text: &str,
+    offset: usize,
+    runs: &[GlyphRun],
+    font_library: &crate::layout::font::FontLibrary,
+) -> f32 {
+    let before = text[..offset].chars().next_back().map_or(offset, |c| offset - c.len_utf8());
+    font_library
+        .get(font_at(runs, before))
+        .or_else(|| font_library.get(crate::layout::font::FontId(0)))
+        .map_or(0.0, |m| m.width('-'))
+}
+
+/// Glyph for the hyphen ending a hyphenated line, when its font has font data
+fn hyphen_glyph(
+    line: &LineLayout,
+    runs: &[GlyphRun],
+    font_library: &crate::layout::font::FontLibrary,
+) -> Option<PositionedGlyph> {
+    let last = line.clusters.iter().max_by_key(|c| c.byte_offset)?;
+    let font_id = font_at(runs, last.byte_offset);
+    let glyph_id = font_library.get(font_id)?.face.as_ref()?.glyph('-')?;
+    Some(PositionedGlyph {
+        font_id,
+        glyph_id,
+        x: last.x + last.width,
+        y: 0.0,
+    })
+}
+
+/// Explicit paragraph level for a base direction (`None` detects it)
+fn base_level(direction: TextDirection) -> Option<Level> {
+    match direction {
+        TextDirection::Auto => None,
+        TextDirection::Ltr => Some(Level::ltr()),
+        TextDirection::Rtl => Some(Level::rtl()),
+    }
+}
+
+/// Record each cluster's level and lay the line's level runs out left to
+/// right, reversing the clusters of right-to-left runs (rule L2)
+fn reorder_line(bidi: &ParagraphBidiInfo, line: &mut LineLayout) {
+    if line.byte_range.is_empty() {
+        return;
+    }
+
+    let (levels, runs) = bidi.visual_runs(line.byte_range.clone());
+    for cluster in &mut line.clusters {
+        cluster.level = levels[cluster.byte_offset].number();
+    }
+
+    let mut x = 0.0;
+    let mut place = |cluster: &mut ClusterInfo| {
+        cluster.x = x;
+        x += cluster.width;
+    };
+    for run in runs {
+        let first = line.clusters.partition_point(|c| c.byte_offset < run.start);
+        let last = line.clusters.partition_point(|c| c.byte_offset < run.end);
+        let clusters = &mut line.clusters[first..last];
+        if levels[run.start].is_rtl() {
+            clusters.iter_mut().rev().for_each(&mut place);
+        } else {
+            clusters.iter_mut().for_each(&mut place);
         }
     }
 }
@@ -208,12 +628,7 @@ mod tests {
     }
 
     fn para_meta() -> BlockMeta {
-        BlockMeta {
-            kind: BlockKind::Paragraph,
-            start_offset: 0,
-            byte_len: 0,
-            styles: Vec::new(),
-        }
+        BlockMeta::paragraph(0, 0)
     }
 
     #[test]
@@ -226,6 +641,7 @@ mod tests {
             &para_meta(),
             100.0,
             &lib,
+            &ObjectTable::default(),
         );
 
         assert_eq!(layout.lines.len(), 1);
@@ -242,6 +658,7 @@ mod tests {
             &para_meta(),
             100.0,
             &lib,
+            &ObjectTable::default(),
         );
 
         assert_eq!(layout.lines.len(), 1);
@@ -253,11 +670,7 @@ mod tests {
     fn test_line_wrap() {
         let breaker = test_breaker();
         let mut lib = crate::layout::font::FontLibrary::new();
-        lib.set(crate::layout::font::FontId(0), crate::layout::font::FontMetrics { 
-             line_height: 10.0, 
-             char_widths: vec![8.0; 256], 
-             default_width: 8.0 
-        });
+        lib.set(crate::layout::font::FontId(0), crate::layout::font::FontMetrics::new(10.0, vec![8.0; 256], 8.0));
 
         // With 8px per char, 40px width = 5 chars per line
         let layout = breaker.layout_paragraph(
@@ -266,6 +679,7 @@ mod tests {
             &para_meta(),
             40.0,
             &lib,
+            &ObjectTable::default(),
         );
 
         assert_eq!(layout.lines.len(), 2);
@@ -281,10 +695,248 @@ mod tests {
             &para_meta(),
             1000.0,
             &lib,
+            &ObjectTable::default(),
         );
 
         assert_eq!(layout.lines.len(), 2);
         assert_eq!(layout.lines[0].byte_range, 0..5);
         assert_eq!(layout.lines[1].byte_range, 6..11);
     }
+
+    #[test]
+    fn test_inline_object_cluster() {
+        let breaker = test_breaker();
+        let mut lib = crate::layout::font::FontLibrary::new();
+        lib.set(crate::layout::font::FontId(0), crate::layout::font::FontMetrics::new(10.0, vec![8.0; 256], 8.0));
+
+        let mut objects = ObjectTable::default();
+        let source = crate::document::ObjectSource::Url("a.png".to_string());
+        objects.insert(ObjectId(0), crate::document::InlineObject::new(source, 30.0, 24.0));
+
+        let text = format!("ab {}", ObjectId(0).replacement_char());
+        let layout = breaker.layout_paragraph(ParagraphId(0), &text, &para_meta(), 40.0, &lib, &objects);
+
+        // The object does not fit after "ab " and moves to its own line
+        assert_eq!(layout.lines.len(), 2);
+        assert_eq!(layout.lines[1].clusters[0].width, 30.0);
+        assert_eq!(layout.lines[1].height, 24.0);
+        assert_eq!(layout.lines[0].height, 10.0);
+    }
+
+    fn fixed_width_library() -> crate::layout::font::FontLibrary {
+        let mut lib = crate::layout::font::FontLibrary::new();
+        lib.set(crate::layout::font::FontId(0), crate::layout::font::FontMetrics::new(10.0, vec![8.0; 256], 8.0));
+        lib
+    }
+
+    #[test]
+    fn test_bidi_visual_order() {
+        let breaker = test_breaker();
+        let lib = fixed_width_library();
+
+        // Left-to-right paragraph: the Hebrew run is reversed in place
+        let layout = breaker.layout_paragraph(ParagraphId(0), "ab אב", &para_meta(), 1000.0, &lib, &ObjectTable::default());
+        let line = &layout.lines[0];
+        assert!(!line.rtl);
+        let xs: Vec<_> = line.clusters.iter().map(|c| (c.byte_offset, c.x, c.level)).collect();
+        assert_eq!(xs, vec![(0, 0.0, 0), (1, 8.0, 0), (2, 16.0, 0), (3, 32.0, 1), (5, 24.0, 1)]);
+
+        // Right-to-left paragraph (detected from the first strong character):
+        // the Latin run ends up on the left
+        let layout = breaker.layout_paragraph(ParagraphId(0), "אב ab", &para_meta(), 1000.0, &lib, &ObjectTable::default());
+        let line = &layout.lines[0];
+        assert!(line.rtl);
+        let xs: Vec<_> = line.clusters.iter().map(|c| (c.byte_offset, c.x)).collect();
+        assert_eq!(xs, vec![(0, 32.0), (2, 24.0), (4, 16.0), (5, 0.0), (6, 8.0)]);
+    }
+
+    #[test]
+    fn test_explicit_direction() {
+        let breaker = test_breaker();
+        let lib = fixed_width_library();
+        let meta = BlockMeta {
+            direction: TextDirection::Rtl,
+            ..para_meta()
+        };
+
+        let layout = breaker.layout_paragraph(ParagraphId(0), "ab cd", &meta, 1000.0, &lib, &ObjectTable::default());
+        assert!(layout.lines[0].rtl);
+        // A single left-to-right run keeps its order
+        assert_eq!(layout.lines[0].clusters[4].x, 32.0);
+
+        let meta = BlockMeta {
+            direction: TextDirection::Ltr,
+            ..para_meta()
+        };
+        let layout = breaker.layout_paragraph(ParagraphId(0), "אב", &meta, 1000.0, &lib, &ObjectTable::default());
+        assert!(!layout.lines[0].rtl);
+    }
+
+    #[test]
+    fn test_font_data_metrics() {
+        let breaker = test_breaker();
+        let mut lib = crate::layout::font::FontLibrary::new();
+        let data = crate::layout::font::tests::test_font_data();
+        assert!(lib.set_font_data(crate::layout::font::FontId(0), &data, 20.0));
+
+        let layout = breaker.layout_paragraph(ParagraphId(0), "AV\u{05D0}", &para_meta(), 1000.0, &lib, &ObjectTable::default());
+        let line = &layout.lines[0];
+
+        // Shaping splits the -1.6px "AV" kern across the pair; alef is
+        // measured from its own glyph
+        let widths: Vec<_> = line.clusters.iter().map(|c| (c.width * 10.0).round() / 10.0).collect();
+        assert_eq!(widths, vec![11.2, 11.2, 14.0]);
+        assert!((line.width - 36.4).abs() < 1e-4);
+        assert!((line.height - 22.0).abs() < 1e-4);
+        assert!((line.baseline - 17.0).abs() < 1e-4);
+
+        // Glyphs are carried through for drawing: alef sits right of "AV"
+        let glyphs: Vec<_> = line.glyphs.iter().map(|g| (g.glyph_id, (g.x * 10.0).round() / 10.0)).collect();
+        assert_eq!(glyphs, vec![(1, 0.0), (2, 10.4), (3, 22.4)]);
+    }
+
+    #[test]
+    fn test_hyphenation() {
+        let mut breaker = test_breaker();
+        breaker.hyphenation.settings.enabled = true;
+        let lib = fixed_width_library();
+        let layout_text = |breaker: &LineBreaker, text: &str, meta: &BlockMeta, width: f32| {
+            breaker.layout_paragraph(ParagraphId(0), text, meta, width, &lib, &ObjectTable::default())
+        };
+        let lines = |layout: &ParagraphLayout| -> Vec<_> {
+            layout.lines.iter().map(|l| (l.byte_range.clone(), l.hyphen)).collect()
+        };
+
+        // "hy-phen-ation": the latest point that fits with its hyphen wins
+        let layout = layout_text(&breaker, "a hyphenation", &para_meta(), 75.0);
+        assert_eq!(lines(&layout), vec![(0..8, true), (8..13, false)]);
+        assert_eq!(layout.lines[0].width, 72.0);
+        assert_eq!(layout.lines[1].clusters[0].x, 0.0);
+
+        // No dictionary for the paragraph language: break at the space
+        let meta = BlockMeta {
+            language: Some("de".to_string()),
+            ..para_meta()
+        };
+        let layout = layout_text(&breaker, "a hyphenation", &meta, 100.0);
+        assert!(layout.lines.iter().all(|l| !l.hyphen));
+        assert_eq!(layout.lines[0].byte_range, 0..2);
+
+        // "hy-" and "phen-" end consecutive lines, unless limited to one
+        let layout = layout_text(&breaker, "hyphenation", &para_meta(), 40.0);
+        assert_eq!(lines(&layout), vec![(0..2, true), (2..6, true), (6..11, false)]);
+        breaker.hyphenation.settings.max_consecutive = 1;
+        let layout = layout_text(&breaker, "hyphenation", &para_meta(), 40.0);
+        assert_eq!(lines(&layout)[..2], [(0..2, true), (2..7, false)]);
+    }
+
+    #[test]
+    fn test_optimal_breaking() {
+        let mut breaker = test_breaker();
+        let lib = fixed_width_library();
+        let ranges = |breaker: &LineBreaker, text: &str, meta: &BlockMeta, width: f32| -> Vec<_> {
+            let layout = breaker.layout_paragraph(ParagraphId(0), text, meta, width, &lib, &ObjectTable::default());
+            layout.lines.iter().map(|l| (l.byte_range.clone(), l.hyphen, l.clusters[0].x)).collect()
+        };
+        let text = "aa bb c ddddd eee";
+
+        // Greedy leaves "ddddd " alone on its line; total fit evens it out
+        let greedy = ranges(&breaker, text, &para_meta(), 56.0);
+        assert_eq!(greedy[1].0, 8..14);
+        let optimal = BlockMeta {
+            line_break: Some(LineBreakMode::Optimal),
+            ..para_meta()
+        };
+        let lines = ranges(&breaker, text, &optimal, 56.0);
+        assert_eq!(lines, vec![(0..6, false, 0.0), (6..14, false, 0.0), (14..17, false, 0.0)]);
+
+        // The document mode applies unless the paragraph sets its own
+        breaker.mode = LineBreakMode::Optimal;
+        assert_eq!(ranges(&breaker, text, &para_meta(), 56.0), lines);
+        let greedy_meta = BlockMeta {
+            line_break: Some(LineBreakMode::Greedy),
+            ..para_meta()
+        };
+        assert_eq!(ranges(&breaker, text, &greedy_meta, 56.0), greedy);
+
+        // Tab widths depend on where their line starts, so a paragraph with a
+        // tab is filled greedily even in optimal mode
+        let tabbed = "aa bb c ddddd\teee";
+        let greedy_tabbed = ranges(&breaker, tabbed, &greedy_meta, 56.0);
+        assert_eq!(greedy_tabbed[1].0, 8..14);
+        assert_eq!(ranges(&breaker, tabbed, &optimal, 56.0), greedy_tabbed);
+
+        // Hyphenation points are penalties; a word too wide to set falls
+        // back to greedy breaking
+        breaker.hyphenation.settings.enabled = true;
+        let lines = ranges(&breaker, "a hyphenation", &para_meta(), 72.0);
+        assert_eq!(lines, vec![(0..8, true, 0.0), (8..13, false, 0.0)]);
+        breaker.hyphenation.settings.enabled = false;
+        let lines = ranges(&breaker, "a hyphenation", &para_meta(), 72.0);
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[test]
+    fn test_tab_stops() {
+        let breaker = test_breaker();
+        let lib = fixed_width_library();
+        let stop = |position: f32, alignment: TabAlignment, leader: TabLeader| crate::document::TabStop {
+            position,
+            alignment,
+            leader,
+        };
+        let tab_widths = |text: &str, stops: Vec<crate::document::TabStop>| -> Vec<f32> {
+            let meta = BlockMeta {
+                tab_stops: stops,
+                ..para_meta()
+            };
+            let layout = breaker.layout_paragraph(ParagraphId(0), text, &meta, 500.0, &lib, &ObjectTable::default());
+            let line = &layout.lines[0];
+            line.tabs.iter().map(|(offset, _)| line.cluster_at_offset(*offset).unwrap().width).collect()
+        };
+
+        // Default stops every half inch, measured from where the tab starts
+        assert_eq!(tab_widths("a\tb\tc", Vec::new()), vec![28.0, 28.0]);
+
+        // Text after right, center and decimal stops ends, centres or has
+        // its point at the stop
+        assert_eq!(tab_widths("a\tb", vec![stop(100.0, TabAlignment::Left, TabLeader::None)]), vec![92.0]);
+        assert_eq!(tab_widths("a\t12\tb", vec![stop(100.0, TabAlignment::Right, TabLeader::None)]), vec![76.0, 8.0]);
+        assert_eq!(tab_widths("a\tbb", vec![stop(100.0, TabAlignment::Center, TabLeader::None)]), vec![84.0]);
+        assert_eq!(tab_widths("a\t3.25", vec![stop(100.0, TabAlignment::Decimal, TabLeader::None)]), vec![84.0]);
+        assert_eq!(tab_widths("a\t3", vec![stop(100.0, TabAlignment::Decimal, TabLeader::None)]), vec![84.0]);
+
+        // Text too wide to end before a stop moves on to the next stop, and
+        // past the last one to the default grid
+        let right = |position: f32| stop(position, TabAlignment::Right, TabLeader::None);
+        assert_eq!(tab_widths("a\tbbbb", vec![right(30.0), right(100.0)]), vec![60.0]);
+        assert_eq!(tab_widths("a\tbbbb", vec![right(30.0)]), vec![28.0]);
+        assert_eq!(tab_widths("aaaa\tbbbbbb", vec![stop(40.0, TabAlignment::Center, TabLeader::None)]), vec![4.0]);
+        assert_eq!(tab_widths("aaaa\tbbbbbb", vec![stop(50.0, TabAlignment::Center, TabLeader::None)]), vec![4.0]);
+
+        // The decimal separator follows the language of the number
+        let decimal = |text: &str, language: Option<&str>| -> Vec<f32> {
+            let meta = BlockMeta {
+                tab_stops: vec![stop(100.0, TabAlignment::Decimal, TabLeader::None)],
+                language: language.map(str::to_string),
+                ..para_meta()
+            };
+            let layout = breaker.layout_paragraph(ParagraphId(0), text, &meta, 500.0, &lib, &ObjectTable::default());
+            let line = &layout.lines[0];
+            line.tabs.iter().map(|(offset, _)| line.cluster_at_offset(*offset).unwrap().width).collect()
+        };
+        assert_eq!(decimal("a\t1.234,5", Some("de-DE")), vec![52.0]);
+        assert_eq!(decimal("a\t1,234.5", Some("en-GB")), vec![52.0]);
+        assert_eq!(decimal("a\t3,25", None), vec![60.0]);
+        assert_eq!(decimal("a\t3.25", Some("de-CH")), vec![84.0]);
+
+        // The tab takes the leader of the stop it reaches
+        let meta = BlockMeta {
+            tab_stops: vec![stop(10.0, TabAlignment::Left, TabLeader::None), stop(80.0, TabAlignment::Right, TabLeader::Dots)],
+            ..para_meta()
+        };
+        let layout = breaker.layout_paragraph(ParagraphId(0), "ab\tc", &meta, 500.0, &lib, &ObjectTable::default());
+        assert_eq!(layout.lines[0].tabs, vec![(2, TabLeader::Dots)]);
+        assert_eq!(layout.lines[0].width, 80.0);
+    }
 }
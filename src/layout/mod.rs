@@ -2,12 +2,22 @@
 
 mod engine;
 pub mod font;
+pub mod hyphenation;
+mod knuth_plass;
 mod line_break;
 mod pagination;
+pub mod shaping;
+mod story;
+mod table;
 
 pub use engine::{
-    ClusterInfo, LayoutConstraints, LayoutState, LineLayout, ParagraphLayout,
-    BASELINE, INDENT_WIDTH,
+    ClusterInfo, LayoutConstraints, LayoutState, LineLayout, ListItemLayout, Orientation, ParagraphLayout,
+    BASELINE, ENDNOTE_GAP, FOOTNOTE_SEPARATOR, HEADING_NUMBER_GAP, INDENT_WIDTH,
 };
-pub use font::FontMetrics;
-pub use pagination::PageLayout;
+pub use font::{FontFace, FontMetrics};
+pub use hyphenation::{Hyphenation, HyphenationSettings, Hyphenator};
+pub use knuth_plass::OptimalFitParams;
+pub use pagination::{NoteSlice, PageLayout};
+pub use shaping::{GlyphRun, PositionedGlyph, ShapedGlyph};
+pub use story::StoryLayout;
+pub use table::{resolve_column_widths, RowLayout, TableLayout};
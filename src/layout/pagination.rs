@@ -1,6 +1,17 @@
 //! Pagination for multi-page documents
 
-use crate::document::ParagraphId;
+use crate::document::{NoteId, ParagraphId, SectionColumns, SectionId};
+use crate::layout::LayoutConstraints;
+
+/// Run of note lines placed on a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteSlice {
+    pub note_id: NoteId,
+    /// First story-wide line index of the note on this page
+    pub start_line: usize,
+    /// Story-wide line index after the last one on this page
+    pub end_line: usize,
+}
 
 /// Layout information for a page
 #[derive(Debug, Clone)]
@@ -15,20 +26,99 @@ pub struct PageLayout {
     pub end_para: ParagraphId,
     /// Ending line index within paragraph
     pub end_line: usize,
+    /// Page size and margins (from the section this page belongs to)
+    pub constraints: LayoutConstraints,
+    /// Columns the body text flows through (from the section)
+    pub columns: SectionColumns,
+    /// Line (paragraph and line index) that starts each column after the
+    /// first, in column order
+    pub column_breaks: Vec<(ParagraphId, usize)>,
+    /// Y offset of the page top in document coordinates
+    pub y_offset: f32,
+    /// Section this page belongs to
+    pub section_id: SectionId,
+    /// Page number within its section (1-based)
+    pub page_in_section: usize,
+    /// Footnote lines at the bottom of the page
+    pub footnotes: Vec<NoteSlice>,
+    /// Endnote lines following the body text
+    pub endnotes: Vec<NoteSlice>,
 }
 
 impl PageLayout {
     /// Create a new page layout
-    pub fn new(page_index: usize) -> Self {
+    pub fn new(page_index: usize, constraints: LayoutConstraints, y_offset: f32) -> Self {
         Self {
             page_index,
             start_para: ParagraphId(0),
             start_line: 0,
             end_para: ParagraphId(0),
             end_line: 0,
+            constraints,
+            columns: SectionColumns::default(),
+            column_breaks: Vec::new(),
+            y_offset,
+            section_id: SectionId::DEFAULT,
+            page_in_section: 1,
+            footnotes: Vec::new(),
+            endnotes: Vec::new(),
         }
     }
 
+    /// Create the page that follows this one in the same section
+    pub fn next_page(&self, constraints: LayoutConstraints) -> Self {
+        Self {
+            section_id: self.section_id,
+            columns: self.columns,
+            page_in_section: self.page_in_section + 1,
+            ..Self::new(
+                self.page_index + 1,
+                constraints,
+                self.y_offset + self.constraints.page_height,
+            )
+        }
+    }
+
+    /// Create a following page that holds only notes
+    ///
+    /// The body range starts after this page's last line, so it is empty.
+    pub fn continuation_page(&self) -> Self {
+        let mut page = self.next_page(self.constraints);
+        page.start_para = self.end_para;
+        page.start_line = self.end_line + 1;
+        page.end_para = self.end_para;
+        page.end_line = self.end_line;
+        page
+    }
+
+    /// Check whether the page holds any body lines
+    pub fn has_body_lines(&self) -> bool {
+        self.start_para != self.end_para || self.start_line <= self.end_line
+    }
+
+    /// Index of the column that starts at a line, if one does
+    pub fn column_starting_at(&self, para_id: ParagraphId, line_idx: usize) -> Option<usize> {
+        self.column_breaks
+            .iter()
+            .position(|&start| start == (para_id, line_idx))
+            .map(|idx| idx + 1)
+    }
+
+    /// Left edge of a column in page coordinates
+    pub fn column_x(&self, column: usize) -> f32 {
+        self.constraints.margin_left + self.columns.offset(column, self.constraints.content_width())
+    }
+
+    /// Get the page bounds in document coordinates
+    pub fn bounds(&self) -> crate::Rect {
+        crate::Rect::new(
+            0.0,
+            self.y_offset,
+            self.constraints.page_width,
+            self.constraints.page_height,
+        )
+    }
+
     /// Check if this page contains a given paragraph
     pub fn contains_paragraph(&self, para_id: ParagraphId) -> bool {
         para_id >= self.start_para && para_id <= self.end_para
@@ -72,6 +162,7 @@ impl Default for PaginationRules {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::layout::Orientation;
 
     #[test]
     fn test_page_layout() {
@@ -81,6 +172,14 @@ mod tests {
             start_line: 0,
             end_para: ParagraphId(2),
             end_line: 5,
+            constraints: LayoutConstraints::default(),
+            columns: SectionColumns::default(),
+            column_breaks: Vec::new(),
+            y_offset: 0.0,
+            section_id: SectionId::DEFAULT,
+            page_in_section: 1,
+            footnotes: Vec::new(),
+            endnotes: Vec::new(),
         };
 
         assert!(page.contains_paragraph(ParagraphId(0)));
@@ -88,4 +187,18 @@ mod tests {
         assert!(page.contains_paragraph(ParagraphId(2)));
         assert!(!page.contains_paragraph(ParagraphId(3)));
     }
+
+    #[test]
+    fn test_next_page_offset() {
+        let page = PageLayout::new(0, LayoutConstraints::default(), 0.0);
+        let landscape = LayoutConstraints::default().with_orientation(Orientation::Landscape);
+        let next = page.next_page(landscape);
+
+        assert_eq!(next.page_index, 1);
+        assert_eq!(next.y_offset, 792.0);
+        assert_eq!(next.bounds().width, 792.0);
+        assert_eq!(next.bounds().height, 612.0);
+        assert_eq!(next.section_id, page.section_id);
+        assert_eq!(next.page_in_section, 2);
+    }
 }
@@ -0,0 +1,367 @@
+//! Text shaping
+//!
+//! A paragraph is itemized into runs of one font, bidi level and script, and
+//! each run is shaped into glyphs. Fonts registered from font data are shaped
+//! with their OpenType rules (ligatures, joining forms, mark positioning,
+//! kerning); fonts with only measured metrics get one glyph per character.
+
+use crate::document::{BlockMeta, ObjectId, ObjectTable};
+use crate::layout::engine::LineLayout;
+use crate::layout::font::{FontId, FontLibrary, FontMetrics};
+use rustybuzz::{Direction, UnicodeBuffer};
+use std::ops::Range;
+use unicode_bidi::Level;
+use unicode_script::{Script, UnicodeScript};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A glyph produced by shaping
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapedGlyph {
+    /// Glyph id in the run's font (0 when the font has no data)
+    pub glyph_id: u16,
+    /// Byte offset within the paragraph where the glyph's cluster starts
+    pub cluster: usize,
+    /// Horizontal advance in logical pixels
+    pub x_advance: f32,
+    /// Horizontal offset from the pen position
+    pub x_offset: f32,
+    /// Vertical offset from the baseline, positive upwards
+    pub y_offset: f32,
+}
+
+/// Glyphs for a run of text in one font, direction and script
+#[derive(Debug, Clone)]
+pub struct GlyphRun {
+    pub font_id: FontId,
+    /// Byte range within the paragraph
+    pub byte_range: Range<usize>,
+    pub rtl: bool,
+    /// Glyphs in visual (left to right) order
+    pub glyphs: Vec<ShapedGlyph>,
+    /// Glyph ids index the font data, so the run can be drawn from them
+    pub has_glyph_ids: bool,
+}
+
+impl GlyphRun {
+    /// Start offset and total advance of each cluster, in logical order
+    pub fn clusters(&self) -> Vec<(usize, f32)> {
+        let mut clusters: Vec<(usize, f32)> = Vec::new();
+        for glyph in &self.glyphs {
+            match clusters.last_mut() {
+                Some(last) if last.0 == glyph.cluster => last.1 += glyph.x_advance,
+                _ => clusters.push((glyph.cluster, glyph.x_advance)),
+            }
+        }
+        if self.rtl {
+            clusters.reverse();
+        }
+        clusters
+    }
+}
+
+/// A glyph placed on a line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph {
+    pub font_id: FontId,
+    pub glyph_id: u16,
+    /// X from the left edge of the line
+    pub x: f32,
+    /// Offset from the baseline, positive upwards
+    pub y: f32,
+}
+
+/// Shape a paragraph into glyph runs, in logical order
+///
+/// `levels` holds the resolved bidi level of every byte of `text`. Each
+/// grapheme is shaped with the first font of its style's fallback chain that
+/// covers it. Tabs, control characters and inline objects get the advances
+/// layout gives them rather than the font's.
+pub fn shape_paragraph(
+    text: &str,
+    block_meta: &BlockMeta,
+    levels: &[Level],
+    font_library: &FontLibrary,
+    objects: &ObjectTable,
+) -> Vec<GlyphRun> {
+    let style_font = |offset: usize| {
+        block_meta
+            .styles
+            .iter()
+            .find(|s| offset >= s.start && offset < s.end)
+            .map_or(FontId(0), |s| s.font_id)
+    };
+
+    // Itemize: split wherever the resolved font, the bidi level or a real
+    // script changes (common characters such as spaces join the run they
+    // are in)
+    let mut items: Vec<(Range<usize>, FontId)> = Vec::new();
+    let mut start = 0;
+    let mut font = FontId(0);
+    let mut script = Script::Common;
+    for (grapheme_offset, grapheme) in text.grapheme_indices(true) {
+        let grapheme_font = font_library.resolve(style_font(grapheme_offset), grapheme);
+        if grapheme_offset == 0 {
+            font = grapheme_font;
+        }
+        for (idx, c) in grapheme.char_indices() {
+            let offset = grapheme_offset + idx;
+            let char_script = match c.script() {
+                Script::Common | Script::Inherited | Script::Unknown => None,
+                real => Some(real),
+            };
+            let script_change = char_script.is_some_and(|s| script != Script::Common && s != script);
+            if offset > start && (grapheme_font != font || levels[offset] != levels[start] || script_change) {
+                items.push((start..offset, font));
+                start = offset;
+                font = grapheme_font;
+                script = Script::Common;
+            }
+            if let (Script::Common, Some(char_script)) = (script, char_script) {
+                script = char_script;
+            }
+        }
+    }
+    if start < text.len() {
+        items.push((start..text.len(), font));
+    }
+
+    // A font missing from the library is measured with default metrics
+    let fallback = FontMetrics::default();
+    items
+        .into_iter()
+        .map(|(byte_range, font_id)| {
+            let metrics = font_library.get(font_id).unwrap_or(&fallback);
+            let face = metrics.face.as_ref().map(|face| face.shaping_face());
+            let rtl = levels[byte_range.start].is_rtl();
+
+            let mut glyphs = match face {
+                Some(face) => shape_run(text, byte_range.clone(), rtl, metrics, face),
+                None => measure_run(text, byte_range.clone(), rtl, metrics),
+            };
+            for glyph in &mut glyphs {
+                let c = text[glyph.cluster..].chars().next().unwrap_or_default();
+                if let Some(object) = ObjectId::from_replacement_char(c).and_then(|id| objects.get(&id)) {
+                    glyph.x_advance = object.width;
+                } else if c.is_control() {
+                    glyph.x_advance = 0.0;
+                }
+            }
+
+            GlyphRun {
+                font_id,
+                byte_range,
+                rtl,
+                glyphs,
+                has_glyph_ids: face.is_some(),
+            }
+        })
+        .collect()
+}
+
+/// Shape a run with the font's OpenType tables
+fn shape_run(text: &str, range: Range<usize>, rtl: bool, metrics: &FontMetrics, face: &rustybuzz::Face) -> Vec<ShapedGlyph> {
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(&text[range.clone()]);
+    // Surrounding text lets joining scripts connect across run boundaries
+    buffer.set_pre_context(&text[..range.start]);
+    buffer.set_post_context(&text[range.end..]);
+    buffer.set_direction(if rtl { Direction::RightToLeft } else { Direction::LeftToRight });
+    buffer.guess_segment_properties();
+
+    let scale = metrics.size / face.units_per_em() as f32;
+    let output = rustybuzz::shape(face, &[], buffer);
+    output
+        .glyph_infos()
+        .iter()
+        .zip(output.glyph_positions())
+        .map(|(info, pos)| ShapedGlyph {
+            glyph_id: info.glyph_id as u16,
+            cluster: range.start + info.cluster as usize,
+            x_advance: pos.x_advance as f32 * scale,
+            x_offset: pos.x_offset as f32 * scale,
+            y_offset: pos.y_offset as f32 * scale,
+        })
+        .collect()
+}
+
+/// One glyph per character from measured metrics, for fonts without data
+fn measure_run(text: &str, range: Range<usize>, rtl: bool, metrics: &FontMetrics) -> Vec<ShapedGlyph> {
+    let run = &text[range.clone()];
+    let mut glyphs: Vec<ShapedGlyph> = run
+        .char_indices()
+        .map(|(idx, c)| {
+            let next = run[idx + c.len_utf8()..].chars().next();
+            ShapedGlyph {
+                glyph_id: 0,
+                cluster: range.start + idx,
+                x_advance: metrics.width(c) + next.map_or(0.0, |next| metrics.kerning(c, next)),
+                x_offset: 0.0,
+                y_offset: 0.0,
+            }
+        })
+        .collect();
+    if rtl {
+        glyphs.reverse();
+    }
+    glyphs
+}
+
+/// Font a byte of the paragraph was shaped with
+pub fn font_at(runs: &[GlyphRun], offset: usize) -> FontId {
+    let idx = runs.partition_point(|r| r.byte_range.end <= offset);
+    runs.get(idx).map_or(FontId(0), |r| r.font_id)
+}
+
+/// Resolved fonts over a byte range, merging neighbouring runs of one font
+pub fn font_spans(runs: &[GlyphRun], range: Range<usize>) -> Vec<(Range<usize>, FontId)> {
+    let mut spans: Vec<(Range<usize>, FontId)> = Vec::new();
+    for run in runs {
+        let span = run.byte_range.start.max(range.start)..run.byte_range.end.min(range.end);
+        if span.is_empty() {
+            continue;
+        }
+        match spans.last_mut() {
+            Some((last, font)) if *font == run.font_id && last.end == span.start => last.end = span.end,
+            _ => spans.push((span, run.font_id)),
+        }
+    }
+    spans
+}
+
+/// Width of each grapheme from the shaped clusters starting in it
+///
+/// `grapheme_starts` are the graphemes' byte offsets in order. A cluster
+/// covering several graphemes (a ligature) has its advance split evenly
+/// between them, so the caret can still stop inside it.
+pub fn grapheme_widths(runs: &[GlyphRun], grapheme_starts: &[usize]) -> Vec<f32> {
+    let mut widths = vec![0.0; grapheme_starts.len()];
+    for run in runs {
+        let clusters = run.clusters();
+        for (idx, &(start, advance)) in clusters.iter().enumerate() {
+            let end = clusters.get(idx + 1).map_or(run.byte_range.end, |c| c.0);
+            let first = grapheme_starts.partition_point(|&s| s < start);
+            let last = grapheme_starts.partition_point(|&s| s < end);
+            if first < last {
+                let share = advance / (last - first) as f32;
+                widths[first..last].iter_mut().for_each(|w| *w += share);
+            } else if first > 0 {
+                // Cluster starting inside a grapheme (a mark measured on its own)
+                widths[first - 1] += advance;
+            }
+        }
+    }
+    widths
+}
+
+/// Place the glyphs of a laid-out line
+///
+/// Only runs whose glyph ids index font data are placed. Each run's part of
+/// the line is contiguous on screen, so its glyphs are laid out from the
+/// leftmost of its clusters; control characters and inline objects have no
+/// glyph to draw. Tabs advance by the width the line breaker gave them.
+pub fn place_glyphs(line: &LineLayout, runs: &[GlyphRun], text: &str) -> Vec<PositionedGlyph> {
+    let mut placed = Vec::new();
+    for run in runs.iter().filter(|r| r.has_glyph_ids) {
+        let range = run.byte_range.start.max(line.byte_range.start)..run.byte_range.end.min(line.byte_range.end);
+        let left = line
+            .clusters
+            .iter()
+            .filter(|c| range.contains(&c.byte_offset))
+            .map(|c| c.x)
+            .min_by(f32::total_cmp);
+        let Some(mut x) = left else {
+            continue;
+        };
+
+        for glyph in run.glyphs.iter().filter(|g| range.contains(&g.cluster)) {
+            let c = text[glyph.cluster..].chars().next().unwrap_or_default();
+            if !c.is_control() && ObjectId::from_replacement_char(c).is_none() {
+                placed.push(PositionedGlyph {
+                    font_id: run.font_id,
+                    glyph_id: glyph.glyph_id,
+                    x: x + glyph.x_offset,
+                    y: glyph.y_offset,
+                });
+            }
+            x += match c {
+                '\t' => line.cluster_at_offset(glyph.cluster).map_or(0.0, |cluster| cluster.width),
+                _ => glyph.x_advance,
+            };
+        }
+    }
+    placed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::font::tests::test_font_data;
+
+    fn shape(text: &str, library: &FontLibrary) -> Vec<GlyphRun> {
+        let bidi = unicode_bidi::ParagraphBidiInfo::new(text, None);
+        shape_paragraph(text, &BlockMeta::paragraph(0, text.len()), &bidi.levels, library, &ObjectTable::default())
+    }
+
+    #[test]
+    fn test_itemize_by_direction_and_script() {
+        let library = FontLibrary::new();
+        let runs = shape("ab \u{05D0}\u{05D1} cd", &library);
+        let ranges: Vec<_> = runs.iter().map(|r| (r.byte_range.clone(), r.rtl)).collect();
+        assert_eq!(ranges, vec![(0..3, false), (3..7, true), (7..10, false)]);
+
+        // Measured fonts get one glyph per character, reversed in RTL runs
+        assert!(!runs[1].has_glyph_ids);
+        let clusters: Vec<_> = runs[1].glyphs.iter().map(|g| g.cluster).collect();
+        assert_eq!(clusters, vec![5, 3]);
+    }
+
+    #[test]
+    fn test_shape_with_font_data() {
+        let mut library = FontLibrary::new();
+        assert!(library.set_font_data(FontId(0), &test_font_data(), 20.0));
+
+        let runs = shape("AV", &library);
+        assert_eq!(runs.len(), 1);
+        assert!(runs[0].has_glyph_ids);
+        let glyphs: Vec<_> = runs[0].glyphs.iter().map(|g| (g.glyph_id, g.cluster)).collect();
+        assert_eq!(glyphs, vec![(1, 0), (2, 1)]);
+        // The kern pair tightens "AV"
+        let advance: f32 = runs[0].glyphs.iter().map(|g| g.x_advance).sum();
+        assert!((advance - 22.4).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_fallback_splits_runs() {
+        let mut library = FontLibrary::new();
+        let latin = library.add(FontMetrics::from_font_data(&test_font_data(), 20.0).unwrap());
+        let meta = BlockMeta {
+            styles: vec![crate::document::StyleSpan { start: 0, end: 7, font_id: latin }],
+            ..BlockMeta::paragraph(0, 7)
+        };
+
+        // The CJK character is missing from the styled font
+        let text = "AV\u{4E2D}AV";
+        let bidi = unicode_bidi::ParagraphBidiInfo::new(text, None);
+        let runs = shape_paragraph(text, &meta, &bidi.levels, &library, &ObjectTable::default());
+        let fonts: Vec<_> = runs.iter().map(|r| (r.byte_range.clone(), r.font_id)).collect();
+        assert_eq!(fonts, vec![(0..2, latin), (2..5, FontId(0)), (5..7, latin)]);
+        assert!(!runs[1].has_glyph_ids);
+        assert_eq!(font_at(&runs, 3), FontId(0));
+        assert_eq!(font_spans(&runs, 1..6), vec![(1..2, latin), (2..5, FontId(0)), (5..6, latin)]);
+    }
+
+    #[test]
+    fn test_ligature_width_split_between_graphemes() {
+        let run = GlyphRun {
+            font_id: FontId(0),
+            byte_range: 0..3,
+            rtl: false,
+            glyphs: vec![
+                ShapedGlyph { glyph_id: 7, cluster: 0, x_advance: 12.0, x_offset: 0.0, y_offset: 0.0 },
+                ShapedGlyph { glyph_id: 8, cluster: 2, x_advance: 5.0, x_offset: 0.0, y_offset: 0.0 },
+            ],
+            has_glyph_ids: true,
+        };
+        assert_eq!(grapheme_widths(&[run], &[0, 1, 2]), vec![6.0, 6.0, 5.0]);
+    }
+}
@@ -0,0 +1,107 @@
+//! Layout of secondary stories (note bodies)
+//!
+//! Stories are small, so they are relaid out as a whole whenever their
+//! document version changes.
+
+use crate::document::Document;
+use crate::editing::DocPosition;
+use crate::layout::engine::{LineLayout, ParagraphLayout};
+use crate::layout::font::FontLibrary;
+use crate::layout::line_break::LineBreaker;
+
+/// Layout of a complete story
+#[derive(Debug, Clone)]
+pub struct StoryLayout {
+    /// Document version the layout was built from
+    pub version: u64,
+    /// Width the story was broken against
+    pub max_width: f32,
+    /// Paragraph layouts in story order
+    pub paragraphs: Vec<ParagraphLayout>,
+}
+
+impl StoryLayout {
+    /// Lay out every paragraph of a story
+    pub(crate) fn build(
+        document: &Document,
+        max_width: f32,
+        line_breaker: &LineBreaker,
+        font_library: &FontLibrary,
+    ) -> Self {
+        let paragraphs = document
+            .paragraph_order()
+            .filter_map(|para_id| {
+                let meta = document.block_meta(para_id)?;
+                Some(line_breaker.layout_paragraph(
+                    para_id,
+                    &document.paragraph_text(para_id),
+                    meta,
+                    max_width,
+                    font_library,
+                    document.objects(),
+                ))
+            })
+            .collect();
+
+        Self {
+            version: document.version(),
+            max_width,
+            paragraphs,
+        }
+    }
+
+    /// Check whether the layout is stale
+    pub fn is_stale(&self, document: &Document, max_width: f32) -> bool {
+        self.version != document.version() || self.max_width != max_width
+    }
+
+    /// Iterate lines in story order as (paragraph layout, line index, line)
+    pub fn lines(&self) -> impl Iterator<Item = (&ParagraphLayout, usize, &LineLayout)> {
+        self.paragraphs
+            .iter()
+            .flat_map(|p| p.lines.iter().enumerate().map(move |(i, l)| (p, i, l)))
+    }
+
+    /// Get the height of each line in story order
+    pub fn line_heights(&self) -> Vec<f32> {
+        self.lines().map(|(_, _, line)| line.height).collect()
+    }
+
+    /// Get the story-wide index of the line holding a position
+    pub fn line_index(&self, pos: &DocPosition) -> Option<usize> {
+        let mut base = 0;
+        for para in &self.paragraphs {
+            if para.para_id == pos.para_id {
+                return para.line_at_offset(pos.offset).map(|(idx, _)| base + idx);
+            }
+            base += para.lines.len();
+        }
+        None
+    }
+
+    /// Get the X offset of a position within its line
+    pub fn x_for_position(&self, pos: &DocPosition) -> Option<f32> {
+        let para = self.paragraphs.iter().find(|p| p.para_id == pos.para_id)?;
+        let (_, line) = para.line_at_offset(pos.offset)?;
+        Some(line.x_for_offset(pos.offset))
+    }
+
+    /// Get the position closest to an X offset on a story-wide line
+    pub fn position_at(&self, line_index: usize, x: f32) -> Option<DocPosition> {
+        let (para, _, line) = self.lines().nth(line_index)?;
+        Some(DocPosition::new(para.para_id, line.offset_for_x(x)))
+    }
+
+    /// Move a position up or down by whole lines
+    pub fn move_vertical(
+        &self,
+        pos: &DocPosition,
+        delta_lines: i32,
+        preferred_x: Option<f32>,
+    ) -> Option<DocPosition> {
+        let current = self.line_index(pos)?;
+        let target = current.checked_add_signed(delta_lines as isize)?;
+        let x = preferred_x.or_else(|| self.x_for_position(pos))?;
+        self.position_at(target, x)
+    }
+}
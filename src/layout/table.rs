@@ -0,0 +1,284 @@
+//! Table layout
+//!
+//! Each cell is laid out as its own story against its column width, and a
+//! row is as tall as its tallest cell. The anchor paragraph of a table gets
+//! one line per row, so pagination splits tables between rows.
+
+use crate::document::{BlockMeta, ColumnWidth, ParagraphId, Table};
+use crate::editing::DocPosition;
+use crate::layout::engine::{LineLayout, ParagraphLayout, BASELINE};
+use crate::layout::font::FontLibrary;
+use crate::layout::line_break::LineBreaker;
+use crate::layout::story::StoryLayout;
+
+/// Layout of a single row
+#[derive(Debug, Clone)]
+pub struct RowLayout {
+    /// Row height including padding and border
+    pub height: f32,
+    /// Cell layouts in column order
+    pub cells: Vec<StoryLayout>,
+}
+
+/// Layout of a complete table
+#[derive(Debug, Clone)]
+pub struct TableLayout {
+    /// Resolved width of each column (including padding)
+    pub column_widths: Vec<f32>,
+    pub rows: Vec<RowLayout>,
+    pub header_rows: usize,
+    pub cell_padding: f32,
+    pub border_width: f32,
+    /// Width the columns were resolved against
+    available_width: f32,
+    /// Column setup the widths were resolved from
+    columns: Vec<ColumnWidth>,
+}
+
+impl TableLayout {
+    /// Lay out a table, reusing cell layouts from a previous layout where
+    /// the cell and its width are unchanged
+    pub(crate) fn build(
+        table: &Table,
+        available_width: f32,
+        previous: Option<TableLayout>,
+        line_breaker: &LineBreaker,
+        font_library: &FontLibrary,
+    ) -> Self {
+        let padding = table.cell_padding * 2.0;
+
+        // Preferred (unwrapped) width of each auto column
+        let preferred: Vec<f32> = (0..table.column_count())
+            .map(|col| {
+                if table.columns[col] != ColumnWidth::Auto {
+                    return 0.0;
+                }
+                table
+                    .rows
+                    .iter()
+                    .filter_map(|row| row.cells.get(col))
+                    .map(|cell| {
+                        StoryLayout::build(&cell.body, f32::INFINITY, line_breaker, font_library)
+                            .lines()
+                            .map(|(_, _, line)| line.width)
+                            .fold(0.0, f32::max)
+                            + padding
+                    })
+                    .fold(0.0, f32::max)
+            })
+            .collect();
+        let column_widths = resolve_column_widths(&table.columns, available_width, &preferred);
+
+        let mut previous_rows = previous.map(|layout| layout.rows).unwrap_or_default();
+        let rows = table
+            .rows
+            .iter()
+            .enumerate()
+            .map(|(row_idx, row)| {
+                let mut previous_cells: Vec<Option<StoryLayout>> = previous_rows
+                    .get_mut(row_idx)
+                    .map(|row| std::mem::take(&mut row.cells).into_iter().map(Some).collect())
+                    .unwrap_or_default();
+
+                let cells: Vec<StoryLayout> = row
+                    .cells
+                    .iter()
+                    .enumerate()
+                    .map(|(col, cell)| {
+                        let width = (column_widths.get(col).copied().unwrap_or(0.0) - padding).max(1.0);
+                        previous_cells
+                            .get_mut(col)
+                            .and_then(Option::take)
+                            .filter(|layout| !layout.is_stale(&cell.body, width))
+                            .unwrap_or_else(|| {
+                                StoryLayout::build(&cell.body, width, line_breaker, font_library)
+                            })
+                    })
+                    .collect();
+
+                let content_height = cells
+                    .iter()
+                    .map(|cell| cell.line_heights().iter().sum::<f32>())
+                    .fold(0.0, f32::max);
+
+                RowLayout {
+                    height: content_height + padding + table.border_width,
+                    cells,
+                }
+            })
+            .collect();
+
+        Self {
+            column_widths,
+            rows,
+            header_rows: table.header_rows.min(table.row_count()),
+            cell_padding: table.cell_padding,
+            border_width: table.border_width,
+            available_width,
+            columns: table.columns.clone(),
+        }
+    }
+
+    /// Check whether the layout is stale
+    pub fn is_stale(&self, table: &Table, available_width: f32) -> bool {
+        if self.available_width != available_width
+            || self.columns != table.columns
+            || self.cell_padding != table.cell_padding
+            || self.border_width != table.border_width
+            || self.header_rows != table.header_rows.min(table.row_count())
+            || self.rows.len() != table.row_count()
+        {
+            return true;
+        }
+
+        self.rows.iter().zip(&table.rows).any(|(row_layout, row)| {
+            row_layout.cells.len() != row.cells.len()
+                || row_layout
+                    .cells
+                    .iter()
+                    .zip(&row.cells)
+                    .any(|(layout, cell)| layout.version != cell.body.version())
+        })
+    }
+
+    /// Get the total table width
+    pub fn width(&self) -> f32 {
+        self.column_widths.iter().sum()
+    }
+
+    /// Get the X offset of a column from the table's left edge
+    pub fn column_x(&self, col: usize) -> f32 {
+        self.column_widths.iter().take(col).sum()
+    }
+
+    /// Get the column under an X offset from the table's left edge
+    pub fn column_at(&self, x: f32) -> usize {
+        let mut right = 0.0;
+        for (col, width) in self.column_widths.iter().enumerate() {
+            right += width;
+            if x < right {
+                return col;
+            }
+        }
+        self.column_widths.len().saturating_sub(1)
+    }
+
+    /// Get the combined height of the header rows
+    pub fn header_height(&self) -> f32 {
+        self.rows.iter().take(self.header_rows).map(|row| row.height).sum()
+    }
+
+    /// Distance from a cell's top-left corner to its content
+    pub fn content_inset(&self) -> f32 {
+        self.border_width + self.cell_padding
+    }
+
+    /// Map a point inside a row to a column and a position in that cell
+    ///
+    /// `x` is relative to the table's left edge and `y` to the row's top.
+    pub fn hit_test(&self, row: usize, x: f32, y: f32) -> Option<(usize, DocPosition)> {
+        let col = self.column_at(x);
+        let cell = self.cell_layout(row, col)?;
+
+        let mut line_top = self.content_inset();
+        let heights = cell.line_heights();
+        let line_index = heights
+            .iter()
+            .position(|height| {
+                line_top += height;
+                y < line_top
+            })
+            .unwrap_or(heights.len().saturating_sub(1));
+
+        let local_x = x - self.column_x(col) - self.content_inset();
+        Some((col, cell.position_at(line_index, local_x)?))
+    }
+
+    /// Get the layout of a cell
+    pub fn cell_layout(&self, row: usize, col: usize) -> Option<&StoryLayout> {
+        self.rows.get(row)?.cells.get(col)
+    }
+
+    /// Build the anchor paragraph's layout: one line per row
+    pub(crate) fn paragraph_layout(&self, para_id: ParagraphId, block_meta: &BlockMeta) -> ParagraphLayout {
+        let lines: Vec<LineLayout> = self
+            .rows
+            .iter()
+            .map(|row| LineLayout {
+                byte_range: 0..0,
+                clusters: Vec::new(),
+                height: row.height,
+                baseline: BASELINE,
+                width: self.width(),
+                rtl: false,
+                glyphs: Vec::new(),
+                fonts: Vec::new(),
+                hyphen: false,
+                tabs: Vec::new(),
+            })
+            .collect();
+
+        ParagraphLayout {
+            para_id,
+            total_height: lines.iter().map(|l| l.height).sum::<f32>()
+                + block_meta.kind.spacing_after() * 16.0,
+            lines,
+            content_hash: 0,
+            max_width: self.available_width,
+            break_before: block_meta.break_before,
+            note_refs: Vec::new(),
+        }
+    }
+}
+
+/// Resolve column widths against the available width
+///
+/// Fixed and percent columns take their own width; auto columns split
+/// what is left, weighted toward their preferred (unwrapped) width.
+pub fn resolve_column_widths(columns: &[ColumnWidth], available: f32, preferred: &[f32]) -> Vec<f32> {
+    let mut widths: Vec<f32> = columns
+        .iter()
+        .map(|column| match column {
+            ColumnWidth::Fixed(width) => *width,
+            ColumnWidth::Percent(percent) => available * percent / 100.0,
+            ColumnWidth::Auto => 0.0,
+        })
+        .collect();
+
+    let auto_count = columns.iter().filter(|c| **c == ColumnWidth::Auto).count();
+    if auto_count == 0 {
+        return widths;
+    }
+
+    let remaining = (available - widths.iter().sum::<f32>()).max(0.0);
+    let share = remaining / auto_count as f32;
+    let weight = |col: usize| preferred.get(col).copied().unwrap_or(0.0).min(remaining) + share;
+    let total_weight: f32 = (0..columns.len())
+        .filter(|&col| columns[col] == ColumnWidth::Auto)
+        .map(weight)
+        .sum();
+
+    for (col, column) in columns.iter().enumerate() {
+        if *column == ColumnWidth::Auto && total_weight > 0.0 {
+            widths[col] = remaining * weight(col) / total_weight;
+        }
+    }
+    widths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_column_widths() {
+        let columns = [ColumnWidth::Fixed(100.0), ColumnWidth::Percent(25.0), ColumnWidth::Auto];
+        assert_eq!(resolve_column_widths(&columns, 400.0, &[0.0; 3]), vec![100.0, 100.0, 200.0]);
+
+        // Auto columns lean toward wider content
+        let columns = [ColumnWidth::Auto, ColumnWidth::Auto];
+        let widths = resolve_column_widths(&columns, 300.0, &[200.0, 0.0]);
+        assert_eq!(widths.iter().sum::<f32>(), 300.0);
+        assert!(widths[0] > widths[1]);
+    }
+}
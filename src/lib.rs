@@ -10,17 +10,32 @@ pub mod document;
 pub mod editing;
 pub mod layout;
 pub mod render;
+pub mod spell;
 pub mod undo;
 pub mod wasm;
 
+use std::ops::Range;
+use editing::{next_sentence_start, next_word_start, prev_sentence_start, prev_word_start, word_at};
+
 // Re-export WASM types for direct use
 pub use wasm::WasmEditor;
 
 // Re-export primary types
-pub use document::{BlockKind, BlockMeta, Document, ListMarker, ParagraphId};
-pub use editing::{Affinity, Cursor, DocPosition, EditOp, EditResult, Selection};
-pub use layout::{LayoutConstraints, LayoutState, LineLayout, ParagraphLayout};
-pub use render::{DisplayItem, DisplayItemId, DisplayList, DisplayPage, RenderDiff, RenderPatch};
+pub use document::{
+    BlockBreak, BlockKind, BlockMeta, CellId, ColumnWidth, DecorationId, DecorationKind, Document, InlineObject,
+    LineBreakMode,
+    Link, LinkSpan, ListDefinition, ListId, ListLevel, ListMarker, MarkerAlignment, Note, NoteId, NoteKind,
+    NumberFormat, ObjectId, ObjectSource, OutlineEntry, ParagraphId, SearchOptions, SearchQuery, Section, SectionColumns,
+    SectionId, StoryId, TabAlignment,
+    TabLeader, TabStop, Table, TableId, TableOfContents, TextDirection, TocId,
+};
+pub use editing::{
+    Affinity, CellSelection, Cursor, DocPosition, EditOp, EditResult, Granularity, Movement, Selection, TextUnit,
+};
+pub use layout::{LayoutConstraints, LayoutState, LineLayout, OptimalFitParams, Orientation, ParagraphLayout};
+pub use render::{
+    DisplayItem, DisplayItemId, DisplayList, DisplayPage, Focus, RenderDiff, RenderPatch,
+};
 pub use undo::UndoManager;
 
 /// Editor coordinates
@@ -64,8 +79,23 @@ pub struct Editor {
     pub document: Document,
     pub cursor: Cursor,
     pub selection: Option<Selection>,
+    /// Further carets and selections in the body text, besides the primary
+    /// `cursor` and `selection`; collapsed selections are carets
+    pub secondary_selections: Vec<Selection>,
     pub layout: LayoutState,
     pub undo_manager: UndoManager,
+    /// Story holding the cursor and selection
+    pub story: StoryId,
+    /// Block of table cells selected across cell edges
+    pub cell_selection: Option<CellSelection>,
+    /// Search whose matches in the body text are highlighted
+    pub search: Option<SearchQuery>,
+    /// Matches of `search`, kept current from edits
+    search_highlights: document::SearchHighlights,
+    /// Spell checker marking misspelled words of the body text
+    pub spelling: spell::SpellChecker,
+    /// Headings of the body text, for navigation panes
+    outline: document::Outline,
     layout_dirty: bool,
 }
 
@@ -76,8 +106,15 @@ impl Editor {
             document: Document::new(),
             cursor: Cursor::default(),
             selection: None,
+            secondary_selections: Vec::new(),
             layout: LayoutState::new(constraints),
             undo_manager: UndoManager::new(100),
+            story: StoryId::Body,
+            cell_selection: None,
+            search: None,
+            search_highlights: document::SearchHighlights::default(),
+            spelling: spell::SpellChecker::default(),
+            outline: document::Outline::default(),
             layout_dirty: true,
         }
     }
@@ -91,81 +128,725 @@ impl Editor {
     }
 
     /// Insert text at the current cursor position
+    ///
+    /// Characters reserved for note references and inline objects are left
+    /// out, so typed or pasted text cannot place a note or object.
     pub fn insert_text(&mut self, text: &str) -> EditResult {
-        self.undo_manager
-            .begin_transaction("insert", &self.cursor, self.selection.as_ref());
+        let text = document::strip_markers(text);
+        if text.is_empty() {
+            return EditResult {
+                version: self.story_document().version(),
+                new_cursor: self.cursor.position,
+                ..EditResult::default()
+            };
+        }
+        self.insert_unchecked(&text)
+    }
 
-        let position = self.document.position_to_offset(&self.cursor.position);
+    /// Insert text at the cursor as given, marker characters included
+    fn insert_unchecked(&mut self, text: &str) -> EditResult {
+        // With several carets the text goes in at each of them
+        if !self.secondary_selections.is_empty() {
+            let at_caret = |editor: &Self, cursor: &Cursor| {
+                let offset = editor.story_document().position_to_offset(&cursor.position).0;
+                offset..offset
+            };
+            if let Some(result) = self.edit_selections("insert", text, false, at_caret) {
+                return result;
+            }
+        }
+
+        // Table anchors hold no text: typing goes into the first cell
+        if let Some(table_id) = self.body_table_at_cursor() {
+            self.enter_table(table_id, true);
+        }
+        self.cell_selection = None;
+
+        self.begin_transaction("insert");
+
+        let document = self.document.story_mut(self.story);
+        let position = document.position_to_offset(&self.cursor.position);
         let op = EditOp::Insert {
             position,
             text: text.to_string(),
         };
 
-        let reverse = self.document.compute_reverse(&op);
-        let result = self.document.apply_edit(op.clone());
+        let reverse = document.compute_reverse(&op);
+        let result = document.apply_edit(op.clone());
 
         self.undo_manager.record_edit(op, reverse);
         self.undo_manager.commit();
 
-        // Update cursor
+        // Update cursor, keeping the caret against the inserted text
         self.cursor.position = result.new_cursor.clone();
+        self.cursor.affinity = Affinity::Upstream;
         self.selection = None;
 
         // Mark layout dirty
-        self.layout.invalidate(&result);
+        self.invalidate_layout(&result);
+        self.layout_dirty = true;
+
+        result
+    }
+
+    /// Insert a manual page break at the cursor
+    pub fn insert_page_break(&mut self) -> EditResult {
+        self.insert_block_break("page break", BlockBreak::Page)
+    }
+
+    /// Insert a section break at the cursor
+    ///
+    /// Pages from the break onwards use the given constraints until the
+    /// next section break. Returns None outside the body text, where
+    /// sections cannot start.
+    pub fn insert_section_break(&mut self, constraints: LayoutConstraints) -> Option<SectionId> {
+        if self.story != StoryId::Body {
+            return None;
+        }
+
+        let section_id = self
+            .document
+            .add_section(Section::with_constraints(constraints));
+        self.insert_block_break("section break", BlockBreak::Section(section_id));
+        Some(section_id)
+    }
+
+    /// Set the columns of the section at the cursor
+    pub fn set_section_columns(&mut self, columns: SectionColumns) {
+        // Pages are split into the new columns from the start
+        self.edit_section("section columns", |section| {
+            section.columns = SectionColumns {
+                count: columns.count.max(1),
+                ..columns
+            };
+        });
+    }
+
+    /// Change the section at the cursor (its page setup, headers and
+    /// footers) as one undoable step; returns false when nothing changed,
+    /// as outside the body text
+    pub fn edit_section(&mut self, description: &str, edit: impl FnOnce(&mut Section)) -> bool {
+        if self.story != StoryId::Body {
+            return false;
+        }
+        let section_id = self.document.section_of(self.cursor.position.para_id);
+        let Some(mut section) = self.document.section(section_id).cloned() else {
+            return false;
+        };
+        edit(&mut section);
+        if self.document.section(section_id) == Some(&section) {
+            return false;
+        }
+
+        self.begin_transaction(description);
+        self.record_op(EditOp::SetSection {
+            position: self.document.position_to_offset(&self.cursor.position),
+            section_id,
+            section,
+        });
+        self.undo_manager.commit();
+        // The edit reports no paragraphs, yet page sizes may have changed
+        self.layout.invalidate_pages();
+        self.layout_dirty = true;
+        true
+    }
+
+    /// Place a forced break before the cursor, splitting the paragraph
+    /// unless the cursor already sits at the start of one
+    fn insert_block_break(&mut self, description: &str, block_break: BlockBreak) -> EditResult {
+        // Notes flow inside their page area and cannot force breaks
+        if self.story != StoryId::Body {
+            return EditResult::default();
+        }
+
+        self.begin_transaction(description);
+
+        let at_para_start = self.cursor.position.offset == 0
+            && self.cursor.position.para_id != self.document.first_paragraph();
+        if !at_para_start {
+            let op = EditOp::Insert {
+                position: self.document.position_to_offset(&self.cursor.position),
+                text: "\n".to_string(),
+            };
+            let reverse = self.document.compute_reverse(&op);
+            let result = self.document.apply_edit(op.clone());
+            self.undo_manager.record_edit(op, reverse);
+            self.cursor.position = result.new_cursor;
+            self.invalidate_body(&result);
+        }
+
+        let para_id = self.cursor.position.para_id;
+        let meta = self.document.block_meta(para_id).cloned().map(|meta| BlockMeta {
+            break_before: Some(block_break),
+            ..meta
+        });
+        let result = match meta {
+            Some(meta) => self.record_block_meta(meta),
+            None => EditResult {
+                version: self.document.version(),
+                new_cursor: self.cursor.position,
+                ..EditResult::default()
+            },
+        };
+
+        self.undo_manager.commit();
+        self.selection = None;
         self.layout_dirty = true;
 
         result
     }
 
+    /// Set the base direction of the selected paragraphs (or the one
+    /// holding the cursor) as one undoable step
+    pub fn set_direction(&mut self, direction: TextDirection) {
+        let (start, end) = match &self.selection {
+            Some(sel) => sel.ordered(),
+            None => (self.cursor.position, self.cursor.position),
+        };
+
+        self.begin_transaction("set direction");
+        let mut para_id = Some(start.para_id);
+        while let Some(id) = para_id {
+            if let Some(meta) = self.story_document().block_meta(id).cloned() {
+                self.record_block_meta(BlockMeta { direction, ..meta });
+            }
+            para_id = self.story_document().next_paragraph(id).filter(|_| id != end.para_id);
+        }
+        self.undo_manager.commit();
+        self.layout_dirty = true;
+    }
+
+    /// Set the language of the selected text, or of the paragraph at the
+    /// cursor when nothing is selected; `None` reverts to the paragraph
+    /// language (or clears the paragraph's)
+    pub fn set_language(&mut self, language: Option<&str>) {
+        let language = language.map(str::to_string);
+        let targets = self.selected_ranges();
+        if !targets.is_empty() {
+            self.format_ranges("set language", targets, |meta, range| {
+                meta.set_language_range(range.start, range.end, language.clone())
+            });
+            return;
+        }
+
+        self.begin_transaction("set language");
+        let para_id = self.cursor.position.para_id;
+        if let Some(meta) = self.story_document().block_meta(para_id).cloned() {
+            self.record_block_meta(BlockMeta { language, ..meta });
+        }
+        self.undo_manager.commit();
+        self.layout_dirty = true;
+    }
+
+    /// Set the font of all selected text; returns false when nothing is
+    /// selected
+    pub fn format_selection(&mut self, font_id: layout::font::FontId) -> bool {
+        let targets = self.selected_ranges();
+        self.format_ranges("format", targets, |meta, range| meta.format_range(range.start, range.end, font_id))
+    }
+
+    /// Set line breaking for the selected paragraphs (`None` follows the
+    /// document)
+    pub fn set_line_break_mode(&mut self, mode: Option<LineBreakMode>) {
+        let (start, end) = match &self.selection {
+            Some(sel) => sel.ordered(),
+            None => (self.cursor.position, self.cursor.position),
+        };
+
+        self.begin_transaction("set line breaking");
+        let mut para_id = Some(start.para_id);
+        while let Some(id) = para_id {
+            if let Some(meta) = self.story_document().block_meta(id).cloned() {
+                self.record_block_meta(BlockMeta { line_break: mode, ..meta });
+            }
+            para_id = self.story_document().next_paragraph(id).filter(|_| id != end.para_id);
+        }
+        self.undo_manager.commit();
+        self.layout_dirty = true;
+    }
+
+    /// Set the tab stops of the selected paragraphs, and the interval of
+    /// the default stops past them (`None` follows the document)
+    pub fn set_tab_stops(&mut self, mut stops: Vec<TabStop>, interval: Option<f32>) {
+        let (start, end) = match &self.selection {
+            Some(sel) => sel.ordered(),
+            None => (self.cursor.position, self.cursor.position),
+        };
+        stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+
+        self.begin_transaction("set tab stops");
+        let mut para_id = Some(start.para_id);
+        while let Some(id) = para_id {
+            if let Some(meta) = self.story_document().block_meta(id).cloned() {
+                self.record_block_meta(BlockMeta { tab_stops: stops.clone(), tab_interval: interval, ..meta });
+            }
+            para_id = self.story_document().next_paragraph(id).filter(|_| id != end.para_id);
+        }
+        self.undo_manager.commit();
+        self.layout_dirty = true;
+    }
+
+    /// Register a list definition for new list items
+    pub fn add_list(&mut self, definition: ListDefinition) -> ListId {
+        self.document.add_list(definition)
+    }
+
+    /// Redefine a list; returns false if no list has the ID
+    pub fn set_list_definition(&mut self, list_id: ListId, definition: ListDefinition) -> bool {
+        if self.document.list(list_id).is_none() {
+            return false;
+        }
+        self.document.set_list(list_id, definition);
+        // Indents of every item may change
+        self.layout.invalidate_all();
+        self.layout_dirty = true;
+        true
+    }
+
+    /// Make the selected body paragraphs items of a list at a (zero-based)
+    /// level, or plain paragraphs again with `None`
+    pub fn set_list(&mut self, list: Option<(ListId, u8)>) {
+        if self.story != StoryId::Body {
+            return;
+        }
+        let (start, end) = match &self.selection {
+            Some(sel) => sel.ordered(),
+            None => (self.cursor.position, self.cursor.position),
+        };
+        let kind = match list {
+            Some((list_id, indent_level)) => {
+                let level = self.document.list(list_id).and_then(|def| def.level(indent_level));
+                let marker = match level {
+                    Some(level) if !level.format.is_bullet() => ListMarker::Numbered { ordinal: level.start },
+                    _ => ListMarker::Bullet,
+                };
+                BlockKind::ListItem { list_id, indent_level, marker }
+            }
+            None => BlockKind::Paragraph,
+        };
+
+        self.begin_transaction("set list");
+        let mut para_id = Some(start.para_id);
+        while let Some(id) = para_id {
+            if let Some(meta) = self.document.block_meta(id).cloned().filter(|meta| !meta.kind.is_table()) {
+                self.record_block_meta(BlockMeta { kind: kind.clone(), ..meta });
+            }
+            para_id = self.document.next_paragraph(id).filter(|_| id != end.para_id);
+        }
+        self.undo_manager.commit();
+        self.layout_dirty = true;
+    }
+
+    /// Number headings by level from document order (`None` stops
+    /// numbering them)
+    pub fn set_heading_numbering(&mut self, numbering: Option<ListDefinition>) {
+        self.document.set_heading_numbering(numbering);
+        self.layout.invalidate_markers();
+        self.layout_dirty = true;
+    }
+
+    /// Make the selected body paragraphs headings of a level (1-6), or
+    /// plain paragraphs again with `None`
+    pub fn set_heading_level(&mut self, level: Option<u8>) {
+        if self.story != StoryId::Body {
+            return;
+        }
+        let (start, end) = match &self.selection {
+            Some(sel) => sel.ordered(),
+            None => (self.cursor.position, self.cursor.position),
+        };
+        let kind = match level {
+            Some(level) => BlockKind::Heading { level: level.clamp(1, 6) },
+            None => BlockKind::Paragraph,
+        };
+
+        self.begin_transaction("set heading");
+        let mut para_id = Some(start.para_id);
+        while let Some(id) = para_id {
+            if let Some(meta) = self.document.block_meta(id).cloned().filter(|meta| !meta.kind.is_table()) {
+                self.record_block_meta(BlockMeta { kind: kind.clone(), ..meta });
+            }
+            para_id = self.document.next_paragraph(id).filter(|_| id != end.para_id);
+        }
+        self.undo_manager.commit();
+        self.layout_dirty = true;
+    }
+
+    /// Apply block metadata as part of the pending transaction
+    fn record_block_meta(&mut self, meta: BlockMeta) -> EditResult {
+        self.record_op(EditOp::set_block_meta(meta.start_offset, meta))
+    }
+
+    /// Begin an undo transaction in the active story
+    ///
+    /// Edits that only apply at the primary caret drop the secondary ones.
+    fn begin_transaction(&mut self, description: &str) {
+        self.secondary_selections.clear();
+        self.undo_manager.begin_story_transaction(
+            self.story,
+            description,
+            &self.cursor,
+            self.selection.as_ref(),
+        );
+    }
+
+    /// Mark paragraphs touched by an edit for relayout
+    ///
+    /// Note layouts track their story's version, so only body edits need this.
+    fn invalidate_layout(&mut self, result: &EditResult) {
+        if self.story == StoryId::Body {
+            self.invalidate_body(result);
+        }
+    }
+
+    /// Mark body paragraphs touched by an edit for relayout and update the
+    /// outline from them
+    fn invalidate_body(&mut self, result: &EditResult) {
+        self.layout.invalidate(result);
+        self.outline.update(&self.document, result);
+        self.spelling.update(result);
+        self.search_highlights.update(result);
+    }
+
+    /// Check whether deleting at a position would merge body text with a
+    /// table anchor
+    fn deletes_into_table(&self, pos: DocPosition, backward: bool) -> bool {
+        if self.story != StoryId::Body {
+            return false;
+        }
+
+        let neighbour = if backward && pos.offset == 0 {
+            self.document.prev_paragraph(pos.para_id)
+        } else if !backward && self.document.block_meta(pos.para_id).is_some_and(|m| pos.offset >= m.byte_len) {
+            self.document.next_paragraph(pos.para_id)
+        } else {
+            None
+        };
+
+        [Some(pos.para_id), neighbour]
+            .into_iter()
+            .flatten()
+            .any(|para_id| self.document.table_at(para_id).is_some())
+    }
+
     /// Delete text in the given range or at cursor
     pub fn delete(&mut self, backward: bool) -> Option<EditResult> {
-        self.undo_manager
-            .begin_transaction("delete", &self.cursor, self.selection.as_ref());
+        // Backspace at the start of a paragraph first removes its forced
+        // break, at one caret or at each of several
+        let at_break = backward && self.selection.is_none() && self.forced_break_at(self.cursor.position).is_some();
+        if !self.secondary_selections.is_empty() || at_break {
+            return self.edit_selections("delete", "", backward, |editor, cursor| {
+                editor.grapheme_range(cursor.position, backward)
+            });
+        }
 
+        let document = self.document.story(self.story);
         let (start, end) = if let Some(ref sel) = self.selection {
             let (s, e) = sel.ordered();
             (
-                self.document.position_to_offset(&s),
-                self.document.position_to_offset(&e),
+                document.position_to_offset(&s),
+                document.position_to_offset(&e),
             )
         } else {
-            let pos = self.document.position_to_offset(&self.cursor.position);
-            if backward {
-                if pos.0 == 0 {
-                    return None;
-                }
-                let prev = self.document.prev_grapheme_offset(pos);
-                (prev, pos)
-            } else {
-                let next = self.document.next_grapheme_offset(pos);
-                if next == pos {
-                    return None;
-                }
-                (pos, next)
+            let range = self.grapheme_range(self.cursor.position, backward);
+            if range.is_empty() {
+                return None;
             }
+            (editing::AbsoluteOffset(range.start), editing::AbsoluteOffset(range.end))
+        };
+
+        Some(self.delete_range(start, end))
+    }
+
+    /// Absolute range of the grapheme (or paragraph break) before or after
+    /// a position, empty at the edge of the story and where body text
+    /// would merge into a table anchor
+    fn grapheme_range(&self, pos: DocPosition, backward: bool) -> Range<usize> {
+        let document = self.story_document();
+        let offset = document.position_to_offset(&pos);
+        if self.deletes_into_table(pos, backward) {
+            return offset.0..offset.0;
+        }
+        if backward {
+            document.prev_grapheme_offset(offset).0..offset.0
+        } else {
+            offset.0..document.next_grapheme_offset(offset).0
+        }
+    }
+
+    /// Delete from the cursor to a word, sentence, line or paragraph
+    /// boundary as one undoable step, or delete the selection
+    ///
+    /// With nothing between the cursor and the boundary (at the edge of a
+    /// paragraph) this deletes a grapheme or paragraph break like `delete`.
+    pub fn delete_by(&mut self, unit: TextUnit, backward: bool) -> Option<EditResult> {
+        if unit == TextUnit::Grapheme || self.selection.is_some() && self.secondary_selections.is_empty() {
+            return self.delete(backward);
+        }
+        self.update_layout();
+
+        if !self.secondary_selections.is_empty() {
+            return self.edit_selections("delete", "", backward, |editor, cursor| {
+                let pos = cursor.position;
+                let boundary = editor.unit_boundary(cursor, unit, backward);
+                if boundary == pos.offset {
+                    return editor.grapheme_range(pos, backward);
+                }
+                let para_start = editor.story_document().position_to_offset(&DocPosition::new(pos.para_id, 0)).0;
+                para_start + pos.offset.min(boundary)..para_start + pos.offset.max(boundary)
+            });
+        }
+
+        let pos = self.cursor.position;
+        let boundary = self.unit_boundary(&self.cursor, unit, backward);
+        if boundary == pos.offset {
+            return self.delete(backward);
+        }
+
+        let para_start = self.story_document().position_to_offset(&DocPosition::new(pos.para_id, 0)).0;
+        let start = editing::AbsoluteOffset(para_start + pos.offset.min(boundary));
+        let end = editing::AbsoluteOffset(para_start + pos.offset.max(boundary));
+        Some(self.delete_range(start, end))
+    }
+
+    /// Offset in the caret's paragraph of the word, sentence, line or
+    /// paragraph boundary before or after it
+    fn unit_boundary(&self, cursor: &Cursor, unit: TextUnit, backward: bool) -> usize {
+        let pos = cursor.position;
+        let text = self.story_document().paragraph_text(pos.para_id);
+        let line = self
+            .story_paragraph_layout(pos.para_id)
+            .and_then(|layout| layout.caret_line(pos.offset, cursor.affinity))
+            .map(|(_, line)| line.byte_range.clone());
+        match (unit, backward) {
+            (TextUnit::Word, true) => prev_word_start(&text, pos.offset),
+            (TextUnit::Word, false) => next_word_start(&text, pos.offset),
+            (TextUnit::Sentence, true) => prev_sentence_start(&text, pos.offset),
+            (TextUnit::Sentence, false) => next_sentence_start(&text, pos.offset),
+            (TextUnit::Line, true) => line.map_or(pos.offset, |range| range.start),
+            (TextUnit::Line, false) => line.map_or(pos.offset, |range| range.end),
+            (TextUnit::Paragraph, true) => 0,
+            (TextUnit::Paragraph, false) => text.len(),
+            (TextUnit::Grapheme, _) => pos.offset,
+        }
+    }
+
+    /// Delete the paragraph holding the cursor with its paragraph break as
+    /// one undoable step
+    ///
+    /// The paragraph after it keeps its formatting; without one, the
+    /// paragraph merges into the one before. Returns None on a table anchor.
+    pub fn delete_paragraph(&mut self) -> Option<EditResult> {
+        let para_id = self.cursor.position.para_id;
+        let document = self.story_document();
+        if document.table_at(para_id).is_some() {
+            return None;
+        }
+        let meta = document.block_meta(para_id)?.clone();
+        let next = document
+            .next_paragraph(para_id)
+            .filter(|&id| document.table_at(id).is_none())
+            .and_then(|id| document.block_meta(id).cloned());
+        let prev = document
+            .prev_paragraph(para_id)
+            .filter(|&id| document.table_at(id).is_none())
+            .and_then(|id| document.block_meta(id));
+        let para_end = meta.start_offset + meta.byte_len;
+        let (start, end) = match (&next, prev) {
+            (Some(next), _) => (meta.start_offset, next.start_offset),
+            (None, Some(prev)) => (prev.start_offset + prev.byte_len, para_end),
+            (None, None) => (meta.start_offset, para_end),
+        };
+
+        self.cell_selection = None;
+        self.begin_transaction("delete paragraph");
+        let document = self.document.story_mut(self.story);
+        let op = EditOp::Delete {
+            start: editing::AbsoluteOffset(start),
+            end: editing::AbsoluteOffset(end),
         };
+        let reverse = document.compute_reverse(&op);
+        let result = document.apply_edit(op.clone());
+        self.undo_manager.record_edit(op, reverse);
+        self.invalidate_layout(&result);
+        // The merged paragraph takes over the formatting of the next one
+        if let Some(next) = next {
+            self.record_block_meta(BlockMeta {
+                start_offset: meta.start_offset,
+                ..next
+            });
+        }
+        self.undo_manager.commit();
+
+        self.cursor.position = result.new_cursor;
+        self.cursor.affinity = Affinity::Downstream;
+        self.selection = None;
+        self.layout_dirty = true;
+        Some(result)
+    }
 
+    /// Delete a range of the active story as one undoable step, leaving the
+    /// cursor at its start
+    fn delete_range(&mut self, start: editing::AbsoluteOffset, end: editing::AbsoluteOffset) -> EditResult {
+        self.cell_selection = None;
+        self.begin_transaction("delete");
+
+        let document = self.document.story_mut(self.story);
         let op = EditOp::Delete { start, end };
-        let reverse = self.document.compute_reverse(&op);
-        let result = self.document.apply_edit(op.clone());
+        let reverse = document.compute_reverse(&op);
+        let result = document.apply_edit(op.clone());
 
         self.undo_manager.record_edit(op, reverse);
         self.undo_manager.commit();
 
         // Update cursor
-        self.cursor.position = result.new_cursor.clone();
+        self.cursor.position = result.new_cursor;
         self.selection = None;
 
         // Mark layout dirty
-        self.layout.invalidate(&result);
+        self.invalidate_layout(&result);
         self.layout_dirty = true;
 
-        Some(result)
+        result
+    }
+
+    /// Replace every selection, or the range `at_caret` gives for each
+    /// caret, with text as one undoable step, leaving a caret after each
+    /// replacement
+    ///
+    /// With `remove_breaks` (backspace), a caret at the start of a paragraph
+    /// with a forced break removes the break instead. Overlapping ranges
+    /// merge. Returns None when nothing would change.
+    fn edit_selections(
+        &mut self,
+        description: &str,
+        text: &str,
+        remove_breaks: bool,
+        at_caret: impl Fn(&Self, &Cursor) -> Range<usize>,
+    ) -> Option<EditResult> {
+        let document = self.story_document();
+        let primary = self
+            .selection
+            .clone()
+            .unwrap_or_else(|| Selection::collapsed(self.cursor.position));
+        let mut ranges: Vec<(Range<usize>, bool)> = Vec::new();
+        let mut breaks: Vec<BlockMeta> = Vec::new();
+        for (index, sel) in std::iter::once(&primary).chain(&self.secondary_selections).enumerate() {
+            let range = if sel.is_collapsed() {
+                let cursor = if index == 0 { self.cursor.clone() } else { Cursor::new(sel.active) };
+                match self.forced_break_at(cursor.position).filter(|_| remove_breaks) {
+                    Some(meta) => {
+                        let offset = document.position_to_offset(&cursor.position).0;
+                        breaks.push(meta);
+                        offset..offset
+                    }
+                    None => at_caret(self, &cursor),
+                }
+            } else {
+                let (start, end) = sel.ordered();
+                document.position_to_offset(&start).0..document.position_to_offset(&end).0
+            };
+            ranges.push((range, index == 0));
+        }
+        if text.is_empty() && breaks.is_empty() && ranges.iter().all(|(range, _)| range.is_empty()) {
+            return None;
+        }
+
+        // Ranges are edited last to first, so earlier ones keep their offsets
+        ranges.sort_by_key(|(range, _)| (range.start, range.end));
+        let mut merged: Vec<(Range<usize>, bool)> = Vec::new();
+        for (range, primary) in ranges {
+            match merged.last_mut() {
+                Some((last, last_primary)) if range.start < last.end || range.start == last.start => {
+                    last.end = last.end.max(range.end);
+                    *last_primary |= primary;
+                }
+                _ => merged.push((range, primary)),
+            }
+        }
+
+        self.cell_selection = None;
+        self.begin_transaction(description);
+        let mut result = None;
+        // Removing a break leaves every offset in place
+        breaks.sort_by_key(|meta| meta.start_offset);
+        breaks.dedup_by_key(|meta| meta.start_offset);
+        for meta in breaks {
+            result = Some(self.record_block_meta(BlockMeta {
+                break_before: None,
+                ..meta
+            }));
+        }
+        for (range, _) in merged.iter().rev() {
+            if !range.is_empty() {
+                result = Some(self.record_op(EditOp::Delete {
+                    start: editing::AbsoluteOffset(range.start),
+                    end: editing::AbsoluteOffset(range.end),
+                }));
+            }
+            if !text.is_empty() {
+                result = Some(self.record_op(EditOp::Insert {
+                    position: editing::AbsoluteOffset(range.start),
+                    text: text.to_string(),
+                }));
+            }
+        }
+        self.undo_manager.commit();
+
+        // Each caret moves by what the edits before it inserted and removed
+        let mut shift = 0isize;
+        let mut secondary = Vec::new();
+        for (range, primary) in merged {
+            let offset = (range.start as isize + shift) as usize + text.len();
+            shift += text.len() as isize - range.len() as isize;
+            let position = self.story_document().offset_to_position(editing::AbsoluteOffset(offset));
+            if primary {
+                self.cursor = Cursor::new(position);
+                if !text.is_empty() {
+                    self.cursor.affinity = Affinity::Upstream;
+                }
+            } else {
+                secondary.push(Selection::collapsed(position));
+            }
+        }
+        self.selection = None;
+        self.secondary_selections = secondary;
+        self.layout_dirty = true;
+
+        result.map(|result| EditResult {
+            new_cursor: self.cursor.position,
+            ..result
+        })
+    }
+
+    /// Paragraph a caret sits at the start of, if it starts with a forced
+    /// break (which backspace removes before any text)
+    fn forced_break_at(&self, position: DocPosition) -> Option<BlockMeta> {
+        if position.offset != 0 {
+            return None;
+        }
+        self.story_document()
+            .block_meta(position.para_id)
+            .filter(|meta| meta.break_before.is_some())
+            .cloned()
+    }
+
+    /// Apply an edit to the active story as part of the pending transaction
+    fn record_op(&mut self, op: EditOp) -> EditResult {
+        let document = self.document.story_mut(self.story);
+        let reverse = document.compute_reverse(&op);
+        let result = document.apply_edit(op.clone());
+        self.undo_manager.record_edit(op, reverse);
+        self.invalidate_layout(&result);
+        result
     }
 
     /// Perform layout if needed and return render diff
     pub fn update_layout(&mut self) -> Option<RenderDiff> {
+        // Squiggles are decorations, so they follow the text without relayout
+        self.spelling.check(&mut self.document);
+        self.search_highlights.refresh(&self.document, self.search.as_ref());
+
         if !self.layout_dirty {
             return None;
         }
@@ -181,19 +862,32 @@ impl Editor {
         self.layout.build_display_list(
             &self.document,
             viewport,
-            &self.cursor,
-            self.selection.as_ref(),
+            Focus {
+                story: self.story,
+                cursor: &self.cursor,
+                selection: self.selection.as_ref(),
+                secondary: &self.secondary_selections,
+                cells: self.cell_selection.as_ref(),
+                search: self.search.as_ref().map(|query| (query, &self.search_highlights)),
+            },
+            &document::today(),
         )
     }
 
     /// Undo the last operation
     pub fn undo(&mut self) -> bool {
         if let Some(result) = self.undo_manager.undo(&mut self.document) {
+            self.story = result.story;
             self.cursor = result.cursor;
             self.selection = result.selection;
+            self.cell_selection = None;
+            self.secondary_selections.clear();
             self.layout_dirty = true;
             // Full layout invalidation for undo
             self.layout.invalidate_all();
+            self.outline.invalidate();
+            self.spelling.invalidate();
+            self.search_highlights.invalidate();
             true
         } else {
             false
@@ -203,10 +897,16 @@ impl Editor {
     /// Redo the last undone operation
     pub fn redo(&mut self) -> bool {
         if let Some(result) = self.undo_manager.redo(&mut self.document) {
+            self.story = result.story;
             self.cursor = result.cursor;
             self.selection = result.selection;
+            self.cell_selection = None;
+            self.secondary_selections.clear();
             self.layout_dirty = true;
             self.layout.invalidate_all();
+            self.outline.invalidate();
+            self.spelling.invalidate();
+            self.search_highlights.invalidate();
             true
         } else {
             false
@@ -215,30 +915,55 @@ impl Editor {
 
     /// Move cursor by the given delta
     pub fn move_cursor(&mut self, horizontal: i32, vertical: i32, extend_selection: bool) {
-        if extend_selection && self.selection.is_none() {
-            self.selection = Some(Selection {
-                anchor: self.cursor.position.clone(),
-                active: self.cursor.position.clone(),
-            });
+        // Moving past the edge of a table cell goes to the neighbouring cell
+        if let StoryId::Cell(cell) = self.story {
+            if self.leaves_cell(horizontal, vertical) {
+                self.move_across_cells(cell, horizontal, vertical, extend_selection);
+                return;
+            }
         }
+        self.begin_move(extend_selection);
 
         // Horizontal movement
         if horizontal != 0 {
-            let offset = self.document.position_to_offset(&self.cursor.position);
+            let document = self.document.story(self.story);
+            let offset = document.position_to_offset(&self.cursor.position);
             let new_offset = if horizontal > 0 {
-                self.document.next_grapheme_offset(offset)
+                document.next_grapheme_offset(offset)
             } else {
-                self.document.prev_grapheme_offset(offset)
+                document.prev_grapheme_offset(offset)
             };
-            self.cursor.position = self.document.offset_to_position(new_offset);
+            self.cursor.position = document.offset_to_position(new_offset);
             self.cursor.preferred_x = None;
+            // The caret stays against the grapheme it just stepped over
+            self.cursor.affinity = if horizontal > 0 {
+                Affinity::Upstream
+            } else {
+                Affinity::Downstream
+            };
         }
 
-        // Vertical movement
+        // Vertical movement within a note or table cell
+        let affinity = self.cursor.affinity;
         if vertical != 0 {
-            if let Some(new_pos) = self.layout.move_cursor_vertical(
+            self.cursor.affinity = Affinity::Downstream;
+        }
+        if let (Some(story_layout), true) = (self.layout.story_layout(self.story), vertical != 0) {
+            if self.cursor.preferred_x.is_none() {
+                self.cursor.preferred_x = story_layout.x_for_position(&self.cursor.position);
+            }
+            if let Some(new_pos) = story_layout.move_vertical(
+                &self.cursor.position,
+                vertical,
+                self.cursor.preferred_x,
+            ) {
+                self.cursor.position = new_pos;
+            }
+        } else if vertical != 0 {
+            if let Some((new_pos, new_affinity)) = self.layout.move_cursor_vertical(
                 &self.document,
                 &self.cursor.position,
+                affinity,
                 vertical,
                 self.cursor.preferred_x,
             ) {
@@ -246,75 +971,2830 @@ impl Editor {
                 if self.cursor.preferred_x.is_none() {
                     self.cursor.preferred_x = self
                         .layout
-                        .position_to_x(&self.document, &self.cursor.position);
+                        .x_in_column(&self.cursor.position, affinity);
                 }
                 self.cursor.position = new_pos;
+                self.cursor.affinity = new_affinity;
             }
         }
 
-        if extend_selection {
-            if let Some(ref mut sel) = self.selection {
-                sel.active = self.cursor.position.clone();
+        self.end_move(horizontal > 0 || vertical > 0, extend_selection);
+    }
+
+    /// Move the cursor by a word, to the end of a line, paragraph or the
+    /// story, or by a page
+    pub fn move_by(&mut self, movement: Movement, extend_selection: bool) {
+        // Page movement steps line by line, keeping the remembered X
+        if let Movement::PageUp(height) | Movement::PageDown(height) = movement {
+            let delta = if matches!(movement, Movement::PageDown(_)) { 1 } else { -1 };
+            let mut moved = 0.0;
+            while moved < height {
+                let before = (self.story, self.cursor.position);
+                let Some(line_height) = self.caret_line_height() else {
+                    break;
+                };
+                self.move_cursor(0, delta, extend_selection);
+                if (self.story, self.cursor.position) == before {
+                    break;
+                }
+                moved += line_height;
             }
-        } else {
-            self.selection = None;
+            return;
         }
-    }
 
-    /// Get document text
-    pub fn text(&self) -> String {
-        self.document.text()
-    }
+        // The start and end of the document are outside any note or cell
+        if matches!(movement, Movement::DocumentStart | Movement::DocumentEnd) && !extend_selection {
+            self.exit_story();
+        }
+        self.begin_move(extend_selection);
+        self.update_layout();
 
-    /// Get total page count
-    pub fn page_count(&self) -> usize {
-        self.layout.page_count()
+        let document = self.story_document();
+        let pos = self.cursor.position;
+        let para_len = document.block_meta(pos.para_id).map_or(0, |meta| meta.byte_len);
+        let para_end = |para_id: ParagraphId| {
+            DocPosition::new(para_id, document.block_meta(para_id).map_or(0, |meta| meta.byte_len))
+        };
+
+        let (position, affinity) = match movement {
+            Movement::WordLeft if pos.offset == 0 => {
+                (document.prev_paragraph(pos.para_id).map_or(pos, para_end), Affinity::Downstream)
+            }
+            Movement::WordLeft => {
+                let text = document.paragraph_text(pos.para_id);
+                (DocPosition::new(pos.para_id, prev_word_start(&text, pos.offset)), Affinity::Downstream)
+            }
+            Movement::WordRight if pos.offset >= para_len => {
+                let next = document.next_paragraph(pos.para_id);
+                (next.map_or(pos, |id| DocPosition::new(id, 0)), Affinity::Downstream)
+            }
+            Movement::WordRight => {
+                let text = document.paragraph_text(pos.para_id);
+                (DocPosition::new(pos.para_id, next_word_start(&text, pos.offset)), Affinity::Downstream)
+            }
+            Movement::LineStart | Movement::LineEnd => {
+                let line = self
+                    .story_paragraph_layout(pos.para_id)
+                    .and_then(|layout| {
+                        let (idx, line) = layout.caret_line(pos.offset, self.cursor.affinity)?;
+                        Some((line.byte_range.clone(), idx + 1 == layout.lines.len()))
+                    });
+                match (movement, line) {
+                    (_, None) => (pos, self.cursor.affinity),
+                    (Movement::LineStart, Some((range, _))) => {
+                        (DocPosition::new(pos.para_id, range.start), Affinity::Downstream)
+                    }
+                    // Past a soft wrap the caret stays at the end of this line
+                    (_, Some((range, last))) => {
+                        let affinity = if last { Affinity::Downstream } else { Affinity::Upstream };
+                        (DocPosition::new(pos.para_id, range.end), affinity)
+                    }
+                }
+            }
+            Movement::DocumentStart => (DocPosition::new(document.first_paragraph(), 0), Affinity::Downstream),
+            Movement::DocumentEnd => {
+                let last = document.paragraph_order().last().unwrap_or(pos.para_id);
+                (para_end(last), Affinity::Downstream)
+            }
+            Movement::ParagraphUp if pos.offset > 0 => (DocPosition::new(pos.para_id, 0), Affinity::Downstream),
+            Movement::ParagraphUp => {
+                let prev = document.prev_paragraph(pos.para_id);
+                (DocPosition::new(prev.unwrap_or(pos.para_id), 0), Affinity::Downstream)
+            }
+            Movement::ParagraphDown => match document.next_paragraph(pos.para_id) {
+                Some(next) => (DocPosition::new(next, 0), Affinity::Downstream),
+                None => (para_end(pos.para_id), Affinity::Downstream),
+            },
+            Movement::PageUp(_) | Movement::PageDown(_) => unreachable!("handled above"),
+        };
+
+        let forward = position > pos;
+        self.cursor.position = position;
+        self.cursor.affinity = affinity;
+        self.cursor.preferred_x = None;
+        self.end_move(forward, extend_selection);
     }
 
-    /// Force layout update on next tick
-    pub fn mark_dirty(&mut self) {
+    /// Start a cursor movement, anchoring a selection to extend
+    fn begin_move(&mut self, extend_selection: bool) {
+        self.secondary_selections.clear();
+        if !extend_selection {
+            self.cell_selection = None;
+        }
+
+        if extend_selection && self.selection.is_none() {
+            self.selection = Some(Selection::collapsed(self.cursor.position));
+        }
+    }
+
+    /// Finish a cursor movement: enter a table the cursor stepped onto and
+    /// extend or drop the selection
+    fn end_move(&mut self, forward: bool, extend_selection: bool) {
+        // Stepping onto a table from the body enters its first or last cell
+        if !extend_selection {
+            if let Some(table_id) = self.body_table_at_cursor() {
+                self.enter_table(table_id, forward);
+                return;
+            }
+        }
+
+        if extend_selection {
+            if let Some(ref mut sel) = self.selection {
+                sel.active = self.cursor.position;
+            }
+        } else {
+            self.selection = None;
+        }
+    }
+
+    /// Select the word or paragraph around a position of the active story
+    /// (double- and triple-click); `Character` just places the caret
+    pub fn select_unit(&mut self, position: DocPosition, granularity: Granularity) {
+        let (start, end) = self.unit_around(position, granularity, false);
+        self.cell_selection = None;
+        self.secondary_selections.clear();
+        self.cursor = Cursor::new(end);
+        self.cursor.affinity = Affinity::Upstream;
+        self.selection = (start != end).then_some(Selection {
+            anchor: start,
+            active: end,
+            granularity,
+        });
+    }
+
+    /// Extend the selection to a position of the active story by the
+    /// selection's granularity (dragging), keeping the unit it started
+    /// with selected
+    pub fn extend_selection_to(&mut self, position: DocPosition) {
+        let selection = self
+            .selection
+            .take()
+            .unwrap_or_else(|| Selection::collapsed(self.cursor.position));
+        let granularity = selection.granularity;
+
+        // A backward selection is anchored at the end of its first unit
+        let backward = selection.active < selection.anchor;
+        let (anchor_start, anchor_end) = self.unit_around(selection.anchor, granularity, backward);
+        let (target_start, target_end) = self.unit_around(position, granularity, false);
+        let (anchor, active) = if position < anchor_start {
+            (anchor_end, target_start)
+        } else {
+            (anchor_start, target_end.max(anchor_end))
+        };
+
+        self.cell_selection = None;
+        self.secondary_selections.clear();
+        self.cursor = Cursor::new(active);
+        self.selection = Some(Selection { anchor, active, granularity });
+    }
+
+    /// Add a caret at a body position (Ctrl+click), making it the primary
+    /// one; returns false outside the body text or where a caret already is
+    pub fn add_caret(&mut self, position: DocPosition) -> bool {
+        let taken = std::iter::once(self.cursor.position)
+            .chain(self.secondary_selections.iter().map(|sel| sel.active))
+            .any(|caret| caret == position);
+        if self.story != StoryId::Body || taken || self.document.table_at(position.para_id).is_some() {
+            return false;
+        }
+        self.push_primary(Selection::collapsed(position));
+        true
+    }
+
+    /// Add a caret a line above (negative `delta`) or below the primary
+    /// one, at the same X (column editing); returns false when there is no
+    /// line there
+    pub fn add_caret_vertical(&mut self, delta: i32) -> bool {
+        if self.story != StoryId::Body {
+            return false;
+        }
+        self.update_layout();
+        let (pos, affinity) = (self.cursor.position, self.cursor.affinity);
+        let preferred_x = self.cursor.preferred_x.or_else(|| self.layout.x_in_column(&pos, affinity));
+        let Some((position, affinity)) = self
+            .layout
+            .move_cursor_vertical(&self.document, &pos, affinity, delta, preferred_x)
+            .filter(|(position, _)| *position != pos)
+        else {
+            return false;
+        };
+        if !self.add_caret(position) {
+            return false;
+        }
+        self.cursor.affinity = affinity;
+        self.cursor.preferred_x = preferred_x;
+        true
+    }
+
+    /// Select the next occurrence of the selected text after the primary
+    /// selection, wrapping around the body, and make it the primary one
+    /// (Ctrl+D)
+    ///
+    /// With nothing selected this selects the word at the cursor. Returns
+    /// false when there is nothing more to select; selections spanning
+    /// paragraphs are not searched for.
+    pub fn select_next_occurrence(&mut self) -> bool {
+        if self.story != StoryId::Body {
+            return false;
+        }
+        let Some(selection) = self.selection.clone().filter(|sel| !sel.is_collapsed()) else {
+            let (start, end) = self.unit_around(self.cursor.position, Granularity::Word, false);
+            if start == end {
+                return false;
+            }
+            self.cursor = Cursor::new(end);
+            self.cursor.affinity = Affinity::Upstream;
+            self.selection = Some(Selection::new(start, end));
+            return true;
+        };
+        let (start, end) = selection.ordered();
+        if start.para_id != end.para_id {
+            return false;
+        }
+
+        let document = &self.document;
+        let Some(needle) = document.paragraph_text(start.para_id).get(start.offset..end.offset).map(str::to_string)
+        else {
+            return false;
+        };
+        let exact = SearchOptions { case_sensitive: true, ..SearchOptions::default() };
+        let Some(query) = SearchQuery::new(&needle, exact) else {
+            return false;
+        };
+        let from = document.position_to_offset(&end).0;
+        let selected: Vec<usize> = std::iter::once(&selection)
+            .chain(&self.secondary_selections)
+            .map(|sel| document.position_to_offset(&sel.ordered().0).0)
+            .collect();
+        // Search on from the selection a paragraph at a time, then wrap
+        // around to it from the start
+        let found = document
+            .find_iter(&query, from)
+            .filter(|range| range.start >= from)
+            .chain(document.find_iter(&query, 0).take_while(|range| range.start < from))
+            .find(|range| !selected.contains(&range.start));
+        let Some(found) = found else {
+            return false;
+        };
+
+        let start = document.offset_to_position(editing::AbsoluteOffset(found.start));
+        let end = document.offset_to_position(editing::AbsoluteOffset(found.end));
+        self.push_primary(Selection::new(start, end));
+        self.cursor.affinity = Affinity::Upstream;
+        true
+    }
+
+    /// Drop every caret and selection but the primary one
+    pub fn clear_secondary_selections(&mut self) {
+        self.secondary_selections.clear();
+    }
+
+    /// Matches of a query in the active story, in document order
+    pub fn find(&self, query: &SearchQuery) -> Vec<Range<DocPosition>> {
+        let document = self.story_document();
+        document
+            .find_iter(query, 0)
+            .map(|range| {
+                document.offset_to_position(editing::AbsoluteOffset(range.start))
+                    ..document.offset_to_position(editing::AbsoluteOffset(range.end))
+            })
+            .collect()
+    }
+
+    /// Select the first match after the selection or cursor (with
+    /// `backward`, the last one before it), wrapping around the story;
+    /// returns None when there are no matches
+    pub fn find_next(&mut self, query: &SearchQuery, backward: bool) -> Option<Range<DocPosition>> {
+        let document = self.story_document();
+        let (start, end) = match &self.selection {
+            Some(sel) => sel.ordered(),
+            None => (self.cursor.position, self.cursor.position),
+        };
+        let (start, end) = (document.position_to_offset(&start).0, document.position_to_offset(&end).0);
+
+        let found = if backward {
+            document
+                .find_before(query, start)
+                .or_else(|| document.find_before(query, usize::MAX))
+        } else {
+            document
+                .find_iter(query, end)
+                .find(|range| range.start >= end)
+                .or_else(|| document.find_iter(query, 0).next())
+        }?;
+
+        let start = document.offset_to_position(editing::AbsoluteOffset(found.start));
+        let end = document.offset_to_position(editing::AbsoluteOffset(found.end));
+        self.secondary_selections.clear();
+        self.cell_selection = None;
+        self.cursor = Cursor::new(end);
+        self.cursor.affinity = Affinity::Upstream;
+        self.selection = Some(Selection::new(start, end));
+        Some(start..end)
+    }
+
+    /// Replace the selected match and select the next one; without a match
+    /// selected this only finds the next. Returns false when no match is
+    /// left to select
+    pub fn replace(&mut self, query: &SearchQuery, replacement: &str) -> bool {
+        if let Some((start, end)) = self.selection.as_ref().map(Selection::ordered) {
+            let text = self.story_document().paragraph_text(start.para_id);
+            let range = start.offset..end.offset;
+            let selected = start.para_id == end.para_id && query.matches_in(&text).any(|m| m == range);
+            if selected {
+                let replacement = document::strip_markers(&query.replacement(&text, range, replacement)).into_owned();
+                let document = self.story_document();
+                let range = document.position_to_offset(&start).0..document.position_to_offset(&end).0;
+                self.replace_ranges("replace", vec![(range, replacement)]);
+            }
+        }
+        self.find_next(query, false).is_some()
+    }
+
+    /// Replace every match in the active story as one undoable step;
+    /// returns how many were replaced
+    pub fn replace_all(&mut self, query: &SearchQuery, replacement: &str) -> usize {
+        let document = self.story_document();
+        let mut paragraph: Option<(ParagraphId, String)> = None;
+        let replacements: Vec<(Range<usize>, String)> = document
+            .find_iter(query, 0)
+            .map(|range| {
+                let start = document.offset_to_position(editing::AbsoluteOffset(range.start));
+                // Regular expressions expand groups against the paragraph text
+                if paragraph.as_ref().is_none_or(|(para_id, _)| *para_id != start.para_id) {
+                    paragraph = Some((start.para_id, document.paragraph_text(start.para_id)));
+                }
+                let text = paragraph.as_ref().map_or("", |(_, text)| text.as_str());
+                let matched = start.offset..start.offset + range.len();
+                // Expanded groups must not copy note references or objects
+                let replacement = query.replacement(text, matched, replacement);
+                (range, document::strip_markers(&replacement).into_owned())
+            })
+            .collect();
+
+        let count = replacements.len();
+        if count > 0 {
+            self.replace_ranges("replace all", replacements);
+        }
+        count
+    }
+
+    /// Replace ranges of the active story (in order, not overlapping) as one
+    /// undoable step, keeping the cursor on the same text
+    ///
+    /// A cursor inside or at the end of a replaced range ends up after its
+    /// replacement.
+    fn replace_ranges(&mut self, description: &str, replacements: Vec<(Range<usize>, String)>) {
+        let cursor = self.story_document().position_to_offset(&self.cursor.position).0;
+        self.cell_selection = None;
+        self.begin_transaction(description);
+        for (range, text) in replacements.iter().rev() {
+            if !range.is_empty() {
+                self.record_op(EditOp::Delete {
+                    start: editing::AbsoluteOffset(range.start),
+                    end: editing::AbsoluteOffset(range.end),
+                });
+            }
+            if !text.is_empty() {
+                self.record_op(EditOp::Insert {
+                    position: editing::AbsoluteOffset(range.start),
+                    text: text.clone(),
+                });
+            }
+        }
+        self.undo_manager.commit();
+
+        let mut offset = cursor;
+        for (range, text) in &replacements {
+            if range.start < cursor && cursor <= range.end || range.is_empty() && range.start == cursor {
+                offset = (offset as isize + (range.start + text.len()) as isize - cursor as isize) as usize;
+            } else if range.end < cursor {
+                offset = (offset as isize + text.len() as isize - range.len() as isize) as usize;
+            }
+        }
+        self.cursor = Cursor::new(self.story_document().offset_to_position(editing::AbsoluteOffset(offset)));
+        self.selection = None;
+        self.layout_dirty = true;
+    }
+
+    /// Misspelled word of the body text at a position, with its text
+    pub fn misspelling_at(&self, pos: DocPosition) -> Option<(Range<DocPosition>, String)> {
+        if !self.spelling.is_enabled() {
+            return None;
+        }
+        let meta = self.document.block_meta(pos.para_id)?;
+        let text = self.document.paragraph_text(pos.para_id);
+        let (start, word) =
+            spell::words(&text).find(|&(start, word)| start <= pos.offset && pos.offset <= start + word.len())?;
+        if self.spelling.is_correct(word, meta.language_at(start)) {
+            return None;
+        }
+        let range = DocPosition::new(pos.para_id, start)..DocPosition::new(pos.para_id, start + word.len());
+        Some((range, word.to_string()))
+    }
+
+    /// Corrections for the misspelled body word at a position, best first
+    pub fn spelling_suggestions(&self, pos: DocPosition) -> Vec<String> {
+        let Some((range, word)) = self.misspelling_at(pos) else {
+            return Vec::new();
+        };
+        let language = self.document.block_meta(pos.para_id).and_then(|meta| meta.language_at(range.start.offset));
+        self.spelling.suggestions(&word, language)
+    }
+
+    /// Replace the misspelled body word at a position as one undoable step
+    pub fn correct_spelling(&mut self, pos: DocPosition, replacement: &str) -> bool {
+        if self.story != StoryId::Body {
+            return false;
+        }
+        let Some((range, _)) = self.misspelling_at(pos) else {
+            return false;
+        };
+        let start = self.document.position_to_offset(&range.start).0;
+        let end = self.document.position_to_offset(&range.end).0;
+        let replacement = document::strip_markers(replacement).into_owned();
+        self.replace_ranges("correct spelling", vec![(start..end, replacement)]);
+        true
+    }
+
+    /// Add a word to the user's dictionary
+    pub fn add_to_dictionary(&mut self, word: &str) {
+        self.spelling.add_word(&mut self.document, word);
+    }
+
+    /// Stop marking a word as misspelled until the editor is closed
+    pub fn ignore_spelling(&mut self, word: &str) {
+        self.spelling.ignore_word(&mut self.document, word);
+    }
+
+    /// Paint a range of a story without changing its text; the decoration
+    /// moves with the text around it until removed or its text is deleted.
+    /// Returns None for an empty range
+    pub fn add_decoration(
+        &mut self,
+        story: StoryId,
+        range: Range<DocPosition>,
+        kind: DecorationKind,
+        color: u32,
+    ) -> Option<DecorationId> {
+        let document = self.document.story_mut(story);
+        let start = document.position_to_offset(&range.start).0;
+        let end = document.position_to_offset(&range.end).0;
+        document.decorations_mut().add(start..end, kind, color)
+    }
+
+    /// Remove a decoration from a story; returns false if it was already gone
+    pub fn remove_decoration(&mut self, story: StoryId, id: DecorationId) -> bool {
+        self.document.story_mut(story).decorations_mut().remove(id)
+    }
+
+    /// Remove every decoration from a story
+    pub fn clear_decorations(&mut self, story: StoryId) {
+        self.document.story_mut(story).decorations_mut().clear();
+    }
+
+    /// Current range of a decoration in a story
+    pub fn decoration_range(&self, story: StoryId, id: DecorationId) -> Option<Range<DocPosition>> {
+        let document = self.document.story(story);
+        let decoration = document.decorations().get(id)?;
+        Some(
+            document.offset_to_position(editing::AbsoluteOffset(decoration.start))
+                ..document.offset_to_position(editing::AbsoluteOffset(decoration.end)),
+        )
+    }
+
+    /// Keep the primary selection (or caret) as a secondary one and make a
+    /// new selection the primary
+    fn push_primary(&mut self, selection: Selection) {
+        let previous = self
+            .selection
+            .take()
+            .unwrap_or_else(|| Selection::collapsed(self.cursor.position));
+        self.secondary_selections.push(previous);
+        self.cell_selection = None;
+        self.cursor = Cursor::new(selection.active);
+        self.selection = (!selection.is_collapsed()).then_some(selection);
+    }
+
+    /// Start and end of the unit of a granularity holding a position
+    ///
+    /// `before` takes the unit ending at the position rather than the one
+    /// starting there.
+    fn unit_around(&self, position: DocPosition, granularity: Granularity, before: bool) -> (DocPosition, DocPosition) {
+        let document = self.story_document();
+        let at = |offset: usize| DocPosition::new(position.para_id, offset);
+        match granularity {
+            Granularity::Character => (position, position),
+            Granularity::Word => {
+                let text = document.paragraph_text(position.para_id);
+                let offset = if before { position.offset.saturating_sub(1) } else { position.offset };
+                let range = word_at(&text, offset);
+                (at(range.start), at(range.end))
+            }
+            Granularity::Paragraph => {
+                let len = document.block_meta(position.para_id).map_or(0, |meta| meta.byte_len);
+                (at(0), at(len))
+            }
+        }
+    }
+
+    /// Layout of a paragraph of the active story
+    fn story_paragraph_layout(&self, para_id: ParagraphId) -> Option<&ParagraphLayout> {
+        match self.layout.story_layout(self.story) {
+            Some(story_layout) => story_layout.paragraphs.iter().find(|p| p.para_id == para_id),
+            None => self.layout.paragraph_layout(para_id),
+        }
+    }
+
+    /// Height of the line holding the caret
+    fn caret_line_height(&self) -> Option<f32> {
+        let pos = self.cursor.position;
+        let layout = self.story_paragraph_layout(pos.para_id)?;
+        Some(layout.caret_line(pos.offset, self.cursor.affinity)?.1.height)
+    }
+
+    /// Move to the next or previous table cell (Tab / Shift+Tab)
+    ///
+    /// Tabbing forward out of the last cell appends a row. Returns false if
+    /// the cursor is not in a table.
+    pub fn move_to_cell(&mut self, backward: bool) -> bool {
+        let StoryId::Cell(cell) = self.story else {
+            return false;
+        };
+        let Some(table) = self.document.table_mut(cell.table_id) else {
+            return false;
+        };
+
+        let target = if backward {
+            table.prev_cell(cell.row, cell.col)
+        } else {
+            table.next_cell(cell.row, cell.col).or_else(|| {
+                table.insert_row(table.row_count());
+                Some((cell.row + 1, 0))
+            })
+        };
+
+        self.cell_selection = None;
+        if let Some((row, col)) = target {
+            self.enter_cell(CellId { row, col, ..cell }, false);
+            self.layout_dirty = true;
+        }
+        true
+    }
+
+    /// Check whether a cursor move would leave the current cell
+    fn leaves_cell(&self, horizontal: i32, vertical: i32) -> bool {
+        let document = self.story_document();
+        let offset = document.position_to_offset(&self.cursor.position);
+        if horizontal > 0 {
+            offset.0 >= document.len()
+        } else if horizontal < 0 {
+            offset.0 == 0
+        } else if vertical != 0 {
+            self.layout
+                .story_layout(self.story)
+                .and_then(|layout| {
+                    layout.move_vertical(&self.cursor.position, vertical, self.cursor.preferred_x)
+                })
+                .is_none()
+        } else {
+            false
+        }
+    }
+
+    /// Move from a cell to its neighbour, or out of the table
+    ///
+    /// Extending the selection across cells selects a block of cells.
+    fn move_across_cells(&mut self, cell: CellId, horizontal: i32, vertical: i32, extend_selection: bool) {
+        let Some(table) = self.document.table(cell.table_id) else {
+            return;
+        };
+
+        let forward = horizontal > 0 || vertical > 0;
+        let target = if horizontal > 0 {
+            table.next_cell(cell.row, cell.col)
+        } else if horizontal < 0 {
+            table.prev_cell(cell.row, cell.col)
+        } else if vertical > 0 {
+            (cell.row + 1 < table.row_count()).then_some((cell.row + 1, cell.col))
+        } else {
+            cell.row.checked_sub(1).map(|row| (row, cell.col))
+        };
+
+        match target {
+            Some((row, col)) if extend_selection => {
+                self.select_cells_to(CellId { row, col, ..cell });
+            }
+            Some((row, col)) => {
+                self.cell_selection = None;
+                self.enter_cell(CellId { row, col, ..cell }, !forward);
+            }
+            None if !extend_selection => self.exit_table(cell.table_id, forward),
+            None => {}
+        }
+    }
+
+    /// Extend a block selection of cells from the current cell to another
+    /// cell of the same table, moving the cursor there
+    pub fn select_cells_to(&mut self, target: CellId) -> bool {
+        let StoryId::Cell(cell) = self.story else {
+            return false;
+        };
+        if cell.table_id != target.table_id || self.document.cell(target).is_none() {
+            return false;
+        }
+
+        let anchor = self
+            .cell_selection
+            .filter(|sel| sel.table_id == cell.table_id)
+            .map_or((cell.row, cell.col), |sel| sel.anchor);
+        self.cell_selection = Some(CellSelection {
+            table_id: cell.table_id,
+            anchor,
+            active: (target.row, target.col),
+        });
+        self.enter_cell(target, false);
+        true
+    }
+
+    /// Get the table anchored at the cursor's body paragraph, if any
+    fn body_table_at_cursor(&self) -> Option<TableId> {
+        (self.story == StoryId::Body)
+            .then(|| self.document.table_at(self.cursor.position.para_id))
+            .flatten()
+    }
+
+    /// Place the cursor at the start or end of a table cell
+    fn enter_cell(&mut self, cell: CellId, at_end: bool) {
+        let Some(body) = self.document.cell(cell).map(|cell| &cell.body) else {
+            return;
+        };
+
+        let offset = if at_end { body.len() } else { 0 };
+        self.cursor = Cursor::new(body.offset_to_position(editing::AbsoluteOffset(offset)));
+        self.story = StoryId::Cell(cell);
+        self.selection = None;
+        self.secondary_selections.clear();
+    }
+
+    /// Enter a table at its first or last cell
+    fn enter_table(&mut self, table_id: TableId, forward: bool) {
+        let Some(table) = self.document.table(table_id) else {
+            return;
+        };
+
+        let (row, col) = if forward {
+            (0, 0)
+        } else {
+            (table.row_count() - 1, table.column_count() - 1)
+        };
+        self.enter_cell(CellId { table_id, row, col }, !forward);
+    }
+
+    /// Return the cursor from a table to the body, just before or after it
+    fn exit_table(&mut self, table_id: TableId, forward: bool) {
+        let Some(anchor) = self.document.table_paragraph(table_id) else {
+            return;
+        };
+        let neighbour = if forward {
+            self.document.next_paragraph(anchor)
+        } else {
+            self.document.prev_paragraph(anchor)
+        };
+        let Some(para_id) = neighbour else {
+            return;
+        };
+
+        // Tables can sit back to back
+        if let Some(next_table) = self.document.table_at(para_id) {
+            self.enter_table(next_table, forward);
+            return;
+        }
+
+        let offset = if forward {
+            0
+        } else {
+            self.document.block_meta(para_id).map_or(0, |meta| meta.byte_len)
+        };
+        self.story = StoryId::Body;
+        self.cursor = Cursor::new(DocPosition::new(para_id, offset));
+        self.selection = None;
+        self.secondary_selections.clear();
+        self.cell_selection = None;
+    }
+
+    /// Leave a note or table for the body text
+    fn exit_story(&mut self) {
+        match self.story {
+            StoryId::Body => {}
+            StoryId::Note(_) => self.exit_note(),
+            StoryId::Cell(cell) => self.exit_table(cell.table_id, true),
+        }
+    }
+
+    /// Get the document of the active story
+    pub fn story_document(&self) -> &Document {
+        self.document.story(self.story)
+    }
+
+    /// Insert a footnote or endnote reference at the cursor and move
+    /// the cursor into the new note; returns None once the document has as
+    /// many notes as there are marker characters
+    pub fn insert_note(&mut self, kind: NoteKind) -> Option<NoteId> {
+        self.exit_story();
+        // Each reference needs its own note, so only the primary caret gets one
+        self.secondary_selections.clear();
+
+        let note_id = self.document.add_note(Note::new(kind))?;
+        self.insert_unchecked(&note_id.reference_char().to_string());
+        self.edit_note(note_id);
+        Some(note_id)
+    }
+
+    /// Move the cursor to the end of a note's text
+    pub fn edit_note(&mut self, note_id: NoteId) -> bool {
+        let Some(note) = self.document.note(note_id) else {
+            return false;
+        };
+
+        let end = note.body.offset_to_position(editing::AbsoluteOffset(note.body.len()));
+        self.story = StoryId::Note(note_id);
+        self.cursor = Cursor::new(end);
+        self.selection = None;
+        self.secondary_selections.clear();
+        true
+    }
+
+    /// Return the cursor to the body, just after the edited note's reference
+    pub fn exit_note(&mut self) {
+        if let StoryId::Note(note_id) = self.story {
+            let offset = self
+                .document
+                .note_reference_offset(note_id)
+                .map(|offset| editing::AbsoluteOffset(offset.0 + note_id.reference_char().len_utf8()))
+                .unwrap_or_default();
+
+            self.story = StoryId::Body;
+            self.cursor = Cursor::new(self.document.offset_to_position(offset));
+            self.selection = None;
+            self.secondary_selections.clear();
+        }
+    }
+
+    /// Insert an inline object at the cursor; returns None once the story
+    /// has as many objects as there are replacement characters
+    pub fn insert_object(&mut self, object: InlineObject) -> Option<ObjectId> {
+        // An object appears once, so only the primary caret gets it
+        self.secondary_selections.clear();
+        let object_id = self.document.story_mut(self.story).add_object(object)?;
+        self.insert_unchecked(&object_id.replacement_char().to_string());
+        Some(object_id)
+    }
+
+    /// Change the displayed size of an inline object in the active story
+    pub fn resize_object(&mut self, object_id: ObjectId, width: f32, height: f32) -> bool {
+        let document = self.document.story(self.story);
+        let Some(position) = document.object_offset(object_id) else {
+            return false;
+        };
+
+        self.begin_transaction("resize object");
+        let document = self.document.story_mut(self.story);
+        let op = EditOp::ResizeObject {
+            position,
+            object_id,
+            width: width.max(1.0),
+            height: height.max(1.0),
+        };
+        let reverse = document.compute_reverse(&op);
+        let result = document.apply_edit(op.clone());
+        self.undo_manager.record_edit(op, reverse);
+        self.undo_manager.commit();
+
+        self.invalidate_layout(&result);
+        self.layout_dirty = true;
+        true
+    }
+
+    /// Get the link at a position in the active story
+    pub fn link_at(&self, position: &DocPosition) -> Option<&Link> {
+        self.story_document().link_at(position).map(|span| &span.link)
+    }
+
+    /// Insert linked text at the cursor; text for a target that is not safe
+    /// to follow (see `Link::is_safe`) goes in unlinked
+    pub fn insert_link(&mut self, text: &str, link: Link) -> EditResult {
+        if !link.is_safe() {
+            return self.insert_text(text);
+        }
+        if let Some(table_id) = self.body_table_at_cursor() {
+            self.enter_table(table_id, true);
+        }
+        self.cell_selection = None;
+
+        self.begin_transaction("insert link");
+
+        let document = self.document.story_mut(self.story);
+        let start = self.cursor.position;
+        let op = EditOp::Insert {
+            position: document.position_to_offset(&start),
+            text: document::strip_markers(text).into_owned(),
+        };
+        let reverse = document.compute_reverse(&op);
+        let result = document.apply_edit(op.clone());
+        self.undo_manager.record_edit(op, reverse);
+        self.invalidate_layout(&result);
+
+        for (para_id, range) in self.paragraph_ranges(start, result.new_cursor) {
+            if let Some(mut meta) = self.story_document().block_meta(para_id).cloned() {
+                meta.set_link(range.start, range.end, link.clone());
+                self.record_block_meta(meta);
+            }
+        }
+
+        self.undo_manager.commit();
+        self.cursor.position = result.new_cursor;
+        self.selection = None;
         self.layout_dirty = true;
+
+        result
+    }
+
+    /// Link the selected text, or change the target of the link at the
+    /// cursor; returns false when there is nothing to link or the target is
+    /// not safe to follow (see `Link::is_safe`)
+    pub fn set_link(&mut self, link: Link) -> bool {
+        if !link.is_safe() {
+            return false;
+        }
+        self.edit_links("set link", |meta, range| meta.set_link(range.start, range.end, link.clone()))
+    }
+
+    /// Remove links from the selected text, or the whole link at the cursor
+    pub fn remove_link(&mut self) -> bool {
+        self.edit_links("remove link", |meta, range| meta.remove_links(range.start, range.end))
+    }
+
+    /// Apply a link edit to the selections (or the link at the cursor) as
+    /// one undoable transaction
+    fn edit_links(&mut self, description: &str, edit: impl Fn(&mut BlockMeta, Range<usize>)) -> bool {
+        let mut targets = self.selected_ranges();
+        if targets.is_empty() {
+            targets = self
+                .story_document()
+                .link_at(&self.cursor.position)
+                .map(|span| vec![(self.cursor.position.para_id, span.start..span.end)])
+                .unwrap_or_default();
+        }
+        self.format_ranges(description, targets, edit)
+    }
+
+    /// Apply a formatting edit to paragraph ranges as one undoable
+    /// transaction, keeping every selection; returns false for no ranges
+    fn format_ranges(
+        &mut self,
+        description: &str,
+        targets: Vec<(ParagraphId, Range<usize>)>,
+        edit: impl Fn(&mut BlockMeta, Range<usize>),
+    ) -> bool {
+        if targets.is_empty() {
+            return false;
+        }
+
+        let secondary = std::mem::take(&mut self.secondary_selections);
+        self.begin_transaction(description);
+        for (para_id, range) in targets {
+            if let Some(mut meta) = self.story_document().block_meta(para_id).cloned() {
+                edit(&mut meta, range);
+                self.record_block_meta(meta);
+            }
+        }
+        self.undo_manager.commit();
+        self.secondary_selections = secondary;
+        self.layout_dirty = true;
+        true
+    }
+
+    /// Paragraph ranges covered by the primary and secondary selections
+    fn selected_ranges(&self) -> Vec<(ParagraphId, Range<usize>)> {
+        self.selection
+            .iter()
+            .chain(&self.secondary_selections)
+            .filter(|sel| !sel.is_collapsed())
+            .flat_map(|sel| {
+                let (start, end) = sel.ordered();
+                self.paragraph_ranges(start, end)
+            })
+            .collect()
+    }
+
+    /// Split a range of the active story into non-empty paragraph ranges
+    fn paragraph_ranges(&self, start: DocPosition, end: DocPosition) -> Vec<(ParagraphId, Range<usize>)> {
+        let document = self.story_document();
+        let mut ranges = Vec::new();
+        let mut para_id = start.para_id;
+        loop {
+            let from = if para_id == start.para_id { start.offset } else { 0 };
+            let to = if para_id == end.para_id {
+                end.offset
+            } else {
+                document.block_meta(para_id).map_or(0, |meta| meta.byte_len)
+            };
+            if from < to {
+                ranges.push((para_id, from..to));
+            }
+
+            match document.next_paragraph(para_id) {
+                Some(next) if para_id != end.para_id => para_id = next,
+                _ => break,
+            }
+        }
+        ranges
+    }
+
+    /// Insert a table after splitting the paragraph at the cursor, and move
+    /// the cursor into its first cell
+    pub fn insert_table(&mut self, rows: usize, columns: usize) -> TableId {
+        self.exit_story();
+        let table_id = self.document.add_table(Table::new(rows, columns));
+
+        self.begin_transaction("insert table");
+
+        // The empty paragraph between the two halves anchors the table
+        let position = self.document.position_to_offset(&self.cursor.position);
+        let op = EditOp::Insert {
+            position,
+            text: "\n\n".to_string(),
+        };
+        let reverse = self.document.compute_reverse(&op);
+        let result = self.document.apply_edit(op.clone());
+        self.undo_manager.record_edit(op, reverse);
+        self.invalidate_body(&result);
+
+        self.cursor.position = self
+            .document
+            .offset_to_position(editing::AbsoluteOffset(position.0 + 1));
+        self.record_block_meta(BlockMeta::table(table_id, position.0 + 1));
+
+        self.undo_manager.commit();
+        self.layout_dirty = true;
+
+        self.enter_table(table_id, true);
+        table_id
+    }
+
+    /// Insert a table of contents listing headings down to `max_level`
+    /// after splitting the paragraph at the cursor
+    pub fn insert_toc(&mut self, max_level: u8) -> TocId {
+        self.exit_story();
+        let toc_id = self.document.add_toc(TableOfContents::new(max_level));
+
+        self.begin_transaction("insert table of contents");
+
+        // The paragraph between the two halves becomes the first entry
+        let position = self.document.position_to_offset(&self.cursor.position);
+        let op = EditOp::Insert {
+            position,
+            text: "\n\n".to_string(),
+        };
+        let reverse = self.document.compute_reverse(&op);
+        let result = self.document.apply_edit(op.clone());
+        self.undo_manager.record_edit(op, reverse);
+        self.invalidate_body(&result);
+        self.record_block_meta(BlockMeta {
+            kind: BlockKind::TocEntry { toc_id, level: 1 },
+            ..BlockMeta::paragraph(position.0 + 1, 0)
+        });
+        self.fill_toc(toc_id);
+
+        self.undo_manager.commit();
+        self.layout_dirty = true;
+        toc_id
+    }
+
+    /// Regenerate a table of contents from the current headings and pages
+    /// as one undoable step; returns false if no entries refer to it, or if
+    /// other paragraphs have been placed between its entries
+    pub fn update_toc(&mut self, toc_id: TocId) -> bool {
+        if self.document.toc_paragraphs(toc_id).is_empty() || !self.document.toc_is_contiguous(toc_id) {
+            return false;
+        }
+        self.begin_transaction("update table of contents");
+        self.fill_toc(toc_id);
+        self.undo_manager.commit();
+        self.layout_dirty = true;
+        true
+    }
+
+    /// Move the cursor to the heading a table of contents entry links to
+    pub fn go_to_anchor(&mut self, href: &str) -> bool {
+        let Some(para_id) = self.document.anchor_target(href) else {
+            return false;
+        };
+        self.exit_story();
+        self.cursor.position = DocPosition::new(para_id, 0);
+        self.cursor.preferred_x = None;
+        self.selection = None;
+        true
+    }
+
+    /// Heading tree of the body text, with the page each heading starts on
+    pub fn outline(&mut self) -> Vec<OutlineEntry> {
+        self.update_layout();
+        let entries: Vec<OutlineEntry> = self
+            .outline
+            .headings(&self.document)
+            .into_iter()
+            .map(|(para_id, level, text)| OutlineEntry {
+                para_id,
+                level,
+                text: text.to_string(),
+                page_index: self
+                    .layout
+                    .line_column(&self.document, para_id, 0)
+                    .map_or(0, |(page, _)| page.page_index),
+                children: Vec::new(),
+            })
+            .collect();
+        document::Outline::tree(entries)
+    }
+
+    /// Y of the top of a body paragraph in document coordinates, for
+    /// scrolling it into view
+    ///
+    /// The distance from the top of the page (or column) the paragraph is
+    /// on comes from the paragraph offsets of the layout.
+    pub fn scroll_to_paragraph(&mut self, para_id: ParagraphId) -> Option<f32> {
+        self.document.block_meta(para_id)?;
+        self.update_layout();
+
+        let (page, column) = self.layout.line_column(&self.document, para_id, 0)?;
+        // Line the paragraph's page or column starts with
+        let (start_para, start_line) = match column {
+            0 => (page.start_para, page.start_line),
+            column => *page.column_breaks.get(column - 1)?,
+        };
+        let skipped: f32 = self
+            .layout
+            .paragraph_layout(start_para)
+            .map_or(0.0, |layout| layout.lines.iter().take(start_line).map(|line| line.height).sum());
+        let flow_top = self.layout.paragraph_y(start_para) + skipped;
+
+        Some(page.y_offset + page.constraints.margin_top + self.layout.paragraph_y(para_id) - flow_top)
+    }
+
+    /// Rewrite the entries of a table of contents until the page numbers
+    /// they show match the layout they produce
+    ///
+    /// Only page numbers gaining or losing digits can move the headings
+    /// again, so the entries settle within a few passes. The entries are
+    /// replaced as one run, so nothing is done once other text has been
+    /// placed between them.
+    fn fill_toc(&mut self, toc_id: TocId) {
+        const MAX_PASSES: usize = 4;
+
+        for _ in 0..MAX_PASSES {
+            self.layout_dirty = true;
+            self.update_layout();
+
+            let paragraphs = self.document.toc_paragraphs(toc_id);
+            let (Some(&first), Some(&last)) = (paragraphs.first(), paragraphs.last()) else {
+                return;
+            };
+            if !self.document.toc_is_contiguous(toc_id) {
+                return;
+            }
+            let entries = self.toc_entries(toc_id, first);
+            let current: Vec<(String, BlockMeta)> = paragraphs
+                .iter()
+                .filter_map(|&id| Some((self.document.paragraph_text(id), self.document.block_meta(id)?.clone())))
+                .collect();
+            let unchanged = entries.len() == current.len()
+                && entries.iter().zip(&current).all(|((text, meta), (old_text, old_meta))| {
+                    text == old_text
+                        && meta.kind == old_meta.kind
+                        && meta.links == old_meta.links
+                        && meta.tab_stops == old_meta.tab_stops
+                });
+            if unchanged {
+                return;
+            }
+
+            // Replace the entries' text, then give each its metadata
+            let (Some(first_meta), Some(last_meta)) = (self.document.block_meta(first), self.document.block_meta(last))
+            else {
+                return;
+            };
+            let start = first_meta.start_offset;
+            let end = last_meta.start_offset + last_meta.byte_len;
+            let text = entries.iter().map(|(text, _)| text.as_str()).collect::<Vec<_>>().join("\n");
+            let mut ops = Vec::new();
+            if end > start {
+                ops.push(EditOp::Delete {
+                    start: editing::AbsoluteOffset(start),
+                    end: editing::AbsoluteOffset(end),
+                });
+            }
+            ops.push(EditOp::Insert {
+                position: editing::AbsoluteOffset(start),
+                text,
+            });
+            for op in ops {
+                let reverse = self.document.compute_reverse(&op);
+                let result = self.document.apply_edit(op.clone());
+                self.undo_manager.record_edit(op, reverse);
+                self.invalidate_body(&result);
+            }
+
+            let mut offset = start;
+            for (text, meta) in entries {
+                self.record_block_meta(BlockMeta {
+                    start_offset: offset,
+                    ..meta
+                });
+                offset += text.len() + 1;
+            }
+        }
+    }
+
+    /// Text and metadata of the entries of a table of contents: each
+    /// heading's number and text, then a tab with a dot leader to its page
+    /// number, linked to the heading
+    fn toc_entries(&self, toc_id: TocId, first_entry: ParagraphId) -> Vec<(String, BlockMeta)> {
+        let width = self
+            .layout
+            .paragraph_layout(first_entry)
+            .map_or(self.layout.constraints().content_width(), |layout| layout.max_width);
+        let numbers = self.document.heading_markers();
+
+        let entries: Vec<(String, BlockMeta)> = self
+            .document
+            .toc_headings(toc_id)
+            .into_iter()
+            .map(|(para_id, level)| {
+                let title = self.document.toc_entry_text(para_id);
+                let title = match numbers.get(&para_id) {
+                    Some(number) => format!("{number} {title}"),
+                    None => title,
+                };
+                let page = self
+                    .layout
+                    .line_column(&self.document, para_id, 0)
+                    .map_or(1, |(page, _)| page.page_index + 1);
+                let text = format!("{title}\t{page}");
+
+                let indent = (level - 1) as f32 * layout::INDENT_WIDTH;
+                let meta = BlockMeta {
+                    kind: BlockKind::TocEntry { toc_id, level },
+                    tab_stops: vec![TabStop {
+                        position: width - indent,
+                        alignment: TabAlignment::Right,
+                        leader: TabLeader::Dots,
+                    }],
+                    links: vec![LinkSpan {
+                        start: 0,
+                        end: text.len(),
+                        link: Link::new(document::heading_anchor(para_id)),
+                    }],
+                    ..BlockMeta::paragraph(0, text.len())
+                };
+                (text, meta)
+            })
+            .collect();
+
+        if entries.is_empty() {
+            let text = "No headings".to_string();
+            let meta = BlockMeta {
+                kind: BlockKind::TocEntry { toc_id, level: 1 },
+                ..BlockMeta::paragraph(0, text.len())
+            };
+            return vec![(text, meta)];
+        }
+        entries
+    }
+
+    /// Get document text
+    pub fn text(&self) -> String {
+        self.document.text()
+    }
+
+    /// Get total page count
+    pub fn page_count(&self) -> usize {
+        self.layout.page_count()
+    }
+
+    /// Force layout update on next tick
+    pub fn mark_dirty(&mut self) {
+        self.layout_dirty = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_constraints() -> LayoutConstraints {
+        LayoutConstraints {
+            page_width: 612.0,  // US Letter
+            page_height: 792.0,
+            margin_top: 72.0,
+            margin_bottom: 72.0,
+            margin_left: 72.0,
+            margin_right: 72.0,
+        }
+    }
+
+    #[test]
+    fn test_create_editor() {
+        let editor = Editor::new(default_constraints());
+        assert_eq!(editor.text(), "");
+    }
+
+    #[test]
+    fn test_insert_text() {
+        let mut editor = Editor::new(default_constraints());
+        editor.insert_text("Hello, World!");
+        assert_eq!(editor.text(), "Hello, World!");
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut editor = Editor::new(default_constraints());
+        editor.insert_text("Hello");
+        assert_eq!(editor.text(), "Hello");
+
+        editor.undo();
+        assert_eq!(editor.text(), "");
+
+        editor.redo();
+        assert_eq!(editor.text(), "Hello");
+    }
+
+    #[test]
+    fn test_page_break() {
+        let mut editor = Editor::with_text("Hello World", default_constraints());
+        editor.update_layout();
+        assert_eq!(editor.page_count(), 1);
+
+        editor.cursor.position = editor.document.offset_to_position(editing::AbsoluteOffset(6));
+        editor.insert_page_break();
+        editor.update_layout();
+        assert_eq!(editor.text(), "Hello \nWorld");
+        assert_eq!(editor.page_count(), 2);
+
+        editor.undo();
+        editor.update_layout();
+        assert_eq!(editor.text(), "Hello World");
+        assert_eq!(editor.page_count(), 1);
+    }
+
+    #[test]
+    fn test_backspace_removes_page_break() {
+        let mut editor = Editor::with_text("Hello\nWorld", default_constraints());
+        editor.cursor.position = editor.document.offset_to_position(editing::AbsoluteOffset(6));
+        editor.insert_page_break();
+        editor.update_layout();
+        assert_eq!(editor.page_count(), 2);
+
+        editor.delete(true);
+        editor.update_layout();
+        assert_eq!(editor.text(), "Hello\nWorld");
+        assert_eq!(editor.page_count(), 1);
+    }
+
+    #[test]
+    fn test_section_break_constraints() {
+        let mut editor = Editor::with_text("Hello\nWorld", default_constraints());
+        editor.cursor.position = editor.document.offset_to_position(editing::AbsoluteOffset(6));
+        let landscape = default_constraints().with_orientation(Orientation::Landscape);
+        editor.insert_section_break(landscape);
+        editor.update_layout();
+
+        let pages = editor.layout.pages();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].constraints, default_constraints());
+        assert_eq!(pages[1].constraints, landscape);
+        assert_eq!(pages[1].y_offset, 792.0);
+
+        // Notes have no sections of their own
+        editor.insert_note(NoteKind::Footnote).unwrap();
+        let sections = editor.document.sections().count();
+        assert_eq!(editor.insert_section_break(landscape), None);
+        assert_eq!(editor.document.sections().count(), sections);
+    }
+
+    #[test]
+    fn test_first_section_page_setup() {
+        let mut editor = Editor::with_text("Hello\nWorld", default_constraints());
+        editor.update_layout();
+        let wide = LayoutConstraints { margin_left: 100.0, ..default_constraints() };
+        assert!(editor.edit_section("page setup", |section| section.constraints = Some(wide)));
+        editor.update_layout();
+
+        // The first page takes the first section's setup, as its lines do
+        let first = editor.document.first_paragraph();
+        assert_eq!(editor.layout.pages()[0].constraints, wide);
+        assert_eq!(editor.layout.paragraph_layout(first).unwrap().max_width, wide.content_width());
+        let x = editor.layout.position_to_x(&editor.document, &DocPosition::new(first, 0));
+        assert_eq!(x, Some(100.0));
+    }
+
+    #[test]
+    fn test_page_break_undo_restores_paragraph() {
+        let mut editor = Editor::with_text("Intro\nHello World", default_constraints());
+        editor.cursor.position = editor.document.offset_to_position(editing::AbsoluteOffset(6));
+        let landscape = default_constraints().with_orientation(Orientation::Landscape);
+        let section_id = editor.insert_section_break(landscape).unwrap();
+        let para = editor.cursor.position.para_id;
+
+        // A page break inside the paragraph splits it, keeping the section
+        // break on the first half
+        editor.cursor.position = DocPosition::new(para, 6);
+        editor.insert_page_break();
+        editor.update_layout();
+        assert_eq!(editor.document.paragraph_count(), 3);
+        assert_eq!(editor.document.block_meta(para).unwrap().break_before, Some(BlockBreak::Section(section_id)));
+        assert_eq!(editor.page_count(), 3);
+
+        // Undo rejoins it in one step, with its own break
+        assert!(editor.undo());
+        editor.update_layout();
+        assert_eq!(editor.text(), "Intro\nHello World");
+        assert_eq!(editor.document.paragraph_count(), 2);
+        assert_eq!(editor.document.block_meta(para).unwrap().break_before, Some(BlockBreak::Section(section_id)));
+        assert_eq!(editor.page_count(), 2);
+    }
+
+    #[test]
+    fn test_section_width_changes() {
+        let mut editor = Editor::with_text("Intro\nHello\nWorld", default_constraints());
+        editor.cursor.position = editor.document.offset_to_position(editing::AbsoluteOffset(6));
+        let landscape = default_constraints().with_orientation(Orientation::Landscape);
+        let section_id = editor.insert_section_break(landscape).unwrap();
+        editor.update_layout();
+        let ids: Vec<ParagraphId> = editor.document.paragraph_order().collect();
+        let widths = |editor: &Editor| -> Vec<f32> {
+            ids.iter().map(|&id| editor.layout.paragraph_layout(id).unwrap().max_width).collect()
+        };
+        assert_eq!(widths(&editor), vec![468.0, 648.0, 648.0]);
+
+        // Typing keeps the width of the paragraph's section
+        editor.cursor.position = DocPosition::new(ids[2], 5);
+        editor.insert_text("!");
+        editor.update_layout();
+        assert_eq!(widths(&editor), vec![468.0, 648.0, 648.0]);
+
+        // Changing a section's page setup rebreaks its paragraphs
+        editor.document.section_mut(section_id).unwrap().constraints = Some(default_constraints());
+        editor.layout_dirty = true;
+        editor.update_layout();
+        assert_eq!(widths(&editor), vec![468.0, 468.0, 468.0]);
+
+        // Removing the section break moves its paragraphs to the first section
+        editor.document.section_mut(section_id).unwrap().constraints = Some(landscape);
+        editor.cursor.position = DocPosition::new(ids[1], 0);
+        editor.delete(true);
+        editor.update_layout();
+        assert_eq!(widths(&editor), vec![468.0, 468.0, 468.0]);
+    }
+
+    #[test]
+    fn test_section_page_height_edit() {
+        let text = (0..200).map(|i| format!("Line {i}")).collect::<Vec<_>>().join("\n");
+        let mut editor = Editor::with_text(&text, default_constraints());
+        let para_id = editor.document.paragraph_order().nth(100).unwrap();
+        editor.cursor.position = DocPosition::new(para_id, 0);
+        editor.insert_section_break(default_constraints());
+        editor.update_layout();
+        let heights = |editor: &Editor| -> Vec<f32> {
+            editor.layout.pages().iter().map(|page| page.constraints.page_height).collect()
+        };
+        let before = heights(&editor);
+        assert!(before.iter().all(|&height| height == 792.0));
+
+        // A page-height-only edit reflows the section onto more, shorter pages
+        let short = LayoutConstraints { page_height: 400.0, ..default_constraints() };
+        assert!(editor.edit_section("page setup", |section| section.constraints = Some(short)));
+        editor.update_layout();
+        let after = heights(&editor);
+        let first_section = editor.layout.pages().iter().filter(|page| page.section_id == SectionId::DEFAULT).count();
+        assert!(after.len() > before.len());
+        assert!(after[..first_section].iter().all(|&height| height == 792.0));
+        assert!(after[first_section..].iter().all(|&height| height == 400.0));
+
+        // The same pages as a full relayout
+        editor.layout.invalidate_all();
+        editor.layout_dirty = true;
+        editor.update_layout();
+        assert_eq!(heights(&editor), after);
+
+        assert!(editor.undo());
+        editor.update_layout();
+        assert_eq!(heights(&editor), before);
+    }
+
+    #[test]
+    fn test_section_columns() {
+        let text = (0..60).map(|i| format!("Line {i}")).collect::<Vec<_>>().join("\n");
+        let mut editor = Editor::with_text(&text, default_constraints());
+        editor.update_layout();
+        assert_eq!(editor.page_count(), 2);
+
+        editor.set_section_columns(SectionColumns {
+            count: 2,
+            gap: 36.0,
+            separator: true,
+        });
+        editor.update_layout();
+
+        // Lines are broken to the column width and flow into the second
+        // column before the next page
+        let first = editor.document.first_paragraph();
+        assert_eq!(editor.layout.paragraph_layout(first).unwrap().max_width, 216.0);
+        assert_eq!(editor.page_count(), 1);
+        let page = editor.layout.pages()[0].clone();
+        assert_eq!(page.column_breaks.len(), 1);
+        let (column_start, _) = page.column_breaks[0];
+        assert_eq!(page.column_x(1), 324.0);
+
+        let list = editor.build_display_list(Rect::new(0.0, 0.0, 612.0, 792.0));
+        let items = &list.pages[0].items;
+        let position = items.iter().find_map(|item| match item {
+            DisplayItem::TextRun { id, position, .. } if id.para_id == column_start => Some(*position),
+            _ => None,
+        });
+        assert_eq!(position, Some(Point { x: 324.0, y: 72.0 }));
+        assert!(items.iter().any(|item| matches!(item, DisplayItem::ColumnSeparator { x, .. } if *x == 306.0)));
+
+        // Moving down from the bottom of the first column continues at the
+        // top of the second, keeping the X within the column
+        let last_in_first = editor.document.prev_paragraph(column_start).unwrap();
+        editor.cursor.position = DocPosition::new(last_in_first, 2);
+        editor.move_cursor(0, 1, false);
+        assert_eq!(editor.cursor.position, DocPosition::new(column_start, 2));
+        let x_in_column = editor.layout.x_in_column(&editor.cursor.position, Affinity::Downstream).unwrap();
+        assert!(x_in_column > 0.0);
+        assert_eq!(editor.layout.position_to_x(&editor.document, &editor.cursor.position), Some(324.0 + x_in_column));
+    }
+
+    #[test]
+    fn test_section_columns_edge_cases() {
+        // Columns are at least one wide and never narrower than nothing;
+        // points past either edge belong to the outer columns
+        assert_eq!(SectionColumns::new(0, 36.0).count, 1);
+        assert_eq!(SectionColumns::new(0, 36.0).width(468.0), 468.0);
+        assert_eq!(SectionColumns::new(3, 300.0).width(468.0), 0.0);
+        let two = SectionColumns::new(2, 36.0);
+        assert_eq!(two.offset(1, 468.0), 252.0);
+        assert_eq!(two.column_at(-10.0, 468.0), 0);
+        assert_eq!(two.column_at(233.0, 468.0), 0);
+        assert_eq!(two.column_at(235.0, 468.0), 1);
+        assert_eq!(two.column_at(1000.0, 468.0), 1);
+
+        // An empty document has one page and no column breaks; its empty
+        // line still gets the separators between all three columns
+        let mut editor = Editor::new(default_constraints());
+        editor.set_section_columns(SectionColumns {
+            count: 3,
+            gap: 36.0,
+            separator: true,
+        });
+        editor.update_layout();
+        assert_eq!(editor.page_count(), 1);
+        assert!(editor.layout.pages()[0].column_breaks.is_empty());
+        let list = editor.build_display_list(Rect::new(0.0, 0.0, 612.0, 792.0));
+        let separators: Vec<_> = list.pages[0]
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::ColumnSeparator { x, height, .. } => Some((*x, *height > 0.0)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(separators, vec![(222.0, true), (390.0, true)]);
+
+        // The editor keeps at least one column
+        editor.set_section_columns(SectionColumns::new(0, 36.0));
+        let section_id = editor.document.section_of(editor.document.first_paragraph());
+        assert_eq!(editor.document.section(section_id).unwrap().columns.count, 1);
+
+        // A paragraph longer than a column continues in the next one
+        let mut editor = Editor::with_text(&"word ".repeat(2000), default_constraints());
+        editor.set_section_columns(SectionColumns::new(2, 36.0));
+        editor.update_layout();
+        let first = editor.document.first_paragraph();
+        let (column_start, line_idx) = editor.layout.pages()[0].column_breaks[0];
+        assert_eq!(column_start, first);
+        assert!(line_idx > 0);
+        assert_eq!(editor.layout.pages()[0].column_starting_at(first, line_idx), Some(1));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_section_columns_undo() {
+        let text = (0..60).map(|i| format!("Line {i}")).collect::<Vec<_>>().join("\n");
+        let mut editor = Editor::with_text(&text, default_constraints());
+        editor.update_layout();
+        let first = editor.document.first_paragraph();
+        let width = |editor: &Editor| editor.layout.paragraph_layout(first).unwrap().max_width;
 
-    fn default_constraints() -> LayoutConstraints {
-        LayoutConstraints {
-            page_width: 612.0,  // US Letter
-            page_height: 792.0,
-            margin_top: 72.0,
-            margin_bottom: 72.0,
-            margin_left: 72.0,
-            margin_right: 72.0,
+        editor.set_section_columns(SectionColumns::new(2, 36.0));
+        editor.update_layout();
+        assert_eq!((width(&editor), editor.page_count()), (216.0, 1));
+
+        // Setting the same columns again records no step and lays out nothing
+        editor.set_section_columns(SectionColumns::new(2, 36.0));
+        assert!(editor.update_layout().is_none());
+        assert!(!editor.edit_section("section columns", |section| section.columns = SectionColumns::new(2, 36.0)));
+
+        assert!(editor.undo());
+        editor.update_layout();
+        assert_eq!((width(&editor), editor.page_count()), (468.0, 2));
+        assert!(editor.layout.pages().iter().all(|page| page.column_breaks.is_empty()));
+
+        assert!(editor.redo());
+        editor.update_layout();
+        assert_eq!((width(&editor), editor.page_count()), (216.0, 1));
+        assert_eq!(editor.layout.pages()[0].column_breaks.len(), 1);
+    }
+
+    #[test]
+    fn test_header_footer_fields() {
+        use document::{HeaderFooter, HeaderFooterAlign};
+
+        let mut editor = Editor::with_text("Hello", default_constraints());
+        editor.document.set_title("Report");
+        let section = editor.document.section_mut(SectionId::DEFAULT).unwrap();
+        section.headers.default = Some(HeaderFooter::parse("{TITLE}", HeaderFooterAlign::Left));
+        section.footers.default = Some(HeaderFooter::parse(
+            "Page {PAGE} of {NUMPAGES}",
+            HeaderFooterAlign::Right,
+        ));
+        editor.update_layout();
+
+        let list = editor.build_display_list(Rect::new(0.0, 0.0, 612.0, 792.0));
+        let regions: Vec<_> = list.pages[0]
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::HeaderFooterLine { region, position, text } => {
+                    Some((*region, position.y, text.as_str()))
+                }
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].0, render::PageRegion::Header);
+        assert_eq!(regions[0].2, "Report");
+        assert!(regions[0].1 < 72.0);
+        assert_eq!(regions[1].0, render::PageRegion::Footer);
+        assert_eq!(regions[1].2, "Page 1 of 1");
+        assert!(regions[1].1 > 720.0);
+    }
+
+    #[test]
+    fn test_header_footer_edits_undo() {
+        use document::{HeaderFooter, HeaderFooterAlign};
+
+        let mut editor = Editor::with_text("One\nTwo", default_constraints());
+        editor.cursor.position = editor.document.offset_to_position(editing::AbsoluteOffset(4));
+        let section_id = editor.insert_section_break(default_constraints()).unwrap();
+        assert!(editor.edit_section("header", |section| {
+            section.headers.default = Some(HeaderFooter::parse("odd", HeaderFooterAlign::Left));
+            section.headers.even = Some(HeaderFooter::parse("even", HeaderFooterAlign::Left));
+            section.headers.different_odd_even = true;
+        }));
+        editor.update_layout();
+
+        // The second page is the first of its section, so it is odd
+        let headers = |editor: &Editor| -> Vec<String> {
+            let list = editor.build_display_list(Rect::new(0.0, 0.0, 612.0, 2.0 * 792.0));
+            list.pages
+                .iter()
+                .flat_map(|page| &page.items)
+                .filter_map(|item| match item {
+                    DisplayItem::HeaderFooterLine { text, .. } => Some(text.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(editor.layout.pages()[1].page_in_section, 1);
+        assert_eq!(headers(&editor), vec!["odd"]);
+
+        // Header edits are undone like text edits
+        assert!(editor.undo());
+        editor.update_layout();
+        assert!(headers(&editor).is_empty());
+        assert!(editor.document.section(section_id).unwrap().headers.default.is_none());
+        assert!(editor.redo());
+        editor.update_layout();
+        assert_eq!(headers(&editor), vec!["odd"]);
+    }
+
+    #[test]
+    fn test_pasted_note_markers_are_dropped() {
+        let mut editor = Editor::with_text(&"filler text\n".repeat(200), default_constraints());
+        let end = editor.document.offset_to_position(editing::AbsoluteOffset(editor.document.len()));
+        editor.cursor.position = end;
+        let note_id = editor.insert_note(NoteKind::Footnote).unwrap();
+        editor.exit_note();
+        let marker = note_id.reference_char();
+
+        // Pasting a note's marker does not reference it a second time
+        editor.cursor.position = DocPosition::new(editor.document.first_paragraph(), 0);
+        editor.insert_text(&format!("a{marker}b{}", NoteId(7).reference_char()));
+        assert!(editor.text().starts_with("abfiller"));
+        assert_eq!(editor.text().matches(marker).count(), 1);
+
+        // Text of nothing but markers is no edit at all
+        let version = editor.document.version();
+        editor.insert_text(&marker.to_string());
+        assert_eq!(editor.document.version(), version);
+
+        // The reference is found past many rope leaves
+        let offset = editor.document.note_reference_offset(note_id).unwrap();
+        assert_eq!(offset.0, editor.text().find(marker).unwrap());
+    }
+
+    #[test]
+    fn test_footnote_editing_and_placement() {
+        let mut editor = Editor::with_text("Body", default_constraints());
+        editor.cursor.position = DocPosition::new(editor.document.first_paragraph(), 4);
+
+        let note_id = editor.insert_note(NoteKind::Footnote).unwrap();
+        assert_eq!(editor.story, StoryId::Note(note_id));
+        editor.insert_text("See ref");
+        editor.update_layout();
+
+        assert_eq!(editor.document.note(note_id).unwrap().body.text(), "See ref");
+        assert_eq!(editor.layout.note_label(note_id), Some("1"));
+        assert_eq!(editor.layout.pages()[0].footnotes.len(), 1);
+
+        let list = editor.build_display_list(Rect::new(0.0, 0.0, 612.0, 792.0));
+        let items = &list.pages[0].items;
+        assert!(items.iter().any(|item| matches!(
+            item,
+            DisplayItem::TextRun { text, .. } if text == "Body\u{00B9}"
+        )));
+        assert!(items.iter().any(|item| matches!(
+            item,
+            DisplayItem::NoteLine { text, label: Some(label), position, .. }
+                if text == "See ref" && label == "1" && position.y > 600.0
+        )));
+
+        // Undo returns to the note and restores its text
+        editor.insert_text("!");
+        assert!(editor.undo());
+        assert_eq!(editor.story, StoryId::Note(note_id));
+        assert_eq!(editor.story_document().text(), "See ref");
+
+        editor.exit_note();
+        assert_eq!(editor.story, StoryId::Body);
+        assert_eq!(editor.cursor.position.offset, 4 + note_id.reference_char().len_utf8());
+    }
+
+    #[test]
+    fn test_endnotes_follow_body() {
+        let mut editor = Editor::with_text("One", default_constraints());
+        editor.cursor.position = DocPosition::new(editor.document.first_paragraph(), 3);
+
+        let first = editor.insert_note(NoteKind::Endnote).unwrap();
+        editor.insert_text("First");
+        editor.exit_note();
+        let second = editor.insert_note(NoteKind::Endnote).unwrap();
+        editor.insert_text("Second");
+        editor.update_layout();
+
+        assert_eq!(editor.layout.note_label(first), Some("i"));
+        assert_eq!(editor.layout.note_label(second), Some("ii"));
+
+        let last = editor.layout.pages().last().unwrap();
+        let ids: Vec<_> = last.endnotes.iter().map(|slice| slice.note_id).collect();
+        assert_eq!(ids, vec![first, second]);
+        assert!(last.footnotes.is_empty());
+    }
+
+    #[test]
+    fn test_long_footnote_continues_on_next_page() {
+        let mut editor = Editor::with_text("Body", default_constraints());
+        editor.cursor.position = DocPosition::new(editor.document.first_paragraph(), 4);
+
+        let note_id = editor.insert_note(NoteKind::Footnote).unwrap();
+        editor.insert_text(&"line\n".repeat(60));
+        editor.update_layout();
+
+        let pages = editor.layout.pages();
+        assert!(pages.len() >= 2);
+        let first = &pages[0].footnotes[0];
+        let rest = &pages[1].footnotes[0];
+        assert_eq!(first.note_id, note_id);
+        assert_eq!(rest.note_id, note_id);
+        assert_eq!(rest.start_line, first.end_line);
+    }
+
+    #[test]
+    fn test_footnotes_take_section_width() {
+        let mut editor = Editor::with_text("Intro\nBody", default_constraints());
+        let ids: Vec<ParagraphId> = editor.document.paragraph_order().collect();
+        editor.cursor.position = DocPosition::new(ids[1], 0);
+        let narrow = LayoutConstraints { margin_left: 150.0, margin_right: 150.0, ..default_constraints() };
+        editor.insert_section_break(narrow).unwrap();
+
+        editor.cursor.position = DocPosition::new(ids[0], 5);
+        let first = editor.insert_note(NoteKind::Footnote).unwrap();
+        editor.exit_note();
+        editor.cursor.position = DocPosition::new(ids[1], 4);
+        let second = editor.insert_note(NoteKind::Footnote).unwrap();
+        editor.update_layout();
+
+        // Each note fits the content area of the page its reference is on
+        assert_eq!(editor.layout.note_layout(first).unwrap().max_width, 468.0);
+        assert_eq!(editor.layout.note_layout(second).unwrap().max_width, narrow.content_width());
+    }
+
+    #[test]
+    fn test_footnotes_incremental_pagination() {
+        let text = (0..150).map(|i| format!("Line {i}")).collect::<Vec<_>>().join("\n");
+        let mut editor = Editor::with_text(&text, default_constraints());
+        let ids: Vec<ParagraphId> = editor.document.paragraph_order().collect();
+        let mut notes = Vec::new();
+        for (index, lines) in [(10, 2), (60, 40), (120, 3)] {
+            editor.cursor.position = DocPosition::new(ids[index], 4);
+            notes.push(editor.insert_note(NoteKind::Footnote).unwrap());
+            editor.insert_text(&vec!["note"; lines].join("\n"));
+            editor.exit_note();
         }
+        editor.update_layout();
+
+        type Snapshot = Vec<(ParagraphId, usize, ParagraphId, usize, Vec<(NoteId, usize, usize)>)>;
+        let snapshot = |editor: &Editor| -> Snapshot {
+            editor
+                .layout
+                .pages()
+                .iter()
+                .map(|page| {
+                    let notes = page.footnotes.iter().map(|s| (s.note_id, s.start_line, s.end_line)).collect();
+                    (page.start_para, page.start_line, page.end_para, page.end_line, notes)
+                })
+                .collect()
+        };
+        let full = |editor: &mut Editor| -> Snapshot {
+            editor.layout.invalidate_all();
+            editor.layout_dirty = true;
+            editor.update_layout();
+            snapshot(editor)
+        };
+        // The long note carries over, so pages after it are not resumed early
+        assert!(snapshot(&editor).iter().filter(|page| page.4.iter().any(|s| s.0 == notes[1])).count() > 1);
+
+        // Typing, adding lines and editing note text after the first page
+        // repaginate from where they are to the same pages as from the start
+        editor.cursor.position = DocPosition::new(ids[130], 0);
+        editor.insert_text("more ");
+        editor.update_layout();
+        assert_eq!(snapshot(&editor), full(&mut editor));
+
+        editor.cursor.position = DocPosition::new(ids[100], 0);
+        editor.insert_text("\n\n\n");
+        editor.update_layout();
+        assert_eq!(snapshot(&editor), full(&mut editor));
+
+        editor.edit_note(notes[2]);
+        editor.insert_text("\nanother\nand another");
+        editor.update_layout();
+        assert_eq!(snapshot(&editor), full(&mut editor));
+
+        editor.edit_note(notes[1]);
+        editor.delete(true);
+        editor.delete(true);
+        editor.delete(true);
+        editor.delete(true);
+        editor.delete(true);
+        editor.update_layout();
+        assert_eq!(snapshot(&editor), full(&mut editor));
+
+        editor.exit_note();
+        editor.cursor.position = DocPosition::new(ids[20], 0);
+        editor.insert_text("\n".repeat(30).as_str());
+        editor.update_layout();
+        assert_eq!(snapshot(&editor), full(&mut editor));
     }
 
     #[test]
-    fn test_create_editor() {
-        let editor = Editor::new(default_constraints());
-        assert_eq!(editor.text(), "");
+    fn test_table_editing() {
+        let mut editor = Editor::with_text("Before", default_constraints());
+        editor.cursor.position = DocPosition::new(editor.document.first_paragraph(), 6);
+
+        let table_id = editor.insert_table(2, 2);
+        let first = CellId { table_id, row: 0, col: 0 };
+        assert_eq!(editor.story, StoryId::Cell(first));
+
+        editor.insert_text("Name");
+        assert!(editor.move_to_cell(false));
+        editor.insert_text("Value");
+        editor.update_layout();
+
+        let table = editor.document.table(table_id).unwrap();
+        assert_eq!(table.cell(0, 0).unwrap().body.text(), "Name");
+        assert_eq!(table.cell(0, 1).unwrap().body.text(), "Value");
+        assert_eq!(editor.document.paragraph_count(), 3);
+
+        let list = editor.build_display_list(Rect::new(0.0, 0.0, 612.0, 792.0));
+        let items = &list.pages[0].items;
+        assert_eq!(items.iter().filter(|item| matches!(item, DisplayItem::TableCell { .. })).count(), 4);
+        let value_x = items.iter().find_map(|item| match item {
+            DisplayItem::CellLine { text, position, .. } if text == "Value" => Some(position.x),
+            _ => None,
+        });
+        assert!(value_x.unwrap() > 72.0 + 468.0 / 2.0);
+
+        // Tabbing out of the last cell appends a row
+        editor.move_to_cell(false);
+        editor.move_to_cell(false);
+        editor.move_to_cell(false);
+        assert_eq!(editor.document.table(table_id).unwrap().row_count(), 3);
+        assert_eq!(editor.story, StoryId::Cell(CellId { table_id, row: 2, col: 0 }));
     }
 
     #[test]
-    fn test_insert_text() {
+    fn test_table_rows_split_across_pages() {
         let mut editor = Editor::new(default_constraints());
-        editor.insert_text("Hello, World!");
-        assert_eq!(editor.text(), "Hello, World!");
+        let table_id = editor.insert_table(80, 2);
+        editor.document.table_mut(table_id).unwrap().header_rows = 1;
+        editor.insert_text("Header");
+        editor.mark_dirty();
+        editor.update_layout();
+
+        let pages = editor.layout.pages();
+        assert!(pages.len() >= 2);
+        let anchor = editor.document.table_paragraph(table_id).unwrap();
+        assert_eq!(pages[1].start_para, anchor);
+        assert!(pages[1].start_line > 0);
+
+        // The header row is repeated at the top of the continuation page
+        let header_height = editor.layout.table_layout(table_id).unwrap().header_height();
+        assert_eq!(editor.layout.repeated_header_height(&editor.document, anchor, pages[1].start_line), header_height);
+
+        let page = pages[1].clone();
+        let viewport = Rect::new(0.0, page.y_offset, 612.0, 792.0);
+        let list = editor.build_display_list(viewport);
+        let items = &list.pages.iter().find(|p| p.page_index == 1).unwrap().items;
+        let first_cell = items.iter().find_map(|item| match item {
+            DisplayItem::TableCell { cell, header, .. } => Some((cell.row, *header)),
+            _ => None,
+        });
+        assert_eq!(first_cell, Some((0, true)));
+        assert!(items.iter().any(|item| matches!(
+            item,
+            DisplayItem::TableCell { cell, .. } if cell.row == page.start_line
+        )));
     }
 
     #[test]
-    fn test_undo_redo() {
-        let mut editor = Editor::new(default_constraints());
-        editor.insert_text("Hello");
-        assert_eq!(editor.text(), "Hello");
+    fn test_cursor_moves_across_cells() {
+        let mut editor = Editor::with_text("Before\nAfter", default_constraints());
+        editor.cursor.position = DocPosition::new(editor.document.first_paragraph(), 6);
+        let table_id = editor.insert_table(2, 2);
+        editor.insert_text("ab");
+        editor.update_layout();
 
-        editor.undo();
-        assert_eq!(editor.text(), "");
+        // Right arrow at the end of a cell moves into the next one
+        editor.move_cursor(1, 0, false);
+        assert_eq!(editor.story, StoryId::Cell(CellId { table_id, row: 0, col: 1 }));
 
-        editor.redo();
-        assert_eq!(editor.text(), "Hello");
+        // Shift+Down selects a block of cells
+        editor.move_cursor(0, 1, true);
+        let selection = editor.cell_selection.unwrap();
+        assert_eq!((selection.anchor, selection.active), ((0, 1), (1, 1)));
+        assert!(selection.contains(1, 1) && !selection.contains(1, 0));
+
+        // Leaving the last cell returns to the body after the table
+        editor.move_cursor(0, 1, false);
+        assert!(editor.cell_selection.is_none());
+        assert_eq!(editor.story, StoryId::Body);
+        let after = editor.document.table_paragraph(table_id).and_then(|p| editor.document.next_paragraph(p));
+        assert_eq!(Some(editor.cursor.position.para_id), after);
+
+        // Moving back up enters the last cell
+        editor.move_cursor(-1, 0, false);
+        assert_eq!(editor.story, StoryId::Cell(CellId { table_id, row: 1, col: 1 }));
+    }
+
+    #[test]
+    fn test_inline_object_resize_and_undo() {
+        let mut editor = Editor::with_text("See ", default_constraints());
+        editor.cursor.position = DocPosition::new(editor.document.first_paragraph(), 4);
+
+        let source = ObjectSource::Url("chart.png".to_string());
+        let object_id = editor.insert_object(InlineObject::new(source, 120.0, 80.0)).unwrap();
+        editor.update_layout();
+
+        let para_id = editor.document.first_paragraph();
+        assert_eq!(editor.layout.paragraph_layout(para_id).unwrap().lines[0].height, 80.0);
+
+        let list = editor.build_display_list(Rect::new(0.0, 0.0, 612.0, 792.0));
+        let bounds = list.pages[0].items.iter().find_map(|item| match item {
+            DisplayItem::InlineObject { object_id: id, bounds, .. } if *id == object_id => Some(*bounds),
+            _ => None,
+        });
+        assert_eq!(bounds.map(|b| (b.width, b.height)), Some((120.0, 80.0)));
+        assert!(list.pages[0].items.iter().any(|item| matches!(
+            item,
+            DisplayItem::TextRun { text, .. } if text == "See \u{FFFC}"
+        )));
+
+        assert!(editor.resize_object(object_id, 60.0, 40.0));
+        editor.update_layout();
+        assert_eq!(editor.layout.paragraph_layout(para_id).unwrap().lines[0].height, 40.0);
+
+        assert!(editor.undo());
+        editor.update_layout();
+        let object = editor.document.object(object_id).unwrap();
+        assert_eq!((object.width, object.height), (120.0, 80.0));
+        assert_eq!(editor.layout.paragraph_layout(para_id).unwrap().lines[0].height, 80.0);
+
+        // A pasted replacement character places no second copy
+        editor.cursor.position = DocPosition::new(para_id, 0);
+        editor.insert_text(&format!("{}x", object_id.replacement_char()));
+        assert_eq!(editor.text().matches(object_id.replacement_char()).count(), 1);
+        assert!(editor.text().starts_with("xSee"));
+    }
+
+    #[test]
+    fn test_link_editing_and_undo() {
+        let mut editor = Editor::with_text("Visit ", default_constraints());
+        let para_id = editor.document.first_paragraph();
+        editor.cursor.position = DocPosition::new(para_id, 6);
+        editor.insert_link("example", Link::new("https://example.com"));
+        editor.insert_text("!");
+        assert_eq!(editor.text(), "Visit example!");
+
+        // Text typed right after the link stays unlinked
+        let link_at = |editor: &Editor, offset| {
+            editor
+                .link_at(&DocPosition::new(para_id, offset))
+                .map(|link| link.href.clone())
+        };
+        assert_eq!(link_at(&editor, 6).as_deref(), Some("https://example.com"));
+        assert_eq!(link_at(&editor, 14), None);
+
+        editor.update_layout();
+        let list = editor.build_display_list(Rect::new(0.0, 0.0, 612.0, 792.0));
+        let links = list.pages[0].items.iter().find_map(|item| match item {
+            DisplayItem::TextRun { links, .. } => Some(links.clone()),
+            _ => None,
+        });
+        assert_eq!(links.map(|l| (l[0].0, l[0].1)), Some((6, 7)));
+
+        // Retarget the link from inside it, then remove it
+        editor.cursor.position = DocPosition::new(para_id, 8);
+        assert!(editor.set_link(Link::new("https://example.org")));
+        assert!(!editor.set_link(Link::new("javascript:alert(1)")));
+        assert_eq!(link_at(&editor, 8).as_deref(), Some("https://example.org"));
+        assert!(editor.remove_link());
+        assert_eq!(link_at(&editor, 8), None);
+
+        assert!(editor.undo());
+        assert_eq!(link_at(&editor, 8).as_deref(), Some("https://example.org"));
+        assert!(editor.undo());
+        assert_eq!(link_at(&editor, 8).as_deref(), Some("https://example.com"));
+    }
+
+    #[test]
+    fn test_set_direction_and_undo() {
+        let mut editor = Editor::with_text("שלום\nworld", default_constraints());
+        editor.update_layout();
+        let first = editor.document.first_paragraph();
+        let rtl = |editor: &Editor, para_id| editor.layout.paragraph_layout(para_id).unwrap().lines[0].rtl;
+        assert!(rtl(&editor, first));
+
+        // One step sets every selected paragraph
+        let second = editor.document.next_paragraph(first).unwrap();
+        editor.selection = Some(Selection::new(DocPosition::new(first, 0), DocPosition::new(second, 2)));
+        editor.set_direction(TextDirection::Rtl);
+        editor.update_layout();
+        assert!(rtl(&editor, second));
+        assert_eq!(editor.document.block_meta(first).unwrap().direction, TextDirection::Rtl);
+
+        assert!(editor.undo());
+        editor.update_layout();
+        assert!(rtl(&editor, first));
+        assert!(!rtl(&editor, second));
+        assert_eq!(editor.document.block_meta(second).unwrap().direction, TextDirection::Auto);
+    }
+
+    #[test]
+    fn test_set_language_and_undo() {
+        let mut editor = Editor::with_text("hello Welt", default_constraints());
+        let para_id = editor.document.first_paragraph();
+        let language_at = |editor: &Editor, para_id, offset| {
+            editor.document.block_meta(para_id).unwrap().language_at(offset).map(str::to_string)
+        };
+
+        // No selection: the paragraph; a selection: an override on the text
+        editor.set_language(Some("en-US"));
+        editor.selection = Some(Selection::new(DocPosition::new(para_id, 6), DocPosition::new(para_id, 10)));
+        editor.set_language(Some("de"));
+        assert_eq!(language_at(&editor, para_id, 0).as_deref(), Some("en-US"));
+        assert_eq!(language_at(&editor, para_id, 7).as_deref(), Some("de"));
+
+        // Undoing a deletion brings back the override with the text
+        editor.delete(true);
+        assert_eq!(editor.text(), "hello ");
+        assert!(editor.undo());
+        assert_eq!(editor.text(), "hello Welt");
+        assert_eq!(language_at(&editor, para_id, 7).as_deref(), Some("de"));
+
+        // Splitting the paragraph carries both into the new one
+        editor.selection = None;
+        editor.cursor.position = DocPosition::new(para_id, 8);
+        editor.insert_text("\n");
+        let second = editor.document.next_paragraph(para_id).unwrap();
+        assert_eq!(language_at(&editor, second, 0).as_deref(), Some("de"));
+        assert_eq!(language_at(&editor, second, 2).as_deref(), Some("en-US"));
+
+        assert!(editor.undo());
+        assert!(editor.undo());
+        assert_eq!(language_at(&editor, para_id, 7).as_deref(), Some("en-US"));
+    }
+
+    #[test]
+    fn test_tab_stops_and_leaders() {
+        let mut editor = Editor::with_text("Chapter\t12", default_constraints());
+        editor.set_tab_stops(
+            vec![TabStop {
+                position: 300.0,
+                alignment: TabAlignment::Right,
+                leader: TabLeader::Dots,
+            }],
+            None,
+        );
+        editor.update_layout();
+
+        // The page number ends at the stop, with dots leading to it
+        let para_id = editor.document.first_paragraph();
+        let line = &editor.layout.paragraph_layout(para_id).unwrap().lines[0];
+        assert_eq!(line.width, 300.0);
+
+        let list = editor.build_display_list(Rect::new(0.0, 0.0, 612.0, 792.0));
+        let tab = list.pages[0].items.iter().find_map(|item| match item {
+            DisplayItem::Tab { bounds, leader } => Some((*bounds, *leader)),
+            _ => None,
+        });
+        let (bounds, leader) = tab.unwrap();
+        assert_eq!(leader, TabLeader::Dots);
+        assert_eq!(bounds.x + bounds.width, default_constraints().margin_left + line.clusters[8].x);
+
+        assert!(editor.undo());
+        editor.update_layout();
+        assert!(editor.document.block_meta(para_id).unwrap().tab_stops.is_empty());
+    }
+
+    #[test]
+    fn test_list_definitions() {
+        let mut editor = Editor::with_text("One\nTwo\nThree\nFour", default_constraints());
+        let ids: Vec<ParagraphId> = editor.document.paragraph_order().collect();
+        let list_id = editor.add_list(ListDefinition::multilevel());
+
+        editor.selection = Some(Selection::new(DocPosition::new(ids[0], 0), DocPosition::new(ids[3], 4)));
+        editor.set_list(Some((list_id, 0)));
+        editor.selection = Some(Selection::new(DocPosition::new(ids[1], 0), DocPosition::new(ids[2], 5)));
+        editor.set_list(Some((list_id, 1)));
+        editor.update_layout();
+
+        let markers: Vec<&str> = ids.iter().map(|id| editor.layout.list_item(*id).unwrap().marker.as_str()).collect();
+        assert_eq!(markers, vec!["1.", "1.1.", "1.2.", "2."]);
+
+        // Text is indented by the level and its marker hangs before it
+        let margin_left = default_constraints().margin_left;
+        let content_width = default_constraints().content_width();
+        assert_eq!(editor.layout.indent_for(ids[1]), 72.0);
+        assert_eq!(editor.layout.paragraph_layout(ids[1]).unwrap().max_width, content_width);
+        let list = editor.build_display_list(Rect::new(0.0, 0.0, 612.0, 792.0));
+        let first = list.pages[0].items.iter().find_map(|item| match item {
+            DisplayItem::ListMarker { position, marker, .. } => Some((position.x, marker.clone())),
+            _ => None,
+        });
+        assert_eq!(first, Some((margin_left + 18.0, render::ListMarkerDisplay::Number("1.".to_string()))));
+
+        // Redefining a level renumbers and realigns its items
+        let mut definition = ListDefinition::multilevel();
+        definition.levels[0].format = NumberFormat::UpperRoman;
+        definition.levels[0].pattern = "%1)".to_string();
+        definition.levels[0].alignment = MarkerAlignment::Right;
+        assert!(editor.set_list_definition(list_id, definition));
+        assert!(!editor.set_list_definition(ListId(9), ListDefinition::bulleted()));
+        editor.update_layout();
+        let item = editor.layout.list_item(ids[3]).unwrap();
+        assert_eq!(item.marker, "II)");
+        assert!(item.marker_x < 18.0);
+        // Deeper patterns write the top-level counter in its own format
+        assert_eq!(editor.layout.list_item(ids[2]).unwrap().marker, "I.2.");
+
+        // Undo turns the nested items back into top-level ones
+        assert!(editor.undo());
+        editor.update_layout();
+        assert_eq!(editor.layout.list_item(ids[2]).unwrap().marker, "III)");
+    }
+
+    #[test]
+    fn test_heading_numbering() {
+        let mut editor = Editor::with_text("Intro\nScope\nText\nDesign", default_constraints());
+        let ids: Vec<ParagraphId> = editor.document.paragraph_order().collect();
+        for (id, level) in [(ids[0], 1), (ids[1], 2), (ids[3], 1)] {
+            editor.cursor.position = DocPosition::new(id, 0);
+            editor.set_heading_level(Some(level));
+        }
+        editor.set_heading_numbering(Some(ListDefinition::outline()));
+        editor.update_layout();
+
+        let number = |editor: &Editor, id: ParagraphId| editor.layout.list_item(id).map(|item| item.marker.clone());
+        assert_eq!(number(&editor, ids[1]).as_deref(), Some("1.1"));
+        assert_eq!(number(&editor, ids[2]), None);
+        assert_eq!(number(&editor, ids[3]).as_deref(), Some("2"));
+
+        // Text starts past the number, which the display list shows at the margin
+        let item = editor.layout.list_item(ids[1]).unwrap();
+        assert_eq!(item.marker_x, 0.0);
+        assert!(editor.layout.indent_for(ids[1]) > editor.layout.indent_for(ids[0]));
+        let list = editor.build_display_list(Rect::new(0.0, 0.0, 612.0, 792.0));
+        let markers = list.pages[0]
+            .items
+            .iter()
+            .filter(|item| matches!(item, DisplayItem::ListMarker { .. }))
+            .count();
+        assert_eq!(markers, 3);
+
+        // Relevelling a heading renumbers the ones after it
+        editor.cursor.position = DocPosition::new(ids[2], 0);
+        editor.set_heading_level(Some(2));
+        editor.update_layout();
+        assert_eq!(number(&editor, ids[2]).as_deref(), Some("1.2"));
+        editor.cursor.position = DocPosition::new(ids[1], 0);
+        editor.set_heading_level(None);
+        editor.update_layout();
+        assert_eq!(number(&editor, ids[1]), None);
+        assert_eq!(number(&editor, ids[2]).as_deref(), Some("1.1"));
+
+        editor.set_heading_numbering(None);
+        editor.update_layout();
+        assert_eq!(number(&editor, ids[3]), None);
+        assert_eq!(editor.layout.indent_for(ids[3]), 0.0);
+    }
+
+    #[test]
+    fn test_heading_numbering_follows_edits() {
+        let mut editor = Editor::with_text("One\nTwo\nThree\nFour\nItem\nItem", default_constraints());
+        let ids: Vec<ParagraphId> = editor.document.paragraph_order().collect();
+        for (id, level) in [(ids[0], 1), (ids[1], 2), (ids[2], 2), (ids[3], 1)] {
+            editor.cursor.position = DocPosition::new(id, 0);
+            editor.set_heading_level(Some(level));
+        }
+        let list_id = editor.add_list(ListDefinition::numbered());
+        editor.cursor.position = DocPosition::new(ids[4], 0);
+        editor.selection = Some(Selection::new(DocPosition::new(ids[4], 0), DocPosition::new(ids[5], 0)));
+        editor.set_list(Some((list_id, 0)));
+        editor.selection = None;
+        editor.set_heading_numbering(Some(ListDefinition::outline()));
+        editor.update_layout();
+
+        // Markers placed from the edits agree with numbering the whole
+        // document afresh
+        let numbers = |editor: &Editor| -> Vec<Option<String>> {
+            let mut fresh = editor.document.heading_markers();
+            fresh.extend(editor.document.list_markers());
+            editor
+                .document
+                .paragraph_order()
+                .map(|id| {
+                    let marker = editor.layout.list_item(id).map(|item| item.marker.clone());
+                    assert_eq!(marker.as_ref(), fresh.get(&id), "marker of {id:?}");
+                    marker
+                })
+                .collect()
+        };
+        let some = |markers: &[&str]| -> Vec<Option<String>> {
+            markers.iter().map(|m| (!m.is_empty()).then(|| m.to_string())).collect()
+        };
+        assert_eq!(numbers(&editor), some(&["1", "1.1", "1.2", "2", "1.", "2."]));
+
+        // Inserting a heading renumbers those after it
+        editor.cursor.position = DocPosition::new(ids[1], 3);
+        editor.insert_text("\nNew");
+        editor.set_heading_level(Some(1));
+        editor.update_layout();
+        assert_eq!(numbers(&editor), some(&["1", "1.1", "2", "2.1", "3", "1.", "2."]));
+
+        // Relevelling it renumbers them again
+        editor.set_heading_level(Some(2));
+        editor.update_layout();
+        assert_eq!(numbers(&editor), some(&["1", "1.1", "1.2", "1.3", "2", "1.", "2."]));
+
+        // Deleting it, or merging a heading into the one above, closes the gap
+        editor.delete_paragraph();
+        editor.update_layout();
+        assert_eq!(numbers(&editor), some(&["1", "1.1", "1.2", "2", "1.", "2."]));
+        let third = editor.document.paragraph_order().nth(2).unwrap();
+        editor.cursor.position = DocPosition::new(third, 0);
+        editor.delete(true);
+        editor.update_layout();
+        assert_eq!(numbers(&editor), some(&["1", "1.1", "2", "1.", "2."]));
+
+        // Typing in a heading leaves the numbers alone; a list item made
+        // plain renumbers the items after it
+        editor.cursor.position = DocPosition::new(ids[0], 3);
+        editor.insert_text("!");
+        editor.cursor.position = DocPosition::new(ids[4], 0);
+        editor.set_list(None);
+        editor.update_layout();
+        assert_eq!(numbers(&editor), some(&["1", "1.1", "2", "", "1."]));
+
+        // Undo renumbers from the start
+        while editor.undo() {}
+        editor.update_layout();
+        numbers(&editor);
+    }
+
+    #[test]
+    fn test_table_of_contents() {
+        let filler = vec!["text"; 80].join("\n");
+        let text = format!("Contents\nIntro\n{filler}\nDesign\n{filler}\nDetail\n{filler}");
+        let mut editor = Editor::with_text(&text, default_constraints());
+        let ids: Vec<ParagraphId> = editor.document.paragraph_order().collect();
+        let (intro, design, detail) = (ids[1], ids[82], ids[163]);
+        for (id, level) in [(intro, 1), (design, 1), (detail, 2)] {
+            editor.cursor.position = DocPosition::new(id, 0);
+            editor.set_heading_level(Some(level));
+        }
+        editor.update_layout();
+
+        editor.cursor.position = DocPosition::new(ids[0], 8);
+        let toc_id = editor.insert_toc(1);
+        editor.update_layout();
+
+        // One entry per heading down to the level, linked to the heading,
+        // with the page the heading lands on once the entries are in place
+        let entries = editor.document.toc_paragraphs(toc_id);
+        assert_eq!(entries.len(), 2);
+        for (&entry, heading) in entries.iter().zip([intro, design]) {
+            let meta = editor.document.block_meta(entry).unwrap();
+            let page = editor.layout.line_column(&editor.document, heading, 0).unwrap().0.page_index + 1;
+            let title = editor.document.paragraph_text(heading);
+            assert_eq!(editor.document.paragraph_text(entry), format!("{title}\t{page}"));
+            assert_eq!(meta.links[0].link.href, document::heading_anchor(heading));
+            assert_eq!(meta.tab_stops[0].alignment, TabAlignment::Right);
+            assert_eq!(meta.tab_stops[0].leader, TabLeader::Dots);
+        }
+        assert!(editor.document.paragraph_text(entries[1]).ends_with("\t3"));
+
+        // Following an entry's link moves to its heading
+        let href = editor.document.block_meta(entries[1]).unwrap().links[0].link.href.clone();
+        assert!(editor.go_to_anchor(&href));
+        assert_eq!(editor.cursor.position, DocPosition::new(design, 0));
+        assert!(!editor.go_to_anchor("https://example.com"));
+
+        // Updating picks up new headings as one undoable step
+        editor.cursor.position = DocPosition::new(detail, 0);
+        editor.set_heading_level(Some(1));
+        assert!(editor.update_toc(toc_id));
+        editor.update_layout();
+        let entries = editor.document.toc_paragraphs(toc_id);
+        assert_eq!(entries.len(), 3);
+        assert!(editor.document.paragraph_text(entries[2]).starts_with("Detail\t"));
+
+        assert!(editor.undo());
+        editor.update_layout();
+        assert_eq!(editor.document.toc_paragraphs(toc_id).len(), 2);
+        assert!(editor.undo());
+        assert!(editor.undo());
+        editor.update_layout();
+        assert!(editor.document.toc_paragraphs(toc_id).is_empty());
+        assert!(!editor.update_toc(toc_id));
+    }
+
+    #[test]
+    fn test_toc_update_keeps_other_paragraphs() {
+        let mut editor = Editor::with_text("Contents\nIntro\nDesign", default_constraints());
+        let ids: Vec<ParagraphId> = editor.document.paragraph_order().collect();
+        for &id in &ids[1..] {
+            editor.cursor.position = DocPosition::new(id, 0);
+            editor.set_heading_level(Some(1));
+        }
+        editor.cursor.position = DocPosition::new(ids[0], 8);
+        let toc_id = editor.insert_toc(1);
+        let entries = editor.document.toc_paragraphs(toc_id);
+        assert_eq!(entries.len(), 2);
+
+        // A paragraph typed after the first entry
+        let end = editor.document.paragraph_text(entries[0]).len();
+        editor.cursor.position = DocPosition::new(entries[0], end);
+        editor.insert_text("\nmy own note");
+        editor.set_heading_level(None);
+        let note = editor.cursor.position.para_id;
+        assert!(!editor.document.toc_paragraphs(toc_id).contains(&note));
+
+        // Updating would replace the note along with the entries, so the
+        // table is left as it is
+        editor.cursor.position = DocPosition::new(ids[1], 5);
+        editor.insert_text("duction");
+        let text = editor.text();
+        assert!(!editor.update_toc(toc_id));
+        assert_eq!(editor.text(), text);
+        assert!(editor.text().contains("my own note"));
+
+        // Once the note is removed, the entries are rewritten
+        editor.selection = Some(Selection::new(DocPosition::new(entries[0], end), DocPosition::new(note, 11)));
+        editor.delete(true);
+        assert!(editor.update_toc(toc_id));
+        let entries = editor.document.toc_paragraphs(toc_id);
+        assert_eq!(entries.len(), 2);
+        assert!(editor.document.paragraph_text(entries[0]).starts_with("Introduction\t"));
+    }
+
+    #[test]
+    fn test_outline() {
+        let filler = vec!["text"; 80].join("\n");
+        let text = format!("Intro\nScope\n{filler}\nDesign\n{filler}");
+        let mut editor = Editor::with_text(&text, default_constraints());
+        let ids: Vec<ParagraphId> = editor.document.paragraph_order().collect();
+        let (intro, scope, design) = (ids[0], ids[1], ids[82]);
+        for (id, level) in [(intro, 1), (scope, 2), (design, 1)] {
+            editor.cursor.position = DocPosition::new(id, 0);
+            editor.set_heading_level(Some(level));
+        }
+
+        let outline = editor.outline();
+        let shape: Vec<(ParagraphId, u8, &str, Vec<ParagraphId>)> = outline
+            .iter()
+            .map(|e| (e.para_id, e.level, e.text.as_str(), e.children.iter().map(|c| c.para_id).collect()))
+            .collect();
+        assert_eq!(shape, vec![(intro, 1, "Intro", vec![scope]), (design, 1, "Design", vec![])]);
+        assert_eq!(outline[0].page_index, 0);
+        assert!(outline[1].page_index > 0);
+
+        // Typing into a heading and deleting headings refresh the tree
+        editor.cursor.position = DocPosition::new(scope, 5);
+        editor.insert_text(" and goals");
+        assert_eq!(editor.outline()[0].children[0].text, "Scope and goals");
+        editor.cursor.position = DocPosition::new(design, 0);
+        editor.set_heading_level(None);
+        assert_eq!(editor.outline().len(), 1);
+        assert!(editor.undo());
+        assert_eq!(editor.outline().len(), 2);
+
+        // Paragraph tops are in document coordinates, past the page margin
+        let margin_top = default_constraints().margin_top;
+        assert_eq!(editor.scroll_to_paragraph(intro), Some(margin_top));
+        let design_y = editor.scroll_to_paragraph(design).unwrap();
+        let page_index = editor.outline()[1].page_index;
+        let page = &editor.layout.pages()[page_index];
+        assert!(design_y >= page.y_offset + margin_top);
+        assert!(design_y < page.y_offset + page.constraints.page_height);
+        assert_eq!(editor.scroll_to_paragraph(ParagraphId(9999)), None);
+    }
+
+    #[test]
+    fn test_cursor_movements() {
+        let long = vec!["lorem ipsum"; 30].join(" ");
+        let filler = vec!["line"; 100].join("\n");
+        let text = format!("Hello, world\n{long}\n{filler}");
+        let mut editor = Editor::with_text(&text, default_constraints());
+        editor.update_layout();
+        let ids: Vec<ParagraphId> = editor.document.paragraph_order().collect();
+        let (first, wrapped) = (ids[0], ids[1]);
+
+        // Words, crossing paragraph edges, with the selection following
+        editor.move_by(Movement::WordRight, false);
+        assert_eq!(editor.cursor.position, DocPosition::new(first, 7));
+        editor.move_by(Movement::WordRight, true);
+        editor.move_by(Movement::WordRight, true);
+        assert_eq!(editor.cursor.position, DocPosition::new(wrapped, 0));
+        assert_eq!(editor.selection.as_ref().unwrap().anchor, DocPosition::new(first, 7));
+        editor.move_by(Movement::WordLeft, false);
+        assert_eq!(editor.cursor.position, DocPosition::new(first, 12));
+        assert!(editor.selection.is_none());
+        editor.move_by(Movement::WordLeft, false);
+        assert_eq!(editor.cursor.position, DocPosition::new(first, 7));
+
+        // End stays on a soft-wrapped line, Home returns to its start
+        let lines = editor.layout.paragraph_layout(wrapped).unwrap().lines.clone();
+        assert!(lines.len() > 1);
+        editor.cursor.position = DocPosition::new(wrapped, 3);
+        editor.move_by(Movement::LineEnd, false);
+        assert_eq!(editor.cursor.position, DocPosition::new(wrapped, lines[0].byte_range.end));
+        assert_eq!(editor.cursor.affinity, Affinity::Upstream);
+        editor.move_by(Movement::LineEnd, false);
+        assert_eq!(editor.cursor.position.offset, lines[0].byte_range.end);
+        editor.move_by(Movement::LineStart, true);
+        assert_eq!(editor.cursor.position, DocPosition::new(wrapped, 0));
+        assert_eq!(editor.selection.as_ref().unwrap().ordered().1.offset, lines[0].byte_range.end);
+
+        // Moving down from the end of a wrapped line goes to the next line
+        editor.move_by(Movement::LineEnd, false);
+        editor.move_cursor(0, 1, false);
+        let (line_idx, _) = editor.layout.paragraph_layout(wrapped).unwrap()
+            .caret_line(editor.cursor.position.offset, editor.cursor.affinity)
+            .unwrap();
+        assert_eq!(line_idx, 1);
+        editor.move_by(Movement::LineStart, false);
+        assert_eq!(editor.cursor.position, DocPosition::new(wrapped, lines[1].byte_range.start));
+
+        // Paragraphs
+        editor.move_by(Movement::ParagraphUp, false);
+        assert_eq!(editor.cursor.position, DocPosition::new(wrapped, 0));
+        editor.move_by(Movement::ParagraphUp, false);
+        assert_eq!(editor.cursor.position, DocPosition::new(first, 0));
+        editor.move_by(Movement::ParagraphDown, false);
+        assert_eq!(editor.cursor.position, DocPosition::new(wrapped, 0));
+
+        // Document ends
+        editor.move_by(Movement::DocumentEnd, false);
+        let last = *ids.last().unwrap();
+        assert_eq!(editor.cursor.position, DocPosition::new(last, 4));
+        editor.move_by(Movement::DocumentStart, true);
+        assert_eq!(editor.cursor.position, DocPosition::new(first, 0));
+        assert_eq!(editor.selection.as_ref().unwrap().anchor, DocPosition::new(last, 4));
+
+        // Pages move by as many lines as fill the viewport
+        editor.cursor.position = DocPosition::new(ids[3], 0);
+        let line_height = editor.layout.paragraph_layout(ids[3]).unwrap().lines[0].height;
+        editor.move_by(Movement::PageDown(line_height * 10.0), false);
+        assert_eq!(editor.cursor.position.para_id, ids[13]);
+        editor.move_by(Movement::PageUp(line_height * 4.0), true);
+        assert_eq!(editor.cursor.position.para_id, ids[9]);
+        assert_eq!(editor.selection.as_ref().unwrap().anchor.para_id, ids[13]);
+        editor.move_by(Movement::PageUp(10_000.0), false);
+        assert_eq!(editor.cursor.position.para_id, first);
+    }
+
+    #[test]
+    fn test_delete_commands() {
+        let mut editor = Editor::with_text("Title\nIt rained all day. Was it cold? Yes.\nLast", default_constraints());
+        editor.update_layout();
+        let ids: Vec<ParagraphId> = editor.document.paragraph_order().collect();
+        let body = ids[1];
+
+        // Words: back to the start of the word, forward to the next word
+        editor.cursor.position = DocPosition::new(body, 14);
+        editor.delete_by(TextUnit::Word, true);
+        assert_eq!(editor.document.paragraph_text(body), "It rained day. Was it cold? Yes.");
+        editor.delete_by(TextUnit::Word, false);
+        assert_eq!(editor.document.paragraph_text(body), "It rained Was it cold? Yes.");
+        assert!(editor.undo());
+        assert_eq!(editor.document.paragraph_text(body), "It rained day. Was it cold? Yes.");
+
+        // Sentences
+        editor.cursor.position = DocPosition::new(body, 19);
+        editor.delete_by(TextUnit::Sentence, false);
+        assert_eq!(editor.document.paragraph_text(body), "It rained day. Was Yes.");
+        editor.delete_by(TextUnit::Sentence, true);
+        assert_eq!(editor.document.paragraph_text(body), "It rained day. Yes.");
+
+        // Paragraph edges: to the end, then the break itself
+        editor.delete_by(TextUnit::Paragraph, false);
+        assert_eq!(editor.document.paragraph_text(body), "It rained day. ");
+        editor.delete_by(TextUnit::Paragraph, false);
+        assert_eq!(editor.document.paragraph_text(body), "It rained day. Last");
+        editor.update_layout();
+        editor.delete_by(TextUnit::Line, true);
+        assert_eq!(editor.document.paragraph_text(body), "Last");
+
+        // Deleting a paragraph keeps the next one's formatting
+        editor.cursor.position = DocPosition::new(ids[0], 0);
+        editor.set_heading_level(Some(1));
+        editor.cursor.position = DocPosition::new(ids[0], 2);
+        assert!(editor.delete_paragraph().is_some());
+        assert_eq!(editor.text(), "Last");
+        let meta = editor.document.block_meta(editor.cursor.position.para_id).unwrap();
+        assert_eq!(meta.kind, BlockKind::Paragraph);
+        assert!(editor.undo());
+        assert_eq!(editor.text(), "Title\nLast");
+        assert!(matches!(editor.document.block_meta(ids[0]).unwrap().kind, BlockKind::Heading { level: 1 }));
+    }
+
+    #[test]
+    fn test_word_and_paragraph_selection() {
+        let mut editor = Editor::with_text("one two three four\nnext line\nlast", default_constraints());
+        let ids: Vec<ParagraphId> = editor.document.paragraph_order().collect();
+        let at = |para: usize, offset: usize| DocPosition::new(ids[para], offset);
+        let selected = |editor: &Editor| editor.selection.as_ref().map(|sel| (sel.anchor, sel.active));
+
+        // Double-click selects the word; dragging extends by whole words
+        editor.select_unit(at(0, 9), Granularity::Word);
+        assert_eq!(selected(&editor), Some((at(0, 8), at(0, 13))));
+        editor.extend_selection_to(at(0, 15));
+        assert_eq!(selected(&editor), Some((at(0, 8), at(0, 18))));
+        assert_eq!(editor.cursor.position, at(0, 18));
+
+        // Dragging back before the word keeps it selected
+        editor.extend_selection_to(at(0, 5));
+        assert_eq!(selected(&editor), Some((at(0, 13), at(0, 4))));
+        editor.extend_selection_to(at(0, 1));
+        assert_eq!(selected(&editor), Some((at(0, 13), at(0, 0))));
+        editor.extend_selection_to(at(0, 10));
+        assert_eq!(selected(&editor), Some((at(0, 8), at(0, 13))));
+
+        // Triple-click selects the paragraph; dragging extends by paragraphs
+        editor.select_unit(at(1, 3), Granularity::Paragraph);
+        assert_eq!(selected(&editor), Some((at(1, 0), at(1, 9))));
+        editor.extend_selection_to(at(2, 1));
+        assert_eq!(selected(&editor), Some((at(1, 0), at(2, 4))));
+        editor.extend_selection_to(at(0, 2));
+        assert_eq!(selected(&editor), Some((at(1, 9), at(0, 0))));
+
+        // A plain click and drag still selects characters
+        editor.selection = None;
+        editor.cursor.position = at(0, 2);
+        editor.extend_selection_to(at(0, 6));
+        assert_eq!(selected(&editor), Some((at(0, 2), at(0, 6))));
+        assert_eq!(editor.selection.as_ref().unwrap().granularity, Granularity::Character);
+    }
+
+    #[test]
+    fn test_multiple_selections() {
+        let mut editor = Editor::with_text("one two\none two\none", default_constraints());
+        editor.update_layout();
+        let ids: Vec<ParagraphId> = editor.document.paragraph_order().collect();
+
+        // Typing and deleting at two carets is one undoable step each
+        editor.cursor = Cursor::new(DocPosition::new(ids[0], 3));
+        assert!(editor.add_caret(DocPosition::new(ids[1], 3)));
+        assert!(!editor.add_caret(DocPosition::new(ids[1], 3)));
+        editor.insert_text("!!");
+        assert_eq!(editor.text(), "one!! two\none!! two\none");
+        assert_eq!(editor.cursor.position, DocPosition::new(ids[1], 5));
+        let carets: Vec<DocPosition> = editor.secondary_selections.iter().map(|sel| sel.active).collect();
+        assert_eq!(carets, vec![DocPosition::new(ids[0], 5)]);
+        editor.delete(true);
+        assert_eq!(editor.text(), "one! two\none! two\none");
+        assert!(editor.undo());
+        assert_eq!(editor.text(), "one!! two\none!! two\none");
+        assert!(editor.undo());
+        assert_eq!(editor.text(), "one two\none two\none");
+        assert!(editor.secondary_selections.is_empty());
+
+        // Select the word, then each further occurrence, wrapping around
+        editor.cursor = Cursor::new(DocPosition::new(ids[1], 1));
+        editor.selection = None;
+        assert!(editor.select_next_occurrence());
+        assert!(editor.select_next_occurrence());
+        assert!(editor.select_next_occurrence());
+        assert!(!editor.select_next_occurrence());
+        assert_eq!(editor.secondary_selections.len(), 2);
+        assert_eq!(editor.cursor.position, DocPosition::new(ids[0], 3));
+
+        // Formatting keeps the selections; replacing them rebases each caret
+        editor.set_language(Some("fr"));
+        for &para_id in &ids {
+            assert_eq!(editor.document.block_meta(para_id).unwrap().language_at(1), Some("fr"));
+        }
+        assert_eq!(editor.secondary_selections.len(), 2);
+        editor.insert_text("1");
+        assert_eq!(editor.text(), "1 two\n1 two\n1");
+        let mut carets: Vec<DocPosition> = editor.secondary_selections.iter().map(|sel| sel.active).collect();
+        carets.push(editor.cursor.position);
+        carets.sort();
+        assert_eq!(carets, ids.iter().map(|&id| DocPosition::new(id, 1)).collect::<Vec<_>>());
+
+        // A caret added below at the same X, drawn alongside the cursor
+        editor.cursor = Cursor::new(DocPosition::new(ids[0], 3));
+        editor.clear_secondary_selections();
+        editor.update_layout();
+        assert!(editor.add_caret_vertical(1));
+        assert_eq!(editor.cursor.position, DocPosition::new(ids[1], 3));
+        editor.secondary_selections.push(Selection::new(DocPosition::new(ids[2], 0), DocPosition::new(ids[2], 1)));
+        let display = editor.build_display_list(Rect::new(0.0, 0.0, 816.0, 1056.0));
+        let items = &display.pages[0].items;
+        let carets: Vec<bool> = items
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Caret { primary, .. } => Some(*primary),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(carets.iter().filter(|&&primary| primary).count(), 1);
+        assert_eq!(carets.len(), 3);
+        assert!(items.iter().any(|item| matches!(item, DisplayItem::SelectionRect { .. })));
+    }
+
+    #[test]
+    fn test_multiple_carets_at_forced_breaks() {
+        let mut editor = Editor::with_text("one\ntwo\nthree", default_constraints());
+        let ids: Vec<ParagraphId> = editor.document.paragraph_order().collect();
+        for &para_id in &ids[1..] {
+            editor.cursor = Cursor::new(DocPosition::new(para_id, 0));
+            editor.insert_page_break();
+        }
+        let breaks = |editor: &Editor| -> Vec<bool> {
+            ids.iter().map(|&id| editor.document.block_meta(id).unwrap().break_before.is_some()).collect()
+        };
+        assert_eq!(breaks(&editor), vec![false, true, true]);
+
+        // Backspace removes the break at each caret, as it does at one,
+        // before any text
+        editor.cursor = Cursor::new(DocPosition::new(ids[1], 0));
+        assert!(editor.add_caret(DocPosition::new(ids[2], 0)));
+        assert!(editor.delete(true).is_some());
+        assert_eq!(breaks(&editor), vec![false, false, false]);
+        assert_eq!(editor.text(), "one\ntwo\nthree");
+        assert_eq!(editor.secondary_selections.len(), 1);
+        assert!(editor.undo());
+        assert_eq!(breaks(&editor), vec![false, true, true]);
+
+        // A caret at a break and one within text each do their own part
+        editor.cursor = Cursor::new(DocPosition::new(ids[1], 0));
+        editor.clear_secondary_selections();
+        assert!(editor.add_caret(DocPosition::new(ids[2], 2)));
+        editor.delete(true);
+        assert_eq!(breaks(&editor), vec![false, false, true]);
+        assert_eq!(editor.text(), "one\ntwo\ntree");
+    }
+
+    #[test]
+    fn test_multiple_carets_insert_one_note_and_object() {
+        let mut editor = Editor::with_text("Hello World", default_constraints());
+        let para_id = editor.document.first_paragraph();
+        editor.cursor = Cursor::new(DocPosition::new(para_id, 2));
+        assert!(editor.add_caret(DocPosition::new(para_id, 8)));
+
+        // The reference goes in at the primary caret only
+        let note_id = editor.insert_note(NoteKind::Footnote).unwrap();
+        assert!(editor.secondary_selections.is_empty());
+        editor.exit_note();
+        assert_eq!(editor.text(), format!("Hello Wo{}rld", note_id.reference_char()));
+        assert_eq!(editor.document.note_reference_offset(note_id).map(|offset| offset.0), Some(8));
+
+        // As does an inline object
+        editor.cursor = Cursor::new(DocPosition::new(para_id, 2));
+        assert!(editor.add_caret(DocPosition::new(para_id, 4)));
+        let source = ObjectSource::Url("chart.png".to_string());
+        let object_id = editor.insert_object(InlineObject::new(source, 20.0, 20.0)).unwrap();
+        assert!(editor.secondary_selections.is_empty());
+        assert_eq!(editor.text().matches(object_id.replacement_char()).count(), 1);
+        assert!(editor.text().starts_with(&format!("Hell{}o", object_id.replacement_char())));
+    }
+
+    #[test]
+    fn test_select_next_occurrence_with_stale_selection() {
+        let mut editor = Editor::with_text("one two\none", default_constraints());
+        let ids: Vec<ParagraphId> = editor.document.paragraph_order().collect();
+
+        // A selection past the end of its paragraph selects nothing more
+        editor.selection = Some(Selection::new(DocPosition::new(ids[1], 0), DocPosition::new(ids[1], 100)));
+        assert!(!editor.select_next_occurrence());
+        assert!(editor.secondary_selections.is_empty());
+
+        // Occurrences are matched exactly, across paragraphs
+        editor.selection = Some(Selection::new(DocPosition::new(ids[1], 0), DocPosition::new(ids[1], 3)));
+        assert!(editor.select_next_occurrence());
+        assert_eq!(editor.selection.as_ref().unwrap().ordered(), (DocPosition::new(ids[0], 0), DocPosition::new(ids[0], 3)));
+        assert!(!editor.select_next_occurrence());
+    }
+
+    #[test]
+    fn test_find_and_replace() {
+        let mut editor = Editor::with_text("Cat and cat
+concat cat", default_constraints());
+        editor.update_layout();
+        let ids: Vec<ParagraphId> = editor.document.paragraph_order().collect();
+        let words = SearchQuery::new("cat", SearchOptions { whole_word: true, ..Default::default() }).unwrap();
+        let pos = |para: usize, offset: usize| DocPosition::new(ids[para], offset);
+        assert_eq!(editor.find(&words), vec![pos(0, 0)..pos(0, 3), pos(0, 8)..pos(0, 11), pos(1, 7)..pos(1, 10)]);
+
+        // Find next from the cursor wraps around; find previous goes back
+        editor.cursor = Cursor::new(pos(0, 4));
+        assert_eq!(editor.find_next(&words, false), Some(pos(0, 8)..pos(0, 11)));
+        assert_eq!(editor.find_next(&words, false), Some(pos(1, 7)..pos(1, 10)));
+        assert_eq!(editor.find_next(&words, false), Some(pos(0, 0)..pos(0, 3)));
+        assert_eq!(editor.find_next(&words, true), Some(pos(1, 7)..pos(1, 10)));
+
+        // The selected match is highlighted as the current one
+        editor.search = Some(words.clone());
+        editor.update_layout();
+        let display = editor.build_display_list(Rect::new(0.0, 0.0, 816.0, 1056.0));
+        let matches: Vec<bool> = display.pages[0]
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::SearchMatch { current, .. } => Some(*current),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(matches, vec![false, false, true]);
+
+        // Replace swaps the selected match and moves on to the next
+        assert!(editor.replace(&words, "dog"));
+        assert_eq!(editor.text(), "Cat and cat\nconcat dog");
+        assert_eq!(editor.selection.as_ref().map(Selection::ordered), Some((pos(0, 0), pos(0, 3))));
+
+        // Replace all is one undoable step, and regular expressions expand groups
+        let regex = SearchQuery::new(r"(\w)at\b", SearchOptions { regex: true, ..Default::default() }).unwrap();
+        editor.cursor = Cursor::new(pos(1, 3));
+        assert_eq!(editor.replace_all(&regex, "${1}ow"), 3);
+        assert_eq!(editor.text(), "Cow and cow\nconcow dog");
+        assert_eq!(editor.cursor.position, pos(1, 3));
+        assert!(editor.undo());
+        assert_eq!(editor.text(), "Cat and cat\nconcat dog");
+    }
+
+    #[test]
+    fn test_decorations() {
+        let mut editor = Editor::with_text("Some mispeled text", default_constraints());
+        editor.update_layout();
+        let para = editor.document.paragraph_order().next().unwrap();
+        let squiggle = editor
+            .add_decoration(StoryId::Body, DocPosition::new(para, 5)..DocPosition::new(para, 13), DecorationKind::Squiggle, 0xff0000ff)
+            .unwrap();
+
+        // Edits before a decoration move it; undoing them moves it back
+        editor.cursor = Cursor::new(DocPosition::new(para, 0));
+        editor.insert_text("So ");
+        let range = editor.decoration_range(StoryId::Body, squiggle);
+        assert_eq!(range, Some(DocPosition::new(para, 8)..DocPosition::new(para, 16)));
+        assert!(editor.undo());
+        let range = editor.decoration_range(StoryId::Body, squiggle);
+        assert_eq!(range, Some(DocPosition::new(para, 5)..DocPosition::new(para, 13)));
+
+        // Each line it covers gets a decoration item
+        editor.update_layout();
+        let display = editor.build_display_list(Rect::new(0.0, 0.0, 816.0, 1056.0));
+        let painted: Vec<(DecorationKind, u32)> = display.pages[0]
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Decoration { kind, color, .. } => Some((*kind, *color)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(painted, vec![(DecorationKind::Squiggle, 0xff0000ff)]);
+
+        assert!(editor.remove_decoration(StoryId::Body, squiggle));
+        assert!(editor.decoration_range(StoryId::Body, squiggle).is_none());
+    }
+
+    #[test]
+    fn test_spell_checking() {
+        let mut editor = Editor::with_text("The quick fox\nSee www.exampel.com for teh 3rd time", default_constraints());
+        let aff = "TRY ehtoiuqckfxsrawnmdl\nSFX S Y 1\nSFX S 0 s .\n";
+        let dic = "9\nthe\nquick\nfox/S\nsee\nfor\ntime\nbrown\nred\nfast\n";
+        let dictionary = spell::HunspellDictionary::parse(aff, dic).unwrap();
+        editor.spelling.add_dictionary("en", Box::new(dictionary));
+        editor.update_layout();
+        let ids: Vec<ParagraphId> = editor.document.paragraph_order().collect();
+        let marked = |editor: &Editor| -> Vec<String> {
+            editor
+                .document
+                .decorations()
+                .overlapping(0..editor.document.len())
+                .into_iter()
+                .map(|(_, d)| editor.document.text_range(d.start..d.end))
+                .collect()
+        };
+        // URLs and words with digits are skipped
+        assert_eq!(marked(&editor), vec!["teh"]);
+
+        // Only the edited paragraph is checked again
+        editor.cursor = Cursor::new(DocPosition::new(ids[0], 13));
+        editor.insert_text("s and redd");
+        editor.update_layout();
+        assert_eq!(marked(&editor), vec!["and", "redd", "teh"]);
+
+        // Corrections for the word at a position replace it in one step
+        let at = DocPosition::new(ids[1], 25);
+        assert_eq!(editor.misspelling_at(at).map(|(_, word)| word), Some("teh".to_string()));
+        assert_eq!(editor.spelling_suggestions(at).first().map(String::as_str), Some("the"));
+        assert!(editor.correct_spelling(at, "the"));
+        editor.update_layout();
+        assert_eq!(marked(&editor), vec!["and", "redd"]);
+        assert!(editor.undo());
+        editor.update_layout();
+        assert_eq!(marked(&editor), vec!["and", "redd", "teh"]);
+
+        // Note and object markers in a correction are dropped, as when typed
+        let marker = NoteId(0).reference_char();
+        assert!(editor.correct_spelling(at, &format!("t{marker}he")));
+        assert!(editor.text().contains("for the 3rd"));
+        assert!(!editor.text().contains(marker));
+        assert!(editor.undo());
+        editor.update_layout();
+
+        // Added and ignored words are unmarked everywhere
+        editor.add_to_dictionary("and");
+        editor.ignore_spelling("teh");
+        assert_eq!(marked(&editor), vec!["redd"]);
+        assert_eq!(editor.spelling.added_words().collect::<Vec<_>>(), vec!["and"]);
+
+        // Text in a language without a dictionary is not checked
+        editor.set_language(Some("fr"));
+        editor.update_layout();
+        assert!(marked(&editor).is_empty());
+        editor.set_language(None);
+        editor.update_layout();
+        assert_eq!(marked(&editor), vec!["redd"]);
+
+        // Turning checking off removes the squiggles
+        editor.spelling.set_enabled(false);
+        editor.update_layout();
+        assert!(editor.document.decorations().is_empty());
     }
 }
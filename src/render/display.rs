@@ -1,8 +1,17 @@
 //! Display list: render-ready representation
 
-use crate::document::{BlockKind, Document, ListMarker, ParagraphId};
-use crate::editing::{Cursor, Selection};
-use crate::layout::{LayoutState, INDENT_WIDTH};
+use crate::document::{
+    BlockKind, BlockMeta, CellId, DecorationKind, Document, FieldContext, HeaderFooterAlign, Link, ListMarker,
+    NoteId, NoteKind, ObjectId, ParagraphId, SearchHighlights, SearchQuery, StoryId, TabLeader, TableId,
+};
+use crate::editing::{Affinity, CellSelection, Cursor, Selection};
+use crate::layout::font::FontId;
+use crate::layout::{
+    LayoutState, LineLayout, ListItemLayout, NoteSlice, PositionedGlyph, StoryLayout, ENDNOTE_GAP,
+    FOOTNOTE_SEPARATOR,
+};
+use std::borrow::Cow;
+use std::ops::Range;
 use crate::{Point, Rect};
 
 /// Unique identifier for a display item
@@ -27,9 +36,21 @@ impl DisplayItemId {
 #[derive(Debug, Clone, PartialEq)]
 pub enum ListMarkerDisplay {
     Bullet,
+    /// Bullet glyph chosen by the list definition
+    Symbol(String),
     Number(String),
 }
 
+impl From<&ListItemLayout> for ListMarkerDisplay {
+    fn from(item: &ListItemLayout) -> Self {
+        match item.marker.as_str() {
+            "•" if item.bullet => ListMarkerDisplay::Bullet,
+            _ if item.bullet => ListMarkerDisplay::Symbol(item.marker.clone()),
+            _ => ListMarkerDisplay::Number(item.marker.clone()),
+        }
+    }
+}
+
 impl From<&ListMarker> for ListMarkerDisplay {
     fn from(marker: &ListMarker) -> Self {
         match marker {
@@ -41,6 +62,42 @@ impl From<&ListMarker> for ListMarkerDisplay {
     }
 }
 
+/// Page margin region holding a header or footer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageRegion {
+    Header,
+    Footer,
+}
+
+/// Where the cursor and selection are when building a display list
+#[derive(Debug, Clone, Copy)]
+pub struct Focus<'a> {
+    /// Story holding the cursor and selection
+    pub story: StoryId,
+    pub cursor: &'a Cursor,
+    pub selection: Option<&'a Selection>,
+    /// Further carets and selections in the body text
+    pub secondary: &'a [Selection],
+    /// Block of selected table cells
+    pub cells: Option<&'a CellSelection>,
+    /// Search whose matches in the body text are highlighted, with the
+    /// matches found so far
+    pub search: Option<(&'a SearchQuery, &'a SearchHighlights)>,
+}
+
+/// Lines of a secondary story (note or cell) placed on a page
+struct StoryLines<'a> {
+    story: StoryId,
+    document: &'a Document,
+    layout: &'a StoryLayout,
+    /// Story-wide line range to emit
+    range: Range<usize>,
+    /// Top-left corner of the first line
+    origin: Point,
+    /// Every line is selected (part of a cell block selection)
+    selected: bool,
+}
+
 /// A display item to render
 #[derive(Debug, Clone, PartialEq)]
 pub enum DisplayItem {
@@ -54,6 +111,12 @@ pub enum DisplayItem {
         selection_range: Option<(usize, usize)>,
         /// Style spans (start, len, font_id) relative to line text (in bytes)
         styles: Vec<(usize, usize, u32)>,
+        /// Link spans (start, len, link) relative to line text (in bytes)
+        links: Vec<(usize, usize, Link)>,
+        /// Paragraph base direction is right-to-left
+        rtl: bool,
+        /// Shaped glyphs, relative to the line origin and baseline
+        glyphs: Vec<PositionedGlyph>,
     },
     /// List marker (bullet or number)
     ListMarker {
@@ -61,12 +124,109 @@ pub enum DisplayItem {
         position: Point,
         marker: ListMarkerDisplay,
     },
+    /// Header or footer line (fields already resolved)
+    HeaderFooterLine {
+        region: PageRegion,
+        position: Point,
+        text: String,
+    },
+    /// Line of a footnote or endnote
+    NoteLine {
+        note_id: NoteId,
+        kind: NoteKind,
+        /// Story-wide line index within the note
+        line_index: usize,
+        position: Point,
+        text: String,
+        /// Note number, shown on the note's first line
+        label: Option<String>,
+        /// UTF-16 code unit range (start, end) relative to line text
+        selection_range: Option<(usize, usize)>,
+        /// Style spans (start, len, font_id) relative to line text (in bytes)
+        styles: Vec<(usize, usize, u32)>,
+        /// Link spans (start, len, link) relative to line text (in bytes)
+        links: Vec<(usize, usize, Link)>,
+        /// Paragraph base direction is right-to-left
+        rtl: bool,
+        /// Shaped glyphs, relative to the line origin and baseline
+        glyphs: Vec<PositionedGlyph>,
+    },
+    /// Table cell box, drawn with its borders
+    TableCell {
+        cell: CellId,
+        bounds: Rect,
+        border_width: f32,
+        /// Cell is part of the header rows
+        header: bool,
+        /// Cell is inside the cell selection
+        selected: bool,
+    },
+    /// Line of text inside a table cell
+    CellLine {
+        cell: CellId,
+        /// Story-wide line index within the cell
+        line_index: usize,
+        position: Point,
+        text: String,
+        /// UTF-16 code unit range (start, end) relative to line text
+        selection_range: Option<(usize, usize)>,
+        /// Style spans (start, len, font_id) relative to line text (in bytes)
+        styles: Vec<(usize, usize, u32)>,
+        /// Link spans (start, len, link) relative to line text (in bytes)
+        links: Vec<(usize, usize, Link)>,
+        /// Paragraph base direction is right-to-left
+        rtl: bool,
+        /// Shaped glyphs, relative to the line origin and baseline
+        glyphs: Vec<PositionedGlyph>,
+    },
+    /// Inline object (image) placed on a line
+    InlineObject {
+        /// Story whose object table holds the object
+        story: StoryId,
+        object_id: ObjectId,
+        bounds: Rect,
+    },
+    /// Line down the middle of the gap between two columns
+    ColumnSeparator {
+        x: f32,
+        y: f32,
+        height: f32,
+    },
+    /// Space filled by a tab, with the leader drawn across it
+    Tab {
+        bounds: Rect,
+        leader: TabLeader,
+    },
     /// Cursor caret
     Caret {
         position: Point,
         height: f32,
         /// UTF-16 code unit offset within the line (for correct JS text measurement)
         utf16_offset_in_line: usize,
+        /// Side of the offset whose grapheme the caret is drawn against
+        affinity: Affinity,
+        /// That grapheme runs right-to-left (on an empty line: the paragraph does)
+        rtl: bool,
+        /// The caret is the primary one rather than a secondary caret
+        primary: bool,
+    },
+    /// Highlight behind part of a secondary selection
+    SelectionRect {
+        bounds: Rect,
+    },
+    /// Part of a decoration on one line: a highlight behind the text or a
+    /// squiggle along the bottom of the bounds
+    Decoration {
+        bounds: Rect,
+        kind: DecorationKind,
+        /// Color as 0xRRGGBBAA
+        color: u32,
+    },
+    /// Highlight behind part of a search match
+    SearchMatch {
+        bounds: Rect,
+        /// The match is the one selected
+        current: bool,
     },
     /// Page break indicator
     PageBreak {
@@ -107,26 +267,29 @@ impl DisplayList {
         document: &Document,
         layout: &LayoutState,
         viewport: Rect,
-        cursor: &Cursor,
-        selection: Option<&Selection>,
+        focus: Focus,
+        date: &str,
     ) -> Self {
-        let constraints = layout.constraints();
         let mut pages = Vec::new();
-
-        // Calculate which pages are visible
-        let page_height = constraints.page_height;
-        let first_visible_page = (viewport.y / page_height).floor() as usize;
-        let last_visible_page = ((viewport.y + viewport.height) / page_height).ceil() as usize;
+        let body_selection = focus.selection.filter(|_| focus.story == StoryId::Body);
+        let secondary = if focus.story == StoryId::Body { focus.secondary } else { &[] };
 
         for (page_idx, page_layout) in layout.pages().iter().enumerate() {
-            // Skip pages outside viewport
-            if page_idx < first_visible_page || page_idx > last_visible_page {
+            // Pages carry their own setup, so cull by their actual extent
+            let page_bounds = page_layout.bounds();
+            let visible = page_bounds.y <= viewport.y + viewport.height
+                && page_bounds.y + page_bounds.height >= viewport.y;
+            if !visible {
                 continue;
             }
 
-            let page_y_offset = page_idx as f32 * page_height;
-            let mut items = Vec::new();
+            let constraints = &page_layout.constraints;
+            let mut items = Self::header_footer_items(document, layout, page_layout, date);
             let mut y = constraints.margin_top;
+            // Column the body lines are placed in, and the bottom of the
+            // tallest column before it
+            let mut column = 0;
+            let mut columns_bottom = y;
 
             // Iterate through paragraphs on this page
             let mut in_page = false;
@@ -146,14 +309,7 @@ impl DisplayList {
                         .unwrap_or(BlockKind::Paragraph);
 
                     let para_text = document.paragraph_text(para_id);
-                    let indent = layout.indent_for(
-                        block_meta.unwrap_or(&crate::document::BlockMeta {
-                            kind: BlockKind::Paragraph,
-                            start_offset: 0,
-                            byte_len: 0,
-                            styles: Vec::new(),
-                        })
-                    );
+                    let indent = layout.indent_for(para_id);
 
                     // Determine line range for this page
                     let start_line = if para_id == page_layout.start_para {
@@ -168,86 +324,139 @@ impl DisplayList {
                         para_layout.lines.len()
                     };
 
-                    for line_idx in start_line..end_line.min(para_layout.lines.len()) {
-                        let line = &para_layout.lines[line_idx];
-
-                        // Emit list marker on first line
-                        if line_idx == 0 {
-                            if let BlockKind::ListItem { marker, indent_level, .. } = &block_kind {
-                                let marker_x = constraints.margin_left 
-                                    + (*indent_level as f32 * INDENT_WIDTH)
-                                    - 16.0; // Marker width
-                                
-                                items.push(DisplayItem::ListMarker {
-                                    id: DisplayItemId::new(para_id, 0, 0),
-                                    position: Point { x: marker_x, y },
-                                    marker: marker.into(),
-                                });
+                    if let BlockKind::Table { table_id } = block_kind {
+                        // Rows are placed a column at a time
+                        let mut segment_start = start_line;
+                        while segment_start < end_line {
+                            let new_column = page_layout.column_starting_at(para_id, segment_start);
+                            if let Some(next) = new_column {
+                                columns_bottom = columns_bottom.max(y);
+                                column = next;
+                                y = constraints.margin_top;
                             }
-                        }
+                            let segment_end = (segment_start + 1..end_line)
+                                .find(|&row| page_layout.column_starting_at(para_id, row).is_some())
+                                .unwrap_or(end_line);
+                            let origin = Point { x: page_layout.column_x(column), y };
 
-                        // Extract line text
-                        let line_text = if line.byte_range.end <= para_text.len() {
-                            para_text[line.byte_range.clone()].to_string()
-                        } else {
-                            String::new()
-                        };
-
-                        // Selection range for this line
-                        let selection_range = selection.and_then(|sel| {
-                            if !sel.is_collapsed() {
-                                Self::selection_range_for_line(
-                                    document,
-                                    para_id,
-                                    line,
-                                    sel,
-                                    &line_text,
-                                )
+                            // Header rows repeat above a table continued from the previous page or column
+                            let continued = new_column.is_some() || para_id == page_layout.start_para && segment_start == start_line;
+                            let repeat_header = continued && layout.repeated_header_height(document, para_id, segment_start) > 0.0;
+                            let y_after_header = if repeat_header {
+                                let header_rows = layout.table_layout(table_id).map_or(0, |t| t.header_rows);
+                                Self::push_table_rows(&mut items, document, layout, table_id, 0..header_rows, origin, &focus)
                             } else {
-                                None
-                            }
+                                y
+                            };
+
+                            let origin = Point { y: y_after_header, ..origin };
+                            y = Self::push_table_rows(&mut items, document, layout, table_id, segment_start..segment_end, origin, &focus);
+                            segment_start = segment_end;
+                        }
+                    } else {
+                        let search_matches = focus
+                            .search
+                            .map(|(query, highlights)| highlights.matches(query, para_id, &para_text))
+                            .unwrap_or_default();
+                        let current_match = body_selection.map(Selection::ordered).and_then(|(start, end)| {
+                            (start.para_id == para_id && end.para_id == para_id).then_some(start.offset..end.offset)
                         });
 
-                        // Calculate styles for this line
-                        // Line byte range is relative to paragraph start
-                        // Styles in block_meta are relative to paragraph start
-                        // We need to output styles relative to line start
-                        let line_styles = if let Some(meta) = block_meta {
-                            meta.styles.iter()
-                                .filter_map(|s| {
-                                    // Intersect [s.start, s.end) with [line.byte_range.start, line.byte_range.end)
-                                    let start = s.start.max(line.byte_range.start);
-                                    let end = s.end.min(line.byte_range.end);
-                                    
-                                    if start < end {
-                                        Some((
-                                            start - line.byte_range.start,
-                                            end - start,
-                                            s.font_id.0
-                                        ))
-                                    } else {
-                                        None
+                        for line_idx in start_line..end_line.min(para_layout.lines.len()) {
+                            let line = &para_layout.lines[line_idx];
+
+                            // Lines continue at the top of the next column
+                            if let Some(next) = page_layout.column_starting_at(para_id, line_idx) {
+                                columns_bottom = columns_bottom.max(y);
+                                column = next;
+                                y = constraints.margin_top;
+                            }
+                            let column_x = page_layout.column_x(column);
+
+                            // Emit list marker on first line
+                            if line_idx == 0 {
+                                if let Some(item) = layout.list_item(para_id).filter(|item| !item.marker.is_empty()) {
+                                    items.push(DisplayItem::ListMarker {
+                                        id: DisplayItemId::new(para_id, 0, 0),
+                                        position: Point { x: column_x + item.marker_x, y },
+                                        marker: item.into(),
+                                    });
+                                }
+                            }
+
+                            // Extract line text
+                            let line_text = if line.byte_range.end <= para_text.len() {
+                                para_text[line.byte_range.clone()].to_string()
+                            } else {
+                                String::new()
+                            };
+
+                            // Selection range for this line
+                            let selection_range = body_selection.and_then(|sel| {
+                                if !sel.is_collapsed() {
+                                    Self::selection_range_for_line(
+                                        layout,
+                                        para_id,
+                                        line,
+                                        sel,
+                                        &line_text,
+                                    )
+                                } else {
+                                    None
+                                }
+                            });
+
+                            let line_styles = Self::line_styles(layout, line, &line_text);
+                            let line_links = Self::line_links(layout, block_meta, line, &line_text);
+
+                            // Text run
+                            items.push(DisplayItem::TextRun {
+                                id: DisplayItemId::new(para_id, line_idx, 0),
+                                position: Point {
+                                    x: column_x + indent,
+                                    y,
+                                },
+                                text: Self::line_display_text(layout, line, &line_text),
+                                block_kind: block_kind.clone(),
+                                selection_range,
+                                styles: line_styles,
+                                links: line_links,
+                                rtl: line.rtl,
+                                glyphs: line.glyphs.clone(),
+                            });
+
+                            let origin = Point { x: column_x + indent, y };
+                            Self::push_line_objects(&mut items, StoryId::Body, document, line, &para_text, origin);
+                            let para_start = block_meta.map_or(0, |m| m.start_offset);
+                            Self::push_line_decorations(&mut items, document, para_start, line, origin);
+
+                            // Search matches and secondary selections are highlighted from the layout's geometry
+                            for found in search_matches.iter() {
+                                let start = found.start.max(line.byte_range.start);
+                                let end = found.end.min(line.byte_range.end);
+                                if start >= end {
+                                    continue;
+                                }
+                                let current = current_match.as_ref() == Some(found);
+                                for (x, width) in line.selection_rects(start..end) {
+                                    items.push(DisplayItem::SearchMatch {
+                                        bounds: Rect::new(origin.x + x, y, width, line.height),
+                                        current,
+                                    });
+                                }
+                            }
+                            for sel in secondary.iter().filter(|sel| !sel.is_collapsed()) {
+                                if let Some((start, end)) = Self::selected_bytes(para_id, line, sel) {
+                                    for (x, width) in line.selection_rects(start..end) {
+                                        items.push(DisplayItem::SelectionRect {
+                                            bounds: Rect::new(origin.x + x, y, width, line.height),
+                                        });
                                     }
-                                })
-                                .collect()
-                        } else {
-                            Vec::new()
-                        };
-
-                        // Text run
-                        items.push(DisplayItem::TextRun {
-                            id: DisplayItemId::new(para_id, line_idx, 0),
-                            position: Point {
-                                x: constraints.margin_left + indent,
-                                y,
-                            },
-                            text: line_text,
-                            block_kind: block_kind.clone(),
-                            selection_range,
-                            styles: line_styles,
-                        });
+                                }
+                            }
 
-                        y += line.height;
+                            y += line.height;
+                        }
                     }
                 }
 
@@ -256,29 +465,68 @@ impl DisplayList {
                 }
             }
 
-            // Cursor
-            if let Some((caret_pos, utf16_offset)) = Self::cursor_position(
-                document,
-                layout,
-                cursor,
-                page_layout,
-                constraints,
-            ) {
-                items.push(DisplayItem::Caret {
-                    position: caret_pos,
-                    height: layout.font_library.get(crate::layout::font::FontId(0)).map(|m| m.line_height).unwrap_or(16.0),
-                    utf16_offset_in_line: utf16_offset,
-                });
+            // Lines between columns run down to the bottom of the tallest
+            y = y.max(columns_bottom);
+            if page_layout.columns.separator && page_layout.has_body_lines() {
+                for gap in 1..page_layout.columns.count {
+                    items.push(DisplayItem::ColumnSeparator {
+                        x: page_layout.column_x(gap) - page_layout.columns.gap / 2.0,
+                        y: constraints.margin_top,
+                        height: y - constraints.margin_top,
+                    });
+                }
+            }
+
+            // Endnotes continue after the body text
+            if page_layout.has_body_lines() && !page_layout.endnotes.is_empty() {
+                y += ENDNOTE_GAP;
+            }
+            for slice in &page_layout.endnotes {
+                let origin = Point { x: constraints.margin_left, y };
+                y = Self::push_note_lines(&mut items, document, layout, slice, origin, &focus);
+            }
+
+            // Footnotes fill the bottom of the content area
+            if !page_layout.footnotes.is_empty() {
+                let notes_height: f32 = FOOTNOTE_SEPARATOR
+                    + page_layout
+                        .footnotes
+                        .iter()
+                        .filter_map(|slice| {
+                            let heights = layout.note_layout(slice.note_id)?.line_heights();
+                            Some(heights[slice.start_line..slice.end_line.min(heights.len())].iter().sum::<f32>())
+                        })
+                        .sum::<f32>();
+                let mut note_y = constraints.margin_top + constraints.content_height() - notes_height
+                    + FOOTNOTE_SEPARATOR;
+                for slice in &page_layout.footnotes {
+                    let origin = Point { x: constraints.margin_left, y: note_y };
+                    note_y = Self::push_note_lines(&mut items, document, layout, slice, origin, &focus);
+                }
+            }
+
+            // Cursor, then the secondary carets
+            let primary = Some((focus.cursor.clone(), true)).filter(|_| focus.story == StoryId::Body);
+            let carets = primary
+                .into_iter()
+                .chain(secondary.iter().map(|sel| (Cursor::new(sel.active), false)));
+            for (cursor, primary) in carets {
+                let caret = Self::cursor_position(document, layout, &cursor, page_layout, constraints);
+                if let Some((caret_pos, utf16_offset, (affinity, rtl))) = caret {
+                    items.push(DisplayItem::Caret {
+                        position: caret_pos,
+                        height: Self::caret_height(layout),
+                        utf16_offset_in_line: utf16_offset,
+                        affinity,
+                        rtl,
+                        primary,
+                    });
+                }
             }
 
             pages.push(DisplayPage {
                 page_index: page_idx,
-                bounds: Rect {
-                    x: 0.0,
-                    y: page_y_offset,
-                    width: constraints.page_width,
-                    height: constraints.page_height,
-                },
+                bounds: page_bounds,
                 items,
             });
         }
@@ -289,6 +537,429 @@ impl DisplayList {
         }
     }
 
+    /// Emit the lines of a note slice, returning the Y below them
+    fn push_note_lines(
+        items: &mut Vec<DisplayItem>,
+        document: &Document,
+        layout: &LayoutState,
+        slice: &NoteSlice,
+        origin: Point,
+        focus: &Focus,
+    ) -> f32 {
+        let (Some(note), Some(note_layout)) =
+            (document.note(slice.note_id), layout.note_layout(slice.note_id))
+        else {
+            return origin.y;
+        };
+
+        let lines = StoryLines {
+            story: StoryId::Note(slice.note_id),
+            document: &note.body,
+            layout: note_layout,
+            range: slice.start_line..slice.end_line,
+            origin,
+            selected: false,
+        };
+        let label = layout.note_label(slice.note_id);
+        Self::push_story_lines(items, layout, lines, focus, |line_index, position, text, selection_range, styles, links, line| {
+            DisplayItem::NoteLine {
+                note_id: slice.note_id,
+                kind: note.kind,
+                line_index,
+                position,
+                text,
+                label: label.filter(|_| line_index == 0).map(str::to_string),
+                selection_range,
+                styles,
+                links,
+                rtl: line.rtl,
+                glyphs: line.glyphs.clone(),
+            }
+        })
+    }
+
+    /// Emit the cells of a run of table rows, returning the Y below them
+    fn push_table_rows(
+        items: &mut Vec<DisplayItem>,
+        document: &Document,
+        layout: &LayoutState,
+        table_id: TableId,
+        rows: Range<usize>,
+        origin: Point,
+        focus: &Focus,
+    ) -> f32 {
+        let Some(table_layout) = layout.table_layout(table_id) else {
+            return origin.y;
+        };
+
+        let mut y = origin.y;
+        for row in rows.start..rows.end.min(table_layout.rows.len()) {
+            let row_layout = &table_layout.rows[row];
+
+            for (col, cell_layout) in row_layout.cells.iter().enumerate() {
+                let cell = CellId { table_id, row, col };
+                let Some(cell_body) = document.cell(cell).map(|c| &c.body) else {
+                    continue;
+                };
+
+                let selected = focus
+                    .cells
+                    .is_some_and(|sel| sel.table_id == table_id && sel.contains(row, col));
+                let x = origin.x + table_layout.column_x(col);
+                items.push(DisplayItem::TableCell {
+                    cell,
+                    bounds: Rect::new(x, y, table_layout.column_widths[col], row_layout.height),
+                    border_width: table_layout.border_width,
+                    header: row < table_layout.header_rows,
+                    selected,
+                });
+
+                let inset = table_layout.content_inset();
+                let lines = StoryLines {
+                    story: StoryId::Cell(cell),
+                    document: cell_body,
+                    layout: cell_layout,
+                    range: 0..usize::MAX,
+                    origin: Point { x: x + inset, y: y + inset },
+                    selected,
+                };
+                Self::push_story_lines(items, layout, lines, focus, |line_index, position, text, selection_range, styles, links, line| {
+                    DisplayItem::CellLine {
+                        cell,
+                        line_index,
+                        position,
+                        text,
+                        selection_range,
+                        styles,
+                        links,
+                        rtl: line.rtl,
+                        glyphs: line.glyphs.clone(),
+                    }
+                });
+            }
+
+            y += row_layout.height;
+        }
+        y
+    }
+
+    /// Emit the lines of a secondary story, with the caret and selection
+    /// when the story has focus; returns the Y below the last line
+    fn push_story_lines(
+        items: &mut Vec<DisplayItem>,
+        layout: &LayoutState,
+        lines: StoryLines,
+        focus: &Focus,
+        make_item: impl Fn(
+            usize,
+            Point,
+            String,
+            Option<(usize, usize)>,
+            Vec<(usize, usize, u32)>,
+            Vec<(usize, usize, Link)>,
+            &LineLayout,
+        ) -> DisplayItem,
+    ) -> f32 {
+        let active = focus.story == lines.story;
+        let mut y = lines.origin.y;
+
+        let placed = lines
+            .layout
+            .lines()
+            .enumerate()
+            .skip(lines.range.start)
+            .take(lines.range.end.saturating_sub(lines.range.start));
+
+        for (line_index, (para_layout, para_line, line)) in placed {
+            let para_id = para_layout.para_id;
+            let para_text = lines.document.paragraph_text(para_id);
+            let line_text = para_text.get(line.byte_range.clone()).unwrap_or("");
+            let block_meta = lines.document.block_meta(para_id);
+            let position = Point { x: lines.origin.x, y };
+
+            let text = Self::line_display_text(layout, line, line_text);
+
+            let selection_range = if lines.selected {
+                Some((0, text.encode_utf16().count()))
+            } else {
+                focus
+                    .selection
+                    .filter(|sel| active && !sel.is_collapsed())
+                    .and_then(|sel| Self::selection_range_for_line(layout, para_id, line, sel, line_text))
+            };
+            let styles = Self::line_styles(layout, line, line_text);
+            let links = Self::line_links(layout, block_meta, line, line_text);
+            items.push(make_item(line_index, position, text, selection_range, styles, links, line));
+            Self::push_line_objects(items, lines.story, lines.document, line, &para_text, position);
+            let para_start = block_meta.map_or(0, |m| m.start_offset);
+            Self::push_line_decorations(items, lines.document, para_start, line, position);
+
+            // Caret on this line
+            let pos = &focus.cursor.position;
+            let on_line = pos.para_id == para_id
+                && para_layout
+                    .caret_line(pos.offset, focus.cursor.affinity)
+                    .is_some_and(|(idx, _)| idx == para_line);
+            if active && on_line {
+                let before = para_text.get(line.byte_range.start..pos.offset).unwrap_or("");
+                let (affinity, rtl) = Self::caret_side(line, focus.cursor);
+                items.push(DisplayItem::Caret {
+                    position,
+                    height: Self::caret_height(layout),
+                    utf16_offset_in_line: Self::display_text(layout, before).encode_utf16().count(),
+                    affinity,
+                    rtl,
+                    primary: true,
+                });
+            }
+
+            y += line.height;
+        }
+        y
+    }
+
+    /// Which neighbouring grapheme the caret is drawn against, and whether
+    /// it runs right-to-left
+    fn caret_side(line: &LineLayout, cursor: &Cursor) -> (Affinity, bool) {
+        line.caret_cluster(cursor.position.offset, cursor.affinity)
+            .map_or((Affinity::Downstream, line.rtl), |(cluster, affinity)| (affinity, cluster.is_rtl()))
+    }
+
+    /// Height of the caret
+    fn caret_height(layout: &LayoutState) -> f32 {
+        layout
+            .font_library
+            .get(crate::layout::font::FontId(0))
+            .map(|m| m.line_height)
+            .unwrap_or(16.0)
+    }
+
+    /// Style spans of a line, relative to the displayed line text
+    ///
+    /// Spans carry the font each run was measured with after fallback (the
+    /// default font needs no span), so text is drawn with the same font.
+    fn line_styles(layout: &LayoutState, line: &LineLayout, line_text: &str) -> Vec<(usize, usize, u32)> {
+        line.fonts
+            .iter()
+            .filter(|(_, font_id)| *font_id != FontId(0))
+            .filter_map(|(range, font_id)| {
+                let (start, len) = Self::line_span(layout, line, line_text, range.clone())?;
+                Some((start, len, font_id.0))
+            })
+            .collect()
+    }
+
+    /// Link spans of a line, relative to the displayed line text
+    fn line_links(
+        layout: &LayoutState,
+        block_meta: Option<&BlockMeta>,
+        line: &LineLayout,
+        line_text: &str,
+    ) -> Vec<(usize, usize, Link)> {
+        let Some(meta) = block_meta else {
+            return Vec::new();
+        };
+
+        meta.links
+            .iter()
+            .filter_map(|l| {
+                let (start, len) = Self::line_span(layout, line, line_text, l.start..l.end)?;
+                Some((start, len, l.link.clone()))
+            })
+            .collect()
+    }
+
+    /// Map a paragraph-relative span onto a line's displayed text as
+    /// (start, len), or None when it misses the line
+    fn line_span(
+        layout: &LayoutState,
+        line: &LineLayout,
+        line_text: &str,
+        span: Range<usize>,
+    ) -> Option<(usize, usize)> {
+        // Intersect the span with the line's byte range
+        let start = span.start.max(line.byte_range.start) - line.byte_range.start;
+        let end = span.end.min(line.byte_range.end).saturating_sub(line.byte_range.start);
+        if start >= end {
+            return None;
+        }
+
+        // Map onto the displayed text (note references change length)
+        let display_start = Self::display_text(layout, line_text.get(..start)?).len();
+        let display_end = Self::display_text(layout, line_text.get(..end)?).len();
+        Some((display_start, display_end - display_start))
+    }
+
+    /// Display text of a line, with the hyphen of a hyphenated line appended
+    fn line_display_text(layout: &LayoutState, line: &LineLayout, line_text: &str) -> String {
+        let mut text = Self::display_text(layout, line_text).into_owned();
+        if line.hyphen {
+            text.push('-');
+        }
+        text
+    }
+
+    /// Text as displayed: note reference markers become superscript labels
+    /// and inline objects become U+FFFC
+    pub fn display_text<'a>(layout: &LayoutState, text: &'a str) -> Cow<'a, str> {
+        let substituted = |c: char| {
+            NoteId::from_reference_char(c).is_some() || ObjectId::from_replacement_char(c).is_some()
+        };
+        if !text.chars().any(substituted) {
+            return Cow::Borrowed(text);
+        }
+
+        let mut out = String::with_capacity(text.len());
+        for c in text.chars() {
+            if let Some(note_id) = NoteId::from_reference_char(c) {
+                out.extend(
+                    layout
+                        .note_label(note_id)
+                        .unwrap_or_default()
+                        .chars()
+                        .map(superscript),
+                );
+            } else if ObjectId::from_replacement_char(c).is_some() {
+                out.push('\u{FFFC}');
+            } else {
+                out.push(c);
+            }
+        }
+        Cow::Owned(out)
+    }
+
+    /// Emit the inline objects on a line, bottom-aligned in the line box,
+    /// and the space filled by each tab
+    fn push_line_objects(
+        items: &mut Vec<DisplayItem>,
+        story: StoryId,
+        document: &Document,
+        line: &LineLayout,
+        para_text: &str,
+        origin: Point,
+    ) {
+        for &(offset, leader) in &line.tabs {
+            if let Some(cluster) = line.cluster_at_offset(offset) {
+                items.push(DisplayItem::Tab {
+                    bounds: Rect::new(origin.x + cluster.x, origin.y, cluster.width, line.height),
+                    leader,
+                });
+            }
+        }
+
+        for cluster in &line.clusters {
+            let object = para_text
+                .get(cluster.byte_offset..)
+                .and_then(|rest| rest.chars().next())
+                .and_then(ObjectId::from_replacement_char)
+                .and_then(|id| Some((id, document.object(id)?)));
+
+            if let Some((object_id, object)) = object {
+                items.push(DisplayItem::InlineObject {
+                    story,
+                    object_id,
+                    bounds: Rect::new(
+                        origin.x + cluster.x,
+                        origin.y + line.height - object.height,
+                        object.width,
+                        object.height,
+                    ),
+                });
+            }
+        }
+    }
+
+    /// Emit the parts of a story's decorations on a line, measured from
+    /// the layout's geometry
+    fn push_line_decorations(
+        items: &mut Vec<DisplayItem>,
+        document: &Document,
+        para_start: usize,
+        line: &LineLayout,
+        origin: Point,
+    ) {
+        let decorations = document.decorations();
+        if decorations.is_empty() {
+            return;
+        }
+        let line_range = para_start + line.byte_range.start..para_start + line.byte_range.end;
+        for (_, decoration) in decorations.overlapping(line_range.clone()) {
+            let start = decoration.start.max(line_range.start) - para_start;
+            let end = decoration.end.min(line_range.end) - para_start;
+            for (x, width) in line.selection_rects(start..end) {
+                items.push(DisplayItem::Decoration {
+                    bounds: Rect::new(origin.x + x, origin.y, width, line.height),
+                    kind: decoration.kind,
+                    color: decoration.color,
+                });
+            }
+        }
+    }
+
+    /// Lay out the header and footer of a page in its margins
+    fn header_footer_items(
+        document: &Document,
+        layout: &LayoutState,
+        page: &crate::layout::PageLayout,
+        date: &str,
+    ) -> Vec<DisplayItem> {
+        let mut items = Vec::new();
+        let Some(section) = document.section(page.section_id) else {
+            return items;
+        };
+
+        let context = FieldContext {
+            page_number: page.page_index + 1,
+            page_count: layout.page_count(),
+            date,
+            title: document.title(),
+        };
+        let constraints = &page.constraints;
+        let content_width = constraints.content_width();
+
+        let regions = [
+            (PageRegion::Header, &section.headers, 0.0, constraints.margin_top),
+            (
+                PageRegion::Footer,
+                &section.footers,
+                constraints.page_height - constraints.margin_bottom,
+                constraints.margin_bottom,
+            ),
+        ];
+
+        for (region, set, margin_y, margin_height) in regions {
+            let Some(content) = set.for_page(page.page_in_section) else {
+                continue;
+            };
+
+            let text = content.resolve(&context);
+            let text_layout = layout.layout_text(&text, content_width);
+            let height: f32 = text_layout.lines.iter().map(|l| l.height).sum();
+
+            // Center the block vertically within the margin
+            let mut y = margin_y + ((margin_height - height) / 2.0).max(0.0);
+
+            for line in &text_layout.lines {
+                let free = (content_width - line.width).max(0.0);
+                let x = constraints.margin_left
+                    + match content.align {
+                        HeaderFooterAlign::Left => 0.0,
+                        HeaderFooterAlign::Center => free / 2.0,
+                        HeaderFooterAlign::Right => free,
+                    };
+
+                items.push(DisplayItem::HeaderFooterLine {
+                    region,
+                    position: Point { x, y },
+                    text: text[line.byte_range.clone()].trim_end_matches('\n').to_string(),
+                });
+                y += line.height;
+            }
+        }
+
+        items
+    }
+
     /// Calculate cursor position on page
     /// Returns (Point, utf16_offset) where utf16_offset is the UTF-16 code unit offset within the line
     /// (UTF-16 offsets are used for correct text measurement in JavaScript)
@@ -298,7 +969,7 @@ impl DisplayList {
         cursor: &Cursor,
         page: &crate::layout::PageLayout,
         constraints: &crate::layout::LayoutConstraints,
-    ) -> Option<(Point, usize)> {
+    ) -> Option<(Point, usize, (Affinity, bool))> {
         // Check if cursor is on this page
         if cursor.position.para_id < page.start_para 
             || cursor.position.para_id > page.end_para 
@@ -307,7 +978,7 @@ impl DisplayList {
         }
 
         let para_layout = layout.paragraph_layout(cursor.position.para_id)?;
-        let (line_idx, line) = para_layout.line_at_offset(cursor.position.offset)?;
+        let (line_idx, line) = para_layout.caret_line(cursor.position.offset, cursor.affinity)?;
 
         // Check line is on this page
         if cursor.position.para_id == page.start_para && line_idx < page.start_line {
@@ -328,11 +999,12 @@ impl DisplayList {
             .get(line_start_byte..cursor_byte)
             .unwrap_or("");
         
-        // Convert to UTF-16 code units for JS
-        let utf16_offset = text_before_cursor.chars().map(|c| c.len_utf16()).sum::<usize>();
+        // Convert to UTF-16 code units for JS (as displayed)
+        let utf16_offset = Self::display_text(layout, text_before_cursor).encode_utf16().count();
 
-        // Calculate Y position
+        // Calculate Y position, in the column holding the line
         let mut y = constraints.margin_top;
+        let mut column = 0;
         
         // Add height of previous paragraphs on this page
         for para_id in document.paragraph_order() {
@@ -357,15 +1029,20 @@ impl DisplayList {
                     if idx < first_line {
                         continue;
                     }
+                    if let Some(next) = page.column_starting_at(para_id, idx) {
+                        column = next;
+                        y = constraints.margin_top;
+                    }
 
                     if para_id == cursor.position.para_id && idx == line_idx {
                         // Found cursor line
                         // Note: We don't calculate precise X here because Web client
                         // calculates it using DOM measurement for perfect alignment.
-                        // We still provide Y and utf16_offset which are essential.
-                        let x = 0.0;
+                        // We still provide Y, the column's left edge and utf16_offset
+                        // which are essential.
+                        let x = page.column_x(column);
                         
-                        return Some((Point { x, y }, utf16_offset));
+                        return Some((Point { x, y }, utf16_offset, Self::caret_side(line, cursor)));
                     }
 
                     y += ln.height;
@@ -382,20 +1059,46 @@ impl DisplayList {
 
     /// Calculate selection range (UTF-16) for a line
     fn selection_range_for_line(
-        _document: &Document,
+        layout: &LayoutState,
         para_id: ParagraphId,
         line: &crate::layout::LineLayout,
         selection: &Selection,
         line_text: &str, // slice of text for this line
     ) -> Option<(usize, usize)> {
-        let (sel_start, sel_end) = selection.ordered();
+        let (intersect_start_byte, intersect_end_byte) = Self::selected_bytes(para_id, line, selection)?;
+        let line_start_byte = line.byte_range.start;
+
+        // Now we have the byte range *within the paragraph* that is selected: [intersect_start_byte, intersect_end_byte)
+        // We need to convert this to UTF-16 offsets *relative to the line start*.
+        
+        // Offset relative to line start (bytes)
+        let rel_start_byte = intersect_start_byte.saturating_sub(line_start_byte);
+        let rel_end_byte = intersect_end_byte.saturating_sub(line_start_byte);
         
-        // Convert selection to absolute byte offsets
-        // Note: selection positions (DocPosition) are relative to paragraph start
-        // But for comparison, we need to handle paragraph boundaries.
-        // Actually, we can just compare DocPosition directly if we are careful.
-        // But line ranges are byte offsets within paragraph.
+        // Safety check for slicing
+        if rel_start_byte > line_text.len() || rel_end_byte > line_text.len() {
+            return None; 
+        }
+
+        // Convert byte offsets to UTF-16 offsets (as displayed)
+        let text_before_start = &line_text[..rel_start_byte];
+        let text_segment = &line_text[rel_start_byte..rel_end_byte];
         
+        let utf16_start = Self::display_text(layout, text_before_start).encode_utf16().count();
+        let utf16_len = Self::display_text(layout, text_segment).encode_utf16().count();
+        let utf16_end = utf16_start + utf16_len;
+
+        Some((utf16_start, utf16_end))
+    }
+
+    /// Byte range of a line (within its paragraph) covered by a selection
+    fn selected_bytes(
+        para_id: ParagraphId,
+        line: &crate::layout::LineLayout,
+        selection: &Selection,
+    ) -> Option<(usize, usize)> {
+        let (sel_start, sel_end) = selection.ordered();
+
         // Check if this paragraph intersects selection
         if para_id < sel_start.para_id || para_id > sel_end.para_id {
             return None;
@@ -418,31 +1121,26 @@ impl DisplayList {
             line_end_byte
         };
 
-        if intersect_start_byte >= intersect_end_byte {
-            return None;
-        }
-
-        // Now we have the byte range *within the paragraph* that is selected: [intersect_start_byte, intersect_end_byte)
-        // We need to convert this to UTF-16 offsets *relative to the line start*.
-        
-        // Offset relative to line start (bytes)
-        let rel_start_byte = intersect_start_byte.saturating_sub(line_start_byte);
-        let rel_end_byte = intersect_end_byte.saturating_sub(line_start_byte);
-        
-        // Safety check for slicing
-        if rel_start_byte > line_text.len() || rel_end_byte > line_text.len() {
-            return None; 
-        }
-
-        // Convert byte offsets to UTF-16 offsets
-        let text_before_start = &line_text[..rel_start_byte];
-        let text_segment = &line_text[rel_start_byte..rel_end_byte];
-        
-        let utf16_start = text_before_start.chars().map(|c| c.len_utf16()).sum::<usize>();
-        let utf16_len = text_segment.chars().map(|c| c.len_utf16()).sum::<usize>();
-        let utf16_end = utf16_start + utf16_len;
+        (intersect_start_byte < intersect_end_byte).then_some((intersect_start_byte, intersect_end_byte))
+    }
+}
 
-        Some((utf16_start, utf16_end))
+/// Superscript form of a note label character
+fn superscript(c: char) -> char {
+    match c {
+        '0' => '\u{2070}',
+        '1' => '\u{00B9}',
+        '2' => '\u{00B2}',
+        '3' => '\u{00B3}',
+        '4'..='9' => char::from_u32(0x2074 + (c as u32 - '4' as u32)).unwrap_or(c),
+        'i' => '\u{2071}',
+        'v' => '\u{1D5B}',
+        'x' => '\u{02E3}',
+        'l' => '\u{02E1}',
+        'c' => '\u{1D9C}',
+        'd' => '\u{1D48}',
+        'm' => '\u{1D50}',
+        _ => c,
     }
 }
 
@@ -4,4 +4,6 @@ mod diff;
 mod display;
 
 pub use diff::{LayoutDiff, RenderDiff, RenderPatch};
-pub use display::{DisplayItem, DisplayItemId, DisplayList, DisplayPage, ListMarkerDisplay};
+pub use display::{
+    DisplayItem, DisplayItemId, DisplayList, DisplayPage, Focus, ListMarkerDisplay, PageRegion,
+};
@@ -0,0 +1,559 @@
+//! Hunspell dictionaries
+//!
+//! Reads the `.aff`/`.dic` pair most open-source word lists ship as. A word
+//! is correct if it is a stem of the `.dic` file, or a stem with one prefix
+//! and/or one suffix rule of the `.aff` file applied. Compounding, twofold
+//! affixes and morphology are not supported, and the files must already be
+//! decoded to text (`SET` names the encoding to decode them with).
+
+use super::Dictionary;
+use std::collections::HashMap;
+
+/// Most suggestions returned for a word
+const MAX_SUGGESTIONS: usize = 8;
+
+/// An affix flag: a character, a pair of characters or a number
+type Flag = u32;
+
+/// How flags are written in the files (`FLAG`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FlagMode {
+    /// One character per flag (also `UTF-8`, since the data is text)
+    Char,
+    /// Two characters per flag
+    Long,
+    /// Comma-separated numbers
+    Num,
+}
+
+impl FlagMode {
+    fn parse(self, text: &str) -> Vec<Flag> {
+        match self {
+            FlagMode::Char => text.chars().map(|c| c as Flag).collect(),
+            FlagMode::Long => {
+                let chars: Vec<char> = text.chars().collect();
+                chars
+                    .chunks(2)
+                    .map(|pair| pair.iter().fold(0, |flag, &c| flag << 16 | c as Flag))
+                    .collect()
+            }
+            FlagMode::Num => text.split(',').filter_map(|n| n.trim().parse().ok()).collect(),
+        }
+    }
+}
+
+/// One character position of an affix condition
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CharClass {
+    Any,
+    Char(char),
+    OneOf(Vec<char>),
+    NoneOf(Vec<char>),
+}
+
+impl CharClass {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            CharClass::Any => true,
+            CharClass::Char(expected) => c == *expected,
+            CharClass::OneOf(set) => set.contains(&c),
+            CharClass::NoneOf(set) => !set.contains(&c),
+        }
+    }
+
+    /// Parse a condition such as `[^aeiou]y` (`.` matches anything)
+    fn parse_condition(condition: &str) -> Vec<CharClass> {
+        let mut classes = Vec::new();
+        let mut chars = condition.chars();
+        while let Some(c) = chars.next() {
+            classes.push(match c {
+                '.' => CharClass::Any,
+                '[' => {
+                    let mut set: Vec<char> = chars.by_ref().take_while(|&c| c != ']').collect();
+                    if set.first() == Some(&'^') {
+                        set.remove(0);
+                        CharClass::NoneOf(set)
+                    } else {
+                        CharClass::OneOf(set)
+                    }
+                }
+                c => CharClass::Char(c),
+            });
+        }
+        classes
+    }
+}
+
+/// A prefix or suffix rule
+#[derive(Debug, Clone)]
+struct Affix {
+    flag: Flag,
+    /// Combines with affixes of the other kind
+    cross_product: bool,
+    /// Removed from the stem before `add` is attached
+    strip: String,
+    add: String,
+    /// What the start (prefixes) or end (suffixes) of the stem must be
+    condition: Vec<CharClass>,
+}
+
+impl Affix {
+    /// Stem the rule derives a word from, if the word carries the rule
+    fn stem(&self, word: &str, prefix: bool) -> Option<String> {
+        let stem = if prefix {
+            format!("{}{}", self.strip, word.strip_prefix(self.add.as_str())?)
+        } else {
+            format!("{}{}", word.strip_suffix(self.add.as_str())?, self.strip)
+        };
+        let chars: Vec<char> = stem.chars().collect();
+        if chars.is_empty() || chars.len() < self.condition.len() {
+            return None;
+        }
+        let tested = if prefix { &chars[..self.condition.len()] } else { &chars[chars.len() - self.condition.len()..] };
+        tested
+            .iter()
+            .zip(&self.condition)
+            .all(|(&c, class)| class.matches(c))
+            .then_some(stem)
+    }
+}
+
+/// A dictionary read from Hunspell `.aff` and `.dic` files
+#[derive(Debug, Default)]
+pub struct HunspellDictionary {
+    /// Stems mapped to their affix flags
+    words: HashMap<String, Vec<Flag>>,
+    /// Prefix rules by the text they add
+    prefixes: HashMap<String, Vec<Affix>>,
+    /// Suffix rules by the text they add
+    suffixes: HashMap<String, Vec<Affix>>,
+    /// Longest text a prefix adds, in characters
+    max_prefix: usize,
+    /// Longest text a suffix adds, in characters
+    max_suffix: usize,
+    /// Characters to try when suggesting, most common first (`TRY`)
+    try_chars: Vec<char>,
+    /// Common misspellings and their corrections (`REP`); `_` is a space
+    replacements: Vec<(String, String)>,
+    /// Stems that are misspellings, whatever their affixes (`FORBIDDENWORD`)
+    forbidden: Option<Flag>,
+    /// Stems only valid with an affix (`NEEDAFFIX`)
+    need_affix: Option<Flag>,
+    /// Stems only valid inside compounds (`ONLYINCOMPOUND`)
+    only_in_compound: Option<Flag>,
+    /// Stems only valid in the case they are written (`KEEPCASE`)
+    keep_case: Option<Flag>,
+}
+
+impl HunspellDictionary {
+    /// Read a dictionary from the text of its `.aff` and `.dic` files;
+    /// returns None if the word list is empty
+    pub fn parse(aff: &str, dic: &str) -> Option<Self> {
+        let mut dictionary = Self::default();
+        let mut flag_mode = FlagMode::Char;
+        // Flag sets numbered from 1 (`AF`), which the word list may use instead of flags
+        let mut aliases: Vec<Vec<Flag>> = Vec::new();
+        // Rules still to read under each affix header, by kind and flag
+        let mut pending: HashMap<(bool, Flag), (bool, usize)> = HashMap::new();
+
+        for line in aff.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (Some(&directive), Some(&argument)) = (fields.first(), fields.get(1)) else {
+                continue;
+            };
+            let flag = || flag_mode.parse(argument).first().copied();
+            match directive {
+                "FLAG" => {
+                    flag_mode = match argument {
+                        "long" => FlagMode::Long,
+                        "num" => FlagMode::Num,
+                        _ => FlagMode::Char,
+                    }
+                }
+                "TRY" => dictionary.try_chars = argument.chars().collect(),
+                "FORBIDDENWORD" => dictionary.forbidden = flag(),
+                "NEEDAFFIX" | "PSEUDOROOT" => dictionary.need_affix = flag(),
+                "ONLYINCOMPOUND" => dictionary.only_in_compound = flag(),
+                "KEEPCASE" => dictionary.keep_case = flag(),
+                // The first line of each table gives its length
+                "AF" if fields.len() == 2 && argument.parse::<usize>().is_err() => {
+                    aliases.push(flag_mode.parse(argument));
+                }
+                "REP" if fields.len() >= 3 => {
+                    let unescape = |text: &str| text.replace('_', " ");
+                    dictionary.replacements.push((unescape(argument), unescape(fields[2])));
+                }
+                "PFX" | "SFX" => {
+                    let prefix = directive == "PFX";
+                    let Some(flag) = flag() else {
+                        continue;
+                    };
+                    match pending.get_mut(&(prefix, flag)) {
+                        Some((cross_product, remaining)) if *remaining > 0 && fields.len() >= 4 => {
+                            *remaining -= 1;
+                            let text = |field: &str| if field == "0" { String::new() } else { field.to_string() };
+                            // Continuation flags after a slash (twofold affixes) are dropped
+                            let add = text(fields[3].split('/').next().unwrap_or(""));
+                            let affix = Affix {
+                                flag,
+                                cross_product: *cross_product,
+                                strip: text(fields[2]),
+                                condition: CharClass::parse_condition(fields.get(4).copied().unwrap_or(".")),
+                                add: add.clone(),
+                            };
+                            let len = add.chars().count();
+                            if prefix {
+                                dictionary.max_prefix = dictionary.max_prefix.max(len);
+                                dictionary.prefixes.entry(add).or_default().push(affix);
+                            } else {
+                                dictionary.max_suffix = dictionary.max_suffix.max(len);
+                                dictionary.suffixes.entry(add).or_default().push(affix);
+                            }
+                        }
+                        _ => {
+                            let cross_product = fields.get(2) == Some(&"Y");
+                            let count = fields.get(3).and_then(|n| n.parse().ok()).unwrap_or(0);
+                            pending.insert((prefix, flag), (cross_product, count));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut lines = dic.lines().map(str::trim_end).filter(|line| !line.is_empty());
+        // The word count comes first
+        let mut first = lines.next();
+        if first.is_some_and(|line| line.trim().parse::<usize>().is_ok()) {
+            first = None;
+        }
+        for line in first.into_iter().chain(lines) {
+            // Morphological fields follow a tab or space
+            let entry = line.split(['\t', ' ']).next().unwrap_or("");
+            let (word, flags) = split_entry(entry);
+            if word.is_empty() {
+                continue;
+            }
+            let mut flags = match flags {
+                Some(flags) if !aliases.is_empty() => {
+                    flags.parse::<usize>().ok().and_then(|n| aliases.get(n.wrapping_sub(1))).cloned().unwrap_or_default()
+                }
+                Some(flags) => flag_mode.parse(flags),
+                None => Vec::new(),
+            };
+            // Homonyms share one entry
+            let entry = dictionary.words.entry(word).or_default();
+            entry.append(&mut flags);
+            entry.sort_unstable();
+            entry.dedup();
+        }
+
+        if dictionary.try_chars.is_empty() {
+            dictionary.try_chars = dictionary.common_letters();
+        }
+        (!dictionary.words.is_empty()).then_some(dictionary)
+    }
+
+    /// Letters of the word list, most common first
+    fn common_letters(&self) -> Vec<char> {
+        let mut counts: HashMap<char, usize> = HashMap::new();
+        for c in self.words.keys().flat_map(|word| word.chars()).filter(|c| c.is_alphabetic()) {
+            *counts.entry(c.to_lowercase().next().unwrap_or(c)).or_default() += 1;
+        }
+        let mut letters: Vec<(char, usize)> = counts.into_iter().collect();
+        letters.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        letters.into_iter().map(|(c, _)| c).collect()
+    }
+
+    fn has_flag(flags: &[Flag], flag: Option<Flag>) -> bool {
+        flag.is_some_and(|flag| flags.binary_search(&flag).is_ok())
+    }
+
+    /// Flags of a stem, if it may take affixes in this case
+    fn stem_flags(&self, stem: &str, exact_case: bool) -> Option<&[Flag]> {
+        let flags = self.words.get(stem)?;
+        let usable = !Self::has_flag(flags, self.forbidden)
+            && !Self::has_flag(flags, self.only_in_compound)
+            && (exact_case || !Self::has_flag(flags, self.keep_case));
+        usable.then_some(flags.as_slice())
+    }
+
+    /// Whether a word is a stem, or a stem with affixes, in exactly this case
+    fn check_form(&self, word: &str, exact_case: bool) -> bool {
+        if let Some(flags) = self.words.get(word) {
+            if Self::has_flag(flags, self.forbidden) {
+                return false;
+            }
+            if self.stem_flags(word, exact_case).is_some_and(|flags| !Self::has_flag(flags, self.need_affix)) {
+                return true;
+            }
+        }
+        self.check_suffixed(word, exact_case, None) || self.check_prefixed(word, exact_case)
+    }
+
+    /// Whether a word is a stem with one suffix; `prefix` is the flag of a
+    /// prefix already removed, which the stem must also take
+    fn check_suffixed(&self, word: &str, exact_case: bool, prefix: Option<Flag>) -> bool {
+        let len = word.chars().count();
+        for (start, _) in word.char_indices().chain([(word.len(), ' ')]).skip(len.saturating_sub(self.max_suffix)) {
+            let Some(rules) = self.suffixes.get(&word[start..]) else {
+                continue;
+            };
+            for rule in rules.iter().filter(|rule| prefix.is_none() || rule.cross_product) {
+                let found = rule.stem(word, false).is_some_and(|stem| {
+                    self.stem_flags(&stem, exact_case).is_some_and(|flags| {
+                        Self::has_flag(flags, Some(rule.flag)) && prefix.is_none_or(|prefix| Self::has_flag(flags, Some(prefix)))
+                    })
+                });
+                if found {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Whether a word is a stem with one prefix, and perhaps a suffix
+    fn check_prefixed(&self, word: &str, exact_case: bool) -> bool {
+        for (end, _) in word.char_indices().chain([(word.len(), ' ')]).take(self.max_prefix + 1) {
+            let Some(rules) = self.prefixes.get(&word[..end]) else {
+                continue;
+            };
+            for rule in rules {
+                let Some(stem) = rule.stem(word, true) else {
+                    continue;
+                };
+                let stem_takes_rule =
+                    self.stem_flags(&stem, exact_case).is_some_and(|flags| Self::has_flag(flags, Some(rule.flag)));
+                if stem_takes_rule || rule.cross_product && self.check_suffixed(&stem, exact_case, Some(rule.flag)) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Whether every word of a candidate (which may contain spaces) is correct
+    fn check_all(&self, candidate: &str) -> bool {
+        candidate.split(' ').all(|word| !word.is_empty() && self.check(word))
+    }
+
+    /// Spellings one edit away from a lowercase word, likeliest first
+    fn edits(&self, word: &str) -> Vec<String> {
+        let chars: Vec<char> = word.chars().collect();
+        let join = |parts: &[&[char]]| parts.iter().flat_map(|part| part.iter()).collect::<String>();
+        let mut candidates = Vec::new();
+
+        for (from, to) in &self.replacements {
+            for (at, _) in word.match_indices(from.as_str()) {
+                candidates.push(format!("{}{}{}", &word[..at], to, &word[at + from.len()..]));
+            }
+        }
+        for i in 1..chars.len() {
+            let mut swapped = chars.clone();
+            swapped.swap(i - 1, i);
+            candidates.push(swapped.into_iter().collect());
+        }
+        for i in 0..chars.len() {
+            candidates.push(join(&[&chars[..i], &chars[i + 1..]]));
+        }
+        for i in 0..chars.len() {
+            for &c in self.try_chars.iter().filter(|&&c| c != chars[i]) {
+                candidates.push(join(&[&chars[..i], &[c], &chars[i + 1..]]));
+            }
+        }
+        for i in 0..=chars.len() {
+            for &c in &self.try_chars {
+                candidates.push(join(&[&chars[..i], &[c], &chars[i..]]));
+            }
+        }
+        for i in 1..chars.len() {
+            candidates.push(join(&[&chars[..i], &[' '], &chars[i..]]));
+        }
+        candidates
+    }
+}
+
+/// Split a word list entry into the word and its flags at the first
+/// unescaped slash
+fn split_entry(entry: &str) -> (String, Option<&str>) {
+    let mut word = String::new();
+    let mut chars = entry.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some((_, escaped)) = chars.next() {
+                    word.push(escaped);
+                }
+            }
+            '/' if !word.is_empty() => return (word, Some(&entry[i + 1..])),
+            c => word.push(c),
+        }
+    }
+    (word, None)
+}
+
+/// Case of a word, for checking and re-casing suggestions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Casing {
+    Lower,
+    /// First letter upper case, the rest lower case
+    Title,
+    /// Every letter upper case (with at least two letters)
+    Upper,
+    Mixed,
+}
+
+impl Casing {
+    fn of(word: &str) -> Self {
+        let mut letters = word.chars().filter(|c| c.is_alphabetic());
+        let Some(first) = letters.next() else {
+            return Casing::Lower;
+        };
+        let rest: Vec<char> = letters.collect();
+        let rest_lower = rest.iter().all(|c| !c.is_uppercase());
+        let rest_upper = rest.iter().all(|c| !c.is_lowercase());
+        match (first.is_uppercase(), rest_lower, rest_upper) {
+            (false, true, _) => Casing::Lower,
+            (true, true, _) => Casing::Title,
+            (true, false, true) => Casing::Upper,
+            _ => Casing::Mixed,
+        }
+    }
+
+    /// Apply to a lowercase word
+    fn apply(self, word: &str) -> String {
+        match self {
+            Casing::Title => title_case(word),
+            Casing::Upper => word.to_uppercase(),
+            Casing::Lower | Casing::Mixed => word.to_string(),
+        }
+    }
+}
+
+fn title_case(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map_or_else(String::new, |first| first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect())
+}
+
+impl Dictionary for HunspellDictionary {
+    /// Words are also accepted in title case or upper case when the
+    /// dictionary has them in lower case (or, upper case, in title case)
+    fn check(&self, word: &str) -> bool {
+        if self.check_form(word, true) {
+            return true;
+        }
+        match Casing::of(word) {
+            Casing::Title => self.check_form(&word.to_lowercase(), false),
+            Casing::Upper => self.check_form(&word.to_lowercase(), false) || self.check_form(&title_case(word), false),
+            Casing::Lower | Casing::Mixed => false,
+        }
+    }
+
+    fn suggest(&self, word: &str) -> Vec<String> {
+        let casing = Casing::of(word);
+        let lower = word.to_lowercase();
+        let mut suggestions: Vec<String> = Vec::new();
+        let push = |candidate: String, suggestions: &mut Vec<String>| {
+            if candidate != word && !suggestions.contains(&candidate) && suggestions.len() < MAX_SUGGESTIONS {
+                suggestions.push(candidate);
+            }
+        };
+
+        // Wrong case only: proper nouns typed in lower case
+        for candidate in [title_case(word), word.to_uppercase()] {
+            if self.check_form(&candidate, true) {
+                push(candidate, &mut suggestions);
+            }
+        }
+        for candidate in self.edits(&lower) {
+            if suggestions.len() >= MAX_SUGGESTIONS {
+                break;
+            }
+            // Keep the word's case, unless only the dictionary's capitals fit
+            let recased = casing.apply(&candidate);
+            if self.check_all(&recased) {
+                push(recased, &mut suggestions);
+            } else if casing == Casing::Lower && self.check_form(&title_case(&candidate), true) {
+                push(title_case(&candidate), &mut suggestions);
+            }
+        }
+        suggestions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AFF: &str = "SET UTF-8
+TRY esianrtolcdugmphbyfvkwz'
+FORBIDDENWORD !
+KEEPCASE K
+REP 1
+REP f ph
+
+PFX U Y 1
+PFX U 0 un .
+
+SFX S Y 3
+SFX S y ies [^aeiou]y
+SFX S 0 s [aeiou]y
+SFX S 0 s [^y]
+
+SFX D Y 4
+SFX D y ied [^aeiou]y
+SFX D 0 ed [aeiou]y
+SFX D 0 ed [^ey]
+SFX D 0 d e
+";
+
+    const DIC: &str = "7
+try/SD
+play/SDU
+happy/U
+Paris
+NASA/K
+phone/S
+colour/!
+";
+
+    fn dictionary() -> HunspellDictionary {
+        HunspellDictionary::parse(AFF, DIC).unwrap()
+    }
+
+    #[test]
+    fn test_affixes() {
+        let dictionary = dictionary();
+        for word in ["try", "tries", "tried", "plays", "played", "unplayed", "unplays", "unhappy", "phones"] {
+            assert!(dictionary.check(word), "{word}");
+        }
+        // Conditions pick the rule; rules only apply to stems with their flag
+        for word in ["trys", "plaies", "happys", "untry", "unphone"] {
+            assert!(!dictionary.check(word), "{word}");
+        }
+        assert!(!dictionary.check("colour"));
+    }
+
+    #[test]
+    fn test_case() {
+        let dictionary = dictionary();
+        assert!(dictionary.check("Tries"));
+        assert!(dictionary.check("TRIES"));
+        assert!(dictionary.check("PARIS"));
+        assert!(!dictionary.check("paris"));
+        assert!(dictionary.check("NASA"));
+        assert!(!dictionary.check("Nasa"));
+        assert!(!dictionary.check("tRies"));
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let dictionary = dictionary();
+        assert_eq!(dictionary.suggest("paris").first().map(String::as_str), Some("Paris"));
+        assert!(dictionary.suggest("fones").contains(&"phones".to_string()));
+        assert!(dictionary.suggest("palyed").contains(&"played".to_string()));
+        assert!(dictionary.suggest("Tryes").contains(&"Tries".to_string()));
+        assert!(dictionary.suggest("tryplay").contains(&"try play".to_string()));
+    }
+}
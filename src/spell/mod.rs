@@ -0,0 +1,259 @@
+//! Spell checking
+//!
+//! Misspelled words in the body text are marked with squiggle decorations.
+//! Words come from Unicode word segmentation, leaving out numbers, URLs and
+//! email addresses, and each is checked with the dictionary for the
+//! language of its text. Like the outline, the checker only looks again at
+//! the paragraphs edit results report, so typing stays cheap in long
+//! documents; changes reported without a result recheck everything.
+
+mod hunspell;
+
+pub use hunspell::HunspellDictionary;
+
+use crate::document::{BlockMeta, DecorationId, DecorationKind, Document, ParagraphId};
+use crate::editing::EditResult;
+use crate::layout::hyphenation::by_language;
+use regex::Regex;
+use rustc_hash::FxHashSet;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+use std::sync::OnceLock;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Color of the squiggles under misspelled words (0xRRGGBBAA)
+pub const MISSPELLING_COLOR: u32 = 0xE5_32_32_FF;
+
+/// A word list that checks spelling and suggests corrections
+pub trait Dictionary {
+    /// Whether a word is spelled correctly
+    fn check(&self, word: &str) -> bool;
+
+    /// Corrections for a misspelled word, best first
+    fn suggest(&self, word: &str) -> Vec<String>;
+}
+
+/// Finds misspelled words and keeps them marked in the document
+pub struct SpellChecker {
+    /// Dictionaries by lowercase language tag
+    dictionaries: HashMap<String, Box<dyn Dictionary>>,
+    /// Words the user added to their dictionary
+    added: BTreeSet<String>,
+    /// Words ignored everywhere until the editor is closed
+    ignored: FxHashSet<String>,
+    enabled: bool,
+    /// Paragraphs edited since they were checked
+    dirty: FxHashSet<ParagraphId>,
+    /// Whether every paragraph has been checked; cleared to check them all again
+    checked: bool,
+    /// Squiggles marking misspellings
+    marks: FxHashSet<DecorationId>,
+}
+
+impl Default for SpellChecker {
+    fn default() -> Self {
+        Self {
+            dictionaries: HashMap::new(),
+            added: BTreeSet::new(),
+            ignored: FxHashSet::default(),
+            enabled: true,
+            dirty: FxHashSet::default(),
+            checked: false,
+            marks: FxHashSet::default(),
+        }
+    }
+}
+
+impl SpellChecker {
+    /// Add or replace the dictionary for a language tag
+    pub fn add_dictionary(&mut self, language: &str, dictionary: Box<dyn Dictionary>) {
+        self.dictionaries.insert(language.to_ascii_lowercase(), dictionary);
+        self.checked = false;
+    }
+
+    /// Remove the dictionary for a language tag; returns false if it had none
+    pub fn remove_dictionary(&mut self, language: &str) -> bool {
+        self.checked = false;
+        self.dictionaries.remove(&language.to_ascii_lowercase()).is_some()
+    }
+
+    /// Turn checking on or off; off removes the squiggles
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if enabled != self.enabled {
+            self.enabled = enabled;
+            self.checked = false;
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Words added to the user's dictionary, in order (to save them)
+    pub fn added_words(&self) -> impl Iterator<Item = &str> {
+        self.added.iter().map(String::as_str)
+    }
+
+    /// Check the paragraphs touched by an edit again on the next `check`
+    pub fn update(&mut self, result: &EditResult) {
+        for para_id in &result.deleted_paragraphs {
+            self.dirty.remove(para_id);
+        }
+        self.dirty.extend(result.affected_paragraphs.iter().chain(&result.created_paragraphs).copied());
+    }
+
+    /// Check every paragraph again on the next `check`, after changes that
+    /// report no edit result (undo, redo, replacing the document)
+    pub fn invalidate(&mut self) {
+        self.checked = false;
+    }
+
+    /// Mark the misspellings of the paragraphs waiting to be checked
+    pub fn check(&mut self, document: &mut Document) {
+        if self.checked && self.dirty.is_empty() {
+            return;
+        }
+        let active = self.enabled && !self.dictionaries.is_empty();
+
+        let paragraphs: Vec<ParagraphId> = if !self.checked {
+            let decorations = document.decorations_mut();
+            for id in self.marks.drain() {
+                decorations.remove(id);
+            }
+            if active { document.paragraph_order().collect() } else { Vec::new() }
+        } else {
+            // Squiggles of deleted text are gone, and those of paragraphs
+            // merged into a dirty one are replaced with its own
+            let decorations = document.decorations_mut();
+            self.marks.retain(|&id| decorations.get(id).is_some());
+            let dirty: Vec<ParagraphId> = self.dirty.iter().copied().filter(|&id| document.block_meta(id).is_some()).collect();
+            for &para_id in &dirty {
+                let Some(range) = document.block_meta(para_id).map(|meta| meta.start_offset..meta.end_offset()) else {
+                    continue;
+                };
+                let stale: Vec<DecorationId> = document
+                    .decorations()
+                    .overlapping(range)
+                    .into_iter()
+                    .map(|(id, _)| id)
+                    .filter(|id| self.marks.contains(id))
+                    .collect();
+                for id in stale {
+                    document.decorations_mut().remove(id);
+                    self.marks.remove(&id);
+                }
+            }
+            if active { dirty } else { Vec::new() }
+        };
+
+        for para_id in paragraphs {
+            let Some(meta) = document.block_meta(para_id) else {
+                continue;
+            };
+            let start = meta.start_offset;
+            let misspelled = self.misspellings(meta, &document.paragraph_text(para_id));
+            for range in misspelled {
+                let range = start + range.start..start + range.end;
+                if let Some(id) = document.decorations_mut().add(range, DecorationKind::Squiggle, MISSPELLING_COLOR) {
+                    self.marks.insert(id);
+                }
+            }
+        }
+        self.dirty.clear();
+        self.checked = true;
+    }
+
+    /// Byte ranges of the misspelled words of a paragraph
+    pub fn misspellings(&self, meta: &BlockMeta, text: &str) -> Vec<Range<usize>> {
+        words(text)
+            .filter(|(start, word)| !self.is_correct(word, meta.language_at(*start)))
+            .map(|(start, word)| start..start + word.len())
+            .collect()
+    }
+
+    /// Whether a word is correct in a language; words in languages without
+    /// a dictionary always are
+    pub fn is_correct(&self, word: &str, language: Option<&str>) -> bool {
+        self.is_personal(word)
+            || by_language(&self.dictionaries, language).is_none_or(|dictionary| dictionary.check(&normalize(word)))
+    }
+
+    /// Whether a word was ignored or added to the user's dictionary (in
+    /// lower case, if it is capitalized)
+    fn is_personal(&self, word: &str) -> bool {
+        self.ignored.contains(word) || self.added.contains(word) || self.added.contains(&word.to_lowercase())
+    }
+
+    /// Corrections for a word in a language, best first; none if it is correct
+    pub fn suggestions(&self, word: &str, language: Option<&str>) -> Vec<String> {
+        if self.is_correct(word, language) {
+            return Vec::new();
+        }
+        by_language(&self.dictionaries, language).map_or_else(Vec::new, |dictionary| dictionary.suggest(&normalize(word)))
+    }
+
+    /// Add a word to the user's dictionary and unmark it
+    pub fn add_word(&mut self, document: &mut Document, word: &str) {
+        self.added.insert(word.to_string());
+        self.unmark(document);
+    }
+
+    /// Ignore a word everywhere and unmark it
+    pub fn ignore_word(&mut self, document: &mut Document, word: &str) {
+        self.ignored.insert(word.to_string());
+        self.unmark(document);
+    }
+
+    /// Remove the squiggles under words added or ignored since they were marked
+    fn unmark(&mut self, document: &mut Document) {
+        let unmarked: Vec<DecorationId> = self
+            .marks
+            .iter()
+            .copied()
+            .filter(|&id| {
+                document
+                    .decorations()
+                    .get(id)
+                    .is_some_and(|decoration| self.is_personal(&document.text_range(decoration.start..decoration.end)))
+            })
+            .collect();
+        for id in unmarked {
+            document.decorations_mut().remove(id);
+            self.marks.remove(&id);
+        }
+    }
+}
+
+/// Words of a paragraph with their byte offsets: word segments with a
+/// letter, leaving out those with digits and those within URLs or email
+/// addresses
+pub fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    static LINKS: OnceLock<Regex> = OnceLock::new();
+    let links = LINKS.get_or_init(|| {
+        Regex::new(r"(?i)\b(?:[a-z][a-z0-9+.-]*://|www\.|mailto:)\S+|[\w.+-]+@[\w-]+\.[\w.-]+").expect("valid pattern")
+    });
+    let skipped: Vec<Range<usize>> = links.find_iter(text).map(|m| m.range()).collect();
+
+    text.split_word_bound_indices().filter(move |&(start, word)| {
+        word.chars().any(char::is_alphabetic)
+            && !word.chars().any(char::is_numeric)
+            && !skipped.iter().any(|range| range.start < start + word.len() && start < range.end)
+    })
+}
+
+/// A word as dictionaries spell it: typographic apostrophes are plain
+fn normalize(word: &str) -> String {
+    word.replace('\u{2019}', "'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_words() {
+        let text = "Visit https://example.com/a_b or mail me@example.org, it’s 42 apples in 2024x!";
+        let found: Vec<&str> = words(text).map(|(_, word)| word).collect();
+        assert_eq!(found, vec!["Visit", "or", "mail", "it’s", "apples", "in"]);
+    }
+}
@@ -1,6 +1,6 @@
 //! Undo/Redo system with transaction support
 
-use crate::document::Document;
+use crate::document::{Document, StoryId};
 use crate::editing::{Cursor, EditOp, Selection};
 
 /// Result of an undo/redo operation
@@ -8,6 +8,8 @@ use crate::editing::{Cursor, EditOp, Selection};
 pub struct UndoResult {
     pub cursor: Cursor,
     pub selection: Option<Selection>,
+    /// Story the transaction was applied to
+    pub story: StoryId,
 }
 
 /// A single transaction that can be undone/redone
@@ -15,6 +17,8 @@ pub struct UndoResult {
 pub struct Transaction {
     /// Description of the operation
     pub description: String,
+    /// Story the operations apply to
+    pub story: StoryId,
     /// Forward operations
     pub forward_ops: Vec<EditOp>,
     /// Reverse operations (for undo)
@@ -36,6 +40,7 @@ impl Transaction {
     ) -> Self {
         Self {
             description: description.into(),
+            story: StoryId::Body,
             forward_ops: Vec::new(),
             reverse_ops: Vec::new(),
             cursor_before: cursor_before.clone(),
@@ -86,6 +91,20 @@ impl UndoManager {
         self.pending = Some(Transaction::new(description, cursor, selection));
     }
 
+    /// Begin a new transaction editing a secondary story
+    pub fn begin_story_transaction(
+        &mut self,
+        story: StoryId,
+        description: &str,
+        cursor: &Cursor,
+        selection: Option<&Selection>,
+    ) {
+        self.pending = Some(Transaction {
+            story,
+            ..Transaction::new(description, cursor, selection)
+        });
+    }
+
     /// Record an edit within the current transaction
     pub fn record_edit(&mut self, forward: EditOp, reverse: EditOp) {
         if let Some(ref mut txn) = self.pending {
@@ -153,6 +172,7 @@ impl UndoManager {
     /// Undo the last transaction
     pub fn undo(&mut self, document: &mut Document) -> Option<UndoResult> {
         let txn = self.undo_stack.pop()?;
+        let document = document.story_mut(txn.story);
 
         // Apply reverse operations
         for op in txn.reverse_ops.iter().rev() {
@@ -162,6 +182,7 @@ impl UndoManager {
         let result = UndoResult {
             cursor: txn.cursor_before.clone(),
             selection: txn.selection_before.clone(),
+            story: txn.story,
         };
 
         // Move to redo stack
@@ -173,6 +194,7 @@ impl UndoManager {
     /// Redo the last undone transaction
     pub fn redo(&mut self, document: &mut Document) -> Option<UndoResult> {
         let txn = self.redo_stack.pop()?;
+        let document = document.story_mut(txn.story);
 
         // Apply forward operations
         let mut final_cursor = txn.cursor_before.clone();
@@ -184,6 +206,7 @@ impl UndoManager {
         let result = UndoResult {
             cursor: final_cursor,
             selection: None,
+            story: txn.story,
         };
 
         // Move to undo stack
@@ -6,52 +6,98 @@
 //! ```text
 //! Header (offset table for random access):
 //! [0]     MAGIC (0x4D575244 = "MWRD" for validation)
-//! [1]     SCHEMA_VERSION (protocol version, currently 1)
+//! [1]     SCHEMA_VERSION (protocol version, currently 8)
 //! [2]     version_lo (document version)
 //! [3]     version_hi (document version)
 //! [4]     page_count
-//! [5]     cursor_present (0 or 1)
+//! [5]     caret_count (the cursor and any secondary carets)
 //! [6]     selection_count
 //! [7]     text_buffer_len
-//! [8]     u32_cursor_offset (index in u32_data where cursor indices start, 0 if no cursor)
+//! [8]     u32_cursor_offset (index in u32_data where caret indices start, 0 if no carets)
 //! [9]     u32_selection_offset (index in u32_data where selection indices start, 0 if no selections)
-//! [10]    f32_cursor_offset (index in f32_data where cursor geometry starts, 0 if no cursor)
+//! [10]    f32_cursor_offset (index in f32_data where caret geometries start, 0 if no carets)
 //! [11]    f32_selection_offset (index in f32_data where selection geometries start, 0 if no selections)
-//! [12..]  page data...
+//! [12]    box_count
+//! [13]    u32_box_offset (index in u32_data where box indices start, 0 if no boxes)
+//! [14]    f32_box_offset (index in f32_data where box geometries start, 0 if no boxes)
+//! [15]    decoration_count
+//! [16]    u32_decoration_offset (index in u32_data where decoration indices start, 0 if none)
+//! [17]    f32_decoration_offset (index in f32_data where decoration geometries start, 0 if none)
+//! [18..]  page data...
 //!
 //! Per-page:
 //!   page_index
 //!   line_count
 //!   per-line: [text_offset, text_len, text_utf16_offset, text_utf16_len, 
-//!              block_type, flags, marker_offset, marker_len, marker_utf16_offset, marker_utf16_len]
+//!              block_type, flags, marker_offset, marker_len, marker_utf16_offset, marker_utf16_len,
+//!              sel_start, sel_end, style_start_idx, style_count, link_start_idx, link_count,
+//!              glyph_start_idx, glyph_count]
 //!     text_offset/text_len: byte offsets in text_data (UTF-8)
 //!     text_utf16_offset/text_utf16_len: offsets for JS substring (after single decode)
-//!     flags: bit0=is_heading, bit1=is_list_item, bits2-4=heading_level
+//!     flags: bit0=is_heading, bit1=is_list_item, bits2-4=heading_level, bit5=is_rtl (paragraph base direction)
 //!     marker: only read if marker_len > 0, otherwise marker_offset is ignored
+//!     styles: style_count records [start, len, font_id] at style_start_idx in style_data
+//!     links: link_count records [start, len, link_key] at link_start_idx in style_data
+//!       (link_key is passed to getLinkHref/getLinkTitle)
+//!     glyphs: glyph_count records at glyph_start_idx in glyph_data (only when glyph
+//!       output is enabled and the line's fonts were registered from font data)
 //!
-//! At u32_cursor_offset (if cursor_present):
-//!   Cursor indices: [page_index, utf16_offset_in_line]
+//! At u32_cursor_offset (if caret_count > 0):
+//!   Per-caret indices: [page_index, utf16_offset_in_line, caret_flags] (caret_count times)
+//!     caret_flags: bit0=upstream (caret sits after the preceding grapheme rather than
+//!     before the following one), bit1=that grapheme is right-to-left,
+//!     bit2=secondary caret (not the cursor)
 //!
 //! At u32_selection_offset (if selection_count > 0):
 //!   Per-selection indices: [page_index] (selection_count times)
+//!     (highlights of secondary selections; the primary one is sel_start/sel_end)
+//!
+//! At u32_box_offset (if box_count > 0):
+//!   Per-box indices: [page_index, box_kind, box_data] (box_count times)
+//!     box_data for table cells: bit0=is_header, bit1=is_selected
+//!     box_data for images: object key (pass to getObjectUrl)
+//!     box_data for tabs: leader (0=none, 1=dots, 2=dashes, 3=underline)
+//!     box_data for column separators: unused (a vertical line at x, border_width wide)
+//!     box_data for search matches: bit0=is_current (the selected match)
+//!
+//! At u32_decoration_offset (if decoration_count > 0):
+//!   Per-decoration indices: [page_index, decoration_kind, color] (decoration_count times)
+//!     decoration_kind: 0=highlight (fill the bounds), 1=squiggle (wavy line along the bottom)
+//!     color: 0xRRGGBBAA
 //! ```
 //!
 //! ## f32 Buffer Layout:
 //! ```text
 //! Per-page: [y_offset, width, height]
 //! Per-line: [x, y]
-//! At f32_cursor_offset (if cursor_present): [x, y, height]
+//! At f32_cursor_offset (for each caret): [x, y, height] (caret_count times)
 //! At f32_selection_offset (for each selection): [x, y, width, height] (selection_count times)
+//! At f32_box_offset (for each box): [x, y, width, height, border_width] (box_count times)
+//! At f32_decoration_offset (for each decoration part): [x, y, width, height] (decoration_count times)
+//! ```
+//!
+//! ## Glyph Buffer Layout (f32):
+//! ```text
+//! Per-glyph: [font_id, glyph_id, x, y]
+//!   font_id/glyph_id: exact integers
+//!   x: from the line's x; y: offset from the baseline, positive upwards
 //! ```
 
+use crate::layout::PositionedGlyph;
+
 /// Magic number for format validation: "MWRD" (MiniWoRD)
 pub const MAGIC: u32 = 0x4D575244;
 
 /// Schema version for protocol compatibility checking
-pub const SCHEMA_VERSION: u32 = 1;
+///
+/// Raised whenever the layout changes, including every new block, box or
+/// decoration kind, so an older decoder refuses the buffer instead of
+/// misreading it. 8 added the image, tab, column separator and search match
+/// boxes.
+pub const SCHEMA_VERSION: u32 = 8;
 
 /// Header size in u32 elements
-pub const HEADER_SIZE: usize = 12;
+pub const HEADER_SIZE: usize = 18;
 
 /// Opcodes for block types
 pub const BLOCK_PARAGRAPH: u32 = 0;
@@ -62,25 +108,67 @@ pub const BLOCK_HEADING_4: u32 = 4;
 pub const BLOCK_HEADING_5: u32 = 5;
 pub const BLOCK_HEADING_6: u32 = 6;
 pub const BLOCK_LIST_ITEM: u32 = 7;
+pub const BLOCK_HEADER: u32 = 8;
+pub const BLOCK_FOOTER: u32 = 9;
+pub const BLOCK_FOOTNOTE: u32 = 10;
+pub const BLOCK_ENDNOTE: u32 = 11;
+pub const BLOCK_TABLE_CELL: u32 = 12;
+
+/// Box kinds
+pub const BOX_TABLE_CELL: u32 = 0;
+pub const BOX_IMAGE: u32 = 1;
+pub const BOX_TAB: u32 = 2;
+pub const BOX_COLUMN_SEPARATOR: u32 = 3;
+pub const BOX_SEARCH_MATCH: u32 = 4;
+
+/// Decoration kinds
+pub const DECORATION_HIGHLIGHT: u32 = 0;
+pub const DECORATION_SQUIGGLE: u32 = 1;
+
+/// Tab leaders
+pub const TAB_LEADER_NONE: u32 = 0;
+pub const TAB_LEADER_DOTS: u32 = 1;
+pub const TAB_LEADER_DASHES: u32 = 2;
+pub const TAB_LEADER_UNDERLINE: u32 = 3;
 
 /// Flags bitmask
 pub const FLAG_IS_HEADING: u32 = 0b0001;
 pub const FLAG_IS_LIST_ITEM: u32 = 0b0010;
+pub const FLAG_IS_RTL: u32 = 0b10_0000;
+
+/// Caret flags bitmask
+pub const CARET_FLAG_UPSTREAM: u32 = 0b0001;
+pub const CARET_FLAG_RTL: u32 = 0b0010;
+pub const CARET_FLAG_SECONDARY: u32 = 0b0100;
+
+/// Box flags bitmask
+pub const BOX_FLAG_HEADER: u32 = 0b0001;
+pub const BOX_FLAG_SELECTED: u32 = 0b0010;
+pub const BOX_FLAG_CURRENT: u32 = 0b0001;
 
 /// Number of u32 values per line in the buffer
 /// [text_offset, text_len, text_utf16_offset, text_utf16_len, 
 ///  block_type, flags, marker_offset, marker_len, marker_utf16_offset, marker_utf16_len,
-///  sel_start, sel_end, style_start_idx, style_count]
-pub const U32_PER_LINE: usize = 14;
+///  sel_start, sel_end, style_start_idx, style_count, link_start_idx, link_count,
+///  glyph_start_idx, glyph_count]
+pub const U32_PER_LINE: usize = 18;
 
 /// Number of u32 values per style span
 /// [start, len, font_id]
 pub const U32_PER_STYLE: usize = 3;
 
-/// Number of u32 values for cursor indices
-pub const U32_PER_CURSOR: usize = 2; // page_index, utf16_offset_in_line
+/// Number of u32 values per link span
+/// [start, len, link_key]
+pub const U32_PER_LINK: usize = 3;
+
+/// Number of f32 values per glyph
+/// [font_id, glyph_id, x, y]
+pub const F32_PER_GLYPH: usize = 4;
+
+/// Number of u32 values per caret's indices
+pub const U32_PER_CURSOR: usize = 3; // page_index, utf16_offset_in_line, caret_flags
 
-/// Number of f32 values for cursor geometry
+/// Number of f32 values per caret's geometry
 pub const F32_PER_CURSOR: usize = 3; // x, y, height
 
 /// Number of u32 values per selection
@@ -89,13 +177,26 @@ pub const U32_PER_SELECTION: usize = 1; // page_index
 /// Number of f32 values per selection geometry
 pub const F32_PER_SELECTION: usize = 4; // x, y, width, height
 
-/// Pending cursor data (written to buffers in finalize())
+/// Number of u32 values per box
+pub const U32_PER_BOX: usize = 3; // page_index, box_kind, box_data
+
+/// Number of f32 values per box geometry
+pub const F32_PER_BOX: usize = 5; // x, y, width, height, border_width
+
+/// Number of u32 values per decoration
+pub const U32_PER_DECORATION: usize = 3; // page_index, decoration_kind, color
+
+/// Number of f32 values per decoration geometry
+pub const F32_PER_DECORATION: usize = 4; // x, y, width, height
+
+/// Pending caret data (written to buffers in finalize())
 struct PendingCursor {
     x: f32,
     y: f32,
     height: f32,
     page_index: usize,
     utf16_offset_in_line: usize,
+    caret_flags: u32,
 }
 
 /// Pending selection data (written to buffers in finalize())
@@ -107,6 +208,56 @@ struct PendingSelection {
     page_index: usize,
 }
 
+/// Pending box data (written to buffers in finalize())
+struct PendingBox {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    border_width: f32,
+    page_index: usize,
+    kind: u32,
+    data: u32,
+}
+
+/// Pending decoration data (written to buffers in finalize())
+struct PendingDecoration {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    page_index: usize,
+    kind: u32,
+    color: u32,
+}
+
+/// One text line as passed to `RenderBuffer::write_line`
+///
+/// `LineRecord::new` gives a plain paragraph line; the other fields are set
+/// with struct update syntax.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LineRecord<'a> {
+    pub x: f32,
+    pub y: f32,
+    pub text: &'a str,
+    pub block_type: u32,
+    pub flags: u32,
+    /// List marker, heading number or note label drawn before the text
+    pub list_marker: Option<&'a str>,
+    pub selection_range: Option<(usize, usize)>,
+    /// (start, len, font_id)
+    pub styles: &'a [(usize, usize, u32)],
+    /// (start, len, link_key)
+    pub links: &'a [(usize, usize, u32)],
+}
+
+impl<'a> LineRecord<'a> {
+    /// A paragraph line with no marker, selection, styles or links
+    pub fn new(x: f32, y: f32, text: &'a str) -> Self {
+        Self { x, y, text, ..Self::default() }
+    }
+}
+
 /// Render buffer for zero-copy WASM transfer
 pub struct RenderBuffer {
     /// Integer data (indices, counts, offsets, opcodes)
@@ -117,10 +268,14 @@ pub struct RenderBuffer {
     pub text_data: Vec<u8>,
     /// Style data buffer (flat list of style spans)
     pub style_data: Vec<u32>,
+    /// Glyph data buffer (positioned glyphs of lines)
+    pub glyph_data: Vec<f32>,
     
-    // Pending cursor/selections (written in finalize() to guarantee correct offsets)
-    pending_cursor: Option<PendingCursor>,
+    // Pending carets/selections (written in finalize() to guarantee correct offsets)
+    pending_carets: Vec<PendingCursor>,
     pending_selections: Vec<PendingSelection>,
+    pending_boxes: Vec<PendingBox>,
+    pending_decorations: Vec<PendingDecoration>,
     
     // Track cumulative UTF-16 offset for efficient JS decoding
     utf16_text_offset: usize,
@@ -139,8 +294,11 @@ impl RenderBuffer {
             f32_data: Vec::with_capacity(1024),
             text_data: Vec::with_capacity(4096),
             style_data: Vec::with_capacity(512),
-            pending_cursor: None,
+            glyph_data: Vec::new(),
+            pending_carets: Vec::new(),
             pending_selections: Vec::new(),
+            pending_boxes: Vec::new(),
+            pending_decorations: Vec::new(),
             utf16_text_offset: 0,
         }
     }
@@ -150,8 +308,11 @@ impl RenderBuffer {
         self.f32_data.clear();
         self.text_data.clear();
         self.style_data.clear();
-        self.pending_cursor = None;
+        self.glyph_data.clear();
+        self.pending_carets.clear();
         self.pending_selections.clear();
+        self.pending_boxes.clear();
+        self.pending_decorations.clear();
         self.utf16_text_offset = 0;
     }
 
@@ -159,10 +320,11 @@ impl RenderBuffer {
     /// Critical: JS holds pointers to these buffers, so realloc would cause invalid pointers.
     /// 
     /// Call this before write_header() with estimated sizes:
-    /// - u32_needed: HEADER_SIZE + pages * (2 + lines * U32_PER_LINE) + cursor (U32_PER_CURSOR) + selections * U32_PER_SELECTION
-    /// - f32_needed: pages * 3 + lines * 2 + cursor (F32_PER_CURSOR) + selections * F32_PER_SELECTION
+    /// - u32_needed: HEADER_SIZE + pages * (2 + lines * U32_PER_LINE) + carets * U32_PER_CURSOR + selections * U32_PER_SELECTION + boxes * U32_PER_BOX + decorations * U32_PER_DECORATION
+    /// - f32_needed: pages * 3 + lines * 2 + carets * F32_PER_CURSOR + selections * F32_PER_SELECTION + boxes * F32_PER_BOX + decorations * F32_PER_DECORATION
     /// - text_needed: sum of text bytes + marker bytes
-    pub fn prepare(&mut self, u32_needed: usize, f32_needed: usize, text_needed: usize) {
+    /// - glyph_needed: glyphs * F32_PER_GLYPH (0 unless glyphs are written)
+    pub fn prepare(&mut self, u32_needed: usize, f32_needed: usize, text_needed: usize, glyph_needed: usize) {
         // Target capacities with headroom
         let u32_target = u32_needed + 32;
         let f32_target = f32_needed + 32;
@@ -196,10 +358,18 @@ impl RenderBuffer {
         } else {
              self.style_data.clear();
         }
+
+        if self.glyph_data.capacity() < glyph_needed {
+            self.glyph_data = Vec::with_capacity(glyph_needed);
+        } else {
+            self.glyph_data.clear();
+        }
         
         // Clear pending data
-        self.pending_cursor = None;
+        self.pending_carets.clear();
         self.pending_selections.clear();
+        self.pending_boxes.clear();
+        self.pending_decorations.clear();
         self.utf16_text_offset = 0;
     }
 
@@ -210,39 +380,48 @@ impl RenderBuffer {
         self.u32_data.push((version & 0xFFFFFFFF) as u32); // [2] version_lo (document version)
         self.u32_data.push((version >> 32) as u32);        // [3] version_hi (document version)
         self.u32_data.push(page_count);                    // [4] page_count
-        self.u32_data.push(0);                             // [5] cursor_present (placeholder)
+        self.u32_data.push(0);                             // [5] caret_count (placeholder)
         self.u32_data.push(0);                             // [6] selection_count (placeholder)
         self.u32_data.push(0);                             // [7] text_buffer_len (placeholder)
         self.u32_data.push(0);                             // [8] u32_cursor_offset (placeholder)
         self.u32_data.push(0);                             // [9] u32_selection_offset (placeholder)
         self.u32_data.push(0);                             // [10] f32_cursor_offset (placeholder)
         self.u32_data.push(0);                             // [11] f32_selection_offset (placeholder)
+        self.u32_data.push(0);                             // [12] box_count (placeholder)
+        self.u32_data.push(0);                             // [13] u32_box_offset (placeholder)
+        self.u32_data.push(0);                             // [14] f32_box_offset (placeholder)
+        self.u32_data.push(0);                             // [15] decoration_count (placeholder)
+        self.u32_data.push(0);                             // [16] u32_decoration_offset (placeholder)
+        self.u32_data.push(0);                             // [17] f32_decoration_offset (placeholder)
     }
 
-    /// Finalize buffer: write pending cursor/selections and synchronize header
+    /// Finalize buffer: write pending carets/selections and synchronize header
     /// CRITICAL: Must be called after all page/line operations to ensure correct offsets
     pub fn finalize(&mut self) {
         if self.u32_data.len() < HEADER_SIZE {
             return;
         }
         
-        // Write pending cursor (if present) AFTER all pages/lines
-        if let Some(cursor) = &self.pending_cursor {
-            // Record cursor offsets in header (indices 8 and 10)
+        // Write pending carets (if any) AFTER all pages/lines
+        if !self.pending_carets.is_empty() {
+            // Record caret offsets in header (indices 8 and 10)
             self.u32_data[8] = self.u32_data.len() as u32;   // u32 offset
             self.u32_data[10] = self.f32_data.len() as u32;  // f32 offset
             
-            // Write cursor indices to u32_data
-            self.u32_data.push(cursor.page_index as u32);
-            self.u32_data.push(cursor.utf16_offset_in_line as u32);
-            
-            // Write cursor geometry to f32_data
-            self.f32_data.push(cursor.x);
-            self.f32_data.push(cursor.y);
-            self.f32_data.push(cursor.height);
+            for cursor in &self.pending_carets {
+                // Write caret indices to u32_data
+                self.u32_data.push(cursor.page_index as u32);
+                self.u32_data.push(cursor.utf16_offset_in_line as u32);
+                self.u32_data.push(cursor.caret_flags);
+                
+                // Write caret geometry to f32_data
+                self.f32_data.push(cursor.x);
+                self.f32_data.push(cursor.y);
+                self.f32_data.push(cursor.height);
+            }
             
-            // Set cursor_present flag
-            self.u32_data[5] = 1;
+            // Set caret count
+            self.u32_data[5] = self.pending_carets.len() as u32;
         } else {
             self.u32_data[5] = 0;
             self.u32_data[10] = 0;
@@ -271,6 +450,53 @@ impl RenderBuffer {
             self.u32_data[6] = 0;
             self.u32_data[11] = 0;
         }
+
+        // Write pending boxes (if any) AFTER selections
+        if !self.pending_boxes.is_empty() {
+            // Record box offsets in header (indices 13 and 14)
+            self.u32_data[13] = self.u32_data.len() as u32;  // u32 offset
+            self.u32_data[14] = self.f32_data.len() as u32;  // f32 offset
+
+            for b in &self.pending_boxes {
+                self.u32_data.push(b.page_index as u32);
+                self.u32_data.push(b.kind);
+                self.u32_data.push(b.data);
+
+                self.f32_data.push(b.x);
+                self.f32_data.push(b.y);
+                self.f32_data.push(b.width);
+                self.f32_data.push(b.height);
+                self.f32_data.push(b.border_width);
+            }
+
+            self.u32_data[12] = self.pending_boxes.len() as u32;
+        } else {
+            self.u32_data[12] = 0;
+            self.u32_data[14] = 0;
+        }
+
+        // Write pending decorations (if any) AFTER boxes
+        if !self.pending_decorations.is_empty() {
+            // Record decoration offsets in header (indices 16 and 17)
+            self.u32_data[16] = self.u32_data.len() as u32;  // u32 offset
+            self.u32_data[17] = self.f32_data.len() as u32;  // f32 offset
+
+            for d in &self.pending_decorations {
+                self.u32_data.push(d.page_index as u32);
+                self.u32_data.push(d.kind);
+                self.u32_data.push(d.color);
+
+                self.f32_data.push(d.x);
+                self.f32_data.push(d.y);
+                self.f32_data.push(d.width);
+                self.f32_data.push(d.height);
+            }
+
+            self.u32_data[15] = self.pending_decorations.len() as u32;
+        } else {
+            self.u32_data[15] = 0;
+            self.u32_data[17] = 0;
+        }
         
         // Sync text buffer length
         self.u32_data[7] = self.text_data.len() as u32;
@@ -348,17 +574,9 @@ impl RenderBuffer {
     }
 
     /// Write a text line
-    pub fn write_line(
-        &mut self,
-        x: f32,
-        y: f32,
-        text: &str,
-        block_type: u32,
-        flags: u32,
-        list_marker: Option<&str>,
-        selection_range: Option<(usize, usize)>,
-        styles: &[(usize, usize, u32)], // (start, len, font_id)
-    ) {
+    pub fn write_line(&mut self, line: &LineRecord) {
+        let &LineRecord { x, y, text, block_type, flags, list_marker, selection_range, styles, links } = line;
+
         // Write text to buffer and record offset
         let text_offset = self.text_data.len() as u32;
         self.text_data.extend_from_slice(text.as_bytes());
@@ -412,9 +630,20 @@ impl RenderBuffer {
             self.style_data.push(font_id);
         }
 
+        // Write links (after the styles, in the same buffer)
+        let link_start_idx = self.style_data.len() as u32;
+        let link_count = links.len() as u32;
+
+        for &(start, len, link_key) in links {
+            self.style_data.push(start as u32);
+            self.style_data.push(len as u32);
+            self.style_data.push(link_key);
+        }
+
         // u32: text_offset, text_len, text_utf16_offset, text_utf16_len,
         //      block_type, flags, marker_offset, marker_len, marker_utf16_offset, marker_utf16_len,
-        //      sel_start, sel_end, style_start_idx, style_count
+        //      sel_start, sel_end, style_start_idx, style_count, link_start_idx, link_count,
+        //      glyph_start_idx, glyph_count
         self.u32_data.push(text_offset);
         self.u32_data.push(text_len);
         self.u32_data.push(text_utf16_offset);
@@ -429,21 +658,40 @@ impl RenderBuffer {
         self.u32_data.push(sel_end);
         self.u32_data.push(style_start_idx);
         self.u32_data.push(style_count);
+        self.u32_data.push(link_start_idx);
+        self.u32_data.push(link_count);
+        self.u32_data.push(self.glyph_data.len() as u32);
+        self.u32_data.push(0); // glyph_count (set by write_glyphs)
 
         // f32: x, y
         self.f32_data.push(x);
         self.f32_data.push(y);
     }
 
-    /// Set pending cursor data (will be written to buffers in finalize())
-    /// This ensures cursor offset is always correct, regardless of call order
-    pub fn write_cursor(&mut self, x: f32, y: f32, height: f32, page_index: usize, utf16_offset_in_line: usize) {
-        self.pending_cursor = Some(PendingCursor {
+    /// Write the glyphs of the line just written by write_line()
+    pub fn write_glyphs(&mut self, glyphs: &[PositionedGlyph]) {
+        let Some(count_idx) = self.u32_data.len().checked_sub(1) else {
+            return;
+        };
+        for glyph in glyphs {
+            self.glyph_data.push(glyph.font_id.0 as f32);
+            self.glyph_data.push(f32::from(glyph.glyph_id));
+            self.glyph_data.push(glyph.x);
+            self.glyph_data.push(glyph.y);
+        }
+        self.u32_data[count_idx] += glyphs.len() as u32;
+    }
+
+    /// Add pending caret data (will be written to buffers in finalize())
+    /// This ensures caret offsets are always correct, regardless of call order
+    pub fn write_cursor(&mut self, x: f32, y: f32, height: f32, page_index: usize, utf16_offset_in_line: usize, caret_flags: u32) {
+        self.pending_carets.push(PendingCursor {
             x,
             y,
             height,
             page_index,
             utf16_offset_in_line,
+            caret_flags,
         });
     }
 
@@ -459,6 +707,54 @@ impl RenderBuffer {
         });
     }
 
+    /// Add pending box (will be written to buffers in finalize())
+    #[allow(clippy::too_many_arguments)]
+    pub fn write_box(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        border_width: f32,
+        page_index: usize,
+        kind: u32,
+        data: u32,
+    ) {
+        self.pending_boxes.push(PendingBox {
+            x,
+            y,
+            width,
+            height,
+            border_width,
+            page_index,
+            kind,
+            data,
+        });
+    }
+
+    /// Add pending decoration part (will be written to buffers in finalize())
+    #[allow(clippy::too_many_arguments)]
+    pub fn write_decoration(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        page_index: usize,
+        kind: u32,
+        color: u32,
+    ) {
+        self.pending_decorations.push(PendingDecoration {
+            x,
+            y,
+            width,
+            height,
+            page_index,
+            kind,
+            color,
+        });
+    }
+
     // Accessors for WASM
     // Return u32 instead of usize for explicit WASM contract (wasm32 linear memory uses u32 offsets)
 
@@ -493,6 +789,14 @@ impl RenderBuffer {
     pub fn style_len(&self) -> u32 {
         self.style_data.len() as u32
     }
+
+    pub fn glyph_ptr(&self) -> u32 {
+        self.glyph_data.as_ptr() as u32
+    }
+
+    pub fn glyph_len(&self) -> u32 {
+        self.glyph_data.len() as u32
+    }
 }
 
 /// Convert BlockKind to block type opcode
@@ -514,6 +818,7 @@ pub fn block_kind_to_opcode(kind: &crate::document::BlockKind) -> (u32, u32) {
             (opcode, FLAG_IS_HEADING | ((*level as u32) << 2))
         }
         BlockKind::ListItem { .. } => (BLOCK_LIST_ITEM, FLAG_IS_LIST_ITEM),
+        BlockKind::Table { .. } | BlockKind::TocEntry { .. } => (BLOCK_PARAGRAPH, 0),
     }
 }
 
@@ -527,7 +832,7 @@ mod tests {
         buf.write_header(42, 1);
         
         let line_idx = buf.begin_page(0, 0.0, 816.0, 1056.0);
-        buf.write_line(96.0, 96.0, "Hello", BLOCK_PARAGRAPH, 0, None, None, &[]);
+        buf.write_line(&LineRecord::new(96.0, 96.0, "Hello"));
         buf.set_line_count(line_idx, 1);
         buf.finalize();
 
@@ -542,10 +847,10 @@ mod tests {
     fn test_render_buffer_with_cursor() {
         let mut buf = RenderBuffer::new();
         buf.write_header(1, 1);
-        buf.write_cursor(100.0, 200.0, 20.0, 0, 5); // page 0, utf16 offset 5
+        buf.write_cursor(100.0, 200.0, 20.0, 0, 5, CARET_FLAG_UPSTREAM); // page 0, utf16 offset 5
         buf.finalize();
 
-        assert_eq!(buf.u32_data[5], 1); // cursor_present
+        assert_eq!(buf.u32_data[5], 1); // caret_count
         
         // Check offset table points to cursor data
         let cursor_offset = buf.u32_data[8] as usize;
@@ -554,6 +859,7 @@ mod tests {
         // Cursor indices at offset
         assert_eq!(buf.u32_data[cursor_offset], 0); // page_index
         assert_eq!(buf.u32_data[cursor_offset + 1], 5); // utf16_offset_in_line
+        assert_eq!(buf.u32_data[cursor_offset + 2], CARET_FLAG_UPSTREAM); // caret_flags
         
         // f32 data should have cursor geometry: x, y, height
         assert_eq!(buf.f32_data[0], 100.0); // x
@@ -561,6 +867,26 @@ mod tests {
         assert_eq!(buf.f32_data[2], 20.0);  // height
     }
 
+    #[test]
+    fn test_render_buffer_with_secondary_carets() {
+        let mut buf = RenderBuffer::new();
+        buf.write_header(1, 1);
+        buf.write_cursor(100.0, 200.0, 20.0, 0, 5, 0);
+        buf.write_cursor(40.0, 220.0, 20.0, 0, 2, CARET_FLAG_SECONDARY);
+        buf.finalize();
+
+        assert_eq!(buf.u32_data[5], 2); // caret_count
+
+        // Carets follow each other in both buffers
+        let u32_offset = buf.u32_data[8] as usize;
+        let f32_offset = buf.u32_data[10] as usize;
+        assert_eq!(buf.u32_data[u32_offset + 2], 0);
+        assert_eq!(buf.u32_data[u32_offset + U32_PER_CURSOR + 1], 2);
+        assert_eq!(buf.u32_data[u32_offset + U32_PER_CURSOR + 2], CARET_FLAG_SECONDARY);
+        assert_eq!(buf.f32_data[f32_offset + F32_PER_CURSOR], 40.0);
+        assert_eq!(buf.f32_data[f32_offset + F32_PER_CURSOR + 1], 220.0);
+    }
+
     #[test]
     fn test_render_buffer_with_selections() {
         let mut buf = RenderBuffer::new();
@@ -594,6 +920,93 @@ mod tests {
         assert_eq!(buf.f32_data[7], 15.0);  // height
     }
 
+    #[test]
+    fn test_line_links_follow_styles() {
+        let mut buf = RenderBuffer::new();
+        buf.write_header(1, 1);
+        let line_idx = buf.begin_page(0, 0.0, 816.0, 1056.0);
+        buf.write_line(&LineRecord {
+            styles: &[(0, 3, 1)],
+            links: &[(4, 4, 0)],
+            ..LineRecord::new(96.0, 96.0, "See docs")
+        });
+        buf.set_line_count(line_idx, 1);
+        buf.finalize();
+
+        let line = HEADER_SIZE + 2;
+        let (style_start, style_count) = (buf.u32_data[line + 12], buf.u32_data[line + 13]);
+        let (link_start, link_count) = (buf.u32_data[line + 14], buf.u32_data[line + 15]);
+        assert_eq!((style_start, style_count), (0, 1));
+        assert_eq!((link_start as usize, link_count), (U32_PER_STYLE, 1));
+        assert_eq!(&buf.style_data[link_start as usize..][..U32_PER_LINK], &[4, 4, 0]);
+    }
+
+    #[test]
+    fn test_line_glyphs() {
+        let mut buf = RenderBuffer::new();
+        buf.write_header(1, 1);
+        let line_idx = buf.begin_page(0, 0.0, 816.0, 1056.0);
+        buf.write_line(&LineRecord::new(96.0, 96.0, "A"));
+        buf.write_line(&LineRecord::new(96.0, 120.0, "AV"));
+        buf.write_glyphs(&[
+            PositionedGlyph { font_id: crate::layout::font::FontId(2), glyph_id: 1, x: 0.0, y: 0.0 },
+            PositionedGlyph { font_id: crate::layout::font::FontId(2), glyph_id: 2, x: 10.4, y: -1.0 },
+        ]);
+        buf.set_line_count(line_idx, 2);
+        buf.finalize();
+
+        // The first line has no glyphs; the second points at both records
+        let first = HEADER_SIZE + 2;
+        let second = first + U32_PER_LINE;
+        assert_eq!(&buf.u32_data[first + 16..first + 18], &[0, 0]);
+        assert_eq!(&buf.u32_data[second + 16..second + 18], &[0, 2]);
+        assert_eq!(&buf.glyph_data[F32_PER_GLYPH..], &[2.0, 2.0, 10.4, -1.0]);
+    }
+
+    #[test]
+    fn test_render_buffer_with_boxes() {
+        let mut buf = RenderBuffer::new();
+        buf.write_header(1, 1);
+
+        buf.write_selection(10.0, 20.0, 100.0, 15.0, 0);
+        buf.write_box(96.0, 96.0, 200.0, 30.0, 1.0, 0, BOX_TABLE_CELL, BOX_FLAG_HEADER);
+        buf.finalize();
+
+        assert_eq!(buf.u32_data[12], 1); // box_count
+
+        // Boxes follow the selections
+        let box_offset = buf.u32_data[13] as usize;
+        assert_eq!(box_offset, HEADER_SIZE + U32_PER_SELECTION);
+        assert_eq!(buf.u32_data[box_offset], 0); // page_index
+        assert_eq!(buf.u32_data[box_offset + 1], BOX_TABLE_CELL);
+        assert_eq!(buf.u32_data[box_offset + 2], BOX_FLAG_HEADER);
+
+        let f32_box_offset = buf.u32_data[14] as usize;
+        assert_eq!(f32_box_offset, F32_PER_SELECTION);
+        assert_eq!(&buf.f32_data[f32_box_offset..f32_box_offset + F32_PER_BOX], &[96.0, 96.0, 200.0, 30.0, 1.0]);
+    }
+
+    #[test]
+    fn test_render_buffer_with_decorations() {
+        let mut buf = RenderBuffer::new();
+        buf.write_header(1, 1);
+
+        buf.write_box(96.0, 96.0, 200.0, 30.0, 1.0, 0, BOX_TAB, TAB_LEADER_DOTS);
+        buf.write_decoration(100.0, 96.0, 40.0, 18.0, 0, DECORATION_SQUIGGLE, 0xff0000ff);
+        buf.finalize();
+
+        assert_eq!(buf.u32_data[15], 1); // decoration_count
+
+        // Decorations follow the boxes
+        let offset = buf.u32_data[16] as usize;
+        assert_eq!(offset, HEADER_SIZE + U32_PER_BOX);
+        assert_eq!(&buf.u32_data[offset..offset + U32_PER_DECORATION], &[0, DECORATION_SQUIGGLE, 0xff0000ff]);
+
+        let f32_offset = buf.u32_data[17] as usize;
+        assert_eq!(f32_offset, F32_PER_BOX);
+        assert_eq!(&buf.f32_data[f32_offset..f32_offset + F32_PER_DECORATION], &[100.0, 96.0, 40.0, 18.0]);
+    }
+
     #[test]
     fn test_prepare_prevents_reallocation() {
         let mut buf = RenderBuffer::new();
@@ -611,7 +1024,7 @@ mod tests {
                          cursor_count * F32_PER_CURSOR + selection_count * F32_PER_SELECTION;
         let text_needed = line_count * avg_text_len;
         
-        buf.prepare(u32_needed, f32_needed, text_needed);
+        buf.prepare(u32_needed, f32_needed, text_needed, 0);
         
         // Capture initial capacities
         let u32_capacity = buf.u32_data.capacity();
@@ -626,14 +1039,14 @@ mod tests {
             let line_idx = buf.begin_page(p, 0.0, 816.0, 1056.0);
             
             for _ in 0..50 {
-                buf.write_line(96.0, 96.0, "Hello, World! This is a test line with some text.", BLOCK_PARAGRAPH, 0, None, None, &[]);
+                buf.write_line(&LineRecord::new(96.0, 96.0, "Hello, World! This is a test line with some text."));
             }
             
             buf.set_line_count(line_idx, 50);
         }
         
         // Write cursor and selections (count is automatic)
-        buf.write_cursor(100.0, 200.0, 20.0, 0, 5);
+        buf.write_cursor(100.0, 200.0, 20.0, 0, 5, 0);
         for i in 0..selection_count {
             buf.write_selection(10.0, 20.0, 100.0, 15.0, i);
         }
@@ -673,7 +1086,7 @@ mod tests {
         buf.write_header(1, 1);
         
         // Write cursor WITHOUT manually calling set_cursor_present
-        buf.write_cursor(100.0, 200.0, 20.0, 0, 5);
+        buf.write_cursor(100.0, 200.0, 20.0, 0, 5, 0);
         
         buf.finalize();
         
@@ -693,17 +1106,17 @@ mod tests {
         buf.write_header(1, 2);
         
         // Call write_cursor EARLY (before pages) - this was the bug scenario!
-        buf.write_cursor(100.0, 200.0, 20.0, 0, 5);
+        buf.write_cursor(100.0, 200.0, 20.0, 0, 5, 0);
         buf.write_selection(10.0, 20.0, 100.0, 15.0, 1);
         
         // Now write pages AFTER cursor/selection
         let line_idx = buf.begin_page(0, 0.0, 816.0, 1056.0);
-        buf.write_line(96.0, 96.0, "First page line 1", BLOCK_PARAGRAPH, 0, None, None, &[]);
-        buf.write_line(96.0, 120.0, "First page line 2", BLOCK_PARAGRAPH, 0, None, None, &[]);
+        buf.write_line(&LineRecord::new(96.0, 96.0, "First page line 1"));
+        buf.write_line(&LineRecord::new(96.0, 120.0, "First page line 2"));
         buf.set_line_count(line_idx, 2);
         
         let line_idx = buf.begin_page(1, 1056.0, 816.0, 1056.0);
-        buf.write_line(96.0, 1152.0, "Second page line 1", BLOCK_PARAGRAPH, 0, None, None, &[]);
+        buf.write_line(&LineRecord::new(96.0, 1152.0, "Second page line 1"));
         buf.set_line_count(line_idx, 1);
         
         buf.finalize();
@@ -732,19 +1145,19 @@ mod tests {
         buf.write_header(1, 2);
         
         // Write cursor and selection BEFORE pages
-        buf.write_cursor(150.0, 250.0, 18.0, 0, 10);
+        buf.write_cursor(150.0, 250.0, 18.0, 0, 10, 0);
         buf.write_selection(30.0, 40.0, 200.0, 20.0, 1);
         
         // Write pages with multiple lines (each line adds 2 f32 values)
         let line_idx = buf.begin_page(0, 0.0, 816.0, 1056.0);
         for _ in 0..5 {
-            buf.write_line(96.0, 100.0, "Line with text", BLOCK_PARAGRAPH, 0, None, None, &[]);
+            buf.write_line(&LineRecord::new(96.0, 100.0, "Line with text"));
         }
         buf.set_line_count(line_idx, 5);
         
         let line_idx = buf.begin_page(1, 1056.0, 816.0, 1056.0);
         for _ in 0..3 {
-            buf.write_line(96.0, 1100.0, "Another line", BLOCK_PARAGRAPH, 0, None, None, &[]);
+            buf.write_line(&LineRecord::new(96.0, 1100.0, "Another line"));
         }
         buf.set_line_count(line_idx, 3);
         
@@ -790,15 +1203,15 @@ mod tests {
         let line_count_idx = buf.begin_page(0, 0.0, 800.0, 1200.0);
         
         // Line 1: ASCII text (1 byte = 1 UTF-16 code unit)
-        buf.write_line(0.0, 0.0, "Hello World", 0, 0, None, None, &[]);
+        buf.write_line(&LineRecord::new(0.0, 0.0, "Hello World"));
         
         // Line 2: Text with emoji (4 bytes = 2 UTF-16 code units)
         // "Test 😀 emoji" = "Test " (5) + 😀 (2 UTF-16) + " emoji" (6) = 13 UTF-16 units
-        buf.write_line(0.0, 20.0, "Test 😀 emoji", 0, 0, None, None, &[]);
+        buf.write_line(&LineRecord::new(0.0, 20.0, "Test 😀 emoji"));
         
         // Line 3: Text with Cyrillic (2 bytes = 1 UTF-16 code unit)
         // "Привет мир" = 10 chars, each 1 UTF-16 unit = 10 UTF-16 units
-        buf.write_line(0.0, 40.0, "Привет мир", 0, 0, None, None, &[]);
+        buf.write_line(&LineRecord::new(0.0, 40.0, "Привет мир"));
         
         buf.set_line_count(line_count_idx, 3);
         buf.finalize();
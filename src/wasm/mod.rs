@@ -5,15 +5,24 @@
 pub mod flat_buffer;
 
 use wasm_bindgen::prelude::*;
-use crate::document::BlockKind;
-use crate::{Editor, LayoutConstraints, Rect};
+use crate::document::{
+    DecorationId, DecorationKind, HeaderFooter, HeaderFooterAlign, HeaderFooterKind, InlineObject, LineBreakMode, Link, ListDefinition,
+    ListId, MarkerAlignment, NoteKind, NumberFormat, ObjectId, ObjectSource, ParagraphId, SearchOptions, SearchQuery,
+    SectionColumns, SectionId, StoryId, TabAlignment, TabLeader, TabStop, TextDirection, TocId,
+};
+use crate::render::{DisplayItem, PageRegion};
+use crate::spell::HunspellDictionary;
+use crate::{Editor, Granularity, LayoutConstraints, Movement, OutlineEntry, Rect, TextUnit};
 use flat_buffer::{
+    LineRecord,
     RenderBuffer, 
     block_kind_to_opcode,
     HEADER_SIZE,
     U32_PER_LINE,
     U32_PER_CURSOR,
     F32_PER_CURSOR,
+    U32_PER_BOX,
+    F32_PER_BOX,
 };
 
 /// Get access to WASM memory for zero-copy data access
@@ -34,6 +43,18 @@ pub fn init() {
 pub struct WasmEditor {
     editor: Editor,
     render_buffer: RenderBuffer,
+    /// Objects referenced by image records of the last render, by object key
+    frame_objects: Vec<(StoryId, ObjectId)>,
+    /// Links referenced by line records of the last render, by link key
+    frame_links: Vec<Link>,
+    /// Headings from the last outline refresh, depth first, with their
+    /// depth in the tree
+    outline: Vec<(usize, OutlineEntry)>,
+    /// Misspelled word from the last suggestion lookup, where it was and
+    /// its corrections
+    misspelling: Option<(crate::editing::DocPosition, String, Vec<String>)>,
+    /// Write shaped glyphs into the glyph buffer
+    glyph_output: bool,
 }
 
 #[wasm_bindgen]
@@ -50,13 +71,7 @@ impl WasmEditor {
             margin_right: 96.0,
         };
 
-        let mut editor = Editor::new(constraints);
-        editor.update_layout();
-        
-        Self { 
-            editor,
-            render_buffer: RenderBuffer::new(),
-        }
+        Self::from_editor(Editor::new(constraints))
     }
 
     /// Create editor with custom page dimensions
@@ -78,13 +93,56 @@ impl WasmEditor {
             margin_right,
         };
 
-        let mut editor = Editor::new(constraints);
-        editor.update_layout();
-        
-        Self { 
-            editor,
-            render_buffer: RenderBuffer::new(),
-        }
+        Self::from_editor(Editor::new(constraints))
+    }
+
+    /// Set a header of the section at the cursor
+    ///
+    /// `kind`: 0 = default, 1 = first page, 2 = even pages.
+    /// `align`: 0 = left, 1 = center, 2 = right.
+    /// The template may contain `{PAGE}`, `{NUMPAGES}`, `{DATE}` and `{TITLE}`;
+    /// an empty template clears the header.
+    #[wasm_bindgen(js_name = setHeader)]
+    pub fn set_header(&mut self, kind: u32, template: &str, align: u32) {
+        self.editor.edit_section("header", |section| {
+            *section.headers.variant_mut(header_footer_kind(kind)) = header_footer_content(template, align);
+        });
+        self.editor.update_layout();
+    }
+
+    /// Set a footer of the section at the cursor (same arguments as `setHeader`)
+    #[wasm_bindgen(js_name = setFooter)]
+    pub fn set_footer(&mut self, kind: u32, template: &str, align: u32) {
+        self.editor.edit_section("footer", |section| {
+            *section.footers.variant_mut(header_footer_kind(kind)) = header_footer_content(template, align);
+        });
+        self.editor.update_layout();
+    }
+
+    /// Use a separate header/footer on the first page of the section at the cursor
+    #[wasm_bindgen(js_name = setDifferentFirstPage)]
+    pub fn set_different_first_page(&mut self, enabled: bool) {
+        self.editor.edit_section("different first page", |section| {
+            section.headers.different_first_page = enabled;
+            section.footers.different_first_page = enabled;
+        });
+        self.editor.update_layout();
+    }
+
+    /// Use separate headers/footers on even pages of the section at the cursor
+    #[wasm_bindgen(js_name = setDifferentOddEven)]
+    pub fn set_different_odd_even(&mut self, enabled: bool) {
+        self.editor.edit_section("different odd and even pages", |section| {
+            section.headers.different_odd_even = enabled;
+            section.footers.different_odd_even = enabled;
+        });
+        self.editor.update_layout();
+    }
+
+    /// Set the document title (used by the `{TITLE}` field)
+    #[wasm_bindgen(js_name = setTitle)]
+    pub fn set_title(&mut self, title: &str) {
+        self.editor.document.set_title(title);
     }
 
     /// Insert text at current cursor position
@@ -114,12 +172,111 @@ impl WasmEditor {
         result
     }
 
+    /// Delete for a `beforeinput` event's `inputType`; returns false if
+    /// nothing was deleted or the type is not a delete
+    ///
+    /// Besides the browser's types (`deleteContentBackward`,
+    /// `deleteWordBackward`, `deleteSoftLineBackward`,
+    /// `deleteHardLineBackward`, their `Forward` forms and
+    /// `deleteEntireSoftLine`), `deleteSentenceBackward`,
+    /// `deleteSentenceForward` and `deleteParagraph` are accepted for the
+    /// app's own shortcuts.
+    #[wasm_bindgen(js_name = deleteByInputType)]
+    pub fn delete_by_input_type(&mut self, input_type: &str) -> bool {
+        let (unit, backward) = match input_type {
+            "deleteContentBackward" => (TextUnit::Grapheme, true),
+            "deleteContentForward" => (TextUnit::Grapheme, false),
+            "deleteWordBackward" => (TextUnit::Word, true),
+            "deleteWordForward" => (TextUnit::Word, false),
+            "deleteSentenceBackward" => (TextUnit::Sentence, true),
+            "deleteSentenceForward" => (TextUnit::Sentence, false),
+            "deleteSoftLineBackward" => (TextUnit::Line, true),
+            "deleteSoftLineForward" | "deleteEntireSoftLine" => (TextUnit::Line, false),
+            "deleteHardLineBackward" => (TextUnit::Paragraph, true),
+            "deleteHardLineForward" => (TextUnit::Paragraph, false),
+            "deleteParagraph" => {
+                let result = self.editor.delete_paragraph().is_some();
+                self.editor.update_layout();
+                return result;
+            }
+            _ => return false,
+        };
+        if input_type == "deleteEntireSoftLine" && self.editor.selection.is_none() {
+            self.editor.move_by(Movement::LineStart, false);
+        }
+        let result = self.editor.delete_by(unit, backward).is_some();
+        if result {
+            self.editor.update_layout();
+        }
+        result
+    }
+
     /// Move cursor
     #[wasm_bindgen(js_name = moveCursor)]
     pub fn move_cursor(&mut self, horizontal: i32, vertical: i32, extend_selection: bool) {
         self.editor.move_cursor(horizontal, vertical, extend_selection);
     }
 
+    /// Move to the start of the previous word (Ctrl+Left)
+    #[wasm_bindgen(js_name = moveWordLeft)]
+    pub fn move_word_left(&mut self, extend_selection: bool) {
+        self.editor.move_by(Movement::WordLeft, extend_selection);
+    }
+
+    /// Move to the start of the next word (Ctrl+Right)
+    #[wasm_bindgen(js_name = moveWordRight)]
+    pub fn move_word_right(&mut self, extend_selection: bool) {
+        self.editor.move_by(Movement::WordRight, extend_selection);
+    }
+
+    /// Move to the start of the visual line (Home)
+    #[wasm_bindgen(js_name = moveLineStart)]
+    pub fn move_line_start(&mut self, extend_selection: bool) {
+        self.editor.move_by(Movement::LineStart, extend_selection);
+    }
+
+    /// Move to the end of the visual line (End)
+    #[wasm_bindgen(js_name = moveLineEnd)]
+    pub fn move_line_end(&mut self, extend_selection: bool) {
+        self.editor.move_by(Movement::LineEnd, extend_selection);
+    }
+
+    /// Move to the start of the document (Ctrl+Home)
+    #[wasm_bindgen(js_name = moveDocumentStart)]
+    pub fn move_document_start(&mut self, extend_selection: bool) {
+        self.editor.move_by(Movement::DocumentStart, extend_selection);
+    }
+
+    /// Move to the end of the document (Ctrl+End)
+    #[wasm_bindgen(js_name = moveDocumentEnd)]
+    pub fn move_document_end(&mut self, extend_selection: bool) {
+        self.editor.move_by(Movement::DocumentEnd, extend_selection);
+    }
+
+    /// Move to the start of the paragraph, or of the previous one (Ctrl+Up)
+    #[wasm_bindgen(js_name = moveParagraphUp)]
+    pub fn move_paragraph_up(&mut self, extend_selection: bool) {
+        self.editor.move_by(Movement::ParagraphUp, extend_selection);
+    }
+
+    /// Move to the start of the next paragraph (Ctrl+Down)
+    #[wasm_bindgen(js_name = moveParagraphDown)]
+    pub fn move_paragraph_down(&mut self, extend_selection: bool) {
+        self.editor.move_by(Movement::ParagraphDown, extend_selection);
+    }
+
+    /// Move up by a viewport's height of lines (Page Up)
+    #[wasm_bindgen(js_name = movePageUp)]
+    pub fn move_page_up(&mut self, viewport_height: f32, extend_selection: bool) {
+        self.editor.move_by(Movement::PageUp(viewport_height), extend_selection);
+    }
+
+    /// Move down by a viewport's height of lines (Page Down)
+    #[wasm_bindgen(js_name = movePageDown)]
+    pub fn move_page_down(&mut self, viewport_height: f32, extend_selection: bool) {
+        self.editor.move_by(Movement::PageDown(viewport_height), extend_selection);
+    }
+
     /// Undo last operation
     pub fn undo(&mut self) -> bool {
         let result = self.editor.undo();
@@ -156,7 +313,7 @@ impl WasmEditor {
         while self.editor.cursor.position.offset > 0 || self.editor.cursor.position.para_id.0 > 0 {
             self.editor.move_cursor(-1, 0, false);
         }
-        let text_len = self.editor.text().len();
+        let text_len = self.editor.story_document().len();
         for _ in 0..text_len {
             self.editor.move_cursor(1, 0, true);
         }
@@ -174,8 +331,456 @@ impl WasmEditor {
         self.insert_text("\n");
     }
 
-    /// Update font metrics from the browser
-    #[wasm_bindgen(js_name = setFontMetrics)]
+    /// Insert a manual page break (Ctrl+Enter)
+    #[wasm_bindgen(js_name = insertPageBreak)]
+    pub fn insert_page_break(&mut self) {
+        self.editor.insert_page_break();
+        self.editor.update_layout();
+    }
+
+    /// Insert a section break; the new section uses the given page setup
+    #[wasm_bindgen(js_name = insertSectionBreak)]
+    pub fn insert_section_break(
+        &mut self,
+        page_width: f32,
+        page_height: f32,
+        margin_top: f32,
+        margin_bottom: f32,
+        margin_left: f32,
+        margin_right: f32,
+    ) {
+        self.editor.insert_section_break(LayoutConstraints {
+            page_width,
+            page_height,
+            margin_top,
+            margin_bottom,
+            margin_left,
+            margin_right,
+        });
+        self.editor.update_layout();
+    }
+
+    /// Set the columns of the section at the cursor, with an optional line
+    /// between them
+    #[wasm_bindgen(js_name = setColumns)]
+    pub fn set_columns(&mut self, count: u32, gap: f32, separator: bool) {
+        self.editor.set_section_columns(SectionColumns {
+            count: count as usize,
+            gap,
+            separator,
+        });
+        self.editor.update_layout();
+    }
+
+    /// Insert a footnote at the cursor and start editing it
+    #[wasm_bindgen(js_name = insertFootnote)]
+    pub fn insert_footnote(&mut self) {
+        self.editor.insert_note(NoteKind::Footnote);
+        self.editor.update_layout();
+    }
+
+    /// Insert an endnote at the cursor and start editing it
+    #[wasm_bindgen(js_name = insertEndnote)]
+    pub fn insert_endnote(&mut self) {
+        self.editor.insert_note(NoteKind::Endnote);
+        self.editor.update_layout();
+    }
+
+    /// Return the cursor from a note to the body text
+    #[wasm_bindgen(js_name = exitNote)]
+    pub fn exit_note(&mut self) {
+        self.editor.exit_note();
+    }
+
+    /// Check whether the cursor is inside a footnote or endnote
+    #[wasm_bindgen(js_name = isEditingNote)]
+    pub fn is_editing_note(&self) -> bool {
+        matches!(self.editor.story, StoryId::Note(_))
+    }
+
+    /// Insert a table with empty cells at the cursor and move into its first cell
+    #[wasm_bindgen(js_name = insertTable)]
+    pub fn insert_table(&mut self, rows: usize, columns: usize) {
+        self.editor.insert_table(rows.max(1), columns.max(1));
+        self.editor.update_layout();
+    }
+
+    /// Move to the next or previous table cell (Tab / Shift+Tab)
+    ///
+    /// Returns false if the cursor is not in a table.
+    #[wasm_bindgen(js_name = moveToCell)]
+    pub fn move_to_cell(&mut self, backward: bool) -> bool {
+        let result = self.editor.move_to_cell(backward);
+        if result {
+            self.editor.update_layout();
+        }
+        result
+    }
+
+    /// Check whether the cursor is inside a table cell
+    #[wasm_bindgen(js_name = isInTable)]
+    pub fn is_in_table(&self) -> bool {
+        matches!(self.editor.story, StoryId::Cell(_))
+    }
+
+    /// Insert an image from a URL at the cursor; returns its object ID, or
+    /// undefined when no more objects fit
+    #[wasm_bindgen(js_name = insertImageUrl)]
+    pub fn insert_image_url(&mut self, url: &str, width: f32, height: f32) -> Option<u32> {
+        let object = InlineObject::new(ObjectSource::Url(url.to_string()), width, height);
+        let object_id = self.editor.insert_object(object);
+        self.editor.update_layout();
+        object_id.map(|id| id.0)
+    }
+
+    /// Insert an embedded image at the cursor; returns its object ID, or
+    /// undefined when no more objects fit
+    #[wasm_bindgen(js_name = insertImageBytes)]
+    pub fn insert_image_bytes(&mut self, data: &[u8], mime_type: &str, width: f32, height: f32) -> Option<u32> {
+        let source = ObjectSource::Bytes {
+            mime_type: mime_type.to_string(),
+            data: data.to_vec(),
+        };
+        let object_id = self.editor.insert_object(InlineObject::new(source, width, height));
+        self.editor.update_layout();
+        object_id.map(|id| id.0)
+    }
+
+    /// Resize an inline object of the active story (undoable)
+    #[wasm_bindgen(js_name = resizeObject)]
+    pub fn resize_object(&mut self, object_id: u32, width: f32, height: f32) -> bool {
+        let result = self.editor.resize_object(ObjectId(object_id), width, height);
+        if result {
+            self.editor.update_layout();
+        }
+        result
+    }
+
+    /// Get the URL of an image record's object from the last render
+    ///
+    /// Embedded images are returned as `data:` URLs.
+    #[wasm_bindgen(js_name = getObjectUrl)]
+    pub fn get_object_url(&self, object_key: u32) -> String {
+        let object = self
+            .frame_objects
+            .get(object_key as usize)
+            .and_then(|(story, id)| self.editor.document.story(*story).object(*id));
+
+        object.map(|object| object.source.url()).unwrap_or_default()
+    }
+
+    /// Insert linked text at the cursor
+    #[wasm_bindgen(js_name = insertLink)]
+    pub fn insert_link(&mut self, text: &str, href: &str, title: &str) {
+        self.editor.insert_link(text, link_from_js(href, title));
+        self.editor.update_layout();
+    }
+
+    /// Set the base direction of the selected paragraphs (0 auto, 1 LTR, 2 RTL)
+    #[wasm_bindgen(js_name = setDirection)]
+    pub fn set_direction(&mut self, direction: u32) {
+        self.editor.set_direction(text_direction(direction));
+        self.editor.update_layout();
+    }
+
+    /// Set the language (BCP 47 tag) of the selection, or of the paragraph
+    /// at the cursor; an empty tag clears it
+    #[wasm_bindgen(js_name = setLanguage)]
+    pub fn set_language(&mut self, language: &str) {
+        self.editor.set_language(Some(language).filter(|l| !l.is_empty()));
+        self.editor.update_layout();
+    }
+
+    /// Set line breaking for the selected paragraphs (0 follow the document,
+    /// 1 greedy, 2 optimal)
+    #[wasm_bindgen(js_name = setLineBreakMode)]
+    pub fn set_line_break_mode(&mut self, mode: u32) {
+        self.editor.set_line_break_mode(line_break_mode(mode));
+        self.editor.update_layout();
+    }
+
+    /// Set line breaking for paragraphs that follow the document (1 greedy,
+    /// 2 optimal)
+    #[wasm_bindgen(js_name = setDocumentLineBreakMode)]
+    pub fn set_document_line_break_mode(&mut self, mode: u32) {
+        let mode = line_break_mode(mode).unwrap_or_default();
+        self.editor.layout.set_line_break_mode(mode);
+
+        self.editor.layout.invalidate_all();
+        self.editor.mark_dirty();
+        self.editor.update_layout();
+    }
+
+    /// Set the tab stops of the selected paragraphs
+    ///
+    /// Each stop has a position, an alignment (0 left, 1 center, 2 right,
+    /// 3 decimal) and a leader (0 none, 1 dots, 2 dashes, 3 underline). A
+    /// tab interval of 0 follows the document.
+    #[wasm_bindgen(js_name = setTabStops)]
+    pub fn set_tab_stops(&mut self, positions: &[f32], alignments: &[u32], leaders: &[u32], interval: f32) {
+        let stops = positions
+            .iter()
+            .enumerate()
+            .map(|(i, &position)| TabStop {
+                position,
+                alignment: tab_alignment(alignments.get(i).copied().unwrap_or(0)),
+                leader: tab_leader(leaders.get(i).copied().unwrap_or(0)),
+            })
+            .collect();
+        self.editor.set_tab_stops(stops, (interval > 0.0).then_some(interval));
+        self.editor.update_layout();
+    }
+
+    /// Set the distance between default tab stops for paragraphs that do
+    /// not set their own
+    #[wasm_bindgen(js_name = setDocumentTabInterval)]
+    pub fn set_document_tab_interval(&mut self, interval: f32) {
+        self.editor.layout.set_tab_interval(interval);
+
+        self.editor.layout.invalidate_all();
+        self.editor.mark_dirty();
+        self.editor.update_layout();
+    }
+
+    /// Register a list and return its ID (kind 0 bulleted, 1 numbered,
+    /// 2 multilevel `1.1.`)
+    #[wasm_bindgen(js_name = createList)]
+    pub fn create_list(&mut self, kind: u32) -> u32 {
+        let definition = match kind {
+            1 => ListDefinition::numbered(),
+            2 => ListDefinition::multilevel(),
+            _ => ListDefinition::bulleted(),
+        };
+        self.editor.add_list(definition).0 as u32
+    }
+
+    /// Make the selected paragraphs items of a list at a (zero-based) level
+    #[wasm_bindgen(js_name = setListItem)]
+    pub fn set_list_item(&mut self, list_id: u32, level: u32) {
+        self.editor.set_list(Some((ListId(list_id as u64), level.min(u8::MAX as u32) as u8)));
+        self.editor.update_layout();
+    }
+
+    /// Turn the selected list items back into paragraphs
+    #[wasm_bindgen(js_name = clearListItem)]
+    pub fn clear_list_item(&mut self) {
+        self.editor.set_list(None);
+        self.editor.update_layout();
+    }
+
+    /// Redefine one level of a list
+    ///
+    /// Formats are 0 bullet (the first character of `pattern`), 1 decimal,
+    /// 2 lower alpha, 3 upper alpha, 4 lower roman and 5 upper roman; a
+    /// numbered pattern writes level n's counter for `%n`. A negative
+    /// `restart_after` never restarts; alignment is 0 left, 1 center or
+    /// 2 right.
+    #[allow(clippy::too_many_arguments)]
+    #[wasm_bindgen(js_name = setListLevel)]
+    pub fn set_list_level(
+        &mut self,
+        list_id: u32,
+        level: u32,
+        format: u32,
+        pattern: &str,
+        start: u32,
+        restart_after: i32,
+        alignment: u32,
+        indent: f32,
+        hanging: f32,
+    ) -> bool {
+        let list_id = ListId(list_id as u64);
+        let Some(mut definition) = self.editor.document.list(list_id).cloned() else {
+            return false;
+        };
+        let Some(list_level) = definition.levels.get_mut(level as usize) else {
+            return false;
+        };
+
+        list_level.format = match format {
+            0 => NumberFormat::Bullet(pattern.chars().next().unwrap_or('•')),
+            2 => NumberFormat::LowerAlpha,
+            3 => NumberFormat::UpperAlpha,
+            4 => NumberFormat::LowerRoman,
+            5 => NumberFormat::UpperRoman,
+            _ => NumberFormat::Decimal,
+        };
+        list_level.pattern = pattern.to_string();
+        list_level.start = start;
+        list_level.restart_after = u8::try_from(restart_after).ok();
+        list_level.alignment = match alignment {
+            1 => MarkerAlignment::Center,
+            2 => MarkerAlignment::Right,
+            _ => MarkerAlignment::Left,
+        };
+        list_level.indent = indent;
+        list_level.hanging = hanging;
+
+        self.editor.set_list_definition(list_id, definition);
+        self.editor.update_layout();
+        true
+    }
+
+    /// Make the selected paragraphs headings of a level (1-6), or plain
+    /// paragraphs with 0
+    #[wasm_bindgen(js_name = setHeadingLevel)]
+    pub fn set_heading_level(&mut self, level: u32) {
+        self.editor.set_heading_level((level > 0).then(|| level.min(6) as u8));
+        self.editor.update_layout();
+    }
+
+    /// Number headings by level (`1`, `1.1`, `1.1.1`) or stop numbering them
+    #[wasm_bindgen(js_name = setHeadingNumbering)]
+    pub fn set_heading_numbering(&mut self, enabled: bool) {
+        self.editor.set_heading_numbering(enabled.then(ListDefinition::outline));
+        self.editor.update_layout();
+    }
+
+    /// Insert a table of contents listing headings down to a level and
+    /// return its ID
+    #[wasm_bindgen(js_name = insertToc)]
+    pub fn insert_toc(&mut self, max_level: u32) -> u32 {
+        let toc_id = self.editor.insert_toc(max_level.min(6) as u8);
+        self.editor.update_layout();
+        toc_id.0
+    }
+
+    /// Regenerate a table of contents from the current headings and pages
+    #[wasm_bindgen(js_name = updateToc)]
+    pub fn update_toc(&mut self, toc_id: u32) -> bool {
+        let updated = self.editor.update_toc(TocId(toc_id));
+        self.editor.update_layout();
+        updated
+    }
+
+    /// Move the cursor to the heading a link of a table of contents points
+    /// to; returns false for other links
+    #[wasm_bindgen(js_name = goToAnchor)]
+    pub fn go_to_anchor(&mut self, href: &str) -> bool {
+        self.editor.go_to_anchor(href)
+    }
+
+    /// Read the heading tree for a navigation pane and return the number
+    /// of headings
+    ///
+    /// Headings are listed depth first; read them with `getOutlineParaId`,
+    /// `getOutlineLevel`, `getOutlineDepth`, `getOutlineText` and
+    /// `getOutlinePage`.
+    #[wasm_bindgen(js_name = refreshOutline)]
+    pub fn refresh_outline(&mut self) -> usize {
+        fn flatten(entries: Vec<OutlineEntry>, depth: usize, out: &mut Vec<(usize, OutlineEntry)>) {
+            for mut entry in entries {
+                let children = std::mem::take(&mut entry.children);
+                out.push((depth, entry));
+                flatten(children, depth + 1, out);
+            }
+        }
+        self.outline.clear();
+        flatten(self.editor.outline(), 0, &mut self.outline);
+        self.outline.len()
+    }
+
+    /// Get the paragraph of a heading from the last outline refresh
+    #[wasm_bindgen(js_name = getOutlineParaId)]
+    pub fn get_outline_para_id(&self, index: usize) -> u64 {
+        self.outline.get(index).map_or(0, |(_, entry)| entry.para_id.0)
+    }
+
+    /// Get the level (1-6) of a heading from the last outline refresh
+    #[wasm_bindgen(js_name = getOutlineLevel)]
+    pub fn get_outline_level(&self, index: usize) -> u8 {
+        self.outline.get(index).map_or(0, |(_, entry)| entry.level)
+    }
+
+    /// Get how deep a heading from the last outline refresh is nested
+    #[wasm_bindgen(js_name = getOutlineDepth)]
+    pub fn get_outline_depth(&self, index: usize) -> usize {
+        self.outline.get(index).map_or(0, |&(depth, _)| depth)
+    }
+
+    /// Get the text of a heading from the last outline refresh
+    #[wasm_bindgen(js_name = getOutlineText)]
+    pub fn get_outline_text(&self, index: usize) -> String {
+        self.outline
+            .get(index)
+            .map(|(_, entry)| entry.text.clone())
+            .unwrap_or_default()
+    }
+
+    /// Get the page a heading from the last outline refresh starts on
+    #[wasm_bindgen(js_name = getOutlinePage)]
+    pub fn get_outline_page(&self, index: usize) -> usize {
+        self.outline.get(index).map_or(0, |(_, entry)| entry.page_index)
+    }
+
+    /// Get the document y of the top of a paragraph to scroll it into view
+    #[wasm_bindgen(js_name = scrollToParagraph)]
+    pub fn scroll_to_paragraph(&mut self, para_id: u64) -> Option<f32> {
+        self.editor.scroll_to_paragraph(ParagraphId(para_id))
+    }
+
+    /// Link the selection, or retarget the link at the cursor
+    #[wasm_bindgen(js_name = setLink)]
+    pub fn set_link(&mut self, href: &str, title: &str) -> bool {
+        let result = self.editor.set_link(link_from_js(href, title));
+        self.editor.update_layout();
+        result
+    }
+
+    /// Unlink the selection, or the link at the cursor
+    #[wasm_bindgen(js_name = removeLink)]
+    pub fn remove_link(&mut self) -> bool {
+        let result = self.editor.remove_link();
+        self.editor.update_layout();
+        result
+    }
+
+    /// Get the target of the link at the cursor
+    #[wasm_bindgen(js_name = getLinkAtCursor)]
+    pub fn get_link_at_cursor(&self) -> Option<String> {
+        self.editor
+            .link_at(&self.editor.cursor.position)
+            .map(|link| link.href.clone())
+    }
+
+    /// Get the target of the link under page coordinates, if any and if it
+    /// is safe to follow (web and mail addresses, anchors in the document)
+    ///
+    /// Call this on click to tell link clicks from caret placement.
+    #[wasm_bindgen(js_name = linkAt)]
+    pub fn link_at(&self, page_index: usize, x: f32, y: f32) -> Option<String> {
+        let (story, cursor) = self.hit_test_story(page_index, x, y)?;
+        let span = self.editor.document.story(story).link_at(&cursor.position)?;
+        span.link.is_safe().then(|| span.link.href.clone())
+    }
+
+    /// Get the target of a link record from the last render
+    #[wasm_bindgen(js_name = getLinkHref)]
+    pub fn get_link_href(&self, link_key: u32) -> String {
+        self.frame_links
+            .get(link_key as usize)
+            .map(|link| link.href.clone())
+            .unwrap_or_default()
+    }
+
+    /// Get the title of a link record from the last render
+    #[wasm_bindgen(js_name = getLinkTitle)]
+    pub fn get_link_title(&self, link_key: u32) -> Option<String> {
+        self.frame_links.get(link_key as usize)?.title.clone()
+    }
+
+    /// Export the document as HTML
+    #[wasm_bindgen(js_name = exportHtml)]
+    pub fn export_html(&self) -> String {
+        self.editor.document.to_html()
+    }
+
+    /// Export the document as Markdown
+    #[wasm_bindgen(js_name = exportMarkdown)]
+    pub fn export_markdown(&self) -> String {
+        self.editor.document.to_markdown()
+    }
+
     /// Update font metrics from the browser
     #[wasm_bindgen(js_name = setFontMetrics)]
     pub fn set_font_metrics(&mut self, line_height: f32, char_widths: &[f32], default_width: f32) {
@@ -204,29 +809,62 @@ impl WasmEditor {
     pub fn build_render_data(&mut self, viewport_y: f32, viewport_height: f32) {
         let viewport = Rect::new(0.0, viewport_y, 816.0, viewport_height);
         let display_list = self.editor.build_display_list(viewport);
-        let constraints = self.editor.layout.constraints();
 
         // Pre-calculate buffer sizes to avoid reallocation (critical: JS holds pointers to these buffers)
         let mut total_lines = 0;
         let mut total_text_bytes = 0;
         let mut total_styles = 0;
+        let mut total_boxes = 0;
+        let mut decoration_count = 0;
+        let mut total_glyphs = 0;
         let mut cursor_count = 0;
+        let mut selection_count = 0;
         
         for page in &display_list.pages {
             for item in &page.items {
                 match item {
-                    crate::render::DisplayItem::TextRun { text, block_kind, styles, .. } => {
+                    crate::render::DisplayItem::TextRun { id, text, styles, links, glyphs, .. } => {
                         total_lines += 1;
                         total_text_bytes += text.len();
-                        total_styles += styles.len();
+                        total_styles += styles.len() + links.len();
+                        total_glyphs += glyphs.len();
                         
                         // Add marker length if present
-                        if let BlockKind::ListItem { marker, .. } = block_kind {
-                            total_text_bytes += marker.display().len();
+                        if let Some(item) = self.editor.layout.list_item(id.para_id) {
+                            total_text_bytes += item.marker.len();
                         }
                     }
+                    crate::render::DisplayItem::HeaderFooterLine { text, .. } => {
+                        total_lines += 1;
+                        total_text_bytes += text.len();
+                    }
+                    crate::render::DisplayItem::NoteLine { text, label, styles, links, glyphs, .. } => {
+                        total_lines += 1;
+                        total_text_bytes += text.len() + label.as_ref().map_or(0, String::len);
+                        total_styles += styles.len() + links.len();
+                        total_glyphs += glyphs.len();
+                    }
+                    crate::render::DisplayItem::CellLine { text, styles, links, glyphs, .. } => {
+                        total_lines += 1;
+                        total_text_bytes += text.len();
+                        total_styles += styles.len() + links.len();
+                        total_glyphs += glyphs.len();
+                    }
+                    crate::render::DisplayItem::TableCell { .. }
+                    | crate::render::DisplayItem::InlineObject { .. }
+                    | crate::render::DisplayItem::Tab { .. }
+                    | crate::render::DisplayItem::ColumnSeparator { .. }
+                    | crate::render::DisplayItem::SearchMatch { .. } => {
+                        total_boxes += 1;
+                    }
                     crate::render::DisplayItem::Caret { .. } => {
-                        cursor_count = 1;
+                        cursor_count += 1;
+                    }
+                    crate::render::DisplayItem::SelectionRect { .. } => {
+                        selection_count += 1;
+                    }
+                    crate::render::DisplayItem::Decoration { .. } => {
+                        decoration_count += 1;
                     }
                     _ => {}
                 }
@@ -235,12 +873,13 @@ impl WasmEditor {
 
         // Estimate buffer sizes
         let page_count = display_list.pages.len();
-        let u32_needed = HEADER_SIZE + page_count * 2 + total_lines * U32_PER_LINE + cursor_count * U32_PER_CURSOR + total_styles * flat_buffer::U32_PER_STYLE;
-        let f32_needed = page_count * 3 + total_lines * 2 + cursor_count * F32_PER_CURSOR;
+        let u32_needed = HEADER_SIZE + page_count * 2 + total_lines * U32_PER_LINE + cursor_count * U32_PER_CURSOR + selection_count * flat_buffer::U32_PER_SELECTION + total_styles * flat_buffer::U32_PER_STYLE + total_boxes * U32_PER_BOX + decoration_count * flat_buffer::U32_PER_DECORATION;
+        let f32_needed = page_count * 3 + total_lines * 2 + cursor_count * F32_PER_CURSOR + selection_count * flat_buffer::F32_PER_SELECTION + total_boxes * F32_PER_BOX + decoration_count * flat_buffer::F32_PER_DECORATION;
         let text_needed = total_text_bytes;
+        let glyph_needed = if self.glyph_output { total_glyphs * flat_buffer::F32_PER_GLYPH } else { 0 };
 
         // Pre-allocate buffers to avoid reallocation during rendering
-        self.render_buffer.prepare(u32_needed, f32_needed, text_needed);
+        self.render_buffer.prepare(u32_needed, f32_needed, text_needed, glyph_needed);
 
         // Write header
         self.render_buffer.write_header(
@@ -248,48 +887,208 @@ impl WasmEditor {
             display_list.pages.len() as u32,
         );
 
-        // Collect cursor separately - it must be written AFTER all pages/lines
-        // cursor_data: (x, y, height, page_index, utf16_offset_in_line)
-        let mut cursor_data: Option<(f32, f32, f32, usize, usize)> = None;
+        self.frame_objects.clear();
+        self.frame_links.clear();
+
+        // Carets (the cursor first) and secondary selection rectangles are
+        // pending data, written after all pages/lines by finalize()
+        let mut secondary_carets = Vec::new();
 
-        // First pass: write pages and lines, collect cursor
+        // Write pages and lines, collect carets
         for page in &display_list.pages {
-            let page_y = page.page_index as f32 * constraints.page_height;
             let line_count_idx = self.render_buffer.begin_page(
                 page.page_index,
-                page_y,
-                constraints.page_width,
-                constraints.page_height,
+                page.bounds.y,
+                page.bounds.width,
+                page.bounds.height,
             );
 
             let mut line_count: u32 = 0;
 
             for item in &page.items {
                 match item {
-                    crate::render::DisplayItem::TextRun { position, text, block_kind, selection_range, styles, id: _ } => {
-                        let (block_type, flags) = block_kind_to_opcode(block_kind);
+                    crate::render::DisplayItem::TextRun { position, text, block_kind, selection_range, styles, links, rtl, glyphs, id } => {
+                        let (block_type, mut flags) = block_kind_to_opcode(block_kind);
+                        if *rtl {
+                            flags |= flat_buffer::FLAG_IS_RTL;
+                        }
                         
-                        let list_marker = if let BlockKind::ListItem { marker, .. } = block_kind {
-                            Some(marker.display())
-                        } else {
-                            None
-                        };
-
-                        self.render_buffer.write_line(
-                            position.x,
-                            position.y,
-                            text,
+                        // Marker numbered across the document by the list definition
+                        let list_marker = self
+                            .editor
+                            .layout
+                            .list_item(id.para_id)
+                            .map(|item| item.marker.clone())
+                            .filter(|marker| !marker.is_empty());
+
+                        let links = self.frame_link_records(links);
+                        self.render_buffer.write_line(&LineRecord {
                             block_type,
                             flags,
-                            list_marker.as_deref(),
-                            *selection_range,
+                            list_marker: list_marker.as_deref(),
+                            selection_range: *selection_range,
                             styles,
-                        );
+                            links: &links,
+                            ..LineRecord::new(position.x, position.y, text)
+                        });
+                        if self.glyph_output {
+                            self.render_buffer.write_glyphs(glyphs);
+                        }
+                        line_count += 1;
+                    }
+                    crate::render::DisplayItem::HeaderFooterLine { region, position, text } => {
+                        let block_type = match region {
+                            PageRegion::Header => flat_buffer::BLOCK_HEADER,
+                            PageRegion::Footer => flat_buffer::BLOCK_FOOTER,
+                        };
+                        self.render_buffer.write_line(&LineRecord {
+                            block_type,
+                            ..LineRecord::new(position.x, position.y, text)
+                        });
+                        line_count += 1;
+                    }
+                    crate::render::DisplayItem::NoteLine { kind, position, text, label, selection_range, styles, links, rtl, glyphs, .. } => {
+                        let links = self.frame_link_records(links);
+                        let block_type = match kind {
+                            NoteKind::Footnote => flat_buffer::BLOCK_FOOTNOTE,
+                            NoteKind::Endnote => flat_buffer::BLOCK_ENDNOTE,
+                        };
+                        self.render_buffer.write_line(&LineRecord {
+                            block_type,
+                            flags: rtl_flag(*rtl),
+                            list_marker: label.as_deref(),
+                            selection_range: *selection_range,
+                            styles,
+                            links: &links,
+                            ..LineRecord::new(position.x, position.y, text)
+                        });
+                        if self.glyph_output {
+                            self.render_buffer.write_glyphs(glyphs);
+                        }
                         line_count += 1;
                     }
-                    crate::render::DisplayItem::Caret { position, height, utf16_offset_in_line } => {
-                        // Collect cursor data to write after all pages
-                        cursor_data = Some((position.x, position.y, *height, page.page_index, *utf16_offset_in_line));
+                    crate::render::DisplayItem::CellLine { position, text, selection_range, styles, links, rtl, glyphs, .. } => {
+                        let links = self.frame_link_records(links);
+                        self.render_buffer.write_line(&LineRecord {
+                            block_type: flat_buffer::BLOCK_TABLE_CELL,
+                            flags: rtl_flag(*rtl),
+                            selection_range: *selection_range,
+                            styles,
+                            links: &links,
+                            ..LineRecord::new(position.x, position.y, text)
+                        });
+                        if self.glyph_output {
+                            self.render_buffer.write_glyphs(glyphs);
+                        }
+                        line_count += 1;
+                    }
+                    crate::render::DisplayItem::TableCell { bounds, border_width, header, selected, .. } => {
+                        let mut flags = 0;
+                        if *header {
+                            flags |= flat_buffer::BOX_FLAG_HEADER;
+                        }
+                        if *selected {
+                            flags |= flat_buffer::BOX_FLAG_SELECTED;
+                        }
+                        self.render_buffer.write_box(
+                            bounds.x,
+                            bounds.y,
+                            bounds.width,
+                            bounds.height,
+                            *border_width,
+                            page.page_index,
+                            flat_buffer::BOX_TABLE_CELL,
+                            flags,
+                        );
+                    }
+                    crate::render::DisplayItem::InlineObject { story, object_id, bounds } => {
+                        let object_key = self.frame_objects.len() as u32;
+                        self.frame_objects.push((*story, *object_id));
+                        self.render_buffer.write_box(
+                            bounds.x,
+                            bounds.y,
+                            bounds.width,
+                            bounds.height,
+                            0.0,
+                            page.page_index,
+                            flat_buffer::BOX_IMAGE,
+                            object_key,
+                        );
+                    }
+                    crate::render::DisplayItem::ColumnSeparator { x, y, height } => {
+                        self.render_buffer.write_box(
+                            *x,
+                            *y,
+                            0.0,
+                            *height,
+                            1.0,
+                            page.page_index,
+                            flat_buffer::BOX_COLUMN_SEPARATOR,
+                            0,
+                        );
+                    }
+                    crate::render::DisplayItem::Tab { bounds, leader } => {
+                        let leader = match leader {
+                            TabLeader::None => flat_buffer::TAB_LEADER_NONE,
+                            TabLeader::Dots => flat_buffer::TAB_LEADER_DOTS,
+                            TabLeader::Dashes => flat_buffer::TAB_LEADER_DASHES,
+                            TabLeader::Underline => flat_buffer::TAB_LEADER_UNDERLINE,
+                        };
+                        self.render_buffer.write_box(
+                            bounds.x,
+                            bounds.y,
+                            bounds.width,
+                            bounds.height,
+                            0.0,
+                            page.page_index,
+                            flat_buffer::BOX_TAB,
+                            leader,
+                        );
+                    }
+                    crate::render::DisplayItem::Caret { position, height, utf16_offset_in_line, affinity, rtl, primary } => {
+                        let mut caret_flags = 0;
+                        if *affinity == crate::editing::Affinity::Upstream {
+                            caret_flags |= flat_buffer::CARET_FLAG_UPSTREAM;
+                        }
+                        if *rtl {
+                            caret_flags |= flat_buffer::CARET_FLAG_RTL;
+                        }
+                        let caret = (position.x, position.y, *height, page.page_index, *utf16_offset_in_line, caret_flags);
+                        if *primary {
+                            self.render_buffer.write_cursor(caret.0, caret.1, caret.2, caret.3, caret.4, caret.5);
+                        } else {
+                            secondary_carets.push(caret);
+                        }
+                    }
+                    crate::render::DisplayItem::SelectionRect { bounds } => {
+                        self.render_buffer.write_selection(bounds.x, bounds.y, bounds.width, bounds.height, page.page_index);
+                    }
+                    crate::render::DisplayItem::Decoration { bounds, kind, color } => {
+                        let kind = match kind {
+                            DecorationKind::Highlight => flat_buffer::DECORATION_HIGHLIGHT,
+                            DecorationKind::Squiggle => flat_buffer::DECORATION_SQUIGGLE,
+                        };
+                        self.render_buffer.write_decoration(
+                            bounds.x,
+                            bounds.y,
+                            bounds.width,
+                            bounds.height,
+                            page.page_index,
+                            kind,
+                            *color,
+                        );
+                    }
+                    crate::render::DisplayItem::SearchMatch { bounds, current } => {
+                        self.render_buffer.write_box(
+                            bounds.x,
+                            bounds.y,
+                            bounds.width,
+                            bounds.height,
+                            0.0,
+                            page.page_index,
+                            flat_buffer::BOX_SEARCH_MATCH,
+                            if *current { flat_buffer::BOX_FLAG_CURRENT } else { 0 },
+                        );
                     }
                     _ => {}
                 }
@@ -298,9 +1097,9 @@ impl WasmEditor {
             self.render_buffer.set_line_count(line_count_idx, line_count);
         }
 
-        // Second pass: write cursor after all pages/lines
-        if let Some((x, y, height, page_index, utf16_offset)) = cursor_data {
-            self.render_buffer.write_cursor(x, y, height, page_index, utf16_offset);
+        for (x, y, height, page_index, utf16_offset, caret_flags) in secondary_carets {
+            let caret_flags = caret_flags | flat_buffer::CARET_FLAG_SECONDARY;
+            self.render_buffer.write_cursor(x, y, height, page_index, utf16_offset, caret_flags);
         }
 
         self.render_buffer.finalize();
@@ -357,13 +1156,49 @@ impl WasmEditor {
         self.render_buffer.style_len()
     }
 
+    /// Get pointer to glyph buffer
+    #[wasm_bindgen(js_name = getGlyphPtr)]
+    pub fn get_glyph_ptr(&self) -> u32 {
+        self.render_buffer.glyph_ptr()
+    }
+
+    /// Get length of glyph buffer
+    #[wasm_bindgen(js_name = getGlyphLen)]
+    pub fn get_glyph_len(&self) -> u32 {
+        self.render_buffer.glyph_len()
+    }
+
+    /// Write shaped glyphs of lines (fonts registered from font data) into
+    /// the glyph buffer, for clients that draw glyphs directly
+    #[wasm_bindgen(js_name = setGlyphOutput)]
+    pub fn set_glyph_output(&mut self, enabled: bool) {
+        self.glyph_output = enabled;
+        self.editor.mark_dirty();
+    }
+
     // =========================================================================
     // Font and Formatting
     // =========================================================================
 
-    /// Register a font with metrics
+    /// Register a font from raw TTF/OTF bytes at a size (font 0 is the
+    /// default font); returns false if the data is not a usable font
     #[wasm_bindgen(js_name = addFont)]
-    pub fn add_font(&mut self, font_id: u32, line_height: f32, char_widths: &[f32], default_width: f32) {
+    pub fn add_font(&mut self, font_id: u32, data: &[u8], size: f32) -> bool {
+        let font_id = crate::layout::font::FontId(font_id);
+        if !self.editor.layout.font_library.set_font_data(font_id, data, size) {
+            return false;
+        }
+
+        self.editor.layout.invalidate_all();
+        self.editor.mark_dirty();
+        self.editor.update_layout();
+        true
+    }
+
+    /// Register a font with metrics measured by the browser (for fonts
+    /// whose data is not available)
+    #[wasm_bindgen(js_name = addFontMetrics)]
+    pub fn add_font_metrics(&mut self, font_id: u32, line_height: f32, char_widths: &[f32], default_width: f32) {
         let metrics = crate::layout::FontMetrics::new(
             line_height,
             char_widths.to_vec(),
@@ -377,21 +1212,155 @@ impl WasmEditor {
         self.editor.update_layout();
     }
 
-    /// Format current selection with a font
+    /// Set the fonts tried, in order, for characters a font has no glyph for
+    #[wasm_bindgen(js_name = setFontFallbacks)]
+    pub fn set_font_fallbacks(&mut self, font_id: u32, chain: &[u32]) {
+        let chain = chain.iter().map(|&id| crate::layout::font::FontId(id)).collect();
+        self.editor.layout.font_library.set_fallbacks(crate::layout::font::FontId(font_id), chain);
+
+        self.editor.layout.invalidate_all();
+        self.editor.mark_dirty();
+        self.editor.update_layout();
+    }
+
+    /// Set the document-wide fallback fonts, tried after a font's own
+    #[wasm_bindgen(js_name = setDefaultFallbacks)]
+    pub fn set_default_fallbacks(&mut self, chain: &[u32]) {
+        let chain = chain.iter().map(|&id| crate::layout::font::FontId(id)).collect();
+        self.editor.layout.font_library.set_default_fallbacks(chain);
+
+        self.editor.layout.invalidate_all();
+        self.editor.mark_dirty();
+        self.editor.update_layout();
+    }
+
+    /// Configure automatic hyphenation; lengths count characters and a
+    /// `max_consecutive` of 0 allows any number of hyphenated lines in a row
+    #[wasm_bindgen(js_name = setHyphenation)]
+    pub fn set_hyphenation(
+        &mut self,
+        enabled: bool,
+        min_word: usize,
+        min_prefix: usize,
+        min_suffix: usize,
+        max_consecutive: usize,
+    ) {
+        self.editor.layout.hyphenation_mut().settings = crate::layout::HyphenationSettings {
+            enabled,
+            min_word,
+            min_prefix,
+            min_suffix,
+            max_consecutive,
+        };
+
+        self.editor.layout.invalidate_all();
+        self.editor.mark_dirty();
+        self.editor.update_layout();
+    }
+
+    /// Add TeX hyphenation patterns for a language, replacing any it had;
+    /// returns false if the data contains no patterns
+    #[wasm_bindgen(js_name = addHyphenationPatterns)]
+    pub fn add_hyphenation_patterns(&mut self, language: &str, patterns: &str) -> bool {
+        if !self.editor.layout.hyphenation_mut().add_patterns(language, patterns) {
+            return false;
+        }
+
+        self.editor.layout.invalidate_all();
+        self.editor.mark_dirty();
+        self.editor.update_layout();
+        true
+    }
+
+    /// Add a Hunspell dictionary (the text of its `.aff` and `.dic` files)
+    /// for a language, replacing any it had; returns false if the word list
+    /// is empty
+    #[wasm_bindgen(js_name = addSpellingDictionary)]
+    pub fn add_spelling_dictionary(&mut self, language: &str, aff: &str, dic: &str) -> bool {
+        let Some(dictionary) = HunspellDictionary::parse(aff, dic) else {
+            return false;
+        };
+        self.editor.spelling.add_dictionary(language, Box::new(dictionary));
+        self.editor.update_layout();
+        true
+    }
+
+    /// Turn spell checking on or off
+    #[wasm_bindgen(js_name = setSpellChecking)]
+    pub fn set_spell_checking(&mut self, enabled: bool) {
+        self.editor.spelling.set_enabled(enabled);
+        self.editor.update_layout();
+    }
+
+    /// Look up the misspelled body word at page coordinates; returns how
+    /// many corrections it has
+    ///
+    /// Read the word with `getMisspelledWord` and the corrections with
+    /// `getSpellingSuggestion`.
+    #[wasm_bindgen(js_name = spellingSuggestionsAt)]
+    pub fn spelling_suggestions_at(&mut self, page_index: usize, x: f32, y: f32) -> usize {
+        self.misspelling = match self.hit_test_story(page_index, x, y) {
+            Some((StoryId::Body, cursor)) => self.editor.misspelling_at(cursor.position).map(|(_, word)| {
+                let suggestions = self.editor.spelling_suggestions(cursor.position);
+                (cursor.position, word, suggestions)
+            }),
+            _ => None,
+        };
+        self.misspelling.as_ref().map_or(0, |(_, _, suggestions)| suggestions.len())
+    }
+
+    /// Get the word from the last suggestion lookup, if it was misspelled
+    #[wasm_bindgen(js_name = getMisspelledWord)]
+    pub fn get_misspelled_word(&self) -> Option<String> {
+        self.misspelling.as_ref().map(|(_, word, _)| word.clone())
+    }
+
+    /// Get a correction from the last suggestion lookup
+    #[wasm_bindgen(js_name = getSpellingSuggestion)]
+    pub fn get_spelling_suggestion(&self, index: usize) -> String {
+        self.misspelling
+            .as_ref()
+            .and_then(|(_, _, suggestions)| suggestions.get(index).cloned())
+            .unwrap_or_default()
+    }
+
+    /// Replace the word from the last suggestion lookup with one of its
+    /// corrections
+    #[wasm_bindgen(js_name = correctSpelling)]
+    pub fn correct_spelling(&mut self, index: usize) -> bool {
+        let Some((position, _, suggestions)) = self.misspelling.take() else {
+            return false;
+        };
+        let corrected = suggestions
+            .get(index)
+            .is_some_and(|replacement| self.editor.correct_spelling(position, replacement));
+        self.editor.update_layout();
+        corrected
+    }
+
+    /// Add a word to the user's dictionary
+    #[wasm_bindgen(js_name = addToDictionary)]
+    pub fn add_to_dictionary(&mut self, word: &str) {
+        self.editor.add_to_dictionary(word);
+    }
+
+    /// Stop marking a word as misspelled until the editor is closed
+    #[wasm_bindgen(js_name = ignoreSpelling)]
+    pub fn ignore_spelling(&mut self, word: &str) {
+        self.editor.ignore_spelling(word);
+    }
+
+    /// Words added to the user's dictionary, one per line (to save them)
+    #[wasm_bindgen(js_name = getAddedWords)]
+    pub fn get_added_words(&self) -> String {
+        self.editor.spelling.added_words().collect::<Vec<_>>().join("\n")
+    }
+
+    /// Format the selections with a font
     #[wasm_bindgen(js_name = formatSelection)]
     pub fn format_selection(&mut self, font_id: u32) {
-        if let Some(selection) = &self.editor.selection {
-             let start = selection.start();
-             let end = selection.end();
-             
-             let start_offset = self.editor.document.position_to_offset(&start);
-             let end_offset = self.editor.document.position_to_offset(&end);
-             
-             let diff = self.editor.document.format_range(start_offset, end_offset, crate::layout::font::FontId(font_id));
-             
-             // Invalidate layout
-             self.editor.layout.invalidate(&diff);
-             self.editor.update_layout();
+        if self.editor.format_selection(crate::layout::font::FontId(font_id)) {
+            self.editor.update_layout();
         }
     }
 
@@ -463,60 +1432,273 @@ impl WasmEditor {
     // =========================================================================
 
     /// Set cursor position from page coordinates
+    ///
+    /// `click_count` is the mouse event's `detail`: 2 selects the word and
+    /// 3 (or more) the paragraph under the point, and a drag that follows
+    /// (`selectTo`) extends the selection by words or paragraphs.
     #[wasm_bindgen(js_name = setCursor)]
-    pub fn set_cursor(&mut self, page_index: usize, x: f32, y: f32) {
-        if let Some(pos) = self.hit_test(page_index, x, y) {
-            self.editor.cursor = crate::editing::Cursor::new(pos);
+    pub fn set_cursor(&mut self, page_index: usize, x: f32, y: f32, click_count: u32) {
+        if let Some((story, cursor)) = self.hit_test_story(page_index, x, y) {
+            self.editor.story = story;
+            self.editor.cursor = cursor;
             self.editor.selection = None;
+            self.editor.cell_selection = None;
+            self.editor.clear_secondary_selections();
+            let granularity = match click_count {
+                0 | 1 => Granularity::Character,
+                2 => Granularity::Word,
+                _ => Granularity::Paragraph,
+            };
+            if granularity != Granularity::Character {
+                self.editor.select_unit(self.editor.cursor.position, granularity);
+            }
             self.editor.update_layout();
         }
     }
 
+    /// Add a caret at page coordinates in the body text (Ctrl+click)
+    #[wasm_bindgen(js_name = addCaretAt)]
+    pub fn add_caret_at(&mut self, page_index: usize, x: f32, y: f32) -> bool {
+        let Some((StoryId::Body, cursor)) = self.hit_test_story(page_index, x, y) else {
+            return false;
+        };
+        let added = self.editor.add_caret(cursor.position);
+        self.editor.update_layout();
+        added
+    }
+
+    /// Add a caret on the line above the cursor
+    #[wasm_bindgen(js_name = addCaretAbove)]
+    pub fn add_caret_above(&mut self) -> bool {
+        let added = self.editor.add_caret_vertical(-1);
+        self.editor.update_layout();
+        added
+    }
+
+    /// Add a caret on the line below the cursor
+    #[wasm_bindgen(js_name = addCaretBelow)]
+    pub fn add_caret_below(&mut self) -> bool {
+        let added = self.editor.add_caret_vertical(1);
+        self.editor.update_layout();
+        added
+    }
+
+    /// Add the next occurrence of the selected text to the selections, or
+    /// select the word at the cursor
+    #[wasm_bindgen(js_name = selectNextOccurrence)]
+    pub fn select_next_occurrence(&mut self) -> bool {
+        let selected = self.editor.select_next_occurrence();
+        self.editor.update_layout();
+        selected
+    }
+
+    /// Drop every caret and selection but the primary one
+    #[wasm_bindgen(js_name = clearSecondarySelections)]
+    pub fn clear_secondary_selections(&mut self) {
+        self.editor.clear_secondary_selections();
+    }
+
+    /// Number of carets and selections, the primary one included
+    #[wasm_bindgen(js_name = getSelectionCount)]
+    pub fn get_selection_count(&self) -> usize {
+        1 + self.editor.secondary_selections.len()
+    }
+
+    /// Paint a range of the body text (0 highlight, 1 squiggle; color as
+    /// 0xRRGGBBAA); returns the decoration's ID, or None for an empty range
+    #[wasm_bindgen(js_name = addDecoration)]
+    pub fn add_decoration(
+        &mut self,
+        start_para: u64,
+        start_offset: usize,
+        end_para: u64,
+        end_offset: usize,
+        kind: u32,
+        color: u32,
+    ) -> Option<u64> {
+        let start = crate::editing::DocPosition::new(ParagraphId(start_para), start_offset);
+        let end = crate::editing::DocPosition::new(ParagraphId(end_para), end_offset);
+        let range = start..end;
+        let id = self.editor.add_decoration(StoryId::Body, range, decoration_kind(kind), color)?;
+        Some(id.0)
+    }
+
+    /// Paint the selected body text; returns the decoration's ID, or None
+    /// without a selection in the body
+    #[wasm_bindgen(js_name = decorateSelection)]
+    pub fn decorate_selection(&mut self, kind: u32, color: u32) -> Option<u64> {
+        if self.editor.story != StoryId::Body {
+            return None;
+        }
+        let (start, end) = self.editor.selection.as_ref()?.ordered();
+        let id = self.editor.add_decoration(StoryId::Body, start..end, decoration_kind(kind), color)?;
+        Some(id.0)
+    }
+
+    /// Remove a decoration from the body text
+    #[wasm_bindgen(js_name = removeDecoration)]
+    pub fn remove_decoration(&mut self, id: u64) -> bool {
+        self.editor.remove_decoration(StoryId::Body, DecorationId(id))
+    }
+
+    /// Remove every decoration from the body text
+    #[wasm_bindgen(js_name = clearDecorations)]
+    pub fn clear_decorations(&mut self) {
+        self.editor.clear_decorations(StoryId::Body);
+    }
+
+    /// Highlight the matches of a pattern; returns false (and clears the
+    /// search) for an empty pattern or an invalid regular expression
+    #[wasm_bindgen(js_name = setSearch)]
+    pub fn set_search(&mut self, pattern: &str, case_sensitive: bool, whole_word: bool, regex: bool) -> bool {
+        let options = SearchOptions { case_sensitive, whole_word, regex };
+        self.editor.search = SearchQuery::new(pattern, options);
+        self.editor.search.is_some()
+    }
+
+    /// Stop highlighting search matches
+    #[wasm_bindgen(js_name = clearSearch)]
+    pub fn clear_search(&mut self) {
+        self.editor.search = None;
+    }
+
+    /// Select the next (or previous) match of the search, wrapping around
+    #[wasm_bindgen(js_name = findNext)]
+    pub fn find_next(&mut self, backward: bool) -> bool {
+        let Some(query) = self.editor.search.clone() else {
+            return false;
+        };
+        let found = self.editor.find_next(&query, backward).is_some();
+        self.editor.update_layout();
+        found
+    }
+
+    /// Replace the selected match and select the next one
+    pub fn replace(&mut self, replacement: &str) -> bool {
+        let Some(query) = self.editor.search.clone() else {
+            return false;
+        };
+        let found = self.editor.replace(&query, replacement);
+        self.editor.update_layout();
+        found
+    }
+
+    /// Replace every match as one undoable step; returns the count
+    #[wasm_bindgen(js_name = replaceAll)]
+    pub fn replace_all(&mut self, replacement: &str) -> usize {
+        let Some(query) = self.editor.search.clone() else {
+            return 0;
+        };
+        let count = self.editor.replace_all(&query, replacement);
+        self.editor.update_layout();
+        count
+    }
+
+    /// Number of matches of the search in the active story
+    #[wasm_bindgen(js_name = getMatchCount)]
+    pub fn get_match_count(&self) -> usize {
+        self.editor.search.as_ref().map_or(0, |query| self.editor.find(query).len())
+    }
+
     /// Extend selection to position from page coordinates
     #[wasm_bindgen(js_name = selectTo)]
     pub fn select_to(&mut self, page_index: usize, x: f32, y: f32) {
-        if let Some(pos) = self.hit_test(page_index, x, y) {
-            let anchor = if let Some(sel) = &self.editor.selection {
-                sel.anchor
-            } else {
-                self.editor.cursor.position
-            };
-            
-            self.editor.cursor = crate::editing::Cursor::new(pos);
-            self.editor.selection = Some(crate::editing::Selection::new(anchor, pos));
+        // Selections stay within the story they started in, except that
+        // dragging across table cells selects a block of cells
+        let hit = self.hit_test_story(page_index, x, y);
+        if let (Some((StoryId::Cell(target), _)), StoryId::Cell(cell)) = (&hit, self.editor.story) {
+            let target = *target;
+            if target != cell && target.table_id == cell.table_id {
+                self.editor.select_cells_to(target);
+                self.editor.update_layout();
+                return;
+            }
+        }
+        if let Some((_, cursor)) = hit.filter(|(story, _)| *story == self.editor.story) {
+            let character_wise = self
+                .editor
+                .selection
+                .as_ref()
+                .is_none_or(|sel| sel.granularity == Granularity::Character);
+            self.editor.extend_selection_to(cursor.position);
+            // Character drags keep the caret side that was hit
+            if character_wise {
+                self.editor.cursor = cursor;
+            }
             self.editor.update_layout();
         }
     }
 
+    /// Helper: Map page coordinates to a story and a position within it
+    ///
+    /// Note lines and table cells are located through the display list,
+    /// which already knows where they sit on the page (including header
+    /// rows repeated at the top of a page).
+    fn hit_test_story(&self, page_index: usize, x: f32, y: f32) -> Option<(StoryId, crate::editing::Cursor)> {
+        let page = self.editor.layout.pages().get(page_index)?;
+        let viewport = Rect::new(0.0, page.y_offset, page.constraints.page_width, page.constraints.page_height);
+        let display_list = self.editor.build_display_list(viewport);
+        let items = display_list
+            .pages
+            .iter()
+            .find(|p| p.page_index == page_index)
+            .map(|p| p.items.as_slice())
+            .unwrap_or_default();
+
+        let point = crate::Point { x, y };
+        for item in items {
+            match item {
+                DisplayItem::NoteLine { note_id, line_index, position, .. } => {
+                    let note_layout = self.editor.layout.note_layout(*note_id)?;
+                    let (para, _, line) = note_layout.lines().nth(*line_index)?;
+                    if y >= position.y && y < position.y + line.height {
+                        return Some((StoryId::Note(*note_id), cursor_at_x(para.para_id, line, x - position.x)));
+                    }
+                }
+                DisplayItem::TableCell { cell, bounds, .. } if bounds.contains_point(point) => {
+                    let table_layout = self.editor.layout.table_layout(cell.table_id)?;
+                    let table_x = bounds.x - table_layout.column_x(cell.col);
+                    let (col, pos) = table_layout.hit_test(cell.row, x - table_x, y - bounds.y)?;
+                    return Some((StoryId::Cell(crate::document::CellId { col, ..*cell }), crate::editing::Cursor::new(pos)));
+                }
+                _ => {}
+            }
+        }
+
+        self.hit_test(page_index, x, y).map(|cursor| (StoryId::Body, cursor))
+    }
+
     /// Helper: Map page coordinates to document position
-    fn hit_test(&self, page_index: usize, x: f32, y: f32) -> Option<crate::editing::DocPosition> {
+    ///
+    /// The column under `x` is searched; points above or below its lines
+    /// land on its first or last line.
+    fn hit_test(&self, page_index: usize, x: f32, y: f32) -> Option<crate::editing::Cursor> {
         let pages = self.editor.layout.pages();
         if page_index >= pages.len() {
             return None;
         }
         
         let page = &pages[page_index];
-        let constraints = self.editor.layout.constraints();
+        let constraints = &page.constraints;
         let document = &self.editor.document;
         let layout = &self.editor.layout;
-        
+        let column = page.columns.column_at(x - constraints.margin_left, constraints.content_width());
+
+        // Cursor on a line of the page, from X relative to its column
+        let cursor_on = |para_id: ParagraphId, line_idx: usize, column: usize| {
+            let line = layout.paragraph_layout(para_id)?.lines.get(line_idx)?;
+            let indent = layout.indent_for(para_id);
+            Some(cursor_at_x(para_id, line, x - page.column_x(column) - indent))
+        };
+
         let mut current_y = constraints.margin_top;
+        let mut current_column = 0;
+        // Last line seen in the column under X
+        let mut last_in_column = None;
         
         // Iterate through paragraphs on this page (logic modified from DisplayList::build)
         let mut in_page = false;
-        
-        // If y is above top margin, clamp to first line
-        if y < current_y {
-            // Find start para/line
-            let para_id = page.start_para;
-            let para_layout = layout.paragraph_layout(para_id)?;
-            let line = &para_layout.lines[page.start_line];
-            let indent = layout.indent_for(document.block_meta(para_id)?);
-            let offset = line.offset_for_x(x - constraints.margin_left - indent);
-            return Some(crate::editing::DocPosition::new(para_id, offset));
-        }
-
-        for para_id in document.paragraph_order() {
+        'paragraphs: for para_id in document.paragraph_order() {
             if para_id == page.start_para {
                 in_page = true;
             }
@@ -538,28 +1720,26 @@ impl WasmEditor {
                     para_layout.lines.len()
                 };
 
-                let indent = if let Some(meta) = document.block_meta(para_id) {
-                    layout.indent_for(meta)
-                } else {
-                    0.0
-                };
-
                 for line_idx in start_line..end_line.min(para_layout.lines.len()) {
+                    if let Some(next) = page.column_starting_at(para_id, line_idx) {
+                        current_column = next;
+                        current_y = constraints.margin_top;
+                    }
+                    if current_column > column {
+                        break 'paragraphs;
+                    }
+
                     let line = &para_layout.lines[line_idx];
                     let line_bottom = current_y + line.height;
-                    
-                    // Check if Y is in this line (or if we are at the last line of the page and Y is below)
-                    let is_last_line_on_page = para_id == page.end_para && line_idx == page.end_line;
-                    
-                    if y < line_bottom || is_last_line_on_page {
-                        // Found line (or clamped to last line)
-                        // Adjust X for margins and indent
-                        let local_x = x - constraints.margin_left - indent;
-                        let offset = line.offset_for_x(local_x);
-                        return Some(crate::editing::DocPosition::new(para_id, offset));
+                    if current_column == column {
+                        // Found line (or clamped to the first line when above it)
+                        if y < line_bottom {
+                            return cursor_on(para_id, line_idx, column);
+                        }
+                        last_in_column = Some((para_id, line_idx));
                     }
 
-                    current_y += line.height;
+                    current_y = line_bottom;
                 }
                 
                 // If we finished this paragraph (processed all lines), add spacing
@@ -575,24 +1755,136 @@ impl WasmEditor {
             }
         }
         
-        // Should have returned by now if logic is correct, but fallback to end of page
-        let end_para = page.end_para;
-        let end_line_idx = page.end_line;
-        if let Some(pl) = layout.paragraph_layout(end_para) {
-            let line = &pl.lines[end_line_idx];
-             let indent = if let Some(meta) = document.block_meta(end_para) {
-                layout.indent_for(meta)
-            } else {
-                0.0
-            };
-            let offset = line.offset_for_x(x - constraints.margin_left - indent);
-            return Some(crate::editing::DocPosition::new(end_para, offset));
+        // Below the column's lines: its last line, else the end of the page
+        match last_in_column {
+            Some((para_id, line_idx)) => cursor_on(para_id, line_idx, column),
+            None => cursor_on(page.end_para, page.end_line, current_column),
+        }
+    }
+
+    /// Register a line's links for this render, returning their records
+    fn frame_link_records(&mut self, links: &[(usize, usize, Link)]) -> Vec<(usize, usize, u32)> {
+        links
+            .iter()
+            .map(|(start, len, link)| {
+                let link_key = self.frame_links.len() as u32;
+                self.frame_links.push(link.clone());
+                (*start, *len, link_key)
+            })
+            .collect()
+    }
+
+    /// Wrap an editor, giving it the default page-number footer
+    fn from_editor(mut editor: Editor) -> Self {
+        if let Some(section) = editor.document.section_mut(SectionId::DEFAULT) {
+            section.footers.default = Some(HeaderFooter::parse("{PAGE}", HeaderFooterAlign::Center));
         }
+        editor.update_layout();
 
-        None
+        Self {
+            editor,
+            render_buffer: RenderBuffer::new(),
+            frame_objects: Vec::new(),
+            frame_links: Vec::new(),
+            outline: Vec::new(),
+            misspelling: None,
+            glyph_output: false,
+        }
     }
 }
 
+/// Place a cursor at an X offset within a line, on the side of the
+/// grapheme that was hit
+fn cursor_at_x(para_id: ParagraphId, line: &crate::layout::LineLayout, x: f32) -> crate::editing::Cursor {
+    let (offset, affinity) = line.position_for_x(x);
+    crate::editing::Cursor {
+        affinity,
+        ..crate::editing::Cursor::new(crate::editing::DocPosition::new(para_id, offset))
+    }
+}
+
+/// Line flags for a paragraph's base direction
+fn rtl_flag(rtl: bool) -> u32 {
+    if rtl {
+        flat_buffer::FLAG_IS_RTL
+    } else {
+        0
+    }
+}
+
+/// Map a JS text direction code (0 auto, 1 LTR, 2 RTL)
+fn text_direction(code: u32) -> TextDirection {
+    match code {
+        1 => TextDirection::Ltr,
+        2 => TextDirection::Rtl,
+        _ => TextDirection::Auto,
+    }
+}
+
+/// Map a JS decoration kind code (0 highlight, 1 squiggle)
+fn decoration_kind(code: u32) -> DecorationKind {
+    match code {
+        1 => DecorationKind::Squiggle,
+        _ => DecorationKind::Highlight,
+    }
+}
+
+/// Map a JS line breaking code (0 none, 1 greedy, 2 optimal)
+fn line_break_mode(code: u32) -> Option<LineBreakMode> {
+    match code {
+        1 => Some(LineBreakMode::Greedy),
+        2 => Some(LineBreakMode::Optimal),
+        _ => None,
+    }
+}
+
+/// Map a JS tab alignment code (0 left, 1 center, 2 right, 3 decimal)
+fn tab_alignment(code: u32) -> TabAlignment {
+    match code {
+        1 => TabAlignment::Center,
+        2 => TabAlignment::Right,
+        3 => TabAlignment::Decimal,
+        _ => TabAlignment::Left,
+    }
+}
+
+/// Map a JS tab leader code (0 none, 1 dots, 2 dashes, 3 underline)
+fn tab_leader(code: u32) -> TabLeader {
+    match code {
+        1 => TabLeader::Dots,
+        2 => TabLeader::Dashes,
+        3 => TabLeader::Underline,
+        _ => TabLeader::None,
+    }
+}
+
+/// Build a link from JS arguments (empty title means none)
+fn link_from_js(href: &str, title: &str) -> Link {
+    Link {
+        href: href.to_string(),
+        title: (!title.is_empty()).then(|| title.to_string()),
+    }
+}
+
+/// Map a JS header/footer kind code
+fn header_footer_kind(kind: u32) -> HeaderFooterKind {
+    match kind {
+        1 => HeaderFooterKind::First,
+        2 => HeaderFooterKind::Even,
+        _ => HeaderFooterKind::Default,
+    }
+}
+
+/// Parse header/footer content from JS (empty template clears it)
+fn header_footer_content(template: &str, align: u32) -> Option<HeaderFooter> {
+    let align = match align {
+        1 => HeaderFooterAlign::Center,
+        2 => HeaderFooterAlign::Right,
+        _ => HeaderFooterAlign::Left,
+    };
+    (!template.is_empty()).then(|| HeaderFooter::parse(template, align))
+}
+
 impl Default for WasmEditor {
     fn default() -> Self {
         Self::new()
@@ -5,6 +5,7 @@ import {
   type RenderData,
   type PageRenderData,
   type LineRenderData,
+  type TabBoxData,
   type CursorRenderData,
   type LayoutConstraints,
   type WasmEditorInterface,
@@ -15,6 +16,14 @@ import {
 const PAGE_GAP = 20;
 const SCALE = 1;
 
+// Links may only lead to web and mail addresses or anchors in the document
+// (the editor applies the same rule); other schemes such as javascript: run
+// code when followed
+const isSafeHref = (href: string): boolean => {
+  const compact = href.replace(/[\s\u0000-\u001f\u007f-\u009f]/g, '');
+  return compact.startsWith('#') || /^(https?|mailto):/i.test(compact);
+};
+
 function App() {
   const [editor, setEditor] = useState<WasmEditorInterface | null>(null);
   const [wasmMemory, setWasmMemory] = useState<WebAssembly.Memory | null>(null);
@@ -23,6 +32,7 @@ function App() {
   const [cursorVisible, setCursorVisible] = useState(true);
   const [isComposing, setIsComposing] = useState(false);
   const [fontFamily, setFontFamily] = useState('Menlo');
+  const [loadedFonts, setLoadedFonts] = useState<string[]>([]);
   const [fontSize, setFontSize] = useState(14);
   const containerRef = useRef<HTMLDivElement>(null);
   const editorRef = useRef<HTMLDivElement>(null);
@@ -59,11 +69,12 @@ function App() {
 
   const updateFontMetrics = useCallback(() => {
     if (!editor) return;
-    const { id, metrics, isNew } = fontService.getOrRegisterFont(fontFamily, fontSize);
+    const { id, metrics, data, isNew } = fontService.getOrRegisterFont(fontFamily, fontSize);
     console.log('[App] updateFontMetrics', { id, fontFamily, fontSize, isNew, metrics });
 
-    if (isNew) {
-      editor.addFont(id, metrics.lineHeight, metrics.charWidths, metrics.defaultWidth);
+    // Prefer measuring from the font file; fall back to browser metrics
+    if (isNew && !(data && editor.addFont(id, data, fontSize))) {
+      editor.addFontMetrics(id, metrics.lineHeight, metrics.charWidths, metrics.defaultWidth);
     }
 
     const hasSelection = editor.hasSelection();
@@ -75,7 +86,9 @@ function App() {
       editor.formatSelection(id);
     } else {
       console.log('[App] No selection, updating global defaults');
-      editor.setFontMetrics(metrics.lineHeight, metrics.charWidths, metrics.defaultWidth);
+      if (!(data && editor.addFont(0, data, fontSize))) {
+        editor.setFontMetrics(metrics.lineHeight, metrics.charWidths, metrics.defaultWidth);
+      }
     }
 
   }, [editor, fontFamily, fontSize]);
@@ -143,10 +156,15 @@ function App() {
           editor.insertParagraph();
           break;
         case 'deleteContentBackward':
-          editor.deleteBackward();
-          break;
         case 'deleteContentForward':
-          editor.deleteForward();
+        case 'deleteWordBackward':
+        case 'deleteWordForward':
+        case 'deleteSoftLineBackward':
+        case 'deleteSoftLineForward':
+        case 'deleteEntireSoftLine':
+        case 'deleteHardLineBackward':
+        case 'deleteHardLineForward':
+          editor.deleteByInputType(inputType);
           break;
         case 'insertFromPaste':
           // Paste is handled by onPaste event
@@ -200,23 +218,131 @@ function App() {
         e.preventDefault();
         editor.selectAll();
         handled = true;
-      }
-      // Navigation keys
-      else if (e.key === 'ArrowLeft') {
+      } else if (isCtrl && e.key === 'Enter') {
+        e.preventDefault();
+        editor.insertPageBreak();
+        handled = true;
+      } else if (isCtrl && e.altKey && e.key.toLowerCase() === 'f') {
+        e.preventDefault();
+        editor.insertFootnote();
+        handled = true;
+      } else if (isCtrl && e.altKey && e.key.toLowerCase() === 'd') {
         e.preventDefault();
-        editor.moveCursor(-1, 0, isShift);
+        editor.insertEndnote();
         handled = true;
-      } else if (e.key === 'ArrowRight') {
+      } else if (isCtrl && e.altKey && e.key.toLowerCase() === 't') {
         e.preventDefault();
-        editor.moveCursor(1, 0, isShift);
+        editor.insertTable(3, 3);
+        handled = true;
+      } else if (isCtrl && e.key.toLowerCase() === 'k') {
+        e.preventDefault();
+        // Edit the link under the caret (empty removes it), link the
+        // selection, or insert new linked text
+        const current = editor.getLinkAtCursor();
+        const href = window.prompt('Link address', current ?? 'https://');
+        if (href !== null) {
+          if (href === '') {
+            editor.removeLink();
+          } else if (!isSafeHref(href)) {
+            window.alert('Links must start with http:, https: or mailto:');
+          } else if (current !== undefined || editor.hasSelection()) {
+            editor.setLink(href, '');
+          } else {
+            editor.insertLink(href, href, '');
+          }
+        }
+        handled = true;
+      } else if (e.ctrlKey && e.key === 'Shift' && e.location !== 0) {
+        // Ctrl+Right Shift makes the paragraph right-to-left, Ctrl+Left Shift left-to-right
+        editor.setDirection(e.location === 2 ? 2 : 1);
+        handled = true;
+      } else if (isCtrl && e.key.toLowerCase() === 'f') {
+        // Find: highlight the matches and select the first after the cursor
+        e.preventDefault();
+        const pattern = window.prompt('Find');
+        if (pattern && editor.setSearch(pattern, false, false, false)) {
+          editor.findNext(false);
+        } else {
+          editor.clearSearch();
+        }
+        handled = true;
+      } else if (isCtrl && e.key.toLowerCase() === 'h') {
+        // Replace every match of the search
+        e.preventDefault();
+        if (editor.getMatchCount() > 0) {
+          const replacement = window.prompt('Replace with');
+          if (replacement !== null) {
+            editor.replaceAll(replacement);
+          }
+        }
+        handled = true;
+      } else if (e.key === 'F3') {
+        e.preventDefault();
+        editor.findNext(isShift);
+        handled = true;
+      } else if (isCtrl && e.key.toLowerCase() === 'd') {
+        // Add the next occurrence of the selection as another selection
+        e.preventDefault();
+        editor.selectNextOccurrence();
+        handled = true;
+      } else if (isCtrl && e.altKey && (e.key === 'ArrowUp' || e.key === 'ArrowDown')) {
+        // Column editing: a caret on the line above or below
+        e.preventDefault();
+        if (e.key === 'ArrowUp') {
+          editor.addCaretAbove();
+        } else {
+          editor.addCaretBelow();
+        }
+        handled = true;
+      } else if (e.key === 'Escape' && editor.getSelectionCount() > 1) {
+        e.preventDefault();
+        editor.clearSecondarySelections();
+        handled = true;
+      } else if (e.key === 'Escape' && editor.isEditingNote()) {
+        e.preventDefault();
+        editor.exitNote();
+        handled = true;
+      } else if (e.key === 'Tab' && editor.isInTable()) {
+        e.preventDefault();
+        editor.moveToCell(isShift);
+        handled = true;
+      }
+      // Navigation keys (left/right follow the direction of the text at the caret)
+      else if (e.key === 'ArrowLeft' || e.key === 'ArrowRight') {
+        e.preventDefault();
+        const forward = (e.key === 'ArrowRight') !== (renderData?.cursor?.rtl ?? false);
+        if (isCtrl) {
+          if (forward) editor.moveWordRight(isShift);
+          else editor.moveWordLeft(isShift);
+        } else {
+          editor.moveCursor(forward ? 1 : -1, 0, isShift);
+        }
         handled = true;
       } else if (e.key === 'ArrowUp') {
         e.preventDefault();
-        editor.moveCursor(0, -1, isShift);
+        if (isCtrl) editor.moveParagraphUp(isShift);
+        else editor.moveCursor(0, -1, isShift);
         handled = true;
       } else if (e.key === 'ArrowDown') {
         e.preventDefault();
-        editor.moveCursor(0, 1, isShift);
+        if (isCtrl) editor.moveParagraphDown(isShift);
+        else editor.moveCursor(0, 1, isShift);
+        handled = true;
+      } else if (e.key === 'Home') {
+        e.preventDefault();
+        if (isCtrl) editor.moveDocumentStart(isShift);
+        else editor.moveLineStart(isShift);
+        handled = true;
+      } else if (e.key === 'End') {
+        e.preventDefault();
+        if (isCtrl) editor.moveDocumentEnd(isShift);
+        else editor.moveLineEnd(isShift);
+        handled = true;
+      } else if (e.key === 'PageUp' || e.key === 'PageDown') {
+        e.preventDefault();
+        const viewportHeight = (containerRef.current?.clientHeight ?? 0) / SCALE;
+        if (e.key === 'PageUp') editor.movePageUp(viewportHeight, isShift);
+        else editor.movePageDown(viewportHeight, isShift);
         handled = true;
       }
 
@@ -225,7 +351,7 @@ function App() {
         updateRenderData(editor, wasmMemory);
       }
     },
-    [editor, wasmMemory, updateRenderData, isComposing]
+    [editor, wasmMemory, updateRenderData, isComposing, renderData]
   );
 
   // Note: beforeinput is now handled via native event listener in useEffect
@@ -293,6 +419,20 @@ function App() {
 
       if (!editor || !wasmMemory) return;
 
+      // Pasted images are embedded at their natural size
+      const image = Array.from(e.clipboardData.files).find((file) => file.type.startsWith('image/'));
+      if (image) {
+        void (async () => {
+          const bitmap = await createImageBitmap(image);
+          const bytes = new Uint8Array(await image.arrayBuffer());
+          editor.insertImageBytes(bytes, image.type, bitmap.width, bitmap.height);
+          bitmap.close();
+          setCursorVisible(true);
+          updateRenderData(editor, wasmMemory);
+        })();
+        return;
+      }
+
       const text = e.clipboardData.getData('text/plain');
       if (text) {
         console.log('[paste]', text);
@@ -372,8 +512,31 @@ function App() {
     setIsMouseDown(true);
     const pos = getDocumentPositionFromMouse(e.clientX, e.clientY);
 
+    // Ctrl/Cmd+click follows links, and in the body text adds a caret
+    if (pos && (e.ctrlKey || e.metaKey)) {
+      const href = editor.linkAt(pos.pageIndex, pos.x, pos.y);
+      if (href) {
+        setIsMouseDown(false);
+        if (href.startsWith('#')) {
+          // Table of contents entries jump to their heading
+          editor.goToAnchor(href);
+          updateRenderData(editor, wasmMemory);
+        } else if (isSafeHref(href)) {
+          window.open(href, '_blank', 'noopener,noreferrer');
+        }
+        return;
+      }
+      if (editor.addCaretAt(pos.pageIndex, pos.x, pos.y)) {
+        setIsMouseDown(false);
+        updateRenderData(editor, wasmMemory);
+        inputLayerRef.current?.focus();
+        return;
+      }
+    }
+
     if (pos) {
-      editor.setCursor(pos.pageIndex, pos.x, pos.y);
+      // e.detail counts clicks: double-click selects a word, triple-click a paragraph
+      editor.setCursor(pos.pageIndex, pos.x, pos.y, e.detail);
       updateRenderData(editor, wasmMemory);
       inputLayerRef.current?.focus();
     }
@@ -394,6 +557,32 @@ function App() {
     setIsMouseDown(false);
   }, []);
 
+  // Right-click on a misspelled word offers its corrections
+  const handleContextMenu = useCallback((e: React.MouseEvent) => {
+    if (!editor || !wasmMemory) return;
+
+    const pos = getDocumentPositionFromMouse(e.clientX, e.clientY);
+    if (!pos) return;
+    const count = editor.spellingSuggestionsAt(pos.pageIndex, pos.x, pos.y);
+    const word = editor.getMisspelledWord();
+    if (word === undefined) return;
+
+    e.preventDefault();
+    const choices = Array.from({ length: count }, (_, i) => `${i + 1}. ${editor.getSpellingSuggestion(i)}`);
+    const answer = window.prompt(
+      [`"${word}"`, ...choices, '+ add to dictionary, - ignore'].join('\n'),
+      count > 0 ? '1' : '',
+    );
+    if (answer === '+') {
+      editor.addToDictionary(word);
+    } else if (answer === '-') {
+      editor.ignoreSpelling(word);
+    } else if (answer) {
+      editor.correctSpelling(Number(answer) - 1);
+    }
+    updateRenderData(editor, wasmMemory);
+  }, [editor, wasmMemory, getDocumentPositionFromMouse, updateRenderData]);
+
   // Focus on click
   const handleClick = useCallback(() => {
     inputLayerRef.current?.focus();
@@ -409,7 +598,13 @@ function App() {
   }
 
   const pageCount = editor.getPageCount();
-  const totalHeight = pageCount * (constraints.pageHeight + PAGE_GAP) * SCALE;
+  // Pages can differ in size across sections, so extend past the last rendered page
+  const totalHeight = Math.max(
+    pageCount * (constraints.pageHeight + PAGE_GAP) * SCALE,
+    ...(renderData?.pages ?? []).map(
+      (page) => (page.yOffset + page.height + (page.pageIndex + 1) * PAGE_GAP) * SCALE
+    )
+  );
 
   return (
     <div className="app">
@@ -432,8 +627,33 @@ function App() {
             <option value="Arial">Arial</option>
             <option value="Times New Roman">Times New Roman</option>
             <option value="Verdana">Verdana</option>
+            {loadedFonts.map(family => (
+              <option key={family} value={family}>{family}</option>
+            ))}
           </select>
 
+          <label className="toolbar-btn" style={{ cursor: 'pointer' }}>
+            Load font…
+            <input
+              type="file"
+              accept=".ttf,.otf"
+              style={{ display: 'none' }}
+              onChange={async (e) => {
+                const file = e.target.files?.[0];
+                e.target.value = '';
+                if (!file) return;
+                const family = file.name.replace(/\.(ttf|otf)$/i, '');
+                try {
+                  await fontService.registerFontData(family, new Uint8Array(await file.arrayBuffer()));
+                  setLoadedFonts(fontService.getFontFamilies());
+                  setFontFamily(family);
+                } catch (err) {
+                  console.error('Failed to load font:', err);
+                }
+              }}
+            />
+          </label>
+
           <select
             value={fontSize}
             onChange={(e) => setFontSize(Number(e.target.value))}
@@ -492,6 +712,7 @@ function App() {
           onMouseMove={handleMouseMove}
           onMouseUp={handleMouseUp}
           onMouseLeave={handleMouseUp}
+          onContextMenu={handleContextMenu}
           style={{ height: totalHeight + 40 }}
         >
           {/* Native input layer for browser-native text input */}
@@ -537,12 +758,12 @@ function App() {
               <Page
                 key={page.pageIndex}
                 page={page}
-                constraints={constraints}
                 cursor={
                   renderData.cursor?.pageIndex === page.pageIndex
                     ? renderData.cursor
                     : null
                 }
+                secondaryCarets={renderData.secondaryCarets.filter((c) => c.pageIndex === page.pageIndex)}
                 cursorVisible={cursorVisible}
                 scale={SCALE}
                 pageGap={PAGE_GAP}
@@ -582,8 +803,8 @@ function App() {
 
 interface PageProps {
   page: PageRenderData;
-  constraints: LayoutConstraints;
   cursor: CursorRenderData | null;
+  secondaryCarets: CursorRenderData[];
   cursorVisible: boolean;
   scale: number;
   pageGap: number;
@@ -591,9 +812,37 @@ interface PageProps {
   fontSize: number;
 }
 
+/**
+ * DOM ranges covering a UTF-16 range of a line's text, one per text node
+ * (styled lines split their text across spans; the list marker is skipped)
+ */
+const lineTextRanges = (lineElement: HTMLElement, start: number, end: number): Range[] => {
+  const ranges: Range[] = [];
+  const walker = document.createTreeWalker(lineElement, NodeFilter.SHOW_TEXT);
+  let nodeStart = 0;
+  for (let node = walker.nextNode() as Text | null; node; node = walker.nextNode() as Text | null) {
+    if (node.parentElement?.hasAttribute('data-marker')) continue;
+    const nodeEnd = nodeStart + node.length;
+    const from = Math.max(start, nodeStart);
+    const to = Math.min(end, nodeEnd);
+    if (from < to) {
+      const range = document.createRange();
+      range.setStart(node, from - nodeStart);
+      range.setEnd(node, to - nodeStart);
+      ranges.push(range);
+    }
+    nodeStart = nodeEnd;
+  }
+  return ranges;
+};
+
 /**
  * Compute cursor X position using browser's DOM Range API
  * This ensures cursor position matches browser-rendered text exactly
+ *
+ * The caret is drawn against one edge of the grapheme it is attached to
+ * (before or after the offset), which keeps it in the right place where
+ * left-to-right and right-to-left text meet.
  */
 const computeCursorX = (cursor: CursorRenderData, page: PageRenderData, scale: number): number => {
   // Find the line containing the cursor by matching Y position
@@ -602,7 +851,13 @@ const computeCursorX = (cursor: CursorRenderData, page: PageRenderData, scale: n
 
   for (let i = 0; i < page.lines.length; i++) {
     const line = page.lines[i];
-    if (cursor.y >= line.y && cursor.y < line.y + lineHeight) {
+    // Cells of a table row and lines in neighbouring columns share their Y,
+    // so lines also match on X: cells exactly, body lines from the caret's
+    // column edge (lines are in column order)
+    const inColumn = line.blockType === 'table-cell'
+      ? Math.abs(line.x - cursor.x) < 0.5
+      : line.x >= cursor.x - 0.5;
+    if (inColumn && cursor.y >= line.y && cursor.y < line.y + lineHeight) {
       targetLineIndex = i;
       break;
     }
@@ -624,40 +879,31 @@ const computeCursorX = (cursor: CursorRenderData, page: PageRenderData, scale: n
     return line.x;
   }
 
-  // Handle empty line or offset at start
-  if (cursor.utf16OffsetInLine === 0 || !line.text) {
+  // Handle empty line
+  if (!line.text) {
     return line.x;
   }
 
-  // Find the text node (skip list marker span if present)
-  let textNode: Text | null = null;
-  for (let i = 0; i < lineElement.childNodes.length; i++) {
-    const node = lineElement.childNodes[i];
-    if (node.nodeType === Node.TEXT_NODE) {
-      textNode = node as Text;
-      break;
-    }
-  }
-
-  if (!textNode) {
+  // Measure the attached grapheme (without splitting a surrogate pair)
+  const offset = Math.min(cursor.utf16OffsetInLine, line.text.length);
+  const upstream = (cursor.upstream && offset > 0) || offset >= line.text.length;
+  const isLow = (i: number) => /[\uDC00-\uDFFF]/.test(line.text[i] ?? '');
+  const start = upstream ? offset - (isLow(offset - 1) ? 2 : 1) : offset;
+  const end = upstream ? offset : offset + (isLow(offset + 1) ? 2 : 1);
+  const [range] = lineTextRanges(lineElement, Math.max(0, start), end);
+  if (!range) {
     return line.x;
   }
 
-  // Clamp offset to text length
-  const offset = Math.min(cursor.utf16OffsetInLine, textNode.length);
-
-  const range = document.createRange();
-  range.setStart(textNode, 0);
-  range.setEnd(textNode, offset);
-
   const rect = range.getBoundingClientRect();
-
-  // rect.width is the width from start of text to cursor offset in scaled pixels
-  // Convert back to unscaled coordinates by dividing by scale
-  return line.x + rect.width / scale;
+  const edge = upstream !== cursor.rtl ? rect.right : rect.left;
+  return line.x + (edge - lineElement.getBoundingClientRect().left) / scale;
 };
 
 // Component to render selection highlights based on DOM measurements
+//
+// A selection crossing a direction boundary is not contiguous on screen,
+// so each text piece contributes its own rectangles.
 const SelectionHighlights: React.FC<{
   page: PageRenderData;
   scale: number;
@@ -668,55 +914,24 @@ const SelectionHighlights: React.FC<{
     const newRects: { x: number, y: number, width: number, height: number }[] = [];
 
     page.lines.forEach((line, lineIndex) => {
-      // Check if line has selection (and not just null)
-      if (line.selectionStart !== null && line.selectionEnd !== null) {
-        const lineElement = document.querySelector(
-          `[data-page-index="${page.pageIndex}"][data-line-index="${lineIndex}"]`
-        ) as HTMLElement;
-
-        if (lineElement) {
-          // Find text node
-          let textNode: Text | null = null;
-          for (let i = 0; i < lineElement.childNodes.length; i++) {
-            const node = lineElement.childNodes[i];
-            if (node.nodeType === Node.TEXT_NODE) {
-              const t = node as Text;
-              // Only use this text node if it's the main text (check length against line text?)
-              // Usually there's only one main text node per line div due to our rendering
-              textNode = t;
-              break;
-            }
-          }
-
-          if (textNode) {
-            // Bounds checks
-            const start = Math.max(0, Math.min(line.selectionStart, textNode.length));
-            const end = Math.max(0, Math.min(line.selectionEnd, textNode.length));
-
-            if (start < end) {
-              // Range for start offset (to determine X)
-              const rangeStart = document.createRange();
-              rangeStart.setStart(textNode, 0);
-              rangeStart.setEnd(textNode, start);
-              const rectStart = rangeStart.getBoundingClientRect();
-              const startWidth = rectStart.width / scale;
-
-              // Range for selection width
-              const rangeSel = document.createRange();
-              rangeSel.setStart(textNode, start);
-              rangeSel.setEnd(textNode, end);
-              const rectSel = rangeSel.getBoundingClientRect();
-              const selWidth = rectSel.width / scale;
-
-              // Position relative to line start
-              // line.x is the absolute X position of the line start in the page
-              const x = line.x + startWidth;
-              const y = line.y;
-              const height = 14 * 1.2; // Use standard line height
-
-              newRects.push({ x, y, width: selWidth, height });
-            }
-          }
+      if (line.selectionStart === null || line.selectionEnd === null) return;
+
+      const lineElement = document.querySelector(
+        `[data-page-index="${page.pageIndex}"][data-line-index="${lineIndex}"]`
+      ) as HTMLElement;
+      if (!lineElement) return;
+
+      const lineLeft = lineElement.getBoundingClientRect().left;
+      const height = 14 * 1.2; // Use standard line height
+      for (const range of lineTextRanges(lineElement, line.selectionStart, line.selectionEnd)) {
+        for (const rect of range.getClientRects()) {
+          if (rect.width === 0) continue;
+          newRects.push({
+            x: line.x + (rect.left - lineLeft) / scale,
+            y: line.y,
+            width: rect.width / scale,
+            height,
+          });
         }
       }
     });
@@ -746,16 +961,17 @@ const SelectionHighlights: React.FC<{
 
 function Page({
   page,
-  constraints,
   cursor,
+  secondaryCarets,
   cursorVisible,
   scale,
   pageGap,
   fontFamily,
   fontSize,
 }: PageProps) {
-  const pageTop = page.pageIndex * (constraints.pageHeight + pageGap) * scale + pageGap;
+  const pageTop = (page.yOffset + page.pageIndex * pageGap) * scale + pageGap;
   const [cursorX, setCursorX] = useState(0);
+  const [secondaryCaretXs, setSecondaryCaretXs] = useState<number[]>([]);
 
   // Compute cursor X position after DOM updates using useLayoutEffect
   // This ensures the DOM has the latest text before we measure
@@ -767,7 +983,8 @@ function Page({
     } else {
       setCursorX(0);
     }
-  }, [cursor, page, scale]);
+    setSecondaryCaretXs(secondaryCarets.map((caret) => computeCursorX(caret, page, scale)));
+  }, [cursor, secondaryCarets, page, scale]);
 
   return (
     <div
@@ -778,15 +995,133 @@ function Page({
         top: pageTop,
         left: '50%',
         transform: 'translateX(-50%)',
-        width: constraints.pageWidth * scale,
-        height: constraints.pageHeight * scale,
+        width: page.width * scale,
+        height: page.height * scale,
         background: 'white',
         boxShadow: '0 2px 8px rgba(0,0,0,0.15)',
         borderRadius: 2,
       }}
     >
+      {/* Inline images */}
+      {page.boxes.map((box, i) => box.kind === 'image' && (
+        <img
+          key={`image-${i}`}
+          src={box.url}
+          alt=""
+          style={{
+            position: 'absolute',
+            left: box.x * scale,
+            top: box.y * scale,
+            width: box.width * scale,
+            height: box.height * scale,
+            pointerEvents: 'none',
+          }}
+        />
+      ))}
+      {/* Table cell borders */}
+      {page.boxes.map((box, i) => box.kind === 'table-cell' && (
+        <div
+          key={`box-${i}`}
+          className="table-cell"
+          style={{
+            position: 'absolute',
+            left: box.x * scale,
+            top: box.y * scale,
+            width: box.width * scale,
+            height: box.height * scale,
+            boxSizing: 'border-box',
+            border: `${Math.max(box.borderWidth * scale, 1)}px solid #444`,
+            background: box.isSelected
+              ? 'rgba(59, 130, 246, 0.2)'
+              : box.isHeader ? '#f3f4f6' : 'transparent',
+            pointerEvents: 'none',
+          }}
+        />
+      ))}
+      {/* Lines between columns */}
+      {page.boxes.map((box, i) => box.kind === 'column-separator' && (
+        <div
+          key={`column-${i}`}
+          style={{
+            position: 'absolute',
+            left: box.x * scale,
+            top: box.y * scale,
+            height: box.height * scale,
+            borderLeft: `${Math.max(box.borderWidth * scale, 1)}px solid #444`,
+            pointerEvents: 'none',
+          }}
+        />
+      ))}
+      {/* Tab leaders, along the baseline of the space each tab fills */}
+      {page.boxes.map((box, i) => box.kind === 'tab' && box.leader !== 'none' && (
+        <div
+          key={`tab-${i}`}
+          style={{
+            position: 'absolute',
+            left: box.x * scale,
+            top: box.y * scale,
+            width: box.width * scale,
+            height: box.height * scale * 0.8,
+            boxSizing: 'border-box',
+            borderBottom: `${Math.max(scale, 1)}px ${TAB_LEADER_BORDERS[box.leader]} #1a1a1a`,
+            pointerEvents: 'none',
+          }}
+        />
+      ))}
+      {/* Decorations: highlights behind the text, squiggles along its bottom */}
+      {page.decorations.map((decoration, i) => (
+        <div
+          key={`decoration-${i}`}
+          style={{
+            position: 'absolute',
+            left: decoration.x * scale,
+            top: decoration.y * scale,
+            width: decoration.width * scale,
+            height: decoration.height * scale,
+            ...(decoration.kind === 'squiggle'
+              ? {
+                  backgroundImage: `linear-gradient(45deg, transparent 65%, ${decoration.color} 80%, transparent 90%), linear-gradient(135deg, transparent 5%, ${decoration.color} 15%, transparent 35%)`,
+                  backgroundSize: '4px 3px',
+                  backgroundRepeat: 'repeat-x',
+                  backgroundPosition: 'left bottom',
+                }
+              : { background: decoration.color }),
+            pointerEvents: 'none',
+          }}
+        />
+      ))}
+      {/* Search matches, the selected one stronger */}
+      {page.boxes.map((box, i) => box.kind === 'search-match' && (
+        <div
+          key={`match-${i}`}
+          style={{
+            position: 'absolute',
+            left: box.x * scale,
+            top: box.y * scale,
+            width: box.width * scale,
+            height: box.height * scale,
+            background: box.isCurrent ? 'rgba(249, 115, 22, 0.45)' : 'rgba(250, 204, 21, 0.4)',
+            pointerEvents: 'none',
+          }}
+        />
+      ))}
       {/* Selection highlights */}
       <SelectionHighlights page={page} scale={scale} />
+      {page.selectionRects.map((rect, i) => (
+        <div
+          key={`selection-${i}`}
+          className="selection"
+          style={{
+            position: 'absolute',
+            left: rect.x * scale,
+            top: rect.y * scale,
+            width: rect.width * scale,
+            height: rect.height * scale,
+            background: 'rgba(59, 130, 246, 0.3)',
+            pointerEvents: 'none',
+          }}
+        />
+      ))}
       {/* Text lines */}
       {page.lines.map((line, i) => (
         <TextLine
@@ -794,6 +1129,7 @@ function Page({
           line={line}
           lineIndex={i}
           pageIndex={page.pageIndex}
+          tabs={lineTabs(page, line)}
           scale={scale}
           fontFamily={fontFamily}
           fontSize={fontSize}
@@ -815,81 +1151,96 @@ function Page({
           }}
         />
       )}
-
-      {/* Page number */}
-      <div
-        className="page-number"
-        style={{
-          position: 'absolute',
-          bottom: 20 * scale,
-          left: 0,
-          right: 0,
-          textAlign: 'center',
-          fontSize: 11,
-          color: '#999',
-        }}
-      >
-        {page.pageIndex + 1}
-      </div>
+      {cursorVisible && secondaryCarets.map((caret, i) => (
+        <div
+          key={`caret-${i}`}
+          className="cursor"
+          style={{
+            position: 'absolute',
+            left: (secondaryCaretXs[i] ?? caret.x) * scale,
+            top: caret.y * scale,
+            width: 2,
+            height: caret.height * scale,
+            background: '#000',
+            pointerEvents: 'none',
+          }}
+        />
+      ))}
     </div>
   );
 }
 
+const TAB_LEADER_BORDERS = { dots: 'dotted', dashes: 'dashed', underline: 'solid' } as const;
+
+/** Tab widths of a line, in order; tabs are boxes on the line's row that
+ *  start before the next line beside it (in another table cell) */
+function lineTabs(page: PageRenderData, line: LineRenderData): number[] {
+  const next = Math.min(
+    ...page.lines.filter((l) => l.y === line.y && l.x > line.x).map((l) => l.x),
+  );
+  return page.boxes
+    .filter((box): box is TabBoxData => box.kind === 'tab')
+    .filter((box) => box.y === line.y && box.x >= line.x && box.x < next)
+    .sort((a, b) => a.x - b.x)
+    .map((box) => box.width);
+}
+
+/** Text with each tab drawn as a space of the width layout gave it;
+ *  `first` is the index of the first tab in `text` on its line */
+function withTabs(text: string, tabs: number[], first: number, scale: number): React.ReactNode[] {
+  return text.split('\t').flatMap((part, i) => i === 0 ? [part] : [
+    <span key={`tab-${first + i}`} style={{ display: 'inline-block', width: (tabs[first + i - 1] ?? 0) * scale }} />,
+    part,
+  ]);
+}
+
 interface TextLineProps {
   line: LineRenderData;
   lineIndex: number;
   pageIndex: number;
+  /** Widths of the tabs on the line */
+  tabs: number[];
   scale: number;
   fontFamily: string;
   fontSize: number;
 }
 
-const TextLine = ({ line, lineIndex, pageIndex, scale, fontFamily, fontSize }: TextLineProps) => {
-  // If we have specific styles, render them
-  if (line.styles && line.styles.length > 0) {
+const TextLine = ({ line, lineIndex, pageIndex, tabs, scale, fontFamily, fontSize }: TextLineProps) => {
+  // If we have specific styles or links, render them
+  if (line.styles.length > 0 || line.links.length > 0) {
     const content = [];
-    let currentIdx = 0;
-
-    // Sort styles by start index just in case
-    // (Rust should guarantee this but good to be safe)
-
-    for (const span of line.styles) {
-      // Gap before span?
-      if (span.start > currentIdx) {
-        content.push(
-          <span key={`gap-${currentIdx}`} style={{
-            fontFamily: `"${fontFamily}"`,
-            fontSize: fontSize * scale,
-          }}>
-            {line.text.substring(currentIdx, span.start)}
-          </span>
-        );
-      }
 
-      const fontData = fontService.getFontDetails(span.fontId);
-      const spanFamily = fontData?.family || fontFamily;
-      const spanSize = (fontData?.size || fontSize) * scale;
+    // Split the line wherever a style or link starts or ends
+    const bounds = new Set([0, line.text.length]);
+    for (const span of [...line.styles, ...line.links]) {
+      bounds.add(span.start);
+      bounds.add(span.start + span.len);
+    }
+    const sorted = [...bounds].filter((b) => b <= line.text.length).sort((a, b) => a - b);
 
-      content.push(
-        <span key={`span-${span.start}`} style={{
-          fontFamily: `"${spanFamily}"`,
-          fontSize: spanSize,
-        }}>
-          {line.text.substring(span.start, span.start + span.len)}
-        </span>
-      );
+    for (let i = 0; i + 1 < sorted.length; i++) {
+      const start = sorted[i];
+      const end = sorted[i + 1];
+      const covers = (span: { start: number; len: number }) =>
+        span.start <= start && end <= span.start + span.len;
+      const style = line.styles.find(covers);
+      const link = line.links.find(covers);
 
-      currentIdx = span.start + span.len;
-    }
+      const fontData = style ? fontService.getFontDetails(style.fontId) : undefined;
+      const spanFamily = fontData?.family || fontFamily;
+      const spanSize = (fontData?.size || fontSize) * scale;
 
-    // Remaining text
-    if (currentIdx < line.text.length) {
       content.push(
-        <span key={`gap-${currentIdx}`} style={{
-          fontFamily: `"${fontFamily}"`,
-          fontSize: fontSize * scale,
-        }}>
-          {line.text.substring(currentIdx)}
+        <span
+          key={`span-${start}`}
+          title={link ? link.title ?? link.href : undefined}
+          style={{
+            fontFamily: `"${spanFamily}"`,
+            fontSize: spanSize,
+            ...(link && { color: '#1a56db', textDecoration: 'underline' }),
+          }}
+        >
+          {withTabs(line.text.substring(start, end), tabs, line.text.substring(0, start).split('\t').length - 1, scale)}
         </span>
       );
     }
@@ -897,6 +1248,7 @@ const TextLine = ({ line, lineIndex, pageIndex, scale, fontFamily, fontSize }: T
     return (
       <div
         className="text-line"
+        dir={line.rtl ? 'rtl' : 'ltr'}
         data-page-index={pageIndex}
         data-line-index={lineIndex}
         style={{
@@ -909,7 +1261,7 @@ const TextLine = ({ line, lineIndex, pageIndex, scale, fontFamily, fontSize }: T
         }}
       >
         {line.listMarker && (
-          <span style={{ marginRight: 8, fontFamily: `"${fontFamily}"`, fontSize: fontSize * scale }}>
+          <span data-marker style={{ marginRight: 8, fontFamily: `"${fontFamily}"`, fontSize: fontSize * scale }}>
             {line.listMarker}
           </span>
         )}
@@ -945,6 +1297,7 @@ const TextLine = ({ line, lineIndex, pageIndex, scale, fontFamily, fontSize }: T
   return (
     <div
       className="text-line"
+      dir={line.rtl ? 'rtl' : 'ltr'}
       data-page-index={pageIndex}
       data-line-index={lineIndex}
       style={{
@@ -960,9 +1313,9 @@ const TextLine = ({ line, lineIndex, pageIndex, scale, fontFamily, fontSize }: T
       }}
     >
       {line.listMarker && (
-        <span style={{ marginRight: 8 }}>{line.listMarker}</span>
+        <span data-marker style={{ marginRight: 8 }}>{line.listMarker}</span>
       )}
-      {line.text || '\u200B'}
+      {line.text ? withTabs(line.text, tabs, 0, scale) : '\u200B'}
     </div>
   );
 };
@@ -11,6 +11,7 @@ export class FontService {
   private metricsCache: Map<number, FontMetrics> = new Map();
   private fontDetails: Map<number, { family: string, size: number }> = new Map();
   private fontStrings: Map<number, string> = new Map();
+  private fontData: Map<string, Uint8Array> = new Map();
   private nextId = 0; // Start at 0
 
   constructor() {
@@ -22,11 +23,27 @@ export class FontService {
     this.ctx = ctx;
   }
 
-  getOrRegisterFont(fontFamily: string, fontSize: number): { id: number, metrics: FontMetrics, isNew: boolean } {
+  /**
+   * Register raw TTF/OTF bytes for a family. The editor measures text from
+   * these bytes natively; the browser gets the same face for rendering.
+   */
+  async registerFontData(fontFamily: string, data: Uint8Array): Promise<void> {
+    const face = new FontFace(fontFamily, data);
+    await face.load();
+    document.fonts.add(face);
+    this.fontData.set(fontFamily, data);
+  }
+
+  getFontFamilies(): string[] {
+    return Array.from(this.fontData.keys());
+  }
+
+  getOrRegisterFont(fontFamily: string, fontSize: number): { id: number, metrics: FontMetrics, data?: Uint8Array, isNew: boolean } {
     const key = `${fontFamily}:${fontSize}`;
+    const data = this.fontData.get(fontFamily);
     if (this.fontCache.has(key)) {
       const id = this.fontCache.get(key)!;
-      return { id, metrics: this.metricsCache.get(id)!, isNew: false };
+      return { id, metrics: this.metricsCache.get(id)!, data, isNew: false };
     }
 
     const id = this.nextId++;
@@ -37,7 +54,7 @@ export class FontService {
     this.fontStrings.set(id, `${fontSize}px "${fontFamily}"`);
     this.fontDetails.set(id, { family: fontFamily, size: fontSize });
 
-    return { id, metrics, isNew: true };
+    return { id, metrics, data, isNew: true };
   }
 
   getFontString(id: number): string | undefined {
@@ -6,41 +6,75 @@
  * u32 buffer:
  * Header (offset table for random access):
  *   [0] MAGIC (0x4D575244 = "MWRD" for validation)
- *   [1] SCHEMA_VERSION (protocol version, currently 1)
+ *   [1] SCHEMA_VERSION (protocol version, currently 8)
  *   [2] version_lo (document version)
  *   [3] version_hi (document version)
  *   [4] page_count
- *   [5] cursor_present (0 or 1)
+ *   [5] caret_count (the cursor and any secondary carets)
  *   [6] selection_count
  *   [7] text_buffer_len
- *   [8] u32_cursor_offset (index where cursor indices start, 0 if no cursor)
+ *   [8] u32_cursor_offset (index where caret indices start, 0 if no carets)
  *   [9] u32_selection_offset (index where selection indices start, 0 if no selections)
- *   [10] f32_cursor_offset (index where cursor geometry starts, 0 if no cursor)
+ *   [10] f32_cursor_offset (index where caret geometries start, 0 if no carets)
  *   [11] f32_selection_offset (index where selection geometries start, 0 if no selections)
+ *   [12] box_count
+ *   [13] u32_box_offset (index where box indices start, 0 if no boxes)
+ *   [14] f32_box_offset (index where box geometries start, 0 if no boxes)
+ *   [15] decoration_count
+ *   [16] u32_decoration_offset (index where decoration indices start, 0 if none)
+ *   [17] f32_decoration_offset (index where decoration geometries start, 0 if none)
  * 
- * Per page (starts at index 12):
+ * Per page (starts at index 18):
  *   - page_index
  *   - line_count
  *   - per line: [text_offset, text_len, text_utf16_offset, text_utf16_len,
  *               block_type, flags, marker_offset, marker_len, marker_utf16_offset, marker_utf16_len,
- *               sel_start, sel_end]
+ *               sel_start, sel_end, style_start_idx, style_count, link_start_idx, link_count,
+ *               glyph_start_idx, glyph_count]
  *     text_offset/text_len: byte offsets in UTF-8 buffer (for validation)
  *     text_utf16_offset/text_utf16_len: offsets for JS substring (after single decode)
+ *     flags: bit0=is_heading, bit1=is_list_item, bits2-4=heading_level, bit5=is_rtl
  *     marker: only read if marker_len > 0, otherwise marker_offset is ignored
  *     sel_start/sel_end: UTF-16 offsets relative to line text start (0xFFFFFFFF if no selection)
+ *     styles: style_count records [start, len, font_id] at style_start_idx in the style buffer
+ *     links: link_count records [start, len, link_key] at link_start_idx in the style buffer
+ *     glyphs: glyph_count records at glyph_start_idx in the glyph buffer (setGlyphOutput only)
  * 
- * At u32_cursor_offset (if cursor_present):
- *   - cursor indices: [page_index, utf16_offset_in_line]
+ * At u32_cursor_offset (if caret_count > 0):
+ *   - per caret: [page_index, utf16_offset_in_line, caret_flags]
+ *     caret_flags: bit0=upstream (caret follows the previous grapheme), bit1=that grapheme is RTL,
+ *     bit2=secondary caret
+ *
+ * At u32_selection_offset (if selection_count > 0):
+ *   - per secondary selection rectangle: [page_index]
+ * 
+ * At u32_box_offset (if box_count > 0):
+ *   - per box: [page_index, box_kind, box_data]
+ *     box_data: flags for table cells, object key for images, leader for tabs,
+ *     bit0=is_current for search matches
+ *     (column separators are vertical lines at x, border_width wide)
+ *
+ * At u32_decoration_offset (if decoration_count > 0):
+ *   - per decoration rectangle: [page_index, decoration_kind, color]
+ *     decoration_kind: 0=highlight, 1=squiggle along the bottom; color: 0xRRGGBBAA
  * 
  * f32 buffer:
  * - per page: [y_offset, width, height]
  * - per line: [x, y]
- * - cursor geometry (if present): [x, y, height]
+ * - per caret (at f32_cursor_offset): [x, y, height]
+ * - per selection rectangle (at f32_selection_offset): [x, y, width, height]
+ * - per box (at f32_box_offset): [x, y, width, height, border_width]
+ * - per decoration rectangle (at f32_decoration_offset): [x, y, width, height]
+ *
+ * glyph buffer (f32):
+ * - per glyph: [font_id, glyph_id, x, y] (x from the line's x, y up from the baseline)
  */
 
 // Protocol constants (must match Rust)
 const MAGIC = 0x4D575244; // "MWRD" (MiniWoRD)
-const SCHEMA_VERSION = 1;
+const SCHEMA_VERSION = 8;
+const HEADER_SIZE = 18;
+const F32_PER_GLYPH = 4;
 
 // Block type opcodes (must match Rust)
 const BLOCK_PARAGRAPH = 0;
@@ -51,15 +85,46 @@ const BLOCK_HEADING_4 = 4;
 const BLOCK_HEADING_5 = 5;
 const BLOCK_HEADING_6 = 6;
 const BLOCK_LIST_ITEM = 7;
+const BLOCK_HEADER = 8;
+const BLOCK_FOOTER = 9;
+const BLOCK_FOOTNOTE = 10;
+const BLOCK_ENDNOTE = 11;
+const BLOCK_TABLE_CELL = 12;
+
+// Box kinds
+const BOX_TABLE_CELL = 0;
+const BOX_IMAGE = 1;
+const BOX_TAB = 2;
+const BOX_COLUMN_SEPARATOR = 3;
+const BOX_SEARCH_MATCH = 4;
+
+// Decoration kinds
+const DECORATION_KINDS: DecorationKind[] = ['highlight', 'squiggle'];
+
+// Tab leaders
+const TAB_LEADERS: TabLeader[] = ['none', 'dots', 'dashes', 'underline'];
+
+// Box flags
+const BOX_FLAG_HEADER = 0b0001;
+const BOX_FLAG_SELECTED = 0b0010;
+const BOX_FLAG_CURRENT = 0b0001;
 
 // Flags
 const FLAG_IS_HEADING = 0b0001;
 const FLAG_IS_LIST_ITEM = 0b0010;
+const FLAG_IS_RTL = 0b10_0000;
+
+// Caret flags
+const CARET_FLAG_UPSTREAM = 0b0001;
+const CARET_FLAG_RTL = 0b0010;
+const CARET_FLAG_SECONDARY = 0b0100;
 
 export interface RenderData {
   version: number;
   pages: PageRenderData[];
   cursor: CursorRenderData | null;
+  /** Carets besides the cursor (multiple selections) */
+  secondaryCarets: CursorRenderData[];
 }
 
 export interface PageRenderData {
@@ -68,6 +133,88 @@ export interface PageRenderData {
   width: number;
   height: number;
   lines: LineRenderData[];
+  boxes: BoxRenderData[];
+  /** Highlights of secondary selections */
+  selectionRects: SelectionRectData[];
+  /** Parts of decorations, one per line they cover */
+  decorations: DecorationData[];
+}
+
+export type DecorationKind = 'highlight' | 'squiggle';
+
+/** Part of a decoration on one line: a background or a wavy underline */
+export interface DecorationData {
+  kind: DecorationKind;
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+  /** CSS color */
+  color: string;
+}
+
+export interface SelectionRectData {
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+export type BoxRenderData = TableCellBoxData | ImageBoxData | TabBoxData | ColumnSeparatorBoxData | SearchMatchBoxData;
+
+export interface TableCellBoxData {
+  kind: 'table-cell';
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+  borderWidth: number;
+  isHeader: boolean;
+  isSelected: boolean;
+}
+
+export interface ImageBoxData {
+  kind: 'image';
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+  /** Key for `getObjectUrl`, valid until the next render */
+  objectKey: number;
+  /** Filled in by `getRenderDataFromEditor` */
+  url: string;
+}
+
+/** Line down the middle of the gap between two columns */
+export interface ColumnSeparatorBoxData {
+  kind: 'column-separator';
+  x: number;
+  y: number;
+  height: number;
+  borderWidth: number;
+}
+
+/** Highlight behind (part of) a search match */
+export interface SearchMatchBoxData {
+  kind: 'search-match';
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+  /** The match is the one selected */
+  isCurrent: boolean;
+}
+
+export type TabLeader = 'none' | 'dots' | 'dashes' | 'underline';
+
+/** Space filled by a tab on the line starting at the same `y` */
+export interface TabBoxData {
+  kind: 'tab';
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+  leader: TabLeader;
 }
 
 export interface LineRenderData {
@@ -82,6 +229,30 @@ export interface LineRenderData {
   selectionStart: number | null;
   selectionEnd: number | null;
   styles: StyleSpan[];
+  links: LinkSpan[];
+  /** Paragraph base direction is right-to-left */
+  rtl: boolean;
+  /** Shaped glyphs, empty unless glyph output is enabled */
+  glyphs: GlyphData[];
+}
+
+export interface GlyphData {
+  fontId: number;
+  glyphId: number;
+  /** From the line's x */
+  x: number;
+  /** Offset from the baseline, positive upwards */
+  y: number;
+}
+
+export interface LinkSpan {
+  start: number;
+  len: number;
+  /** Key for `getLinkHref`/`getLinkTitle`, valid until the next render */
+  linkKey: number;
+  /** Filled in by `getRenderDataFromEditor` */
+  href: string;
+  title: string | null;
 }
 
 export interface StyleSpan {
@@ -97,6 +268,10 @@ export interface CursorRenderData {
   pageIndex: number;
   /** UTF-16 code unit offset within the line for correct JS text measurement */
   utf16OffsetInLine: number;
+  /** Caret is drawn against the grapheme before the offset (else the one after) */
+  upstream: boolean;
+  /** That grapheme runs right-to-left */
+  rtl: boolean;
 }
 
 const blockTypeToString = (blockType: number): string => {
@@ -109,6 +284,11 @@ const blockTypeToString = (blockType: number): string => {
     case BLOCK_HEADING_5: return 'heading-5';
     case BLOCK_HEADING_6: return 'heading-6';
     case BLOCK_LIST_ITEM: return 'list-item';
+    case BLOCK_HEADER: return 'header';
+    case BLOCK_FOOTER: return 'footer';
+    case BLOCK_FOOTNOTE: return 'footnote';
+    case BLOCK_ENDNOTE: return 'endnote';
+    case BLOCK_TABLE_CELL: return 'table-cell';
     default: return 'paragraph';
   }
 };
@@ -138,13 +318,16 @@ export const decodeRenderData = (
   textPtr: number,
   textLen: number,
   stylePtr: number,
-  styleLen: number
+  styleLen: number,
+  glyphPtr = 0,
+  glyphLen = 0
 ): RenderData => {
   // Create views into WASM memory
   const u32View = new Uint32Array(memory.buffer, u32Ptr, u32Len);
   const f32View = new Float32Array(memory.buffer, f32Ptr, f32Len);
   const textView = new Uint8Array(memory.buffer, textPtr, textLen);
   const styleView = new Uint32Array(memory.buffer, stylePtr, styleLen);
+  const glyphView = new Float32Array(memory.buffer, glyphPtr, glyphLen);
 
   // Validate header
   const magic = u32View[0];
@@ -162,15 +345,21 @@ export const decodeRenderData = (
   const versionHi = u32View[3];
   const version = versionLo + versionHi * 0x100000000;
   const pageCount = u32View[4];
-  const cursorPresent = u32View[5] === 1;
-  // const selectionCount = u32View[6]; // Deprecated
+  const caretCount = u32View[5];
+  const selectionCount = u32View[6];
   // u32View[7] is text_buffer_len
   const u32CursorOffset = u32View[8];
-  // const u32SelectionOffset = u32View[9]; // Deprecated
+  const u32SelectionOffset = u32View[9];
   const f32CursorOffset = u32View[10];
-  // const f32SelectionOffset = u32View[11]; // Deprecated
-
-  let u32Idx = 12; // Pages start after header
+  const f32SelectionOffset = u32View[11];
+  const boxCount = u32View[12];
+  const u32BoxOffset = u32View[13];
+  const f32BoxOffset = u32View[14];
+  const decorationCount = u32View[15];
+  const u32DecorationOffset = u32View[16];
+  const f32DecorationOffset = u32View[17];
+
+  let u32Idx = HEADER_SIZE; // Pages start after header
   let f32Idx = 0;
 
   // PERFORMANCE: Decode entire text buffer once, then use substring for each line
@@ -191,7 +380,7 @@ export const decodeRenderData = (
     const lines: LineRenderData[] = [];
 
     for (let l = 0; l < lineCount; l++) {
-      // Read all 14 u32 values per line (was 12)
+      // Read all 18 u32 values per line
       u32Idx++;  // skip text_offset
       u32Idx++;  // skip text_length
       const textUtf16Offset = u32View[u32Idx++];
@@ -206,6 +395,10 @@ export const decodeRenderData = (
       const selEnd = u32View[u32Idx++];
       const styleStartIdx = u32View[u32Idx++];
       const styleCount = u32View[u32Idx++];
+      const linkStartIdx = u32View[u32Idx++];
+      const linkCount = u32View[u32Idx++];
+      const glyphStartIdx = u32View[u32Idx++];
+      const glyphCount = u32View[u32Idx++];
 
       const x = f32View[f32Idx++];
       const y = f32View[f32Idx++];
@@ -236,6 +429,26 @@ export const decodeRenderData = (
         }
       }
 
+      // Decode links (hrefs are resolved by getRenderDataFromEditor)
+      const links: LinkSpan[] = [];
+      for (let k = 0, lIdx = linkStartIdx; k < linkCount; k++) {
+        const start = styleView[lIdx++];
+        const len = styleView[lIdx++];
+        const linkKey = styleView[lIdx++];
+        links.push({ start, len, linkKey, href: '', title: null });
+      }
+
+      // Decode glyphs
+      const glyphs: GlyphData[] = [];
+      for (let g = 0, gIdx = glyphStartIdx; g < glyphCount; g++, gIdx += F32_PER_GLYPH) {
+        glyphs.push({
+          fontId: glyphView[gIdx],
+          glyphId: glyphView[gIdx + 1],
+          x: glyphView[gIdx + 2],
+          y: glyphView[gIdx + 3],
+        });
+      }
+
       lines.push({
         x,
         y,
@@ -248,6 +461,9 @@ export const decodeRenderData = (
         selectionStart: hasSelection ? selStart : null,
         selectionEnd: hasSelection ? selEnd : null,
         styles,
+        links,
+        rtl: (flags & FLAG_IS_RTL) !== 0,
+        glyphs,
       });
     }
 
@@ -257,28 +473,108 @@ export const decodeRenderData = (
       width: pageWidth,
       height: pageHeight,
       lines,
+      boxes: [],
+      selectionRects: [],
+      decorations: [],
     });
   }
 
-  // Decode cursor using offset table (random access for both u32 and f32)
+  // Decode secondary selection rectangles and attach them to their pages
+  for (let s = 0; s < selectionCount; s++) {
+    const page = pages.find((p) => p.pageIndex === u32View[u32SelectionOffset + s]);
+    const f32Base = f32SelectionOffset + s * 4;
+    page?.selectionRects.push({
+      x: f32View[f32Base],
+      y: f32View[f32Base + 1],
+      width: f32View[f32Base + 2],
+      height: f32View[f32Base + 3],
+    });
+  }
+
+  // Decode boxes using offset table and attach them to their pages
+  for (let b = 0; b < boxCount; b++) {
+    const u32Base = u32BoxOffset + b * 3;
+    const f32Base = f32BoxOffset + b * 5;
+    const pageIndex = u32View[u32Base];
+    const kind = u32View[u32Base + 1];
+    const data = u32View[u32Base + 2];
+    const page = pages.find((p) => p.pageIndex === pageIndex);
+    if (!page) continue;
+
+    const x = f32View[f32Base];
+    const y = f32View[f32Base + 1];
+    const width = f32View[f32Base + 2];
+    const height = f32View[f32Base + 3];
+
+    if (kind === BOX_TABLE_CELL) {
+      page.boxes.push({
+        kind: 'table-cell',
+        x,
+        y,
+        width,
+        height,
+        borderWidth: f32View[f32Base + 4],
+        isHeader: (data & BOX_FLAG_HEADER) !== 0,
+        isSelected: (data & BOX_FLAG_SELECTED) !== 0,
+      });
+    } else if (kind === BOX_IMAGE) {
+      page.boxes.push({ kind: 'image', x, y, width, height, objectKey: data, url: '' });
+    } else if (kind === BOX_TAB) {
+      page.boxes.push({ kind: 'tab', x, y, width, height, leader: TAB_LEADERS[data] ?? 'none' });
+    } else if (kind === BOX_COLUMN_SEPARATOR) {
+      page.boxes.push({ kind: 'column-separator', x, y, height, borderWidth: f32View[f32Base + 4] });
+    } else if (kind === BOX_SEARCH_MATCH) {
+      page.boxes.push({ kind: 'search-match', x, y, width, height, isCurrent: (data & BOX_FLAG_CURRENT) !== 0 });
+    }
+  }
+
+  // Decode decorations and attach them to their pages
+  for (let d = 0; d < decorationCount; d++) {
+    const u32Base = u32DecorationOffset + d * 3;
+    const f32Base = f32DecorationOffset + d * 4;
+    const page = pages.find((p) => p.pageIndex === u32View[u32Base]);
+    const color = u32View[u32Base + 2];
+    page?.decorations.push({
+      kind: DECORATION_KINDS[u32View[u32Base + 1]] ?? 'highlight',
+      x: f32View[f32Base],
+      y: f32View[f32Base + 1],
+      width: f32View[f32Base + 2],
+      height: f32View[f32Base + 3],
+      color: `rgba(${color >>> 24}, ${(color >>> 16) & 0xff}, ${(color >>> 8) & 0xff}, ${(color & 0xff) / 255})`,
+    });
+  }
+
+  // Decode carets using offset table (random access for both u32 and f32)
   let cursor: CursorRenderData | null = null;
-  if (cursorPresent && u32CursorOffset > 0 && f32CursorOffset > 0) {
+  const secondaryCarets: CursorRenderData[] = [];
+  for (let c = 0; c < caretCount; c++) {
     // u32: indices at u32CursorOffset
-    const pageIndex = u32View[u32CursorOffset];
-    const utf16OffsetInLine = u32View[u32CursorOffset + 1];
-    
+    const u32Base = u32CursorOffset + c * 3;
+    const caretFlags = u32View[u32Base + 2];
+
     // f32: geometry at f32CursorOffset (random access, not sequential)
-    const x = f32View[f32CursorOffset];
-    const y = f32View[f32CursorOffset + 1];
-    const height = f32View[f32CursorOffset + 2];
-    
-    cursor = { x, y, height, pageIndex, utf16OffsetInLine };
+    const f32Base = f32CursorOffset + c * 3;
+    const caret = {
+      x: f32View[f32Base],
+      y: f32View[f32Base + 1],
+      height: f32View[f32Base + 2],
+      pageIndex: u32View[u32Base],
+      utf16OffsetInLine: u32View[u32Base + 1],
+      upstream: (caretFlags & CARET_FLAG_UPSTREAM) !== 0,
+      rtl: (caretFlags & CARET_FLAG_RTL) !== 0,
+    };
+    if (caretFlags & CARET_FLAG_SECONDARY) {
+      secondaryCarets.push(caret);
+    } else {
+      cursor = caret;
+    }
   }
 
   return {
     version,
     pages,
     cursor,
+    secondaryCarets,
   };
 };
 
@@ -303,8 +599,29 @@ export const getRenderDataFromEditor = (
   const textLen = editor.getTextLen();
   const stylePtr = editor.getStylePtr();
   const styleLen = editor.getStyleLen();
+  const glyphPtr = editor.getGlyphPtr();
+  const glyphLen = editor.getGlyphLen();
+
+  const data = decodeRenderData(
+    memory, u32Ptr, u32Len, f32Ptr, f32Len, textPtr, textLen, stylePtr, styleLen, glyphPtr, glyphLen
+  );
+
+  // Resolve image sources and link targets while their keys are valid
+  for (const page of data.pages) {
+    for (const box of page.boxes) {
+      if (box.kind === 'image') {
+        box.url = editor.getObjectUrl(box.objectKey);
+      }
+    }
+    for (const line of page.lines) {
+      for (const link of line.links) {
+        link.href = editor.getLinkHref(link.linkKey);
+        link.title = editor.getLinkTitle(link.linkKey) ?? null;
+      }
+    }
+  }
 
-  return decodeRenderData(memory, u32Ptr, u32Len, f32Ptr, f32Len, textPtr, textLen, stylePtr, styleLen);
+  return data;
 };
 
 /**
@@ -315,8 +632,19 @@ export interface WasmEditorInterface {
   insertText(text: string): void;
   deleteBackward(): boolean;
   deleteForward(): boolean;
+  deleteByInputType(inputType: string): boolean;
   moveCursor(horizontal: number, vertical: number, extendSelection: boolean): void;
-  setCursor(pageIndex: number, x: number, y: number): void;
+  moveWordLeft(extendSelection: boolean): void;
+  moveWordRight(extendSelection: boolean): void;
+  moveLineStart(extendSelection: boolean): void;
+  moveLineEnd(extendSelection: boolean): void;
+  moveDocumentStart(extendSelection: boolean): void;
+  moveDocumentEnd(extendSelection: boolean): void;
+  moveParagraphUp(extendSelection: boolean): void;
+  moveParagraphDown(extendSelection: boolean): void;
+  movePageUp(viewportHeight: number, extendSelection: boolean): void;
+  movePageDown(viewportHeight: number, extendSelection: boolean): void;
+  setCursor(pageIndex: number, x: number, y: number, clickCount: number): void;
   selectTo(pageIndex: number, x: number, y: number): void;
   undo(): boolean;
   redo(): boolean;
@@ -325,7 +653,92 @@ export interface WasmEditorInterface {
   getPageCount(): number;
   selectAll(): void;
   clearSelection(): void;
+  addCaretAt(pageIndex: number, x: number, y: number): boolean;
+  addCaretAbove(): boolean;
+  addCaretBelow(): boolean;
+  selectNextOccurrence(): boolean;
+  clearSecondarySelections(): void;
+  getSelectionCount(): number;
+  addSpellingDictionary(language: string, aff: string, dic: string): boolean;
+  setSpellChecking(enabled: boolean): void;
+  spellingSuggestionsAt(pageIndex: number, x: number, y: number): number;
+  getMisspelledWord(): string | undefined;
+  getSpellingSuggestion(index: number): string;
+  correctSpelling(index: number): boolean;
+  addToDictionary(word: string): void;
+  ignoreSpelling(word: string): void;
+  getAddedWords(): string;
+  addDecoration(startPara: bigint, startOffset: number, endPara: bigint, endOffset: number, kind: number, color: number): bigint | undefined;
+  decorateSelection(kind: number, color: number): bigint | undefined;
+  removeDecoration(id: bigint): boolean;
+  clearDecorations(): void;
+  setSearch(pattern: string, caseSensitive: boolean, wholeWord: boolean, regex: boolean): boolean;
+  clearSearch(): void;
+  findNext(backward: boolean): boolean;
+  replace(replacement: string): boolean;
+  replaceAll(replacement: string): number;
+  getMatchCount(): number;
   insertParagraph(): void;
+  insertPageBreak(): void;
+  insertSectionBreak(
+    pageWidth: number,
+    pageHeight: number,
+    marginTop: number,
+    marginBottom: number,
+    marginLeft: number,
+    marginRight: number
+  ): void;
+  setHeader(kind: number, template: string, align: number): void;
+  setFooter(kind: number, template: string, align: number): void;
+  setDifferentFirstPage(enabled: boolean): void;
+  setDifferentOddEven(enabled: boolean): void;
+  setTitle(title: string): void;
+  insertFootnote(): void;
+  insertEndnote(): void;
+  exitNote(): void;
+  isEditingNote(): boolean;
+  insertTable(rows: number, columns: number): void;
+  moveToCell(backward: boolean): boolean;
+  isInTable(): boolean;
+  insertImageUrl(url: string, width: number, height: number): number | undefined;
+  insertImageBytes(data: Uint8Array, mimeType: string, width: number, height: number): number | undefined;
+  resizeObject(objectId: number, width: number, height: number): boolean;
+  getObjectUrl(objectKey: number): string;
+  insertLink(text: string, href: string, title: string): void;
+  setLink(href: string, title: string): boolean;
+  removeLink(): boolean;
+  getLinkAtCursor(): string | undefined;
+  linkAt(pageIndex: number, x: number, y: number): string | undefined;
+  getLinkHref(linkKey: number): string;
+  getLinkTitle(linkKey: number): string | undefined;
+  setDirection(direction: number): void;
+  setLanguage(language: string): void;
+  setLineBreakMode(mode: number): void;
+  setDocumentLineBreakMode(mode: number): void;
+  setTabStops(positions: Float32Array, alignments: Uint32Array, leaders: Uint32Array, interval: number): void;
+  setDocumentTabInterval(interval: number): void;
+  setColumns(count: number, gap: number, separator: boolean): void;
+  createList(kind: number): number;
+  setListItem(listId: number, level: number): void;
+  clearListItem(): void;
+  setListLevel(
+    listId: number, level: number, format: number, pattern: string, start: number,
+    restartAfter: number, alignment: number, indent: number, hanging: number,
+  ): boolean;
+  setHeadingLevel(level: number): void;
+  setHeadingNumbering(enabled: boolean): void;
+  insertToc(maxLevel: number): number;
+  updateToc(tocId: number): boolean;
+  goToAnchor(href: string): boolean;
+  refreshOutline(): number;
+  getOutlineParaId(index: number): bigint;
+  getOutlineLevel(index: number): number;
+  getOutlineDepth(index: number): number;
+  getOutlineText(index: number): string;
+  getOutlinePage(index: number): number;
+  scrollToParagraph(paraId: bigint): number | undefined;
+  exportHtml(): string;
+  exportMarkdown(): string;
 
   // Buffer API
   buildRenderData(viewportY: number, viewportHeight: number): void;
@@ -337,9 +750,17 @@ export interface WasmEditorInterface {
   getTextLen(): number;
   getStylePtr(): number;
   getStyleLen(): number;
+  getGlyphPtr(): number;
+  getGlyphLen(): number;
+  setGlyphOutput(enabled: boolean): void;
 
   // Font/Style methods
-  addFont(id: number, lineHeight: number, charWidths: Float32Array, defaultWidth: number): void;
+  addFont(id: number, data: Uint8Array, size: number): boolean;
+  addFontMetrics(id: number, lineHeight: number, charWidths: Float32Array, defaultWidth: number): void;
+  setFontFallbacks(id: number, chain: Uint32Array): void;
+  setDefaultFallbacks(chain: Uint32Array): void;
+  setHyphenation(enabled: boolean, minWord: number, minPrefix: number, minSuffix: number, maxConsecutive: number): void;
+  addHyphenationPatterns(language: string, patterns: string): boolean;
   formatSelection(fontId: number): void;
 
   // Direct layout constraint accessors